@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use xml_no_std::escape::{escape_str_attribute, escape_str_pcdata, Escaped, PcDataEscapes};
+use xml_no_std::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+/// Text with a special character every few words, like prose with some markup in it
+fn dirty_text(len: usize) -> String {
+    let words = ["lorem", "ipsum", "a < b", "dolor", "sit", "Tom & Jerry", "amet", "\"quoted\"", "consectetur"];
+    let mut text = String::with_capacity(len + 16);
+    for word in words.iter().cycle() {
+        if text.len() >= len {
+            break;
+        }
+        text.push_str(word);
+        text.push(' ');
+    }
+    text
+}
+
+fn escape(c: &mut Criterion) {
+    let clean = "plain text with no special characters at all, ".repeat(200);
+    let dirty = dirty_text(clean.len());
+
+    let mut group = c.benchmark_group("escape");
+    for (name, text) in [("clean", &clean), ("dirty", &dirty)] {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(format!("attribute/{name}"), |b| b.iter(|| escape_str_attribute(black_box(text))));
+        group.bench_function(format!("pcdata/{name}"), |b| b.iter(|| escape_str_pcdata(black_box(text))));
+    }
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let dirty = dirty_text(100);
+    let config = EmitterConfig::new().write_document_declaration(false);
+
+    c.bench_function("write/escaped_elements", |b| b.iter(|| {
+        let mut writer = EventWriter::new_with_config(config.clone());
+        writer.write(XmlEvent::start_element("root")).unwrap();
+        for _ in 0..100 {
+            writer.write(XmlEvent::start_element("item").attr("title", &dirty)).unwrap();
+            writer.write(XmlEvent::characters(&dirty)).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+        }
+        writer.write(XmlEvent::end_element()).unwrap();
+        writer.into_inner()
+    }));
+}
+
+/// Typical attribute values and text: mostly clean, some with a few special characters
+const CORPUS: &[&str] = &[
+    "id-42", "Hello, world!", "https://example.com/path/to/resource.html", "2024-01-01T00:00:00Z",
+    "A longer sentence of plain text which needs no escaping at all, as most text doesn't.",
+    "Tom & Jerry", "a < b && b > c", "say \"hi\"", "it's", "line one\nline two",
+];
+
+/// Short strings, where finding that there's nothing to escape is most of the work
+fn corpus(c: &mut Criterion) {
+    let mut group = c.benchmark_group("corpus");
+    group.throughput(Throughput::Bytes(CORPUS.iter().map(|s| s.len() as u64).sum()));
+    group.bench_function("escape_str", |b| b.iter(|| {
+        for s in CORPUS {
+            black_box(escape_str_attribute(black_box(s)));
+            black_box(escape_str_pcdata(black_box(s)));
+        }
+    }));
+    let mut out = String::new();
+    group.bench_function("escaped_display", |b| b.iter(|| {
+        out.clear();
+        for s in CORPUS {
+            write!(out, "{}", Escaped::<PcDataEscapes>::new(black_box(s))).unwrap();
+        }
+        out.len()
+    }));
+    group.bench_function("write_escaped", |b| b.iter(|| {
+        let mut writer = EventWriter::new();
+        writer.write(XmlEvent::start_element("root")).unwrap();
+        for s in CORPUS {
+            writer.write(XmlEvent::start_element("item").attr("value", s)).unwrap();
+            writer.write(XmlEvent::characters(s)).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+        }
+        writer.write(XmlEvent::end_element()).unwrap();
+        writer.into_inner()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, escape, write, corpus);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use xml_no_std::reader::{EventReader, IterSource, ParserConfig, ParserConfig2, Source, XmlEvent};
+
+/// Paragraphs of prose with an occasional reference
+fn text_heavy() -> String {
+    let mut doc = String::from("<book>\n");
+    for i in 0..200 {
+        doc.push_str("  <p>");
+        for _ in 0..4 {
+            doc.push_str("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. ");
+        }
+        doc.push_str(if i % 10 == 0 { "Tom &amp; Jerry.</p>\n" } else { "Ut enim ad minim veniam.</p>\n" });
+    }
+    doc.push_str("</book>\n");
+    doc
+}
+
+/// Small elements with attributes and a few chars of text each
+fn markup_heavy() -> String {
+    let mut doc = String::from("<items>\n");
+    for i in 0..2000 {
+        doc.push_str(&format!("  <item id=\"{i}\" kind='k{}'><name>n{i}</name><v/></item>\n", i % 7));
+    }
+    doc.push_str("</items>\n");
+    doc
+}
+
+/// A GPS track: elements with two attributes or none, which is most elements of most documents,
+/// each on a new line if `indent` is one
+fn track(indent: &str) -> String {
+    let mut doc = String::from("<gpx version='1.1'><trk><trkseg>");
+    for i in 0..10000 {
+        doc.push_str(&format!("{indent}<trkpt lat='47.{i}' lon='8.{i}'>{indent}<ele>{i}</ele>{indent}<time>2024-01-01T00:00:{}Z</time>{indent}</trkpt>", i % 60));
+    }
+    doc.push_str("</trkseg></trk></gpx>");
+    doc
+}
+
+/// A table of the same few names over and over
+fn rows() -> String {
+    let mut doc = String::from("<table xmlns='urn:t'>");
+    for i in 0..10000 {
+        doc.push_str(&format!("<row id='{i}' kind='a'><cell>{i}</cell></row>"));
+    }
+    doc.push_str("</table>");
+    doc
+}
+
+fn events(source: impl Source) -> usize {
+    EventReader::new(source).into_iter().map(Result::unwrap).count()
+}
+
+/// A slice source lets the lexer take runs of text at once, while bytes from an iterator
+/// are read a char at a time
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for (name, doc) in [("text_heavy", text_heavy()), ("markup_heavy", markup_heavy())] {
+        group.throughput(Throughput::Bytes(doc.len() as u64));
+        group.bench_function(format!("{name}/slice"), |b| b.iter(|| events(black_box(doc.as_bytes()))));
+        group.bench_function(format!("{name}/bytes"), |b| b.iter(|| events(IterSource(black_box(doc.as_bytes()).iter()))));
+    }
+    group.finish();
+}
+
+/// Start elements with few attributes, which `Attributes` keeps without an allocation
+fn attribute_light(c: &mut Criterion) {
+    let doc = track("");
+    let mut group = c.benchmark_group("attribute_light");
+    group.throughput(Throughput::Bytes(doc.len() as u64));
+    group.bench_function("track", |b| b.iter(|| events(black_box(doc.as_bytes()))));
+    group.finish();
+}
+
+/// Names, end tags and dropped whitespace are parsed in reused buffers, see `tests/allocations.rs`
+fn reused_buffers(c: &mut Criterion) {
+    let doc = track("\n    ");
+    let config = ParserConfig::new().trim_whitespace(true);
+    let mut group = c.benchmark_group("reused_buffers");
+    group.throughput(Throughput::Bytes(doc.len() as u64));
+    group.bench_function("indented_track", |b| b.iter(|| {
+        EventReader::new_with_config(black_box(doc.as_bytes()), config.clone()).into_iter().map(Result::unwrap).count()
+    }));
+    group.finish();
+}
+
+/// Keeping the element names of a repetitive document, allocated for every event or interned
+fn names(c: &mut Criterion) {
+    let doc = rows();
+    let mut group = c.benchmark_group("names");
+    group.throughput(Throughput::Bytes(doc.len() as u64));
+    group.bench_function("owned", |b| b.iter(|| {
+        let mut names = Vec::new();
+        for e in EventReader::new(black_box(doc.as_bytes())) {
+            if let XmlEvent::StartElement { name, .. } = e.unwrap() {
+                names.push(name);
+            }
+        }
+        names
+    }));
+    group.bench_function("interned", |b| b.iter(|| {
+        let mut names = Vec::new();
+        let mut reader = EventReader::new_with_config(black_box(doc.as_bytes()), ParserConfig2::new().intern_names(true));
+        loop {
+            match reader.next().unwrap() {
+                XmlEvent::StartElement { .. } => names.push(reader.element_name().unwrap().clone()),
+                XmlEvent::EndDocument => break names,
+                _ => {},
+            }
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, read, attribute_light, reused_buffers, names);
+criterion_main!(benches);
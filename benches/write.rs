@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use xml_no_std::writer::{EmitterConfig, EventWriter, Result, XmlEvent};
+
+/// Writes a table of rows whose names are literals, with `write_static` or `write`
+fn rows(config: EmitterConfig, write_static: bool) -> String {
+    let mut writer = EventWriter::new_with_config(config);
+    let mut write = |event: XmlEvent<'static>| -> Result<()> {
+        if write_static { writer.write_static(event) } else { writer.write(event) }
+    };
+    write(XmlEvent::start_element("table").into()).unwrap();
+    for _ in 0..10000 {
+        write(XmlEvent::start_element("row").attr("kind", "a").into()).unwrap();
+        write(XmlEvent::end_element().into()).unwrap();
+    }
+    write(XmlEvent::end_element().into()).unwrap();
+    writer.into_inner()
+}
+
+/// The emitter keeps the name of each open element for its end tag: copied by `write`,
+/// borrowed by `write_static`, or not kept at all
+fn names_stack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("names_stack");
+    group.bench_function("copied", |b| b.iter(|| rows(EmitterConfig::new(), false)));
+    group.bench_function("static", |b| b.iter(|| rows(EmitterConfig::new(), true)));
+    group.bench_function("none", |b| b.iter(|| {
+        // end tags need their names then
+        let mut writer = EventWriter::new_with_config(EmitterConfig::new().keep_element_names_stack(false));
+        writer.write(XmlEvent::start_element("table")).unwrap();
+        for _ in 0..10000 {
+            writer.write(XmlEvent::start_element("row").attr("kind", "a")).unwrap();
+            writer.write(XmlEvent::end_element().name("row")).unwrap();
+        }
+        writer.write(XmlEvent::end_element().name("table")).unwrap();
+        writer.into_inner()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, names_stack);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+//! Pretty-prints an XML file, or minifies it with `--minify`.
+//!
+//! `cargo run --example reformat -- tests/documents/sample_1.xml`
+
+use std::fs;
+use std::path::PathBuf;
+
+use xml_no_std::reader::WhitespaceHandling;
+use xml_no_std::{reformat, EmitterConfig, ParserConfig};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut minify = false;
+    let mut file_path = PathBuf::from("tests/documents/sample_1.xml");
+    for arg in std::env::args_os().skip(1) {
+        if arg == "--minify" {
+            minify = true;
+        } else {
+            file_path = arg.into();
+        }
+    }
+    let input = fs::read_to_string(&file_path)
+        .map_err(|e| format!("Can't open {}: {e}", file_path.display()))?;
+
+    let parser = ParserConfig::new().ignore_comments(false);
+    let output = if minify {
+        reformat(&input, parser.whitespace_handling(Some(WhitespaceHandling::Drop)), EmitterConfig::new())
+    } else {
+        reformat(&input, parser, EmitterConfig::new().perform_indent(true))
+    }.map_err(|e| format!("{}: {e}", file_path.display()))?;
+
+    println!("{output}");
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Writes generated events with a generated configuration, then reads the output back with
+//! another generated configuration. Neither side may panic, whatever the events are.
+//!
+//! Run with `cargo fuzz run round_trip` from the repository root.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xml_no_std::reader::{ParserConfig, XmlEvent};
+use xml_no_std::writer::{EmitterConfig, OwnedXmlEvent};
+use xml_no_std::EventReader;
+
+fuzz_target!(|input: (EmitterConfig, ParserConfig, Vec<OwnedXmlEvent>)| {
+    let (emitter, parser, events) = input;
+    let mut writer = emitter.create_writer();
+    for event in &events {
+        if writer.write(event.borrow()).is_err() {
+            break;
+        }
+    }
+    let document = writer.into_inner();
+    let mut reader = EventReader::new_with_config(document.as_bytes().iter(), parser);
+    // `ignore_end_of_stream` can make the reader wait forever
+    for _ in 0..=document.len() + 2 {
+        match reader.next() {
+            Ok(XmlEvent::EndDocument) | Err(_) => break,
+            Ok(_) => {},
+        }
+    }
+});
@@ -2,11 +2,23 @@
 //!
 extern crate alloc;
 
+use core::array;
 use core::fmt;
-use alloc::string::String;
+use core::hash::{Hash, Hasher};
+use core::iter::{self, FusedIterator};
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::slice;
+use core::str::FromStr;
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::{self, Vec};
 
+use crate::chars::{is_whitespace_char, is_xml_char};
 use crate::escape::{AttributeEscapes, Escaped};
-use crate::name::{Name, OwnedName};
+use crate::name::{CowName, ExpandedName, Name, NameError, OwnedName};
 
 /// A borrowed version of an XML attribute.
 ///
@@ -43,12 +55,164 @@ impl<'a> Attribute<'a> {
     pub fn new(name: Name<'a>, value: &'a str) -> Attribute<'a> {
         Attribute { name, value }
     }
+
+    /// Like `Attribute::new`, but fails if the name isn't a `QName` or the value has characters
+    /// which can't be in an XML 1.0 document, even escaped, e.g. `\0`.
+    ///
+    /// ```rust
+    /// # use xml_no_std::attribute::{Attribute, AttributeError};
+    /// # use xml_no_std::name::Name;
+    /// assert!(Attribute::try_new(Name::local("title"), "a < b").is_ok());
+    /// assert!(matches!(Attribute::try_new(Name::local("my title"), ""), Err(AttributeError::Name(_))));
+    /// let err = Attribute::try_new(Name::local("title"), "a\u{1}").unwrap_err();
+    /// assert_eq!(err, AttributeError::Value { index: 1, found: '\u{1}' });
+    /// ```
+    pub fn try_new(name: Name<'a>, value: &'a str) -> Result<Attribute<'a>, AttributeError> {
+        let attr = Attribute::new(name, value);
+        attr.validate()?;
+        Ok(attr)
+    }
+
+    /// Checks the name and the value, see `Attribute::try_new`.
+    pub fn validate(&self) -> Result<(), AttributeError> {
+        self.name.validate().map_err(AttributeError::Name)?;
+        match self.value.char_indices().find(|&(_, c)| !is_xml_char(c)) {
+            Some((index, found)) => Err(AttributeError::Value { index, found }),
+            None => Ok(()),
+        }
+    }
+
+    /// Creates an attribute in the namespace `uri`, without a prefix.
+    ///
+    /// The writer finds the prefix which is bound to `uri` when it writes the attribute,
+    /// see `EmitterConfig::prefix_generator`.
+    #[inline]
+    #[must_use]
+    pub fn in_namespace(uri: &'a str, local_name: &'a str, value: &'a str) -> Attribute<'a> {
+        Attribute::new(Name::qualified(local_name, uri, None), value)
+    }
+
+    /// Returns the value without leading and trailing whitespace.
+    #[inline]
+    #[must_use]
+    pub fn value_trimmed(&self) -> &'a str {
+        self.value.trim_matches(is_whitespace_char)
+    }
+
+    /// Parses the value, ignoring leading and trailing whitespace like XML Schema does for
+    /// types other than strings.
+    ///
+    /// ```rust
+    /// # use xml_no_std::attribute::Attribute;
+    /// # use xml_no_std::name::Name;
+    /// assert_eq!(Attribute::new(Name::local("count"), " 42 ").value_as::<u32>(), Ok(42));
+    /// let err = Attribute::new(Name::local("count"), "4x2").value_as::<u32>().unwrap_err();
+    /// assert_eq!(err.to_string(), "count=\"4x2\": invalid digit found in string");
+    /// ```
+    pub fn value_as<T>(&self) -> Result<T, AttrParseError>
+        where T: FromStr, T::Err: fmt::Display
+    {
+        self.value_trimmed().parse().map_err(|e: T::Err| self.parse_error(e.to_string()))
+    }
+
+    /// Parses the value as an XML Schema boolean: `true`, `false`, `1` or `0`, ignoring leading
+    /// and trailing whitespace.
+    pub fn value_as_bool(&self) -> Result<bool, AttrParseError> {
+        match self.value_trimmed() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(self.parse_error("expected true, false, 1 or 0".into())),
+        }
+    }
+
+    #[cold]
+    fn parse_error(&self, reason: String) -> AttrParseError {
+        AttrParseError {
+            name: self.name.to_repr(),
+            value: self.value.into(),
+            reason,
+        }
+    }
+}
+
+/// Why an attribute can't be written, see `Attribute::try_new`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AttributeError {
+    /// The name isn't a `QName`.
+    Name(NameError),
+
+    /// The value has a character which isn't allowed in XML.
+    Value {
+        /// Byte offset of the character in the value.
+        index: usize,
+
+        /// The character.
+        found: char,
+    },
 }
 
+impl fmt::Display for AttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeError::Name(e) => e.fmt(f),
+            AttributeError::Value { index, found } =>
+                write!(f, "attribute value has invalid character {:?} at index {index}", found),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for AttributeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            // displayed as the name error
+            AttributeError::Name(e) => e.source(),
+            AttributeError::Value { .. } => None,
+        }
+    }
+}
+
+/// An attribute value which couldn't be parsed by `Attribute::value_as()` or `value_as_bool()`.
+///
+/// It's displayed as the attribute, followed by the reason.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AttrParseError {
+    name: String,
+    value: String,
+    reason: String,
+}
+
+impl AttrParseError {
+    /// The qualified name of the attribute, as written in the document, e.g. `xsi:nil`.
+    #[must_use]
+    pub fn name(&self) -> &str { &self.name }
+
+    /// The value which couldn't be parsed.
+    #[must_use]
+    pub fn value(&self) -> &str { &self.value }
+
+    /// Why the value couldn't be parsed, e.g. the parse error's message.
+    #[must_use]
+    pub fn reason(&self) -> &str { &self.reason }
+}
+
+impl fmt::Display for AttrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=\"{}\": {}", self.name, Escaped::<AttributeEscapes>::new(&self.value), self.reason)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for AttrParseError {}
+
 /// An owned version of an XML attribute.
 ///
 /// Consists of an owned qualified name and an owned string value.
-#[derive(Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+#[derive(Clone, Default, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OwnedAttribute {
     /// Attribute name.
     pub name: OwnedName,
@@ -76,6 +240,34 @@ impl OwnedAttribute {
             value: value.into(),
         }
     }
+
+    /// Like `OwnedAttribute::new`, but checks the name and the value, see `Attribute::try_new`.
+    pub fn try_new<S: Into<String>>(name: OwnedName, value: S) -> Result<OwnedAttribute, AttributeError> {
+        let attr = OwnedAttribute::new(name, value);
+        attr.borrow().validate()?;
+        Ok(attr)
+    }
+
+    /// Returns the value without leading and trailing whitespace, see `Attribute::value_trimmed`.
+    #[inline]
+    #[must_use]
+    pub fn value_trimmed(&self) -> &str {
+        self.value.trim_matches(is_whitespace_char)
+    }
+
+    /// Parses the value, see `Attribute::value_as`.
+    #[inline]
+    pub fn value_as<T>(&self) -> Result<T, AttrParseError>
+        where T: FromStr, T::Err: fmt::Display
+    {
+        self.borrow().value_as()
+    }
+
+    /// Parses the value as an XML Schema boolean, see `Attribute::value_as_bool`.
+    #[inline]
+    pub fn value_as_bool(&self) -> Result<bool, AttrParseError> {
+        self.borrow().value_as_bool()
+    }
 }
 
 impl fmt::Display for OwnedAttribute {
@@ -84,11 +276,813 @@ impl fmt::Display for OwnedAttribute {
     }
 }
 
+/// An attribute which borrows or owns its name and value.
+///
+/// It's meant for pipelines which read elements, change some attributes and write them back:
+/// attributes made from `Attribute` or `&OwnedAttribute` borrow everything, and only the
+/// values which are replaced allocate. The writer accepts them through `borrow()` or
+/// `StartElementBuilder::attrs()`.
+///
+/// ```rust
+/// # use std::borrow::Cow;
+/// # use xml_no_std::attribute::{CowAttribute, OwnedAttribute};
+/// # use xml_no_std::name::OwnedName;
+/// let read = [
+///     OwnedAttribute::new(OwnedName::local("id"), "1"),
+///     OwnedAttribute::new(OwnedName::local("href"), "http://example.com"),
+/// ];
+/// let mut rewritten: Vec<CowAttribute<'_>> = read.iter().map(CowAttribute::from).collect();
+/// rewritten[1].value = Cow::Owned(rewritten[1].value.replace("http:", "https:"));
+///
+/// assert!(matches!(rewritten[0].value, Cow::Borrowed(_)));
+/// assert_eq!(rewritten[1].to_string(), "href=\"https://example.com\"");
+/// ```
+#[derive(Clone, Eq, PartialEq, Hash, Debug, PartialOrd, Ord)]
+pub struct CowAttribute<'a> {
+    /// Attribute name.
+    pub name: CowName<'a>,
+
+    /// Attribute value.
+    pub value: Cow<'a, str>,
+}
+
+impl<'a> CowAttribute<'a> {
+    /// Creates an attribute from anything which converts into a name and a value.
+    #[inline]
+    pub fn new<N, V>(name: N, value: V) -> CowAttribute<'a>
+        where N: Into<CowName<'a>>, V: Into<Cow<'a, str>>
+    {
+        CowAttribute {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Returns a borrowed `Attribute` out of this one.
+    #[inline]
+    #[must_use]
+    pub fn borrow(&self) -> Attribute<'_> {
+        Attribute {
+            name: self.name.borrow(),
+            value: &self.value,
+        }
+    }
+
+    /// Converts into an `OwnedAttribute`, copying only the borrowed parts.
+    #[must_use]
+    pub fn into_owned(self) -> OwnedAttribute {
+        OwnedAttribute {
+            name: self.name.into_owned(),
+            value: self.value.into_owned(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for CowAttribute<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.borrow(), f)
+    }
+}
+
+impl<'a> From<Attribute<'a>> for CowAttribute<'a> {
+    #[inline]
+    fn from(a: Attribute<'a>) -> CowAttribute<'a> {
+        CowAttribute {
+            name: a.name.into(),
+            value: Cow::Borrowed(a.value),
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedAttribute> for CowAttribute<'a> {
+    #[inline]
+    fn from(a: &'a OwnedAttribute) -> CowAttribute<'a> {
+        a.borrow().into()
+    }
+}
+
+impl From<OwnedAttribute> for CowAttribute<'static> {
+    #[inline]
+    fn from(a: OwnedAttribute) -> CowAttribute<'static> {
+        CowAttribute {
+            name: a.name.into(),
+            value: Cow::Owned(a.value),
+        }
+    }
+}
+
+impl<'a> From<CowAttribute<'a>> for OwnedAttribute {
+    #[inline]
+    fn from(a: CowAttribute<'a>) -> OwnedAttribute {
+        a.into_owned()
+    }
+}
+
+impl<'a> From<&'a CowAttribute<'_>> for Attribute<'a> {
+    #[inline]
+    fn from(a: &'a CowAttribute<'_>) -> Attribute<'a> {
+        a.borrow()
+    }
+}
+
+impl<'a> From<&'a OwnedAttribute> for Attribute<'a> {
+    #[inline]
+    fn from(a: &'a OwnedAttribute) -> Attribute<'a> {
+        a.borrow()
+    }
+}
+
+impl FromStr for OwnedAttribute {
+    type Err = AttributeSyntaxError;
+
+    /// Parses an attribute as it's written in a start tag, e.g. `xml:lang="en"`.
+    ///
+    /// The name must be a `QName`, and the value is quoted with `"` or `'`. Whitespace is
+    /// allowed around the `=` and the whole attribute. References in the value are expanded
+    /// with `escape::unescape`, so only predefined entities are known. The name gets no namespace.
+    ///
+    /// ```rust
+    /// # use xml_no_std::attribute::{AttributeSyntaxError, OwnedAttribute};
+    /// let attr: OwnedAttribute = " title = 'Tom &amp; &#x4A;erry' ".parse().unwrap();
+    /// assert_eq!((attr.name.local_name.as_str(), attr.value.as_str()), ("title", "Tom & Jerry"));
+    /// assert_eq!("a=\"b".parse::<OwnedAttribute>(), Err(AttributeSyntaxError::UnterminatedQuote));
+    /// ```
+    fn from_str(s: &str) -> Result<OwnedAttribute, AttributeSyntaxError> {
+        let s = s.trim_matches(is_whitespace_char);
+        let (name, rest) = s.split_once('=').ok_or(AttributeSyntaxError::MissingEquals)?;
+        let name = OwnedName::try_from_str(name.trim_end_matches(is_whitespace_char))
+            .map_err(AttributeSyntaxError::InvalidName)?;
+
+        let rest = rest.trim_start_matches(is_whitespace_char);
+        let quote = match rest.chars().next() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Err(AttributeSyntaxError::MissingQuote),
+        };
+        let rest = &rest[1..];
+        let end = rest.find(quote).ok_or(AttributeSyntaxError::UnterminatedQuote)?;
+        if end + 1 != rest.len() {
+            return Err(AttributeSyntaxError::TrailingCharacters);
+        }
+        let raw = &rest[..end];
+        if raw.contains('<') {
+            return Err(AttributeSyntaxError::InvalidValue("'<' is not allowed in attributes".into()));
+        }
+        let value = crate::escape::unescape(raw).map_err(|e| AttributeSyntaxError::InvalidValue(e.to_string()))?;
+        Ok(OwnedAttribute { name, value: value.into_owned() })
+    }
+}
+
+/// A string which couldn't be parsed into an `OwnedAttribute`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AttributeSyntaxError {
+    /// There's no `=` after the name.
+    MissingEquals,
+
+    /// The name isn't a valid `QName`.
+    InvalidName(NameError),
+
+    /// The value doesn't start with `"` or `'`.
+    MissingQuote,
+
+    /// The value has no closing quote.
+    UnterminatedQuote,
+
+    /// There's something after the closing quote.
+    TrailingCharacters,
+
+    /// The value has a `<` or a reference which can't be expanded, the message says which.
+    InvalidValue(String),
+}
+
+impl fmt::Display for AttributeSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeSyntaxError::MissingEquals => f.write_str("expected '=' after the attribute name"),
+            AttributeSyntaxError::InvalidName(e) => e.fmt(f),
+            AttributeSyntaxError::MissingQuote => f.write_str("expected a quoted attribute value"),
+            AttributeSyntaxError::UnterminatedQuote => f.write_str("unterminated attribute value"),
+            AttributeSyntaxError::TrailingCharacters => f.write_str("unexpected characters after the attribute value"),
+            AttributeSyntaxError::InvalidValue(e) => write!(f, "invalid attribute value: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for AttributeSyntaxError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            // displayed as the name error
+            AttributeSyntaxError::InvalidName(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<(OwnedName, String)> for OwnedAttribute {
+    #[inline]
+    fn from((name, value): (OwnedName, String)) -> OwnedAttribute {
+        OwnedAttribute { name, value }
+    }
+}
+
+impl From<(&str, &str)> for OwnedAttribute {
+    /// Creates an attribute without a namespace from its local name and value.
+    #[inline]
+    fn from((local_name, value): (&str, &str)) -> OwnedAttribute {
+        OwnedAttribute::new(OwnedName::local(local_name), value)
+    }
+}
+
+impl<'a> From<(&'a str, &'a str)> for Attribute<'a> {
+    /// Creates an attribute without a namespace from its local name and value.
+    #[inline]
+    fn from((local_name, value): (&'a str, &'a str)) -> Attribute<'a> {
+        Attribute::new(Name::local(local_name), value)
+    }
+}
+
+/// An attribute as it's written in a document, with its value not unescaped yet, see
+/// `EventReader::raw_attributes`. It borrows the reader.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RawAttribute<'a> {
+    /// The qualified name, e.g. `xml:lang`.
+    pub name: &'a str,
+
+    /// The text between the quotes, with its references.
+    pub raw_value: &'a str,
+}
+
+impl<'a> RawAttribute<'a> {
+    /// Unescapes the value, expanding character references and the predefined entities with
+    /// `escape::unescape`. It's borrowed if there are none.
+    ///
+    /// Entities declared in the document's DTD are expanded by
+    /// `EventReader::unescape_attribute_value` instead.
+    ///
+    /// # Errors
+    ///
+    /// A reference is broken or to an entity which isn't predefined.
+    #[inline]
+    pub fn value(&self) -> Result<Cow<'a, str>, crate::escape::UnescapeError> {
+        crate::escape::unescape(self.raw_value)
+    }
+}
+
+/// An attribute name which is used more than once, see `Attributes::try_into_map()`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DuplicateAttribute {
+    /// The repeated name.
+    pub name: OwnedName,
+}
+
+impl fmt::Display for DuplicateAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "attribute {} is specified more than once", self.name.borrow().repr_display())
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for DuplicateAttribute {}
+
+/// Attributes of an element, in document order.
+///
+/// Dereferences to a slice of attributes, and has lookups by name. Lookups are linear scans,
+/// which are the fastest for the few attributes elements usually have.
+///
+/// Up to 2 attributes are stored inline, so most elements don't need an allocation for their
+/// list. Converting them to a `Vec` allocates for such short lists, iterating by value doesn't.
+///
+/// ```rust
+/// # use xml_no_std::attribute::{Attributes, OwnedAttribute};
+/// # use xml_no_std::name::OwnedName;
+/// let attributes: Attributes = [
+///     OwnedAttribute::new(OwnedName::local("id"), "1"),
+///     OwnedAttribute::new(OwnedName::qualified("type", "http://www.w3.org/2001/XMLSchema-instance", Some("xsi")), "t"),
+/// ].into_iter().collect();
+///
+/// assert_eq!(attributes.get("id").map(|a| &*a.value), Some("1"));
+/// assert!(attributes.get("type").is_none());
+/// assert!(attributes.get_ns("http://www.w3.org/2001/XMLSchema-instance", "type").is_some());
+/// assert!(attributes.get_qname(Some("xsi"), "type").is_some());
+/// assert_eq!(attributes[0].name.local_name, "id");
+/// ```
+#[derive(Clone)]
+pub struct Attributes(Storage);
+
+/// The number of attributes stored without an allocation. Two covers most elements, and every
+/// reader event is as large as these slots, so more would cost more than the allocation they save.
+const INLINE_ATTRIBUTES: usize = 2;
+
+/// Fills the unused inline slots, without allocating
+const EMPTY_ATTRIBUTE: OwnedAttribute = OwnedAttribute {
+    name: OwnedName { local_name: String::new(), namespace: None, prefix: None },
+    value: String::new(),
+};
+
+// being large is the point, it's what saves the allocation
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Storage {
+    /// The first `usize` slots are attributes, the others are `EMPTY_ATTRIBUTE`
+    Inline(usize, [OwnedAttribute; INLINE_ATTRIBUTES]),
+    Heap(Vec<OwnedAttribute>),
+}
+
+impl Attributes {
+    /// Returns an empty list of attributes.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Attributes {
+        Attributes(Storage::Inline(0, [EMPTY_ATTRIBUTE; INLINE_ATTRIBUTES]))
+    }
+
+    /// Adds an attribute after the others.
+    ///
+    /// Attributes with the same name as one already in the list are not checked for.
+    #[inline]
+    pub fn push(&mut self, attribute: OwnedAttribute) {
+        match &mut self.0 {
+            Storage::Inline(len, slots) if *len < INLINE_ATTRIBUTES => {
+                slots[*len] = attribute;
+                *len += 1;
+            },
+            Storage::Inline(..) => {
+                let mut heap = Vec::with_capacity(2 * INLINE_ATTRIBUTES);
+                self.move_inline_to(&mut heap);
+                heap.push(attribute);
+                self.0 = Storage::Heap(heap);
+            },
+            Storage::Heap(heap) => heap.push(attribute),
+        }
+    }
+
+    /// Removes the last attribute.
+    #[inline]
+    pub fn pop(&mut self) -> Option<OwnedAttribute> {
+        match &mut self.0 {
+            Storage::Inline(len, slots) => {
+                *len = len.checked_sub(1)?;
+                Some(mem::replace(&mut slots[*len], EMPTY_ATTRIBUTE))
+            },
+            Storage::Heap(heap) => heap.pop(),
+        }
+    }
+
+    /// Removes all attributes, keeping the allocation if there's one.
+    #[inline]
+    pub fn clear(&mut self) {
+        match &mut self.0 {
+            Storage::Inline(len, slots) => {
+                slots[..*len].iter_mut().for_each(|attr| *attr = EMPTY_ATTRIBUTE);
+                *len = 0;
+            },
+            Storage::Heap(heap) => heap.clear(),
+        }
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        match &mut self.0 {
+            Storage::Inline(len, _) if *len + additional <= INLINE_ATTRIBUTES => Ok(()),
+            Storage::Inline(len, _) => {
+                let mut heap = Vec::new();
+                heap.try_reserve((*len + additional).max(2 * INLINE_ATTRIBUTES))?;
+                self.move_inline_to(&mut heap);
+                self.0 = Storage::Heap(heap);
+                Ok(())
+            },
+            Storage::Heap(heap) => heap.try_reserve(additional),
+        }
+    }
+
+    /// Moves inline attributes to the end of `heap`, which must have room for them
+    fn move_inline_to(&mut self, heap: &mut Vec<OwnedAttribute>) {
+        if let Storage::Inline(len, slots) = &mut self.0 {
+            heap.extend(slots[..*len].iter_mut().map(|attr| mem::replace(attr, EMPTY_ATTRIBUTE)));
+            *len = 0;
+        }
+    }
+
+    /// Returns the attribute with the local name `local` and without a namespace.
+    ///
+    /// That's how unprefixed attributes are read, whatever the default namespace.
+    #[must_use]
+    pub fn get(&self, local: &str) -> Option<&OwnedAttribute> {
+        self.iter().find(|attr| attr.name.matches(None, local))
+    }
+
+    /// Returns the attribute with the local name `local` in the `namespace`, whatever its prefix.
+    ///
+    /// An empty `namespace` finds attributes without a namespace, same as `get()`.
+    #[must_use]
+    pub fn get_ns(&self, namespace: &str, local: &str) -> Option<&OwnedAttribute> {
+        self.iter().find(|attr| attr.name.matches(Some(namespace), local))
+    }
+
+    /// Returns the attribute which is written as `prefix:local`, or just `local` if `prefix` is `None`.
+    #[must_use]
+    pub fn get_qname(&self, prefix: Option<&str>, local: &str) -> Option<&OwnedAttribute> {
+        self.iter().find(|attr| attr.name.prefix_ref() == prefix && attr.name.local_is(local))
+    }
+
+    /// Returns an iterator over the attributes in document order.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, OwnedAttribute> {
+        self.as_slice().iter()
+    }
+
+    /// Returns the attributes as a slice.
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[OwnedAttribute] {
+        match &self.0 {
+            Storage::Inline(len, slots) => &slots[..*len],
+            Storage::Heap(heap) => heap,
+        }
+    }
+
+    /// Returns the attributes as a vector.
+    #[inline]
+    #[must_use]
+    pub fn into_vec(mut self) -> Vec<OwnedAttribute> {
+        match self.0 {
+            Storage::Inline(len, _) => {
+                let mut heap = Vec::with_capacity(len);
+                self.move_inline_to(&mut heap);
+                heap
+            },
+            Storage::Heap(heap) => heap,
+        }
+    }
+
+    /// Converts into a map from names to values, failing if a name is used more than once.
+    ///
+    /// Names are compared whole as map keys are, so `a:x` and `b:x` are different keys even if
+    /// both prefixes are bound to the same namespace.
+    pub fn try_into_map(self) -> Result<BTreeMap<OwnedName, String>, DuplicateAttribute> {
+        let mut map = BTreeMap::new();
+        for attr in self {
+            if map.contains_key(&attr.name) {
+                return Err(DuplicateAttribute { name: attr.name });
+            }
+            map.insert(attr.name, attr.value);
+        }
+        Ok(map)
+    }
+
+    /// Converts into a map from names to values, where a later attribute with the same name
+    /// overrides the earlier ones, e.g. for appending overrides to a list of defaults.
+    ///
+    /// ```rust
+    /// # use xml_no_std::attribute::Attributes;
+    /// let mut attributes: Attributes = [("id", "1"), ("class", "a")].into_iter().collect();
+    /// attributes.push(("class", "b").into());
+    /// let map = attributes.into_map_last_wins();
+    /// assert_eq!(map.values().collect::<Vec<_>>(), ["b", "1"]);
+    /// ```
+    #[must_use]
+    pub fn into_map_last_wins(self) -> BTreeMap<OwnedName, String> {
+        self.into_iter().map(|attr| (attr.name, attr.value)).collect()
+    }
+}
+
+impl Deref for Attributes {
+    type Target = [OwnedAttribute];
+
+    #[inline]
+    fn deref(&self) -> &[OwnedAttribute] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for Attributes {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [OwnedAttribute] {
+        match &mut self.0 {
+            Storage::Inline(len, slots) => &mut slots[..*len],
+            Storage::Heap(heap) => heap,
+        }
+    }
+}
+
+impl Default for Attributes {
+    #[inline]
+    fn default() -> Attributes {
+        Attributes::new()
+    }
+}
+
+impl PartialEq for Attributes {
+    #[inline]
+    fn eq(&self, other: &Attributes) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Attributes {}
+
+impl PartialOrd for Attributes {
+    #[inline]
+    fn partial_cmp(&self, other: &Attributes) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Attributes {
+    #[inline]
+    fn cmp(&self, other: &Attributes) -> core::cmp::Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl Hash for Attributes {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state);
+    }
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Attributes").field(&self.as_slice()).finish()
+    }
+}
+
+/// Serialized as a sequence of attributes.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Attributes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Attributes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Attributes, D::Error> {
+        Vec::<OwnedAttribute>::deserialize(deserializer).map(Attributes::from)
+    }
+}
+
+impl From<Vec<OwnedAttribute>> for Attributes {
+    #[inline]
+    fn from(attributes: Vec<OwnedAttribute>) -> Attributes {
+        Attributes(Storage::Heap(attributes))
+    }
+}
+
+impl From<Attributes> for Vec<OwnedAttribute> {
+    #[inline]
+    fn from(attributes: Attributes) -> Vec<OwnedAttribute> {
+        attributes.into_vec()
+    }
+}
+
+impl FromIterator<OwnedAttribute> for Attributes {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = OwnedAttribute>>(iter: T) -> Attributes {
+        let mut attributes = Attributes::new();
+        attributes.extend(iter);
+        attributes
+    }
+}
+
+impl FromIterator<(OwnedName, String)> for Attributes {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = (OwnedName, String)>>(iter: T) -> Attributes {
+        iter.into_iter().map(OwnedAttribute::from).collect()
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a str)> for Attributes {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a str)>>(iter: T) -> Attributes {
+        iter.into_iter().map(OwnedAttribute::from).collect()
+    }
+}
+
+impl From<BTreeMap<OwnedName, String>> for Attributes {
+    /// The attributes are in the order of the map's keys.
+    #[inline]
+    fn from(map: BTreeMap<OwnedName, String>) -> Attributes {
+        map.into_iter().collect()
+    }
+}
+
+impl From<&[OwnedAttribute]> for Attributes {
+    #[inline]
+    fn from(attributes: &[OwnedAttribute]) -> Attributes {
+        attributes.iter().cloned().collect()
+    }
+}
+
+impl Extend<OwnedAttribute> for Attributes {
+    #[inline]
+    fn extend<T: IntoIterator<Item = OwnedAttribute>>(&mut self, iter: T) {
+        match &mut self.0 {
+            Storage::Heap(heap) => heap.extend(iter),
+            Storage::Inline(..) => iter.into_iter().for_each(|attr| self.push(attr)),
+        }
+    }
+}
+
+impl IntoIterator for Attributes {
+    type Item = OwnedAttribute;
+    type IntoIter = IntoIter;
+
+    #[inline]
+    fn into_iter(self) -> IntoIter {
+        IntoIter(match self.0 {
+            Storage::Inline(len, slots) => IntoIterStorage::Inline(slots.into_iter().take(len)),
+            Storage::Heap(heap) => IntoIterStorage::Heap(heap.into_iter()),
+        })
+    }
+}
+
+/// An iterator which moves the attributes out of `Attributes`, without allocating.
+#[derive(Clone, Debug)]
+pub struct IntoIter(IntoIterStorage);
+
+#[derive(Clone, Debug)]
+enum IntoIterStorage {
+    Inline(iter::Take<array::IntoIter<OwnedAttribute, INLINE_ATTRIBUTES>>),
+    Heap(vec::IntoIter<OwnedAttribute>),
+}
+
+impl Iterator for IntoIter {
+    type Item = OwnedAttribute;
+
+    #[inline]
+    fn next(&mut self) -> Option<OwnedAttribute> {
+        match &mut self.0 {
+            IntoIterStorage::Inline(iter) => iter.next(),
+            IntoIterStorage::Heap(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IntoIterStorage::Inline(iter) => iter.size_hint(),
+            IntoIterStorage::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for IntoIter {
+    #[inline]
+    fn next_back(&mut self) -> Option<OwnedAttribute> {
+        match &mut self.0 {
+            IntoIterStorage::Inline(iter) => iter.next_back(),
+            IntoIterStorage::Heap(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl FusedIterator for IntoIter {}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = &'a OwnedAttribute;
+    type IntoIter = slice::Iter<'a, OwnedAttribute>;
+
+    #[inline]
+    fn into_iter(self) -> slice::Iter<'a, OwnedAttribute> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Attributes {
+    type Item = &'a mut OwnedAttribute;
+    type IntoIter = slice::IterMut<'a, OwnedAttribute>;
+
+    #[inline]
+    fn into_iter(self) -> slice::IterMut<'a, OwnedAttribute> {
+        self.iter_mut()
+    }
+}
+
+/// Checks that two lists have the same attributes, whatever their order.
+///
+/// Attributes are matched by their namespace and local name, so prefixes don't matter, and
+/// must have equal values. Lists which have several attributes with the same name are
+/// ambiguous and never equal. `attributes_diff()` tells what the differences are.
+///
+/// ```rust
+/// # use xml_no_std::attribute::{attributes_set_eq, OwnedAttribute};
+/// # use xml_no_std::name::OwnedName;
+/// let a = [OwnedAttribute::new(OwnedName::local("x"), "1"), OwnedAttribute::new(OwnedName::local("y"), "2")];
+/// let b = [a[1].clone(), a[0].clone()];
+/// assert!(attributes_set_eq(&a, &b));
+/// assert!(!attributes_set_eq(&a, &a[..1]));
+/// ```
+#[must_use]
+pub fn attributes_set_eq(a: &[OwnedAttribute], b: &[OwnedAttribute]) -> bool {
+    a.len() == b.len() && attributes_diff(a, b).is_empty()
+}
+
+/// Compares two lists of attributes like `attributes_set_eq()`, and returns the differences.
+#[must_use]
+pub fn attributes_diff<'a>(a: &'a [OwnedAttribute], b: &'a [OwnedAttribute]) -> AttributesDiff<'a> {
+    let mut diff = AttributesDiff::default();
+    let a_names = index_by_name(a, &mut diff.duplicates);
+    let b_names = index_by_name(b, &mut diff.duplicates);
+
+    for (name, attr) in a.iter().map(|attr| (attr.name.expanded(), attr)) {
+        if !ptr::eq(a_names[&name], attr) {
+            continue;
+        }
+        match b_names.get(&name) {
+            None => diff.only_in_a.push(attr),
+            Some(other) if other.value != attr.value => diff.value_differs.push((attr, other)),
+            Some(_) => {},
+        }
+    }
+    for (name, attr) in b.iter().map(|attr| (attr.name.expanded(), attr)) {
+        if ptr::eq(b_names[&name], attr) && !a_names.contains_key(&name) {
+            diff.only_in_b.push(attr);
+        }
+    }
+    diff
+}
+
+/// Maps names to their first attributes, and collects the attributes which repeat a name.
+fn index_by_name<'a>(attributes: &'a [OwnedAttribute], duplicates: &mut Vec<&'a OwnedAttribute>)
+    -> BTreeMap<ExpandedName<'a>, &'a OwnedAttribute>
+{
+    let mut names = BTreeMap::new();
+    for attr in attributes {
+        if names.insert(attr.name.expanded(), attr).is_some() {
+            duplicates.push(attr);
+        }
+    }
+    // `insert()` keeps the last attribute, put the first ones back
+    for attr in attributes.iter().rev() {
+        names.insert(attr.name.expanded(), attr);
+    }
+    names
+}
+
+/// The differences between two lists of attributes, see `attributes_diff()`.
+///
+/// Attributes are listed in document order. It's displayed with one difference per line,
+/// for assertion messages.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct AttributesDiff<'a> {
+    /// Attributes of the first list whose names aren't in the second one.
+    pub only_in_a: Vec<&'a OwnedAttribute>,
+
+    /// Attributes of the second list whose names aren't in the first one.
+    pub only_in_b: Vec<&'a OwnedAttribute>,
+
+    /// Pairs of attributes with the same name but different values, first list first.
+    pub value_differs: Vec<(&'a OwnedAttribute, &'a OwnedAttribute)>,
+
+    /// Attributes of either list which repeat a name already in the same list.
+    pub duplicates: Vec<&'a OwnedAttribute>,
+}
+
+impl AttributesDiff<'_> {
+    /// Returns `true` if the lists have the same attributes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty()
+            && self.value_differs.is_empty() && self.duplicates.is_empty()
+    }
+}
+
+impl fmt::Display for AttributesDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for attr in &self.only_in_a {
+            writeln!(f, "only in a: {attr}")?;
+        }
+        for attr in &self.only_in_b {
+            writeln!(f, "only in b: {attr}")?;
+        }
+        for (a, b) in &self.value_differs {
+            writeln!(f, "values differ: {a} != {b}")?;
+        }
+        for attr in &self.duplicates {
+            writeln!(f, "duplicate: {attr}")?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Attribute;
+    extern crate alloc;
 
-    use crate::name::Name;
+    use alloc::format;
+    use alloc::vec::Vec;
+
+    use super::{attributes_diff, attributes_set_eq, Attribute, AttributeError, AttributeSyntaxError, Attributes, CowAttribute, DuplicateAttribute, OwnedAttribute};
+
+    use crate::name::{Name, OwnedName};
 
     #[test]
     fn attribute_display() {
@@ -102,4 +1096,146 @@ mod tests {
             "{urn:namespace}n:attribute=\"its value with &gt; &amp; &quot; &apos; &lt; weird symbols\""
         );
     }
+
+    #[test]
+    fn typed_values() {
+        let attr = |value: &str| OwnedAttribute::new(OwnedName::qualified("v", "urn:x", Some("x")), value);
+        for (value, expected) in [("true", true), (" 1\n", true), ("false", false), ("\t0", false)] {
+            assert_eq!(attr(value).value_as_bool(), Ok(expected), "{value}");
+        }
+        for value in ["", "TRUE", "yes", "01", "t rue"] {
+            let err = attr(value).value_as_bool().unwrap_err();
+            assert_eq!((err.name(), err.value()), ("x:v", value));
+        }
+
+        assert_eq!(attr(" -1.5 ").value_as::<f64>(), Ok(-1.5));
+        assert_eq!(attr(" a b ").value_trimmed(), "a b");
+        let err = attr("<42>").value_as::<i8>().unwrap_err();
+        assert_eq!(err.reason(), "invalid digit found in string");
+        assert_eq!(err.to_string(), "x:v=\"&lt;42&gt;\": invalid digit found in string");
+    }
+
+    #[test]
+    fn cow_attribute_conversions() {
+        let owned = OwnedAttribute::new(OwnedName::qualified("v", "urn:x", Some("x")), "a < \"b\"");
+        let cow = CowAttribute::from(&owned);
+        assert_eq!(cow.to_string(), owned.to_string());
+        assert_eq!(cow.borrow(), owned.borrow());
+        assert_eq!(Attribute::from(&cow), Attribute::from(&owned));
+        assert_eq!(cow.clone().into_owned(), owned);
+        assert_eq!(CowAttribute::from(owned.clone()), cow);
+        assert_eq!(CowAttribute::new(Name::local("v"), "1").into_owned(), OwnedAttribute::new(OwnedName::local("v"), "1"));
+    }
+
+    #[test]
+    fn set_comparison() {
+        let attr = |prefix: Option<&str>, local: &str, value: &str| match prefix {
+            Some(p) => OwnedAttribute::new(OwnedName::qualified(local, "urn:x", Some(p)), value),
+            None => OwnedAttribute::new(OwnedName::local(local), value),
+        };
+        let a = [attr(None, "id", "1"), attr(Some("x"), "v", "2"), attr(None, "a", "3")];
+        let b = [attr(Some("y"), "v", "2"), attr(None, "id", "1"), attr(None, "a", "3")];
+        assert!(attributes_set_eq(&a, &b));
+        assert!(attributes_set_eq(&[], &[]));
+
+        let c = [attr(None, "v", "2"), attr(None, "id", "1"), attr(None, "a", "4"), attr(None, "id", "5")];
+        assert!(!attributes_set_eq(&a, &c));
+        let diff = attributes_diff(&a, &c);
+        assert_eq!(diff.only_in_a, [&a[1]]);
+        assert_eq!(diff.only_in_b, [&c[0]]);
+        assert_eq!(diff.value_differs, [(&a[2], &c[2])]);
+        assert_eq!(diff.duplicates, [&c[3]]);
+        assert_eq!(diff.to_string(), "only in a: {urn:x}x:v=\"2\"\nonly in b: v=\"2\"\nvalues differ: a=\"3\" != a=\"4\"\nduplicate: id=\"5\"\n");
+
+        let dup = [attr(None, "id", "1"), attr(None, "id", "1")];
+        assert!(!attributes_set_eq(&dup, &dup));
+    }
+
+    #[test]
+    fn map_conversions() {
+        let x = |local: &str, prefix: &str| OwnedName::qualified(local, "urn:x", Some(prefix));
+        let attributes: Attributes = [
+            (x("v", "x"), "1".into()),
+            (OwnedName::local("id"), "2".into()),
+            (x("v", "y"), "3".into()),
+        ].into_iter().collect();
+        let map = attributes.clone().try_into_map().unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&OwnedName::local("id")], "2");
+
+        let from_map = Attributes::from(map);
+        assert_eq!(from_map.len(), 3);
+        assert_eq!(from_map.get_qname(Some("y"), "v").map(|a| &*a.value), Some("3"));
+
+        let mut repeated = Attributes::from(&attributes[..]);
+        repeated.push(("id", "4").into());
+        assert_eq!(repeated.clone().try_into_map(), Err(DuplicateAttribute { name: OwnedName::local("id") }));
+        assert_eq!(repeated.clone().try_into_map().unwrap_err().to_string(), "attribute id is specified more than once");
+        assert_eq!(repeated.into_map_last_wins()[&OwnedName::local("id")], "4");
+
+        assert_eq!(Attribute::from(("a", "b")), Attribute::new(Name::local("a"), "b"));
+        assert_eq!(OwnedAttribute::from(("a", "b")), Attribute::new(Name::local("a"), "b").to_owned());
+    }
+
+    #[test]
+    fn inline_and_spilled() {
+        let attr = |i: usize| OwnedAttribute::new(OwnedName::local(format!("a{i}")), "v");
+        let mut attributes = Attributes::new();
+        let mut vec = Vec::new();
+        for i in 0..10 {
+            assert_eq!(attributes, Attributes::from(vec.clone()));
+            assert_eq!(attributes.clone().into_vec(), vec);
+            assert!(attributes.clone().into_iter().rev().eq(vec.iter().rev().cloned()));
+            assert_eq!(attributes.iter().rev().next(), vec.last());
+            attributes.push(attr(i));
+            vec.push(attr(i));
+        }
+        attributes[9].value = "w".into();
+        assert_eq!(attributes.get("a9").map(|a| &*a.value), Some("w"));
+        assert_eq!(attributes.into_iter().map(|a| a.name.local_name).nth(5).as_deref(), Some("a5"));
+
+        let mut short: Attributes = vec[..2].iter().cloned().collect();
+        short.extend(vec[2..6].iter().cloned());
+        assert_eq!(&short[..], &vec[..6]);
+        assert!(short < Attributes::from(&vec[..]));
+    }
+
+    #[test]
+    fn attribute_from_str() {
+        let attr: OwnedAttribute = "\txml:lang\n=\"en &lt;&#65;&gt;\" ".parse().unwrap();
+        assert_eq!(attr, OwnedAttribute::new("xml:lang".parse().unwrap(), "en <A>"));
+        let attr: OwnedAttribute = "a='say \"hi\"'".parse().unwrap();
+        assert_eq!(attr.value, "say \"hi\"");
+
+        for (input, expected) in [
+            ("a", AttributeSyntaxError::MissingEquals),
+            ("a b", AttributeSyntaxError::MissingEquals),
+            ("a=b", AttributeSyntaxError::MissingQuote),
+            ("a=", AttributeSyntaxError::MissingQuote),
+            ("a='b", AttributeSyntaxError::UnterminatedQuote),
+            ("a=\"b'", AttributeSyntaxError::UnterminatedQuote),
+            ("a='b' c='d'", AttributeSyntaxError::TrailingCharacters),
+            ("a=''x", AttributeSyntaxError::TrailingCharacters),
+        ] {
+            assert_eq!(input.parse::<OwnedAttribute>(), Err(expected), "{input}");
+        }
+        for input in ["=''", "1a=''", "a:b:c=''", "a b=''"] {
+            assert!(matches!(input.parse::<OwnedAttribute>(), Err(AttributeSyntaxError::InvalidName(_))), "{input}");
+        }
+        for input in ["a='<'", "a='&nope;'", "a='&'", "a='&#0;'"] {
+            assert!(matches!(input.parse::<OwnedAttribute>(), Err(AttributeSyntaxError::InvalidValue(_))), "{input}");
+        }
+    }
+
+    #[test]
+    fn checked_construction() {
+        assert!(OwnedAttribute::try_new(OwnedName::local("a"), "\t<&>\u{10FFFF}").is_ok());
+        assert!(Attribute::try_new(Name::qualified("a", "urn:x", Some("x")), "").is_ok());
+        for name in ["", "a b", "1a", "a:b:c"] {
+            assert!(matches!(Attribute::try_new(Name::local(name), ""), Err(AttributeError::Name(_))), "{name}");
+        }
+        let err = OwnedAttribute::try_new(OwnedName::local("a"), "é\u{FFFE}").unwrap_err();
+        assert_eq!(err, AttributeError::Value { index: 2, found: '\u{FFFE}' });
+        assert_eq!(err.to_string(), "attribute value has invalid character '\\u{fffe}' at index 2");
+    }
 }
@@ -0,0 +1,354 @@
+//! Contains `canonicalize`, which writes documents in Canonical XML, e.g. to compute digests.
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use crate::attribute::OwnedAttribute;
+use crate::chars::is_whitespace_char;
+use crate::common::{Position, TextPosition};
+use crate::escape::{escape_str_with, CanonicalAttributeEscapes, CanonicalPcDataEscapes, PreEscaped};
+use crate::name::Name;
+use crate::namespace::{NamespaceStack, NS_XMLNS_PREFIX, NS_XML_PREFIX};
+use crate::reader::{self, ParserConfig2, XmlEvent};
+use crate::writer::{self, EventWriter};
+use crate::{EmitterConfig, EventReader};
+
+/// An error of `canonicalize`.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The input couldn't be read, e.g. because it's not well-formed.
+    Read(reader::Error),
+    /// An event of the input couldn't be written, e.g. because the `fallible-alloc` feature is
+    /// on and the output can't grow. The position is the event's in the input.
+    Write(TextPosition, writer::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(e) => e.fmt(f),
+            Error::Write(pos, e) => write!(f, "{pos} {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Read(e) => Some(e),
+            Error::Write(_, e) => Some(e),
+        }
+    }
+}
+
+impl Position for Error {
+    #[inline]
+    fn position(&self) -> TextPosition {
+        match self {
+            Error::Read(e) => e.position(),
+            Error::Write(pos, _) => *pos,
+        }
+    }
+}
+
+impl From<reader::Error> for Error {
+    #[cold]
+    fn from(e: reader::Error) -> Self {
+        Error::Read(e)
+    }
+}
+
+/// Writes the `input` document in its canonical form, as defined by
+/// [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n), without comments.
+///
+/// Documents which differ only in ways XML doesn't give a meaning to, like attribute order,
+/// quotes, empty-element tags, CDATA sections or references, have the same canonical form, so
+/// it's what digests are computed over. In the canonical form:
+///
+/// * there's no XML declaration or DTD, and attributes declared with defaults in the internal
+///   subset are added, see `ParserConfig2::apply_attribute_defaults`;
+/// * line breaks are `\n`, tabs and line breaks in attribute values are spaces unless they
+///   are written as character references;
+/// * empty elements have an end tag, CDATA sections are text, references are replaced;
+/// * namespace declarations come first, sorted by prefix, and only where a binding changes;
+///   attributes follow, sorted by namespace URI and local name;
+/// * attribute values are quoted with `"`, and the characters `&`, `<`, `>` and `\r` in text
+///   and `&`, `<`, `"`, `\t`, `\n` and `\r` in attribute values are written as references;
+/// * processing instructions and comments outside the document element are each on a line.
+///
+/// Only whole documents are canonicalized, not document subsets. External entities aren't
+/// read, so the input shouldn't refer to any.
+///
+/// ```rust
+/// use xml_no_std::canonical::canonicalize;
+///
+/// let input = "<?xml version='1.0'?>\n<a xmlns='urn:a' z='1' y = \"2\"><b/><![CDATA[1 < 2]]></a>";
+/// assert_eq!(canonicalize(input).unwrap(), r#"<a xmlns="urn:a" y="2" z="1"><b></b>1 &lt; 2</a>"#);
+/// ```
+pub fn canonicalize(input: &str) -> Result<String, Error> {
+    canonicalize_document(input, false)
+}
+
+/// Like `canonicalize`, but keeps comments, which is the "with comments" variant of Canonical XML.
+pub fn canonicalize_with_comments(input: &str) -> Result<String, Error> {
+    canonicalize_document(input, true)
+}
+
+fn canonicalize_document(input: &str, with_comments: bool) -> Result<String, Error> {
+    // the reader keeps line breaks as they are, but XML turns them into `\n` before parsing
+    let input = if input.contains('\r') {
+        Cow::Owned(input.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(input)
+    };
+    let mut parser = ParserConfig2::new()
+        .ignore_comments(!with_comments)
+        .cdata_to_characters(true)
+        .apply_attribute_defaults(true);
+    parser.normalize_attribute_whitespace = true;
+    // text and attribute values are escaped here, since the emitter's rules are different
+    let emitter = EmitterConfig::new()
+        .write_document_declaration(false)
+        .normalize_empty_elements(false)
+        .autopad_comments(false);
+    let mut reader = EventReader::new_with_config(input.as_bytes().iter(), parser);
+    let mut writer = emitter.create_writer();
+    // namespaces of the open elements, which have the bindings that are declared already
+    let mut scopes = NamespaceStack::empty();
+    let mut after_root = false;
+    loop {
+        let outside_root = scopes.0.is_empty();
+        let written = match reader.next()? {
+            XmlEvent::StartDocument { .. } => Ok(()),
+            XmlEvent::EndDocument => return Ok(writer.into_inner()),
+            XmlEvent::StartElement { name, attributes, namespace } => {
+                let declarations: Vec<(String, String)> = scopes.delta(&namespace)
+                    .filter(|&(prefix, _)| prefix != NS_XML_PREFIX && prefix != NS_XMLNS_PREFIX)
+                    .map(|(prefix, uri)| (prefix.into(), escape_str_with::<CanonicalAttributeEscapes>(uri).into_owned()))
+                    .collect();
+                scopes.0.push(namespace);
+                write_start_element(&mut writer, Name { namespace: None, ..name.borrow() }, &declarations, &attributes)
+            },
+            XmlEvent::EndElement { .. } => {
+                scopes.try_pop();
+                after_root = scopes.0.is_empty();
+                writer.write(writer::XmlEvent::end_element())
+            },
+            XmlEvent::ProcessingInstruction { name, data } => {
+                // the target and the data are separated by one space
+                let data = data.as_deref().map(|data| data.trim_start_matches(is_whitespace_char)).filter(|data| !data.is_empty());
+                write_markup(&mut writer, outside_root, after_root, writer::XmlEvent::processing_instruction(&name, data))
+            },
+            XmlEvent::Comment(content) => write_markup(&mut writer, outside_root, after_root, writer::XmlEvent::comment(&content)),
+            // whitespace between markup outside the document element, which is dropped
+            XmlEvent::Characters(_) | XmlEvent::Whitespace(_) | XmlEvent::CData(_) if outside_root => Ok(()),
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) | XmlEvent::CData(text) =>
+                writer.write_pre_escaped(PreEscaped::new(&escape_str_with::<CanonicalPcDataEscapes>(&text))),
+        };
+        written.map_err(|e| Error::Write(reader.position(), e))?;
+    }
+}
+
+/// Writes the namespace declarations and the attributes in canonical order
+fn write_start_element(writer: &mut EventWriter, name: Name<'_>, declarations: &[(String, String)], attributes: &[OwnedAttribute]) -> writer::Result<()> {
+    let mut sorted: Vec<&OwnedAttribute> = attributes.iter().collect();
+    sorted.sort_by(|a, b| {
+        (a.name.namespace_ref().unwrap_or(""), &*a.name.local_name).cmp(&(b.name.namespace_ref().unwrap_or(""), &*b.name.local_name))
+    });
+    let values: Vec<Cow<'_, str>> = sorted.iter().map(|attr| escape_str_with::<CanonicalAttributeEscapes>(&attr.value)).collect();
+
+    // `delta` gives the default namespace first, then prefixes in order
+    let mut element = writer::XmlEvent::start_element(name);
+    for (prefix, uri) in declarations {
+        let name = if prefix.is_empty() {
+            Name::local(NS_XMLNS_PREFIX)
+        } else {
+            Name { local_name: prefix, namespace: None, prefix: Some(NS_XMLNS_PREFIX) }
+        };
+        element = element.attr_pre_escaped(name, PreEscaped::new(uri));
+    }
+    for (attr, value) in sorted.iter().zip(&values) {
+        element = element.attr_pre_escaped(Name { namespace: None, ..attr.name.borrow() }, PreEscaped::new(value));
+    }
+    writer.write_start_element(element)
+}
+
+/// Writes a processing instruction or a comment, which is on a line of its own outside the
+/// document element: the line break goes after it before the element, and before it after
+fn write_markup(writer: &mut EventWriter, outside_root: bool, after_root: bool, event: writer::XmlEvent<'_>) -> writer::Result<()> {
+    if outside_root && after_root {
+        writer.write_pre_escaped(PreEscaped::new("\n"))?;
+    }
+    writer.write(event)?;
+    if outside_root && !after_root {
+        writer.write_pre_escaped(PreEscaped::new("\n"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, canonicalize_with_comments, Error};
+    use crate::common::Position;
+
+    // the examples of section 3 of the specification, https://www.w3.org/TR/xml-c14n#Examples
+
+    #[test]
+    fn pis_comments_and_outside_of_document_element() {
+        let input = r#"<?xml version="1.0"?>
+
+<?xml-stylesheet   href="doc.xsl"
+   type="text/xsl"   ?>
+
+<!DOCTYPE doc SYSTEM "doc.dtd">
+
+<doc>Hello, world!<!-- Comment 1 --></doc>
+
+<?pi-without-data     ?>
+
+<!-- Comment 2 -->
+
+<!-- Comment 3 -->"#;
+        assert_eq!(canonicalize(input).unwrap(), r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!</doc>
+<?pi-without-data?>"#);
+        assert_eq!(canonicalize_with_comments(input).unwrap(), r#"<?xml-stylesheet href="doc.xsl"
+   type="text/xsl"   ?>
+<doc>Hello, world!<!-- Comment 1 --></doc>
+<?pi-without-data?>
+<!-- Comment 2 -->
+<!-- Comment 3 -->"#);
+    }
+
+    #[test]
+    fn whitespace_in_document_content() {
+        let input = r#"<doc>
+   <clean>   </clean>
+   <dirty>   A   B   </dirty>
+   <mixed>
+      A
+      <clean>   </clean>
+      B
+      <dirty>   A   B   </dirty>
+      C
+   </mixed>
+</doc>"#;
+        assert_eq!(canonicalize(input).unwrap(), input);
+    }
+
+    #[test]
+    fn start_and_end_tags() {
+        let input = r#"<!DOCTYPE doc [<!ATTLIST e9 attr CDATA "default">]>
+<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+        assert_eq!(canonicalize(input).unwrap(), r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org" attr="default"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#);
+    }
+
+    #[test]
+    fn character_modifications_and_character_references() {
+        let input = r#"<!DOCTYPE doc [
+<!ATTLIST normId id ID #IMPLIED>
+<!ATTLIST normNames attr NMTOKENS #IMPLIED>
+]>
+<doc>
+   <text>First line&#x0d;&#10;Second line</text>
+   <value>&#x32;</value>
+   <compute><![CDATA[value>"0" && value<"10" ?"valid":"error"]]></compute>
+   <compute expr='value>"0" &amp;&amp; value&lt;"10" ?"valid":"error"'>valid</compute>
+   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+   <normNames attr='   A   &#x20;&#13;&#xa;&#9;   B   '/>
+   <normId id=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+</doc>"#;
+        assert_eq!(canonicalize(input).unwrap(), r#"<doc>
+   <text>First line&#xD;
+Second line</text>
+   <value>2</value>
+   <compute>value&gt;"0" &amp;&amp; value&lt;"10" ?"valid":"error"</compute>
+   <compute expr="value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;">valid</compute>
+   <norm attr=" '    &#xD;&#xA;&#x9;   ' "></norm>
+   <normNames attr="A &#xD;&#xA;&#x9; B"></normNames>
+   <normId id="' &#xD;&#xA;&#x9; '"></normId>
+</doc>"#);
+    }
+
+    #[test]
+    fn entity_references() {
+        // `ent2` is external in the specification, with "world" in world.txt
+        let input = r#"<!DOCTYPE doc [
+<!ATTLIST doc attrExtEnt ENTITY #IMPLIED>
+<!ENTITY ent1 "Hello">
+<!ENTITY ent2 "world">
+<!ENTITY entExt SYSTEM "earth.gif" NDATA gif>
+<!NOTATION gif SYSTEM "viewgif.exe">
+]>
+<doc attrExtEnt="entExt">
+   &ent1;, &ent2;!
+</doc>
+
+<!-- Let world.txt contain "world" (excluding the quotes) -->"#;
+        assert_eq!(canonicalize(input).unwrap(), "<doc attrExtEnt=\"entExt\">\n   Hello, world!\n</doc>");
+    }
+
+    #[test]
+    fn utf8_encoding() {
+        let input = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<doc>&#169;</doc>";
+        assert_eq!(canonicalize(input).unwrap(), "<doc>©</doc>");
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(canonicalize("<a>\r\nq\rr</a>").unwrap(), "<a>\nq\nr</a>");
+        assert_eq!(canonicalize("<a b='x\r\ny'/>").unwrap(), "<a b=\"x y\"></a>");
+    }
+
+    #[test]
+    fn attribute_whitespace() {
+        assert_eq!(canonicalize("<a b='x\ny\tz'/>").unwrap(), "<a b=\"x y z\"></a>");
+        assert_eq!(canonicalize("<a b='x&#xA;y&#9;z'/>").unwrap(), "<a b=\"x&#xA;y&#x9;z\"></a>");
+        // defaults are normalized like written values
+        let input = "<!DOCTYPE a [<!ATTLIST a b CDATA 'x\ty&#9;z'>]><a/>";
+        assert_eq!(canonicalize(input).unwrap(), "<a b=\"x y&#x9;z\"></a>");
+    }
+
+    #[test]
+    fn malformed() {
+        let err = canonicalize("<a>\n<b></a>").unwrap_err();
+        assert!(matches!(err, Error::Read(_)), "{err}");
+        assert_eq!(err.position().row, 1);
+    }
+}
@@ -0,0 +1,509 @@
+//! Contains `Capacity`, the storage of the stacks and buffers `EventReader` keeps while it reads.
+//!
+//! By default they're on the heap and grow as the document needs, see `Unbounded`. With the
+//! `fixed-capacity` feature, `Fixed` keeps them in arrays whose sizes are const parameters,
+//! and the reader fails with `ErrorKind::LimitExceeded` when one is full rather than growing it.
+//! Events still own their names and text, so reading allocates for them either way.
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "fixed-capacity")]
+use core::{fmt, mem};
+
+use crate::attribute::{Attributes, OwnedAttribute};
+use crate::name::OwnedName;
+use crate::namespace::Namespace;
+
+/// Where the reader keeps the attributes of a start tag, the names and namespace scopes of
+/// the open elements, and the text it's reading. The parser is the same for all of them.
+///
+/// It's a type parameter of `EventReader`, `Unbounded` by default. See `FixedEventReader`.
+pub trait Capacity {
+    /// The attributes of the start tag being read
+    type Attributes: Stack<OwnedAttribute> + Into<Attributes>;
+    /// The names of the open elements, and whether `xml:space="preserve"` is in effect in each
+    type Elements: Stack<(OwnedName, bool)>;
+    /// The namespace scopes of the open elements, after the document's
+    type Namespaces: Stack<Namespace>;
+    /// Text, names and attribute values being read
+    type Text: Text;
+}
+
+/// Stacks and buffers on the heap, which grow as needed. This is the default.
+#[derive(Debug)]
+pub enum Unbounded {}
+
+impl Capacity for Unbounded {
+    type Attributes = Attributes;
+    type Elements = Vec<(OwnedName, bool)>;
+    type Namespaces = Vec<Namespace>;
+    type Text = String;
+}
+
+/// Stacks and buffers in arrays, with the `fixed-capacity` feature: at most `ATTRIBUTES`
+/// attributes in a start tag, `DEPTH` namespace scopes, which is elements nested `DEPTH - 1`
+/// deep as the document has a scope of its own, and `TEXT` bytes of text, a name or an
+/// attribute value at once. `DEPTH` must be at least 1.
+#[cfg(feature = "fixed-capacity")]
+#[derive(Debug)]
+pub enum Fixed<const ATTRIBUTES: usize, const DEPTH: usize, const TEXT: usize> {}
+
+#[cfg(feature = "fixed-capacity")]
+impl<const ATTRIBUTES: usize, const DEPTH: usize, const TEXT: usize> Capacity for Fixed<ATTRIBUTES, DEPTH, TEXT> {
+    type Attributes = FixedVec<OwnedAttribute, ATTRIBUTES>;
+    type Elements = FixedVec<(OwnedName, bool), DEPTH>;
+    type Namespaces = FixedVec<Namespace, DEPTH>;
+    type Text = FixedString<TEXT>;
+}
+
+/// A stack of the reader, e.g. of the open elements, see `Capacity`.
+pub trait Stack<T>: Default {
+    /// Adds `item` on top of the stack.
+    ///
+    /// # Panics
+    ///
+    /// If the stack `is_full()`.
+    fn push(&mut self, item: T);
+
+    /// Removes the item on top of the stack.
+    fn pop(&mut self) -> Option<T>;
+
+    /// The items, from the bottom of the stack to its top.
+    fn as_slice(&self) -> &[T];
+
+    /// The items, from the bottom of the stack to its top.
+    fn as_mut_slice(&mut self) -> &mut [T];
+
+    /// Removes all items.
+    fn clear(&mut self);
+
+    /// Whether `push` can't add another item. A stack which grows is never full.
+    #[inline]
+    fn is_full(&self) -> bool {
+        false
+    }
+
+    /// Makes room for `additional` items, with the `fallible-alloc` feature.
+    #[cfg(feature = "fallible-alloc")]
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        let _ = additional;
+        Ok(())
+    }
+
+    /// The number of items.
+    #[inline]
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Whether there are no items.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.as_slice().is_empty()
+    }
+
+    /// The item on top of the stack.
+    #[inline]
+    fn last(&self) -> Option<&T> {
+        self.as_slice().last()
+    }
+
+    /// The item on top of the stack.
+    #[inline]
+    fn last_mut(&mut self) -> Option<&mut T> {
+        self.as_mut_slice().last_mut()
+    }
+}
+
+impl<T> Stack<T> for Vec<T> {
+    #[inline]
+    fn push(&mut self, item: T) {
+        Vec::push(self, item);
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        Vec::try_reserve(self, additional)
+    }
+}
+
+impl Stack<OwnedAttribute> for Attributes {
+    #[inline]
+    fn push(&mut self, item: OwnedAttribute) {
+        Attributes::push(self, item);
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<OwnedAttribute> {
+        Attributes::pop(self)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[OwnedAttribute] {
+        Attributes::as_slice(self)
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [OwnedAttribute] {
+        self
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        Attributes::clear(self);
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        Attributes::try_reserve(self, additional)
+    }
+}
+
+/// The reader's buffer for text, names and attribute values, see `Capacity`.
+///
+/// The text it's pushed is added whole or not at all. What doesn't fit in a buffer of a fixed
+/// size is dropped, and the buffer is `is_exceeded()` from then on, which the reader checks
+/// after each token it reads, before the text could be used in an event.
+pub trait Text: Default {
+    /// Adds `c` at the end.
+    fn push(&mut self, c: char);
+
+    /// Adds `s` at the end.
+    fn push_str(&mut self, s: &str);
+
+    /// The text.
+    fn as_str(&self) -> &str;
+
+    /// The length of the text in bytes.
+    fn len(&self) -> usize;
+
+    /// Removes the text.
+    fn clear(&mut self);
+
+    /// Returns the text in `spare`, which is cleared first, and leaves this buffer empty.
+    /// Buffers which can hand over their own allocation return it and keep `spare`.
+    fn take_into(&mut self, spare: String) -> String;
+
+    /// Returns the text before byte `at`, and keeps the text after it.
+    fn split_to(&mut self, at: usize) -> String;
+
+    /// Whether text has been dropped, as it didn't fit. Clearing the buffer doesn't reset it.
+    #[inline]
+    fn is_exceeded(&self) -> bool {
+        false
+    }
+
+    /// Makes room for `additional` bytes, with the `fallible-alloc` feature.
+    #[cfg(feature = "fallible-alloc")]
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        let _ = additional;
+        Ok(())
+    }
+
+    /// Whether there's no text.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Text for String {
+    #[inline]
+    fn push(&mut self, c: char) {
+        String::push(self, c);
+    }
+
+    #[inline]
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s);
+    }
+
+    #[inline]
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        String::len(self)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        String::clear(self);
+    }
+
+    #[inline]
+    fn take_into(&mut self, spare: String) -> String {
+        let mut spare = spare;
+        spare.clear();
+        core::mem::replace(self, spare)
+    }
+
+    #[inline]
+    fn split_to(&mut self, at: usize) -> String {
+        let rest = self.split_off(at);
+        core::mem::replace(self, rest)
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), alloc::collections::TryReserveError> {
+        String::try_reserve(self, additional)
+    }
+}
+
+/// A stack of at most `N` items in an array, with the `fixed-capacity` feature. The free
+/// slots hold `T::default()`.
+#[cfg(feature = "fixed-capacity")]
+#[derive(Clone)]
+pub struct FixedVec<T, const N: usize> {
+    len: usize,
+    items: [T; N],
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T: Default, const N: usize> Default for FixedVec<T, N> {
+    #[inline]
+    fn default() -> Self {
+        FixedVec { len: 0, items: [(); N].map(|()| T::default()) }
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T: Default, const N: usize> Stack<T> for FixedVec<T, N> {
+    #[inline]
+    #[track_caller]
+    fn push(&mut self, item: T) {
+        assert!(self.len < N, "FixedVec of {N} is full");
+        self.items[self.len] = item;
+        self.len += 1;
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        Some(mem::take(&mut self.items[self.len]))
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.items[..self.len]
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.items[..self.len].iter_mut().for_each(|item| *item = T::default());
+        self.len = 0;
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T: PartialEq + Default, const N: usize> PartialEq for FixedVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T: Eq + Default, const N: usize> Eq for FixedVec<T, N> {}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T: fmt::Debug + Default, const N: usize> fmt::Debug for FixedVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<T, const N: usize> IntoIterator for FixedVec<T, N> {
+    type Item = T;
+    type IntoIter = core::iter::Take<core::array::IntoIter<T, N>>;
+
+    /// Iterates over the items from the bottom of the stack to its top.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter().take(self.len)
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<const N: usize> From<FixedVec<OwnedAttribute, N>> for Attributes {
+    #[inline]
+    fn from(attributes: FixedVec<OwnedAttribute, N>) -> Attributes {
+        attributes.into_iter().collect()
+    }
+}
+
+/// A buffer of at most `N` bytes of text in an array, with the `fixed-capacity` feature.
+///
+/// Since the crate has no `unsafe` code, `as_str()` checks that the bytes are UTF-8, which
+/// takes time in proportion to the text.
+#[cfg(feature = "fixed-capacity")]
+#[derive(Clone)]
+pub struct FixedString<const N: usize> {
+    len: usize,
+    bytes: [u8; N],
+    exceeded: bool,
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<const N: usize> Default for FixedString<N> {
+    #[inline]
+    fn default() -> Self {
+        FixedString { len: 0, bytes: [0; N], exceeded: false }
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<const N: usize> Text for FixedString<N> {
+    #[inline]
+    fn push(&mut self, c: char) {
+        match self.bytes.get_mut(self.len..self.len + c.len_utf8()) {
+            Some(free) => self.len += c.encode_utf8(free).len(),
+            None => self.exceeded = true,
+        }
+    }
+
+    #[inline]
+    fn push_str(&mut self, s: &str) {
+        match self.bytes.get_mut(self.len..self.len + s.len()) {
+            Some(free) => {
+                free.copy_from_slice(s.as_bytes());
+                self.len += s.len();
+            },
+            None => self.exceeded = true,
+        }
+    }
+
+    #[inline]
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).expect("only whole chars are pushed")
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    fn take_into(&mut self, spare: String) -> String {
+        let mut spare = spare;
+        spare.clear();
+        spare.push_str(self.as_str());
+        self.clear();
+        spare
+    }
+
+    fn split_to(&mut self, at: usize) -> String {
+        let text = String::from(&self.as_str()[..at]);
+        self.bytes.copy_within(at..self.len, 0);
+        self.len -= at;
+        text
+    }
+
+    #[inline]
+    fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+#[cfg(feature = "fixed-capacity")]
+impl<const N: usize> fmt::Debug for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+#[cfg(all(test, feature = "fixed-capacity"))]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::{FixedString, FixedVec, Stack, Text};
+
+    #[test]
+    fn fixed_vec() {
+        let mut stack = FixedVec::<Vec<u8>, 2>::default();
+        stack.push(Vec::from(*b"a"));
+        stack.push(Vec::from(*b"b"));
+        assert!(stack.is_full());
+        assert_eq!(stack.last().map(Vec::as_slice), Some(&b"b"[..]));
+        assert_eq!(stack.pop(), Some(Vec::from(*b"b")));
+        assert!(!stack.is_full());
+        stack.push(Vec::from(*b"c"));
+        assert_eq!(stack.clone().into_iter().collect::<Vec<_>>(), [&b"a"[..], b"c"]);
+        stack.clear();
+        assert!(stack.is_empty() && stack.pop().is_none());
+        assert_eq!(stack, FixedVec::default());
+    }
+
+    #[test]
+    #[should_panic = "FixedVec of 1 is full"]
+    fn fixed_vec_overflow() {
+        let mut stack = FixedVec::<u8, 1>::default();
+        stack.push(1);
+        stack.push(2);
+    }
+
+    #[test]
+    fn fixed_string() {
+        let mut text = FixedString::<8>::default();
+        text.push_str("ab");
+        text.push('ü');
+        text.push_str("cd");
+        assert_eq!((text.as_str(), text.len(), text.is_exceeded()), ("abücd", 6, false));
+        // dropped whole, not in part
+        text.push('€');
+        text.push_str("efg");
+        assert_eq!((text.as_str(), text.is_exceeded()), ("abücd", true));
+        text.push_str("ef");
+        assert_eq!(text.as_str(), "abücdef");
+
+        assert_eq!(text.split_to(2), "ab");
+        assert_eq!(text.as_str(), "ücdef");
+        assert_eq!(text.take_into("spare".into()), "ücdef");
+        assert!(text.is_empty() && text.is_exceeded());
+    }
+}
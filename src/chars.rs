@@ -0,0 +1,139 @@
+//! Character classes of the XML grammar, as the reader and the writer check them.
+//!
+//! These are the productions of [XML 1.0][xml10] (fifth edition) section 2.2 and 2.3, which are
+//! the same as in [XML 1.1][xml11] except for `Char`, see `is_xml11_char`. They're for checking
+//! names and text before writing them, or for validating input the same way the reader does.
+//!
+//! [xml10]: https://www.w3.org/TR/xml/#charsets
+//! [xml11]: https://www.w3.org/TR/xml11/#charsets
+
+/// Checks whether the character may appear in an XML 1.0 document (`Char`).
+///
+/// It's any Unicode character except most C0 controls, the surrogates, which a `char` can't
+/// be anyway, and U+FFFE and U+FFFF. Tab, line feed and carriage return are allowed.
+///
+/// ```rust
+/// use xml_no_std::chars::is_xml_char;
+///
+/// assert!(is_xml_char('\t') && is_xml_char('€') && is_xml_char('\u{10FFFF}'));
+/// assert!(!is_xml_char('\0') && !is_xml_char('\u{1B}') && !is_xml_char('\u{FFFE}'));
+/// ```
+#[must_use]
+#[inline]
+pub fn is_xml_char(c: char) -> bool {
+    matches!(c, '\u{09}' | '\u{0A}' | '\u{0D}' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
+}
+
+/// Checks whether the character may appear in an XML 1.1 document (`Char`), which allows all
+/// C0 controls but NUL. Most of them may only be written as character references, see
+/// `is_xml11_char_not_restricted`.
+#[must_use]
+#[inline]
+pub fn is_xml11_char(c: char) -> bool {
+    matches!(c, '\u{01}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
+}
+
+/// Checks whether the character may appear literally in an XML 1.1 document, i.e. it's a
+/// `Char` but not a `RestrictedChar`: C0 controls other than whitespace, DEL, and C1 controls
+/// other than NEL.
+#[must_use]
+#[inline]
+pub fn is_xml11_char_not_restricted(c: char) -> bool {
+    is_xml11_char(c) && !matches!(c, '\u{01}'..='\u{08}' | '\u{0B}'..='\u{0C}' | '\u{0E}'..='\u{1F}' | '\u{7F}'..='\u{84}' | '\u{86}'..='\u{9F}')
+}
+
+/// Checks whether the character is white space (`S`): space, tab, line feed or carriage return.
+///
+/// Other Unicode white space, like U+00A0 NO-BREAK SPACE, isn't white space to XML.
+#[must_use]
+#[inline]
+pub fn is_whitespace_char(c: char) -> bool {
+    matches!(c, '\x20' | '\x0a' | '\x09' | '\x0d')
+}
+
+/// Checks whether the string is only white space, see `is_whitespace_char`. It's true for an
+/// empty string.
+#[must_use]
+pub fn is_whitespace_str(s: &str) -> bool {
+    s.chars().all(is_whitespace_char)
+}
+
+/// Checks whether the character may start a name (`NameStartChar`).
+///
+/// It's `:`, `_`, ASCII letters, and most letters and symbols above U+00BF, but not digits,
+/// combining marks, or punctuation like `-` and `.`, which may only follow, see `is_name_char`.
+///
+/// ```rust
+/// use xml_no_std::chars::is_name_start_char;
+///
+/// assert!(is_name_start_char('a') && is_name_start_char('_') && is_name_start_char('ł'));
+/// assert!(!is_name_start_char('1') && !is_name_start_char('-') && !is_name_start_char('\u{D7}'));
+/// ```
+#[must_use]
+pub fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z' |
+        '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}' |
+        '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' |
+        '\u{200C}'..='\u{200D}' | '\u{2070}'..='\u{218F}' |
+        '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}' |
+        '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' |
+        '\u{10000}'..='\u{EFFFF}')
+}
+
+/// Checks whether the character may be in a name after the first one (`NameChar`).
+///
+/// It's a `NameStartChar`, or `-`, `.`, a digit, U+00B7, a combining mark of U+0300 to U+036F,
+/// or U+203F or U+2040.
+#[must_use]
+pub fn is_name_char(c: char) -> bool {
+    is_name_start_char(c) || matches!(c, '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_name_char, is_name_start_char, is_whitespace_char, is_whitespace_str, is_xml11_char, is_xml11_char_not_restricted, is_xml_char};
+
+    /// Checks the first and last character of each range, and the ones just outside it
+    fn check_ranges(class: fn(char) -> bool, ranges: &[(u32, u32)]) {
+        let within = |c: u32| ranges.iter().any(|&(start, end)| (start..=end).contains(&c));
+        for &(start, end) in ranges {
+            for c in [start.wrapping_sub(1), start, end, end + 1] {
+                if let Some(c) = char::from_u32(c) {
+                    assert_eq!(class(c), within(c as u32), "U+{:04X}", c as u32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xml_char() {
+        check_ranges(is_xml_char, &[(0x9, 0xA), (0xD, 0xD), (0x20, 0xD7FF), (0xE000, 0xFFFD), (0x10000, 0x10FFFF)]);
+        check_ranges(is_xml11_char, &[(0x1, 0xD7FF), (0xE000, 0xFFFD), (0x10000, 0x10FFFF)]);
+        check_ranges(is_xml11_char_not_restricted, &[
+            (0x9, 0xA), (0xD, 0xD), (0x20, 0x7E), (0x85, 0x85), (0xA0, 0xD7FF), (0xE000, 0xFFFD), (0x10000, 0x10FFFF),
+        ]);
+    }
+
+    #[test]
+    fn whitespace() {
+        check_ranges(is_whitespace_char, &[(0x9, 0xA), (0xD, 0xD), (0x20, 0x20)]);
+        assert!(!is_whitespace_char('\u{A0}') && !is_whitespace_char('\u{85}') && !is_whitespace_char('\u{3000}'));
+        assert!(is_whitespace_str("") && is_whitespace_str(" \t\r\n"));
+        assert!(!is_whitespace_str(" \u{A0}"));
+    }
+
+    #[test]
+    fn name_chars() {
+        const NAME_START: &[(u32, u32)] = &[
+            (0x3A, 0x3A), (0x41, 0x5A), (0x5F, 0x5F), (0x61, 0x7A), (0xC0, 0xD6), (0xD8, 0xF6),
+            (0xF8, 0x2FF), (0x370, 0x37D), (0x37F, 0x1FFF), (0x200C, 0x200D), (0x2070, 0x218F),
+            (0x2C00, 0x2FEF), (0x3001, 0xD7FF), (0xF900, 0xFDCF), (0xFDF0, 0xFFFD), (0x10000, 0xEFFFF),
+        ];
+        check_ranges(is_name_start_char, NAME_START);
+
+        let mut name = NAME_START.to_vec();
+        name.extend([(0x2D, 0x2E), (0x30, 0x39), (0xB7, 0xB7), (0x300, 0x36F), (0x203F, 0x2040)]);
+        check_ranges(is_name_char, &name);
+    }
+}
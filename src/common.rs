@@ -54,6 +54,13 @@ impl fmt::Display for TextPosition {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for TextPosition {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=u64}:{=u64}", self.row + 1, self.column + 1);
+    }
+}
+
 /// Get the position in the document corresponding to the object
 ///
 /// This trait is implemented by parsers, lexers and errors.
@@ -70,12 +77,17 @@ impl Position for TextPosition {
 }
 
 /// XML version enumeration.
+///
+/// With the `serde` feature, it's serialized as its `Display` form, i.e. `"1.0"` or `"1.1"`.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum XmlVersion {
     /// XML version 1.0.
+    #[cfg_attr(feature = "serde", serde(rename = "1.0"))]
     Version10,
 
     /// XML version 1.1.
+    #[cfg_attr(feature = "serde", serde(rename = "1.1"))]
     Version11,
 }
 
@@ -88,6 +100,16 @@ impl fmt::Display for XmlVersion {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for XmlVersion {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", match *self {
+            XmlVersion::Version10 => "1.0",
+            XmlVersion::Version11 => "1.1",
+        });
+    }
+}
+
 impl fmt::Debug for XmlVersion {
     #[cold]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -95,63 +117,43 @@ impl fmt::Debug for XmlVersion {
     }
 }
 
-/// Checks whether the given character is a white space character (`S`)
-/// as is defined by XML 1.1 specification, [section 2.3][1].
-///
-/// [1]: http://www.w3.org/TR/2006/REC-xml11-20060816/#sec-common-syn
-#[must_use]
-#[inline]
-pub fn is_whitespace_char(c: char) -> bool {
-    matches!(c, '\x20' | '\x0a' | '\x09' | '\x0d')
-}
+// the character classes are in `chars`, these are kept for compatibility
+pub use crate::chars::is_xml_char as is_xml10_char;
+pub use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char, is_whitespace_str, is_xml11_char, is_xml11_char_not_restricted};
 
-/// Checks whether the given string is compound only by white space
-/// characters (`S`) using the previous `is_whitespace_char` to check
-/// all characters of this string
-pub fn is_whitespace_str(s: &str) -> bool {
-    s.chars().all(is_whitespace_char)
-}
+/// The most digits, leading zeros included, a character reference may have
+pub(crate) const MAX_CHAR_REFERENCE_DIGITS: usize = 32;
 
-#[must_use] pub fn is_xml10_char(c: char) -> bool {
-    matches!(c, '\u{09}' | '\u{0A}' | '\u{0D}' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
+/// Why `char_reference_value` can't decode a character reference
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum CharReferenceError {
+    /// Not decimal digits or `x` and hexadecimal digits, or more than `MAX_CHAR_REFERENCE_DIGITS` of them
+    Malformed,
+    /// A number above U+10FFFF
+    OutOfRange,
+    /// A number in the surrogate range, U+D800 to U+DFFF
+    Surrogate(u32),
 }
 
-#[must_use] pub fn is_xml11_char(c: char) -> bool {
-    matches!(c, '\u{01}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
-}
-
-#[must_use] pub fn is_xml11_char_not_restricted(c: char) -> bool {
-    is_xml11_char(c) && !matches!(c, '\u{01}'..='\u{08}' | '\u{0B}'..='\u{0C}' | '\u{0E}'..='\u{1F}' | '\u{7F}'..='\u{84}' | '\u{86}'..='\u{9F}')
-}
-
-/// Checks whether the given character is a name start character (`NameStartChar`)
-/// as is defined by XML 1.1 specification, [section 2.3][1].
+/// Decodes the text of a character reference between `&#` and `;`, e.g. `x41` or `65`.
 ///
-/// [1]: http://www.w3.org/TR/2006/REC-xml11-20060816/#sec-common-syn
-#[must_use]
-pub fn is_name_start_char(c: char) -> bool {
-    match c {
-        ':' | 'A'..='Z' | '_' | 'a'..='z' |
-        '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}' |
-        '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' |
-        '\u{200C}'..='\u{200D}' | '\u{2070}'..='\u{218F}' |
-        '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}' |
-        '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' |
-        '\u{10000}'..='\u{EFFFF}' => true,
-        _ => false
+/// Signs and whitespace aren't accepted. Digits are accumulated with overflow checks, so
+/// arbitrarily long input can't wrap around to a valid character. Whether the character is
+/// allowed in a document is left to the caller.
+pub(crate) fn char_reference_value(num: &str) -> Result<char, CharReferenceError> {
+    let (digits, radix) = match num.strip_prefix('x') {
+        Some(hex) => (hex, 16),
+        None => (num, 10),
+    };
+    if digits.is_empty() || digits.len() > MAX_CHAR_REFERENCE_DIGITS {
+        return Err(CharReferenceError::Malformed);
     }
-}
-
-/// Checks whether the given character is a name character (`NameChar`)
-/// as is defined by XML 1.1 specification, [section 2.3][1].
-///
-/// [1]: http://www.w3.org/TR/2006/REC-xml11-20060816/#sec-common-syn
-#[must_use]
-pub fn is_name_char(c: char) -> bool {
-    match c {
-        _ if is_name_start_char(c) => true,
-        '-' | '.' | '0'..='9' | '\u{B7}' |
-        '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}' => true,
-        _ => false
+    let mut value = Some(0u32);
+    for c in digits.chars() {
+        let digit = c.to_digit(radix).ok_or(CharReferenceError::Malformed)?;
+        // keeps checking the remaining digits after an overflow
+        value = value.and_then(|v| v.checked_mul(radix)?.checked_add(digit)).filter(|&v| v <= char::MAX as u32);
     }
+    let value = value.ok_or(CharReferenceError::OutOfRange)?;
+    char::from_u32(value).ok_or(CharReferenceError::Surrogate(value))
 }
@@ -0,0 +1,390 @@
+//! Conversions to and from the types of the `xml` crate (xml-rs), with the `xml-rs-compat` feature.
+//!
+//! Names, attributes, namespaces and reader events convert both ways without loss, so code
+//! which still uses xml-rs can be moved over one module at a time.
+//!
+//! The configuration conversions map the options both crates have:
+//!
+//! * `ParserConfig` converts both ways.
+//! * `ParserConfig2` converts to `xml::reader::ParserConfig2`, but not back, because xml-rs
+//!   doesn't expose the `ParserConfig` inside it. `lazy_attributes`, `on_unknown_entity`,
+//!   `apply_attribute_defaults`, `whitespace_handling`, `max_document_size`,
+//!   `max_element_depth`, `capture_doctype`, `characters_chunk_size` and `intern_names` have no
+//!   equivalent and are dropped. xml-rs can't split text, but a `characters_chunk_size` turns off
+//!   `coalesce_characters` as it does here.
+//! * `EmitterConfig` converts both ways. `validate_names`, `single_quote_attributes`,
+//!   `entity_substitutions` and `prefix_generator` have no equivalent; they're dropped
+//!   going to xml-rs, and get their defaults coming from it.
+//!
+//! `Encoding` converts with `TryFrom` coming from xml-rs, since xml-rs may add encodings.
+extern crate alloc;
+extern crate std;
+
+use alloc::string::String;
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::attribute::OwnedAttribute;
+use crate::common::XmlVersion;
+use crate::name::OwnedName;
+use crate::namespace::Namespace;
+use crate::reader::{ParserConfig, ParserConfig2, XmlEvent};
+use crate::writer::EmitterConfig;
+use crate::Encoding;
+
+impl From<OwnedName> for xml::name::OwnedName {
+    fn from(name: OwnedName) -> Self {
+        let OwnedName { local_name, namespace, prefix } = name;
+        xml::name::OwnedName { local_name, namespace, prefix }
+    }
+}
+
+impl From<xml::name::OwnedName> for OwnedName {
+    fn from(name: xml::name::OwnedName) -> Self {
+        let xml::name::OwnedName { local_name, namespace, prefix } = name;
+        OwnedName { local_name, namespace, prefix }
+    }
+}
+
+impl From<OwnedAttribute> for xml::attribute::OwnedAttribute {
+    fn from(attribute: OwnedAttribute) -> Self {
+        xml::attribute::OwnedAttribute { name: attribute.name.into(), value: attribute.value }
+    }
+}
+
+impl From<xml::attribute::OwnedAttribute> for OwnedAttribute {
+    fn from(attribute: xml::attribute::OwnedAttribute) -> Self {
+        OwnedAttribute { name: attribute.name.into(), value: attribute.value }
+    }
+}
+
+impl From<Namespace> for xml::namespace::Namespace {
+    fn from(namespace: Namespace) -> Self {
+        xml::namespace::Namespace(namespace.into())
+    }
+}
+
+impl From<xml::namespace::Namespace> for Namespace {
+    fn from(namespace: xml::namespace::Namespace) -> Self {
+        namespace.0.into()
+    }
+}
+
+impl From<XmlVersion> for xml::common::XmlVersion {
+    fn from(version: XmlVersion) -> Self {
+        match version {
+            XmlVersion::Version10 => xml::common::XmlVersion::Version10,
+            XmlVersion::Version11 => xml::common::XmlVersion::Version11,
+        }
+    }
+}
+
+impl From<xml::common::XmlVersion> for XmlVersion {
+    fn from(version: xml::common::XmlVersion) -> Self {
+        match version {
+            xml::common::XmlVersion::Version10 => XmlVersion::Version10,
+            xml::common::XmlVersion::Version11 => XmlVersion::Version11,
+        }
+    }
+}
+
+impl From<Encoding> for xml::Encoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Utf8 => xml::Encoding::Utf8,
+            Encoding::Default => xml::Encoding::Default,
+            Encoding::Latin1 => xml::Encoding::Latin1,
+            Encoding::Ascii => xml::Encoding::Ascii,
+            Encoding::Utf16Be => xml::Encoding::Utf16Be,
+            Encoding::Utf16Le => xml::Encoding::Utf16Le,
+            Encoding::Utf16 => xml::Encoding::Utf16,
+            Encoding::Unknown => xml::Encoding::Unknown,
+        }
+    }
+}
+
+/// An xml-rs `Encoding` which this crate doesn't have.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnsupportedEncoding(pub String);
+
+impl fmt::Display for UnsupportedEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "encoding {} is not supported", self.0)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for UnsupportedEncoding {}
+
+impl TryFrom<xml::Encoding> for Encoding {
+    type Error = UnsupportedEncoding;
+
+    fn try_from(encoding: xml::Encoding) -> Result<Self, UnsupportedEncoding> {
+        Ok(match encoding {
+            xml::Encoding::Utf8 => Encoding::Utf8,
+            xml::Encoding::Default => Encoding::Default,
+            xml::Encoding::Latin1 => Encoding::Latin1,
+            xml::Encoding::Ascii => Encoding::Ascii,
+            xml::Encoding::Utf16Be => Encoding::Utf16Be,
+            xml::Encoding::Utf16Le => Encoding::Utf16Le,
+            xml::Encoding::Utf16 => Encoding::Utf16,
+            xml::Encoding::Unknown => Encoding::Unknown,
+            other => return Err(UnsupportedEncoding(alloc::format!("{other}"))),
+        })
+    }
+}
+
+impl From<XmlEvent> for xml::reader::XmlEvent {
+    fn from(event: XmlEvent) -> Self {
+        use xml::reader::XmlEvent as X;
+        match event {
+            XmlEvent::StartDocument { version, encoding, standalone } =>
+                X::StartDocument { version: version.into(), encoding, standalone },
+            XmlEvent::EndDocument => X::EndDocument,
+            XmlEvent::ProcessingInstruction { name, data } => X::ProcessingInstruction { name, data },
+            XmlEvent::StartElement { name, attributes, namespace } => X::StartElement {
+                name: name.into(),
+                attributes: attributes.into_iter().map(Into::into).collect(),
+                namespace: namespace.into(),
+            },
+            XmlEvent::EndElement { name } => X::EndElement { name: name.into() },
+            XmlEvent::CData(data) => X::CData(data),
+            XmlEvent::Comment(data) => X::Comment(data),
+            XmlEvent::Characters(data) => X::Characters(data),
+            XmlEvent::Whitespace(data) => X::Whitespace(data),
+        }
+    }
+}
+
+impl From<xml::reader::XmlEvent> for XmlEvent {
+    fn from(event: xml::reader::XmlEvent) -> Self {
+        use xml::reader::XmlEvent as X;
+        match event {
+            X::StartDocument { version, encoding, standalone } =>
+                XmlEvent::StartDocument { version: version.into(), encoding, standalone },
+            X::EndDocument => XmlEvent::EndDocument,
+            X::ProcessingInstruction { name, data } => XmlEvent::ProcessingInstruction { name, data },
+            X::StartElement { name, attributes, namespace } => XmlEvent::StartElement {
+                name: name.into(),
+                attributes: attributes.into_iter().map(OwnedAttribute::from).collect(),
+                namespace: namespace.into(),
+            },
+            X::EndElement { name } => XmlEvent::EndElement { name: name.into() },
+            X::CData(data) => XmlEvent::CData(data),
+            X::Comment(data) => XmlEvent::Comment(data),
+            X::Characters(data) => XmlEvent::Characters(data),
+            X::Whitespace(data) => XmlEvent::Whitespace(data),
+        }
+    }
+}
+
+impl From<ParserConfig> for xml::ParserConfig {
+    fn from(config: ParserConfig) -> Self {
+        let mut converted = xml::ParserConfig::new();
+        converted.trim_whitespace = config.trim_whitespace;
+        converted.whitespace_to_characters = config.whitespace_to_characters;
+        converted.cdata_to_characters = config.cdata_to_characters;
+        converted.ignore_comments = config.ignore_comments;
+        converted.coalesce_characters = config.coalesce_characters;
+        converted.extra_entities = config.extra_entities.into_iter().collect();
+        converted.ignore_end_of_stream = config.ignore_end_of_stream;
+        converted.replace_unknown_entity_references = config.replace_unknown_entity_references;
+        converted.ignore_root_level_whitespace = config.ignore_root_level_whitespace;
+        converted
+    }
+}
+
+impl From<xml::ParserConfig> for ParserConfig {
+    fn from(config: xml::ParserConfig) -> Self {
+        let mut converted = ParserConfig::new();
+        converted.trim_whitespace = config.trim_whitespace;
+        converted.whitespace_to_characters = config.whitespace_to_characters;
+        converted.cdata_to_characters = config.cdata_to_characters;
+        converted.ignore_comments = config.ignore_comments;
+        converted.coalesce_characters = config.coalesce_characters;
+        converted.extra_entities = config.extra_entities.into_iter().collect();
+        converted.ignore_end_of_stream = config.ignore_end_of_stream;
+        converted.replace_unknown_entity_references = config.replace_unknown_entity_references;
+        converted.ignore_root_level_whitespace = config.ignore_root_level_whitespace;
+        converted
+    }
+}
+
+impl From<ParserConfig2> for xml::reader::ParserConfig2 {
+    fn from(config: ParserConfig2) -> Self {
+        // listing every field, so that a new option has to be mapped or dropped here
+        let ParserConfig2 {
+            c,
+            override_encoding,
+            ignore_invalid_encoding_declarations,
+            allow_multiple_root_elements,
+            max_entity_expansion_length,
+            max_entity_expansion_depth,
+            max_name_length,
+            max_attributes,
+            max_attribute_length,
+            max_data_length,
+            lazy_attributes: _,
+            on_unknown_entity: _,
+            apply_attribute_defaults: _,
+            whitespace_handling: _,
+            max_document_size: _,
+            max_element_depth: _,
+            capture_doctype: _,
+            characters_chunk_size,
+            intern_names: _,
+            normalize_attribute_whitespace: _,
+        } = config;
+        // chunks are never merged, which is the closest xml-rs gets to them
+        let coalesce_characters = c.coalesce_characters && characters_chunk_size.is_none();
+        let mut converted = xml::reader::ParserConfig2::from(xml::ParserConfig::from(c))
+            .coalesce_characters(coalesce_characters);
+        converted.override_encoding = override_encoding.map(Into::into);
+        converted.ignore_invalid_encoding_declarations = ignore_invalid_encoding_declarations;
+        converted.allow_multiple_root_elements = allow_multiple_root_elements;
+        converted.max_entity_expansion_length = max_entity_expansion_length;
+        converted.max_entity_expansion_depth = max_entity_expansion_depth;
+        converted.max_name_length = max_name_length;
+        converted.max_attributes = max_attributes;
+        converted.max_attribute_length = max_attribute_length;
+        converted.max_data_length = max_data_length;
+        converted
+    }
+}
+
+impl From<EmitterConfig> for xml::EmitterConfig {
+    fn from(config: EmitterConfig) -> Self {
+        let mut converted = xml::EmitterConfig::new();
+        converted.line_separator = config.line_separator;
+        converted.indent_string = config.indent_string;
+        converted.perform_indent = config.perform_indent;
+        converted.perform_escaping = config.perform_escaping;
+        converted.write_document_declaration = config.write_document_declaration;
+        converted.normalize_empty_elements = config.normalize_empty_elements;
+        converted.cdata_to_characters = config.cdata_to_characters;
+        converted.keep_element_names_stack = config.keep_element_names_stack;
+        converted.autopad_comments = config.autopad_comments;
+        converted.pad_self_closing = config.pad_self_closing;
+        converted
+    }
+}
+
+impl From<xml::EmitterConfig> for EmitterConfig {
+    fn from(config: xml::EmitterConfig) -> Self {
+        let mut converted = EmitterConfig::new();
+        converted.line_separator = config.line_separator;
+        converted.indent_string = config.indent_string;
+        converted.perform_indent = config.perform_indent;
+        converted.perform_escaping = config.perform_escaping;
+        converted.write_document_declaration = config.write_document_declaration;
+        converted.normalize_empty_elements = config.normalize_empty_elements;
+        converted.cdata_to_characters = config.cdata_to_characters;
+        converted.keep_element_names_stack = config.keep_element_names_stack;
+        converted.autopad_comments = config.autopad_comments;
+        converted.pad_self_closing = config.pad_self_closing;
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+
+    use crate::reader::{ParserConfig, ParserConfig2, XmlEvent};
+    use crate::writer::EmitterConfig;
+    use crate::Encoding;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<!-- head -->
+<root xmlns="urn:a" xmlns:b="urn:b" id="1 &amp; 2">
+  <b:item b:key="&lt;&#x1F600;&gt;">text &amp; more<![CDATA[ <raw> ]]></b:item>
+  <?target some data?>
+  <empty/>
+</root>"#;
+
+    fn parse_with_xml_rs(document: &str) -> Vec<XmlEvent> {
+        let mut events = Vec::new();
+        for event in xml::EventReader::new(document.as_bytes()) {
+            let event = XmlEvent::from(event.unwrap());
+            let end = event == XmlEvent::EndDocument;
+            events.push(event);
+            if end {
+                break;
+            }
+        }
+        events
+    }
+
+    fn parse(document: &str) -> Vec<XmlEvent> {
+        crate::EventReader::new(document.as_bytes().iter()).into_iter().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn round_trips_through_xml_rs() {
+        let ours = parse(DOCUMENT);
+        let theirs = parse_with_xml_rs(DOCUMENT);
+        assert_eq!(ours, theirs);
+        for event in &ours {
+            assert_eq!(&XmlEvent::from(xml::reader::XmlEvent::from(event.clone())), event);
+        }
+
+        // read with xml-rs, write with this crate
+        let mut writer = EmitterConfig::from(xml::EmitterConfig::new()).create_writer();
+        for event in &theirs {
+            if let Some(event) = event.as_writer_event() {
+                writer.write(event).unwrap();
+            }
+        }
+        let written = writer.into_inner();
+        assert_eq!(parse_with_xml_rs(&written), theirs);
+
+        // read with this crate, write with xml-rs
+        let mut writer = xml::EmitterConfig::from(EmitterConfig::new()).create_writer(Vec::new());
+        for event in ours.iter().cloned().map(xml::reader::XmlEvent::from) {
+            if let Some(event) = event.as_writer_event() {
+                writer.write(event).unwrap();
+            }
+        }
+        let written = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(parse(&written), ours);
+    }
+
+    #[test]
+    fn configs() {
+        let config = ParserConfig::new().trim_whitespace(true).add_entity("e", "x").ignore_root_level_whitespace(false);
+        let converted = xml::ParserConfig::from(config.clone());
+        assert!(converted.trim_whitespace && !converted.ignore_root_level_whitespace);
+        assert_eq!(converted.extra_entities["e"], "x");
+        assert_eq!(ParserConfig::from(converted), config);
+
+        let config = ParserConfig2::from(config).override_encoding(Some(Encoding::Latin1)).max_attributes(3);
+        let converted = xml::reader::ParserConfig2::from(config);
+        assert_eq!(converted.override_encoding, Some(xml::Encoding::Latin1));
+        assert_eq!(converted.max_attributes, 3);
+        let mut reader = converted.create_reader("<a>&e;</a>".as_bytes());
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.next().unwrap(), xml::reader::XmlEvent::Characters("x".into()));
+        let chunked = ParserConfig2::new().coalesce_characters(true).characters_chunk_size(Some(4));
+        let mut reader = xml::reader::ParserConfig2::from(chunked).create_reader("<a>x<!--c-->y</a>".as_bytes());
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.next().unwrap(), xml::reader::XmlEvent::Characters("x".into()));
+
+        let config = EmitterConfig::new().perform_indent(true).indent_string("\t").single_quote_attributes(true);
+        let converted = xml::EmitterConfig::from(config);
+        assert!(converted.perform_indent);
+        assert_eq!(converted.indent_string, "\t");
+        let back = EmitterConfig::from(converted);
+        assert!(back.perform_indent && !back.single_quote_attributes);
+
+        for encoding in [Encoding::Utf8, Encoding::Latin1, Encoding::Utf16Be, Encoding::Unknown] {
+            assert_eq!(Encoding::try_from(xml::Encoding::from(encoding)), Ok(encoding));
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! Decoding of bytes into chars, as the reader does it.
+//!
+//! `Decoder` takes bytes in one of the supported encodings and returns chars one at a time,
+//! so it works on any byte iterator without buffering the input. It detects the encoding from
+//! a byte order mark or the first character like the reader does, and the encoding can be
+//! changed while decoding, e.g. once an XML or text declaration names it, see
+//! `Encoding::with_declared`. This is for parsers of other XML-related input, like external
+//! DTD subsets, which should decode it the same way.
+//!
+//! ```rust
+//! use xml_no_std::decode::{Decoder, Encoding};
+//!
+//! let bytes = b"\xFF\xFE<\0a\0>\0";
+//! let mut chars = Decoder::new().decode(bytes.iter());
+//! assert_eq!(chars.by_ref().collect::<Result<String, _>>().unwrap(), "<a>");
+//! assert_eq!(chars.decoder().encoding(), Encoding::Utf16Le);
+//! ```
+extern crate alloc;
+
+use core::borrow::Borrow;
+use core::char::DecodeUtf16Error;
+use core::fmt;
+use core::str::{self, FromStr};
+
+/// An error decoding bytes, see `Decoder::next_char`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The bytes ended within a character or a byte order mark.
+    UnexpectedEof,
+    /// The bytes aren't valid UTF-8.
+    Utf8(str::Utf8Error),
+    /// The byte isn't ASCII, but the encoding is US-ASCII.
+    NotAscii(u8),
+    /// The bytes are an unpaired surrogate in UTF-16.
+    Utf16(DecodeUtf16Error),
+}
+
+impl From<str::Utf8Error> for DecodeError {
+    #[cold]
+    fn from(e: str::Utf8Error) -> DecodeError {
+        DecodeError::Utf8(e)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    #[cold]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => write!(f, "unexpected end of stream"),
+            DecodeError::Utf8(e) => write!(f, "UTF-8 decoding error: {e}"),
+            DecodeError::NotAscii(b) => write!(f, "byte 0x{b:02X} is not ASCII"),
+            DecodeError::Utf16(e) => write!(f, "UTF-16 decoding error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            DecodeError::Utf8(e) => Some(e),
+            DecodeError::Utf16(e) => Some(e),
+            DecodeError::UnexpectedEof | DecodeError::NotAscii(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DecodeError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            DecodeError::UnexpectedEof => defmt::write!(f, "unexpected end of stream"),
+            DecodeError::Utf8(e) => defmt::write!(f, "UTF-8 decoding error at byte {=usize}", e.valid_up_to()),
+            DecodeError::NotAscii(b) => defmt::write!(f, "byte {=u8:#04x} is not ASCII", b),
+            DecodeError::Utf16(e) => defmt::write!(f, "UTF-16 decoding error: unpaired surrogate {=u16:#06x}", e.unpaired_surrogate()),
+        }
+    }
+}
+
+/// Character encoding used for parsing
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// Explicitly UTF-8 only
+    Utf8,
+    /// UTF-8 fallback, but can be any 8-bit encoding
+    Default,
+    /// ISO-8859-1
+    Latin1,
+    /// US-ASCII
+    Ascii,
+    /// Big-Endian
+    Utf16Be,
+    /// Little-Endian
+    Utf16Le,
+    /// Unknown endianness yet, will be sniffed
+    Utf16,
+    /// Not determined yet, may be sniffed to be anything
+    Unknown,
+}
+
+// Rustc inlines eq_ignore_ascii_case and creates kilobytes of code!
+#[inline(never)]
+fn icmp(lower: &str, varcase: &str) -> bool {
+    lower.bytes().zip(varcase.bytes()).all(|(l, v)| l == v.to_ascii_lowercase())
+}
+
+impl FromStr for Encoding {
+    type Err = &'static str;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        if ["utf-8", "utf8"].into_iter().any(move |label| icmp(label, val)) {
+            Ok(Encoding::Utf8)
+        } else if ["iso-8859-1", "latin1"].into_iter().any(move |label| icmp(label, val)) {
+            Ok(Encoding::Latin1)
+        } else if ["utf-16", "utf16"].into_iter().any(move |label| icmp(label, val)) {
+            Ok(Encoding::Utf16)
+        } else if ["ascii", "us-ascii"].into_iter().any(move |label| icmp(label, val)) {
+            Ok(Encoding::Ascii)
+        } else {
+            Err("unknown encoding name")
+        }
+    }
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Default => "UTF-8",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Ascii => "US-ASCII",
+            Encoding::Utf16Be => "UTF-16",
+            Encoding::Utf16Le => "UTF-16",
+            Encoding::Utf16 => "UTF-16",
+            Encoding::Unknown => "(unknown)",
+        }
+    }
+
+    /// Returns the encoding to go on decoding with when input decoded with this one declares
+    /// the encoding `declared`, e.g. in its XML declaration, or `None` if they conflict.
+    ///
+    /// A declaration can only choose an encoding if none was detected, or if it was only
+    /// assumed to be UTF-8 for lack of a byte order mark. Otherwise it has to agree with the
+    /// detected one, and `UTF-16` agrees with either byte order.
+    ///
+    /// ```rust
+    /// use xml_no_std::Encoding;
+    ///
+    /// assert_eq!(Encoding::Default.with_declared(Encoding::Latin1), Some(Encoding::Latin1));
+    /// assert_eq!(Encoding::Utf16Le.with_declared(Encoding::Utf16), Some(Encoding::Utf16Le));
+    /// assert_eq!(Encoding::Utf8.with_declared(Encoding::Latin1), None);
+    /// ```
+    #[must_use]
+    pub fn with_declared(self, declared: Encoding) -> Option<Encoding> {
+        match (self, declared) {
+            (current, declared) if current == declared => Some(current),
+            (Encoding::Unknown | Encoding::Default, declared) if declared != Encoding::Utf16 => Some(declared),
+            (Encoding::Utf16Be | Encoding::Utf16Le, Encoding::Utf16) => Some(self),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    #[cold]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Encoding {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.name());
+    }
+}
+
+/// Decodes bytes into chars, one char at a time.
+///
+/// It starts out with `Encoding::Unknown`, and detects the encoding from the first bytes:
+///
+/// * A UTF-8 or UTF-16 byte order mark sets the encoding and is skipped.
+/// * With `Encoding::Utf16`, the byte order is taken from the first character, which is
+///   assumed to be ASCII, as `<` is in XML.
+/// * Otherwise it's `Encoding::Default`, i.e. UTF-8 which a declaration may still change.
+///
+/// Decoding errors don't stop the decoder: the bytes of the bad character are skipped, and
+/// the next call goes on with the bytes after them. In UTF-16, a high surrogate which isn't
+/// followed by a low one is an error, and the code unit after it is decoded by the next call.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    encoding: Encoding,
+    /// UTF-16 code unit which was read after an unpaired high surrogate
+    pending_unit: Option<u16>,
+}
+
+impl Default for Decoder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    /// Creates a decoder which detects the encoding.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_encoding(Encoding::Unknown)
+    }
+
+    /// Creates a decoder for the encoding. With `Encoding::Unknown` or `Encoding::Utf16`, it's
+    /// still detected, see `Decoder`.
+    #[inline]
+    #[must_use]
+    pub fn with_encoding(encoding: Encoding) -> Self {
+        Self { encoding, pending_unit: None }
+    }
+
+    /// Returns the encoding, which is `Encoding::Unknown` until it's detected.
+    #[inline]
+    #[must_use]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Changes the encoding for the bytes which follow.
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Wraps a byte iterator into an iterator of the chars decoded from it.
+    #[inline]
+    pub fn decode<I: Iterator>(self, bytes: I) -> Decode<I> where I::Item: Borrow<u8> {
+        Decode { decoder: self, bytes }
+    }
+
+    /// Takes the bytes of the next char from `bytes`, of `u8` or `&u8`, and decodes it, or
+    /// returns `Ok(None)` if `bytes` ends before it, i.e. at the end of the input.
+    ///
+    /// # Errors
+    ///
+    /// `DecodeError::UnexpectedEof` if `bytes` ends within a char or byte order mark, or
+    /// another `DecodeError` if the bytes aren't valid in the encoding.
+    pub fn next_char<S: Iterator>(&mut self, bytes: &mut S) -> Result<Option<char>, DecodeError> where S::Item: Borrow<u8> {
+        const MAX_CODEPOINT_LEN: usize = 4;
+
+        if let Some(unit) = self.pending_unit.take() {
+            return self.decode_utf16(bytes, unit);
+        }
+
+        let mut buf = [0u8; MAX_CODEPOINT_LEN];
+        let mut pos = 0;
+        loop {
+            let next = match bytes.next() {
+                Some(b) => *b.borrow(),
+                None if pos == 0 => return Ok(None),
+                None => return Err(DecodeError::UnexpectedEof),
+            };
+
+            match self.encoding {
+                Encoding::Utf8 | Encoding::Default => {
+                    // fast path for ASCII subset
+                    if pos == 0 && next.is_ascii() {
+                        return Ok(Some(next.into()));
+                    }
+
+                    buf[pos] = next;
+                    pos += 1;
+
+                    match str::from_utf8(&buf[..pos]) {
+                        Ok(s) => return Ok(s.chars().next()), // always Some(..)
+                        Err(_) if pos < MAX_CODEPOINT_LEN => continue,
+                        Err(e) => return Err(e.into()),
+                    }
+                },
+                Encoding::Latin1 => {
+                    return Ok(Some(next.into()));
+                },
+                Encoding::Ascii => {
+                    if next.is_ascii() {
+                        return Ok(Some(next.into()));
+                    } else {
+                        return Err(DecodeError::NotAscii(next));
+                    }
+                },
+                Encoding::Unknown | Encoding::Utf16 => {
+                    buf[pos] = next;
+                    pos += 1;
+
+                    // sniff BOM
+                    if pos <= 3 && buf[..pos] == [0xEF, 0xBB, 0xBF][..pos] {
+                        if pos == 3 && self.encoding != Encoding::Utf16 {
+                            pos = 0;
+                            self.encoding = Encoding::Utf8;
+                            debug!("UTF-8 byte order mark");
+                        }
+                    } else if pos <= 2 && buf[..pos] == [0xFE, 0xFF][..pos] {
+                        if pos == 2 {
+                            pos = 0;
+                            self.encoding = Encoding::Utf16Be;
+                            debug!("UTF-16BE byte order mark");
+                        }
+                    } else if pos <= 2 && buf[..pos] == [0xFF, 0xFE][..pos] {
+                        if pos == 2 {
+                            pos = 0;
+                            self.encoding = Encoding::Utf16Le;
+                            debug!("UTF-16LE byte order mark");
+                        }
+                    } else if pos == 1 && self.encoding == Encoding::Utf16 {
+                        // sniff ASCII char in UTF-16
+                        self.encoding = if next == 0 { Encoding::Utf16Be } else { Encoding::Utf16Le };
+                        debug!("{} detected from the first character", self.encoding);
+                    } else {
+                        // UTF-8 is the default, but XML decl can change it to other 8-bit encoding
+                        self.encoding = Encoding::Default;
+                        debug!("no byte order mark, assuming UTF-8");
+                        if pos == 1 && next.is_ascii() {
+                            return Ok(Some(next.into()));
+                        }
+                    }
+                },
+                Encoding::Utf16Be | Encoding::Utf16Le => {
+                    buf[pos] = next;
+                    pos += 1;
+                    if pos == 2 {
+                        let unit = self.utf16_unit([buf[0], buf[1]]);
+                        return self.decode_utf16(bytes, unit);
+                    }
+                },
+            }
+        }
+    }
+
+    #[inline]
+    fn utf16_unit(&self, bytes: [u8; 2]) -> u16 {
+        if self.encoding == Encoding::Utf16Be { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    }
+
+    /// Decodes the UTF-16 code unit, with the low surrogate after it if it's a high surrogate
+    fn decode_utf16<S: Iterator>(&mut self, bytes: &mut S, unit: u16) -> Result<Option<char>, DecodeError> where S::Item: Borrow<u8> {
+        if !(0xD800..=0xDBFF).contains(&unit) {
+            // a lone low surrogate is an error
+            return char::decode_utf16([unit]).next().transpose().map_err(DecodeError::Utf16);
+        }
+        let low = match (bytes.next(), bytes.next()) {
+            (Some(first), Some(second)) => self.utf16_unit([*first.borrow(), *second.borrow()]),
+            _ => return Err(DecodeError::UnexpectedEof),
+        };
+        match char::decode_utf16([unit, low]).next() {
+            Some(Ok(c)) => Ok(Some(c)),
+            Some(Err(e)) => {
+                // not a low surrogate, so it's a char of its own
+                self.pending_unit = Some(low);
+                Err(DecodeError::Utf16(e))
+            },
+            None => unreachable!(),
+        }
+    }
+}
+
+/// An iterator of the chars decoded from bytes, see `Decoder::decode`.
+///
+/// After an error, it goes on with the bytes after the bad char, see `Decoder`.
+pub struct Decode<I> {
+    decoder: Decoder,
+    bytes: I,
+}
+
+impl<I> Decode<I> {
+    /// Returns the decoder, e.g. to check which encoding it detected.
+    #[inline]
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Returns the decoder, e.g. to change the encoding after a declaration.
+    #[inline]
+    pub fn decoder_mut(&mut self) -> &mut Decoder {
+        &mut self.decoder
+    }
+
+    /// Returns the decoder and the rest of the bytes.
+    #[inline]
+    pub fn into_inner(self) -> (Decoder, I) {
+        (self.decoder, self.bytes)
+    }
+}
+
+impl<I: Iterator> Iterator for Decode<I> where I::Item: Borrow<u8> {
+    type Item = Result<char, DecodeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decoder.next_char(&mut self.bytes).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, Decoder, Encoding};
+
+    fn first_char(encoding: Encoding, bytes: &[u8]) -> Result<Option<char>, DecodeError> {
+        Decoder::with_encoding(encoding).next_char(&mut bytes.iter())
+    }
+
+    #[test]
+    fn next_char() {
+        assert_eq!(first_char(Encoding::Unknown, b"correct"), Ok(Some('c')));
+        assert_eq!(first_char(Encoding::Unknown, "правильно".as_bytes()), Ok(Some('п')));
+        assert_eq!(first_char(Encoding::Unknown, "😊".as_bytes()), Ok(Some('😊')));
+        assert_eq!(first_char(Encoding::Unknown, b""), Ok(None));
+        assert_eq!(first_char(Encoding::Unknown, b"\xf0\x9f\x98"), Err(DecodeError::UnexpectedEof));
+        assert!(matches!(first_char(Encoding::Unknown, b"\xff\x9f\x98\x32"), Err(DecodeError::Utf8(_))));
+
+        assert_eq!(first_char(Encoding::Utf16Be, "правильно".as_bytes()), Ok(Some('킿')));
+        assert_eq!(first_char(Encoding::Utf16Le, "правильно".as_bytes()), Ok(Some('뿐')));
+        assert_eq!(first_char(Encoding::Utf16Be, b"\x00"), Err(DecodeError::UnexpectedEof));
+        assert_eq!(first_char(Encoding::Utf16, b"\x00\x42"), Ok(Some('B')));
+        assert_eq!(first_char(Encoding::Utf16, b"\x42\x00"), Ok(Some('B')));
+        assert!(matches!(first_char(Encoding::Utf16, b"\xD8\xD8\x80\x00"), Err(DecodeError::Utf16(_))));
+
+        assert_eq!(first_char(Encoding::Latin1, b"\xE9"), Ok(Some('é')));
+        assert_eq!(first_char(Encoding::Ascii, b"\xE9"), Err(DecodeError::NotAscii(0xE9)));
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.next_char(&mut b"\xEF\xBB\xBF\xE2\x80\xA2!".iter()), Ok(Some('•')));
+        assert_eq!(decoder.encoding(), Encoding::Utf8);
+
+        assert_eq!(first_char(Encoding::Unknown, b"\xEF\xBB\xBF"), Ok(None));
+        assert_eq!(first_char(Encoding::Unknown, b"\xEF\xBB"), Err(DecodeError::UnexpectedEof));
+        assert!(first_char(Encoding::Unknown, b"\xEF\xBB\x42").is_err());
+        assert_eq!(first_char(Encoding::Unknown, b"\xFE\xFF\x00\x42"), Ok(Some('B')));
+        assert_eq!(first_char(Encoding::Unknown, b"\xFF\xFE\x42\x00"), Ok(Some('B')));
+        assert_eq!(first_char(Encoding::Unknown, b"\xFF\xFE"), Ok(None));
+        assert_eq!(first_char(Encoding::Unknown, b"\xFF\xFE\x00"), Err(DecodeError::UnexpectedEof));
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(first_char(Encoding::Utf16Le, b"\x3D\xD8\x00\xDE"), Ok(Some('\u{1F600}')));
+        assert_eq!(first_char(Encoding::Utf16Be, b"\xD8\x3D\xDE\x00"), Ok(Some('\u{1F600}')));
+        assert_eq!(first_char(Encoding::Utf16Be, b"\xD8\x3D\xDE"), Err(DecodeError::UnexpectedEof));
+        assert_eq!(first_char(Encoding::Utf16Be, b"\xD8\x3D"), Err(DecodeError::UnexpectedEof));
+
+        let unpaired = |e: Result<_, _>| match e {
+            Err(DecodeError::Utf16(e)) => e.unpaired_surrogate(),
+            e => panic!("{e:?}"),
+        };
+        // a lone low surrogate doesn't take the next unit with it
+        let mut chars = Decoder::with_encoding(Encoding::Utf16Be).decode(b"\xDE\x00\x00a".iter());
+        assert_eq!(unpaired(chars.next().unwrap()), 0xDE00);
+        assert_eq!(chars.next(), Some(Ok('a')));
+        assert_eq!(chars.next(), None);
+
+        // neither does a high surrogate followed by something else
+        let bytes = b"\x3D\xD8a\x00\x3D\xD8\x3D\xD8\x00\xDE";
+        let mut chars = Decoder::with_encoding(Encoding::Utf16Le).decode(bytes.iter());
+        assert_eq!(unpaired(chars.next().unwrap()), 0xD83D);
+        assert_eq!(chars.next(), Some(Ok('a')));
+        assert_eq!(unpaired(chars.next().unwrap()), 0xD83D);
+        assert_eq!(chars.next(), Some(Ok('\u{1F600}')));
+        assert_eq!(chars.next(), None);
+    }
+
+    #[test]
+    fn decode_goes_on_after_errors() {
+        let mut chars = Decoder::with_encoding(Encoding::Ascii).decode(b"a\xE9b".iter());
+        assert_eq!(chars.next(), Some(Ok('a')));
+        assert_eq!(chars.next(), Some(Err(DecodeError::NotAscii(0xE9))));
+        chars.decoder_mut().set_encoding(Encoding::Latin1);
+        assert_eq!(chars.next(), Some(Ok('b')));
+        assert_eq!(chars.next(), None);
+    }
+
+    #[test]
+    fn declared_encoding() {
+        assert_eq!(Encoding::Unknown.with_declared(Encoding::Utf8), Some(Encoding::Utf8));
+        assert_eq!(Encoding::Unknown.with_declared(Encoding::Utf16), None);
+        assert_eq!(Encoding::Utf16Be.with_declared(Encoding::Utf16), Some(Encoding::Utf16Be));
+        assert_eq!(Encoding::Utf16Be.with_declared(Encoding::Utf8), None);
+        assert_eq!(Encoding::Ascii.with_declared(Encoding::Ascii), Some(Encoding::Ascii));
+        assert_eq!(Encoding::Default.with_declared(Encoding::Ascii), Some(Encoding::Ascii));
+    }
+}
@@ -2,28 +2,64 @@
 extern crate alloc;
 
 use alloc::borrow::Cow;
-use alloc::string::ToString;
+use alloc::string::String;
 use core::marker::PhantomData;
 use core::fmt::{Display, Result, Formatter};
 
-pub(crate) trait Escapes {
+#[cfg(feature = "html-entities")]
+mod html_entities;
+
+/// An escaping context: which characters are replaced, and with what.
+///
+/// `AttributeEscapes` and `PcDataEscapes` are the ones the writer uses. Other contexts can
+/// be defined for `Escaped` and `escape_str_with`, e.g. for XML embedded in XPath 2.0 string
+/// literals, where a `"` is written twice:
+///
+/// ```rust
+/// # use xml_no_std::escape::{escape_str_with, Escapes};
+/// struct XPathLiteral;
+///
+/// impl Escapes for XPathLiteral {
+///     fn escape(c: u8) -> Option<&'static str> {
+///         match c {
+///             b'"' => Some("\"\""),
+///             b'<' => Some("&lt;"),
+///             b'&' => Some("&amp;"),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// assert_eq!(escape_str_with::<XPathLiteral>(r#"say "<hi>""#), r#"say ""&lt;hi>"""#);
+/// ```
+pub trait Escapes {
+    /// Returns the replacement for the byte `c`, or `None` if it's written as is.
+    ///
+    /// Only ASCII characters can be replaced, other bytes are never passed in.
     fn escape(c: u8) -> Option<&'static str>;
 
+    /// Returns `true` if `c` has a replacement. Can be overridden with a faster check.
+    #[inline]
     fn byte_needs_escaping(c: u8) -> bool {
         Self::escape(c).is_some()
     }
-
-    fn str_needs_escaping(s: &str) -> bool {
-        s.bytes().any(|c| Self::escape(c).is_some())
-    }
 }
 
-pub(crate) struct Escaped<'a, E: Escapes> {
+/// Displays a string escaped with the rules `E`, without allocating.
+///
+/// ```rust
+/// # use xml_no_std::escape::{Escaped, PcDataEscapes};
+/// assert_eq!(format!("<p>{}</p>", Escaped::<PcDataEscapes>::new("1 < 2")), "<p>1 &lt; 2</p>");
+/// ```
+pub struct Escaped<'a, E: Escapes> {
     _escape_phantom: PhantomData<E>,
     to_escape: &'a str,
 }
 
 impl<'a, E: Escapes> Escaped<'a, E> {
+    /// Wraps the string to escape.
+    #[inline]
+    #[must_use]
     pub fn new(s: &'a str) -> Self {
         Escaped {
             _escape_phantom: PhantomData,
@@ -34,43 +70,176 @@ impl<'a, E: Escapes> Escaped<'a, E> {
 
 impl<'a, E: Escapes> Display for Escaped<'a, E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        let mut total_remaining = self.to_escape;
+        escape_runs::<E>(self.to_escape, |s| f.write_str(s))
+    }
+}
 
-        // find the next occurence
-        while let Some(n) = total_remaining
-            .bytes()
-            .position(E::byte_needs_escaping)
-        {
-            let (start, remaining) = total_remaining.split_at(n);
+/// Passes the runs of `s` which need no escaping and the replacements between them to `write`,
+/// so runs are copied at once rather than char by char
+#[inline]
+fn escape_runs<E: Escapes>(s: &str, mut write: impl FnMut(&str) -> Result) -> Result {
+    let mut total_remaining = s;
 
-            f.write_str(start)?;
+    // find the next occurence
+    while let Some(n) = total_remaining
+        .bytes()
+        .position(needs_escaping::<E>)
+    {
+        let (start, remaining) = total_remaining.split_at(n);
 
-            // unwrap is safe because we checked is_some for position n earlier
-            let next_byte = remaining.bytes().next().unwrap();
-            let replacement = E::escape(next_byte).unwrap_or("unexpected token");
-            f.write_str(replacement)?;
+        write(start)?;
 
-            total_remaining = &remaining[1..];
-        }
+        // unwrap is safe because we checked is_some for position n earlier
+        let next_byte = remaining.bytes().next().unwrap();
+        let replacement = E::escape(next_byte).unwrap_or("unexpected token");
+        write(replacement)?;
 
-        f.write_str(total_remaining)
+        total_remaining = &remaining[1..];
     }
+
+    write(total_remaining)
 }
 
-fn escape_str<E: Escapes>(s: &str) -> Cow<'_, str> {
-    if E::str_needs_escaping(s) {
-        Cow::Owned(Escaped::<E>::new(s).to_string())
-    } else {
-        Cow::Borrowed(s)
+#[inline]
+fn needs_escaping<E: Escapes>(c: u8) -> bool {
+    c.is_ascii() && E::byte_needs_escaping(c)
+}
+
+/// Writes `input` escaped with `rules` to `out`, without allocating.
+///
+/// Use it to escape large text straight into a sink. To escape text which is formatted,
+/// wrap the sink in an `EscapingWriter` instead.
+///
+/// ```rust
+/// # use xml_no_std::escape::{escape_to, AttributeEscapes};
+/// let mut out = String::from("<a b=\"");
+/// escape_to(AttributeEscapes, "\"1 < 2\"", &mut out).unwrap();
+/// out.push_str("\"/>");
+/// assert_eq!(out, "<a b=\"&quot;1 &lt; 2&quot;\"/>");
+/// ```
+#[inline]
+pub fn escape_to<E: Escapes, W: core::fmt::Write + ?Sized>(rules: E, input: &str, out: &mut W) -> Result {
+    let _ = rules;
+    escape_runs::<E>(input, |run| out.write_str(run))
+}
+
+/// Appends escaped `s` to `target`, without formatting machinery or temporary strings
+#[inline]
+pub(crate) fn escape_into<E: Escapes>(rules: E, target: &mut String, s: &str) {
+    // writing to a `String` can't fail
+    let _ = escape_to(rules, s, target);
+}
+
+/// A `fmt::Write` adapter which escapes everything written through it.
+///
+/// ```rust
+/// # use core::fmt::Write;
+/// # use xml_no_std::escape::EscapingWriter;
+/// let mut out = String::from("<p>");
+/// write!(EscapingWriter::pcdata(&mut out), "{} & {}", "<b>", 42).unwrap();
+/// out.push_str("</p>");
+/// assert_eq!(out, "<p>&lt;b&gt; &amp; 42</p>");
+/// ```
+#[derive(Debug)]
+pub struct EscapingWriter<W, E: Escapes> {
+    inner: W,
+    _escape_phantom: PhantomData<E>,
+}
+
+impl<W: core::fmt::Write> EscapingWriter<W, PcDataEscapes> {
+    /// Escapes text written to `inner` as character data.
+    #[inline]
+    pub fn pcdata(inner: W) -> Self {
+        Self::new(PcDataEscapes, inner)
+    }
+}
+
+impl<W: core::fmt::Write> EscapingWriter<W, AttributeEscapes> {
+    /// Escapes text written to `inner` as an attribute value quoted with `"`.
+    #[inline]
+    pub fn attribute(inner: W) -> Self {
+        Self::new(AttributeEscapes, inner)
+    }
+}
+
+impl<W: core::fmt::Write, E: Escapes> EscapingWriter<W, E> {
+    /// Escapes text written to `inner` with `rules`.
+    #[inline]
+    pub fn new(rules: E, inner: W) -> Self {
+        let _ = rules;
+        EscapingWriter { inner, _escape_phantom: PhantomData }
+    }
+
+    /// Returns a reference to the underlying writer.
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer. Text written to it directly
+    /// isn't escaped.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: core::fmt::Write, E: Escapes> core::fmt::Write for EscapingWriter<W, E> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result {
+        escape_runs::<E>(s, |run| self.inner.write_str(run))
+    }
+
+    #[inline]
+    fn write_char(&mut self, c: char) -> Result {
+        self.write_str(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+/// Escapes a string with the rules `E`, like `escape_str_attribute` does with `AttributeEscapes`.
+///
+/// Does not perform allocations if the given string does not contain escapable characters.
+#[must_use]
+pub fn escape_str_with<E: Escapes>(s: &str) -> Cow<'_, str> {
+    match s.bytes().position(needs_escaping::<E>) {
+        None => Cow::Borrowed(s),
+        Some(first) => {
+            // most strings need few replacements, which are at most six bytes longer
+            let mut escaped = String::with_capacity(s.len() + 8);
+            escaped.push_str(&s[..first]);
+            let _ = escape_runs::<E>(&s[first..], |run| {
+                escaped.push_str(run);
+                Ok(())
+            });
+            Cow::Owned(escaped)
+        },
     }
 }
 
 macro_rules! escapes {
     {
+        $(#[$meta: meta])*
         $name: ident,
         $($k: expr => $v: expr),* $(,)?
     } => {
-        pub(crate) struct $name;
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug)]
+        pub struct $name;
+
+        impl $name {
+            /// Which bytes have a replacement, so scanning is a lookup per byte
+            const NEEDS_ESCAPING: [bool; 256] = {
+                let mut table = [false; 256];
+                $( table[$k as usize] = true; )*
+                table
+            };
+        }
 
         impl Escapes for $name {
             fn escape(c: u8) -> Option<&'static str> {
@@ -79,11 +248,17 @@ macro_rules! escapes {
                     _ => None
                 }
             }
+
+            #[inline(always)]
+            fn byte_needs_escaping(c: u8) -> bool {
+                Self::NEEDS_ESCAPING[c as usize]
+            }
         }
     };
 }
 
 escapes!(
+    /// The rules for attribute values, see `escape_str_attribute`.
     AttributeEscapes,
     b'<'  => "&lt;",
     b'>'  => "&gt;",
@@ -91,14 +266,50 @@ escapes!(
     b'\'' => "&apos;",
     b'&'  => "&amp;",
     b'\n' => "&#xA;",
-    b'\r' => "&#xD;",
+    // the same as in character data
+    b'\r' => "&#13;",
+);
+
+escapes!(
+    /// The rules for attribute values quoted with `'`, see `escape_str_attribute_apos`.
+    AposAttributeEscapes,
+    b'<'  => "&lt;",
+    b'>'  => "&gt;",
+    b'\'' => "&apos;",
+    b'&'  => "&amp;",
+    b'\n' => "&#xA;",
+    // the same as in character data
+    b'\r' => "&#13;",
 );
 
 escapes!(
+    /// The rules for character data, see `escape_str_pcdata`.
     PcDataEscapes,
     b'<' => "&lt;",
     b'>' => "&gt;",
     b'&' => "&amp;",
+    // a literal CR would be read back as LF
+    b'\r' => "&#13;",
+);
+
+escapes!(
+    /// The rules of Canonical XML for attribute values, see `canonical::canonicalize`.
+    CanonicalAttributeEscapes,
+    b'<'  => "&lt;",
+    b'"'  => "&quot;",
+    b'&'  => "&amp;",
+    b'\t' => "&#x9;",
+    b'\n' => "&#xA;",
+    b'\r' => "&#xD;",
+);
+
+escapes!(
+    /// The rules of Canonical XML for character data, see `canonical::canonicalize`.
+    CanonicalPcDataEscapes,
+    b'<'  => "&lt;",
+    b'>'  => "&gt;",
+    b'&'  => "&amp;",
+    b'\r' => "&#xD;",
 );
 
 /// Performs escaping of common XML characters inside an attribute value.
@@ -115,15 +326,37 @@ escapes!(
 /// The following characters are escaped so that attributes are printed on
 /// a single line:
 /// * `\n` → `&#xA;`
-/// * `\r` → `&#xD;`
+/// * `\r` → `&#13;`
 ///
 /// The resulting string is safe to use inside XML attribute values or in PCDATA sections.
+/// It's exactly what `EventWriter` writes for attribute values when
+/// `EmitterConfig::perform_escaping` is on, which it is by default.
 ///
 /// Does not perform allocations if the given string does not contain escapable characters.
+///
+/// ```rust
+/// # use xml_no_std::escape::escape_str_attribute;
+/// assert_eq!(escape_str_attribute("say \"1 < 2\""), "say &quot;1 &lt; 2&quot;");
+/// ```
 #[inline]
 #[must_use]
 pub fn escape_str_attribute(s: &str) -> Cow<'_, str> {
-    escape_str::<AttributeEscapes>(s)
+    escape_str_with::<AttributeEscapes>(s)
+}
+
+/// Like `escape_str_attribute`, for attribute values quoted with `'` rather than `"`.
+///
+/// `'` is replaced with `&apos;`, and `"` is left as is. It's what `EventWriter` writes with
+/// `EmitterConfig::single_quote_attributes`.
+///
+/// ```rust
+/// # use xml_no_std::escape::escape_str_attribute_apos;
+/// assert_eq!(escape_str_attribute_apos(r#"it's "1 < 2""#), r#"it&apos;s "1 &lt; 2""#);
+/// ```
+#[inline]
+#[must_use]
+pub fn escape_str_attribute_apos(s: &str) -> Cow<'_, str> {
+    escape_str_with::<AposAttributeEscapes>(s)
 }
 
 /// Performs escaping of common XML characters inside PCDATA.
@@ -132,36 +365,665 @@ pub fn escape_str_attribute(s: &str) -> Cow<'_, str> {
 /// entity equivalents:
 ///
 /// * `<` → `&lt;`
+/// * `>` → `&gt;`
 /// * `&` → `&amp;`
 ///
+/// Carriage returns are escaped as `&#13;`, since readers replace literal ones with `\n`.
+///
 /// The resulting string is safe to use inside PCDATA sections but NOT inside attribute values.
+/// It's exactly what `EventWriter` writes for `XmlEvent::Characters` when
+/// `EmitterConfig::perform_escaping` is on, which it is by default.
 ///
 /// Does not perform allocations if the given string does not contain escapable characters.
 #[inline]
 #[must_use]
 pub fn escape_str_pcdata(s: &str) -> Cow<'_, str> {
-    escape_str::<PcDataEscapes>(s)
+    escape_str_with::<PcDataEscapes>(s)
+}
+
+/// Text which is already escaped, e.g. copied verbatim from another XML document, and is
+/// written as is.
+///
+/// It's accepted by `StartElementBuilder::attr_pre_escaped` for attribute values and by
+/// `EventWriter::write_pre_escaped` for character data. The writer checks it when writing:
+/// a raw `<`, a `&` which doesn't start a reference, the quote character in an attribute value
+/// or `]]>` in character data fail with `EmitterError::InvalidPreEscaped`, instead of making
+/// a malformed document.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PreEscaped<'a>(&'a str);
+
+impl<'a> PreEscaped<'a> {
+    /// Wraps an escaped string. It's checked later, by the writer.
+    #[inline]
+    #[must_use]
+    pub const fn new(escaped: &'a str) -> PreEscaped<'a> {
+        PreEscaped(escaped)
+    }
+
+    /// Returns the escaped string.
+    #[inline]
+    #[must_use]
+    pub const fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    /// Checks that the text can be written as is into an attribute value quoted with `"`.
+    pub fn check_attribute(&self) -> core::result::Result<(), PreEscapedError> {
+        self.check(|s, _| s.starts_with(['<', '"']))
+    }
+
+    /// Checks that the text can be written as is into an attribute value quoted with `'`.
+    pub fn check_attribute_apos(&self) -> core::result::Result<(), PreEscapedError> {
+        self.check(|s, _| s.starts_with(['<', '\'']))
+    }
+
+    /// Checks that the text can be written as is into character data.
+    pub fn check_pcdata(&self) -> core::result::Result<(), PreEscapedError> {
+        self.check(|s, index| s.starts_with('<') || (s.starts_with('>') && self.0[..index].ends_with("]]")))
+    }
+
+    fn check(&self, forbidden: impl Fn(&str, usize) -> bool) -> core::result::Result<(), PreEscapedError> {
+        for (index, found) in self.0.char_indices() {
+            let rest = &self.0[index..];
+            if forbidden(rest, index) || (found == '&' && !starts_with_reference(rest)) {
+                return Err(PreEscapedError { index, found });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for PreEscaped<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Checks for `&name;`, `&#123;` or `&#x7B;` at the start of `s`
+fn starts_with_reference(s: &str) -> bool {
+    let body = match s[1..].split_once(';') {
+        Some((body, _)) => body,
+        None => return false,
+    };
+    if let Some(hex) = body.strip_prefix("#x") {
+        !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit())
+    } else if let Some(dec) = body.strip_prefix('#') {
+        !dec.is_empty() && dec.bytes().all(|b| b.is_ascii_digit())
+    } else {
+        crate::name::is_ncname(body)
+    }
+}
+
+/// A character which can't be in `PreEscaped` text where it's written.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PreEscapedError {
+    /// Byte offset of the character.
+    pub index: usize,
+
+    /// The character, e.g. `&` for a `&` which doesn't start a reference, or `>` of `]]>`.
+    pub found: char,
+}
+
+impl Display for PreEscapedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "unescaped {:?} at index {} in pre-escaped text", self.found, self.index)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for PreEscapedError {}
+
+/// Expands the predefined entities (`&lt;`, `&gt;`, `&amp;`, `&apos;` and `&quot;`) and
+/// character references like `&#60;` and `&#x3C;`, as the reader does in text and attribute values.
+///
+/// Does not perform allocations if the given string does not contain references.
+///
+/// ```rust
+/// # use xml_no_std::escape::{unescape, UnescapeError};
+/// assert_eq!(unescape("1 &lt; 2&#xA;"), Ok("1 < 2\n".into()));
+/// assert_eq!(unescape("&nbsp;"), Err(UnescapeError::UnknownEntity { index: 0, name: "nbsp".into() }));
+/// ```
+pub fn unescape(s: &str) -> core::result::Result<Cow<'_, str>, UnescapeError> {
+    unescape_with(s, |_| None)
+}
+
+/// Like `unescape`, and also expands the entities for which `entities` returns a value,
+/// e.g. the ones declared in a DTD. The values are inserted as they are, without expanding
+/// references in them.
+///
+/// ```rust
+/// # use std::collections::BTreeMap;
+/// # use xml_no_std::escape::unescape_with;
+/// let entities = BTreeMap::from([("nbsp".to_string(), "\u{a0}".to_string())]);
+/// let unescaped = unescape_with("a&nbsp;&amp;", |name| entities.get(name).map(String::as_str));
+/// assert_eq!(unescaped.unwrap(), "a\u{a0}&");
+/// ```
+pub fn unescape_with<'a, 'e, F>(s: &'a str, entities: F) -> core::result::Result<Cow<'a, str>, UnescapeError>
+    where F: Fn(&str) -> Option<&'e str>
+{
+    let rest = match s.find('&') {
+        Some(start) => start,
+        None => return Ok(Cow::Borrowed(s)),
+    };
+    let mut unescaped = String::with_capacity(s.len());
+    unescaped.push_str(&s[..rest]);
+    unescape_into(&s[rest..], rest, &entities, &mut unescaped, false)?;
+    Ok(Cow::Owned(unescaped))
+}
+
+/// Unescapes `s`, which starts at `index` of the whole text, into `out`.
+///
+/// With `allow_partial`, a reference cut off by the end of `s` is left out rather than being
+/// an error. Returns how much of `s` was unescaped.
+fn unescape_into<'e, F>(s: &str, index: usize, entities: &F, out: &mut String, allow_partial: bool) -> core::result::Result<usize, UnescapeError>
+    where F: Fn(&str) -> Option<&'e str>
+{
+    let mut rest = 0;
+    while let Some(start) = s[rest..].find('&').map(|i| rest + i) {
+        out.push_str(&s[rest..start]);
+        let body = &s[start + 1..];
+        let end = match body.find(|c: char| c != '#' && !crate::chars::is_name_char(c)) {
+            Some(end) if body[end..].starts_with(';') => end,
+            None if allow_partial => return Ok(start),
+            _ => return Err(UnescapeError::Unterminated { index: index + start }),
+        };
+        let name = &body[..end];
+        if let Some(c) = predefined_entity(name) {
+            out.push(c);
+        } else if let Some(num) = name.strip_prefix('#') {
+            match crate::common::char_reference_value(num) {
+                Ok(c) if crate::chars::is_xml_char(c) => out.push(c),
+                _ => return Err(UnescapeError::InvalidCharacterReference { index: index + start, reference: num.into() }),
+            }
+        } else if let Some(value) = entities(name) {
+            out.push_str(value);
+        } else {
+            return Err(UnescapeError::UnknownEntity { index: index + start, name: name.into() });
+        }
+        rest = start + end + 2;
+    }
+    out.push_str(&s[rest..]);
+    Ok(s.len())
+}
+
+/// Unescapes text which arrives in chunks, like `unescape_with` does for a whole string.
+///
+/// A reference split between chunks, like `&am` and `p;`, is kept until the rest of it is
+/// pushed; the text around it is written out right away. Error indices count from the start
+/// of the first chunk. After an error, the output is incomplete.
+///
+/// So that only so much is kept, a split reference which gets longer than a character
+/// reference can be, or than `max_reference_length` for an entity name, is `Unterminated`.
+///
+/// ```rust
+/// # use xml_no_std::escape::StreamingUnescaper;
+/// let mut unescaper = StreamingUnescaper::new();
+/// let mut out = String::new();
+/// for chunk in ["1 &l", "t; 2 &am", "p", "; 3"] {
+///     unescaper.push(chunk, &mut out).unwrap();
+/// }
+/// unescaper.finish(&mut out).unwrap();
+/// assert_eq!(out, "1 < 2 & 3");
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamingUnescaper<F> {
+    /// The start of a reference which isn't terminated yet
+    partial: String,
+    /// Where `partial` or the next chunk starts
+    index: usize,
+    max_reference_length: usize,
+    entities: F,
+}
+
+impl StreamingUnescaper<fn(&str) -> Option<&'static str>> {
+    /// Expands the predefined entities and character references only, like `unescape`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_entities(|_| None)
+    }
+}
+
+impl Default for StreamingUnescaper<fn(&str) -> Option<&'static str>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'e, F: Fn(&str) -> Option<&'e str>> StreamingUnescaper<F> {
+    /// Also expands other entities with `entities`, like `unescape_with`.
+    pub fn with_entities(entities: F) -> Self {
+        StreamingUnescaper { partial: String::new(), index: 0, max_reference_length: 256, entities }
+    }
+
+    /// Sets how long a reference to an entity, `&` and `;` included, can be when it's split
+    /// between chunks. It's 256 bytes by default.
+    #[must_use]
+    pub fn max_reference_length(mut self, max: usize) -> Self {
+        self.max_reference_length = max;
+        self
+    }
+
+    /// Fails if `partial` would get too long with `additional` more bytes
+    fn reserve_partial(&mut self, additional: usize) -> core::result::Result<(), UnescapeError> {
+        let max = if self.partial.starts_with("&#") {
+            // `&#x`, the digits and `;`
+            crate::common::MAX_CHAR_REFERENCE_DIGITS + 4
+        } else {
+            self.max_reference_length
+        };
+        // `;` isn't in `partial` yet
+        if additional > 0 && self.partial.len() + additional >= max {
+            let index = self.index;
+            self.index += self.partial.len();
+            self.partial.clear();
+            return Err(UnescapeError::Unterminated { index });
+        }
+        Ok(())
+    }
+
+    /// Unescapes the next chunk into `out`.
+    pub fn push(&mut self, chunk: &str, out: &mut String) -> core::result::Result<(), UnescapeError> {
+        let mut chunk = chunk;
+        if !self.partial.is_empty() {
+            // only take the rest of the buffered reference, up to the character ending it
+            let end = match chunk.find(|c: char| c != '#' && !crate::chars::is_name_char(c)) {
+                Some(end) => end,
+                None => {
+                    self.reserve_partial(chunk.len())?;
+                    self.partial.push_str(chunk);
+                    return Ok(());
+                },
+            };
+            self.reserve_partial(end)?;
+            let end = end + chunk[end..].chars().next().map_or(0, char::len_utf8);
+            self.partial.push_str(&chunk[..end]);
+            chunk = &chunk[end..];
+            let partial = core::mem::take(&mut self.partial);
+            unescape_into(&partial, self.index, &self.entities, out, false)?;
+            self.index += partial.len();
+        }
+        let unescaped = unescape_into(chunk, self.index, &self.entities, out, true)?;
+        self.index += unescaped;
+        self.reserve_partial(chunk.len() - unescaped)?;
+        self.partial.push_str(&chunk[unescaped..]);
+        Ok(())
+    }
+
+    /// Ends the text, failing if it ends inside a reference.
+    ///
+    /// The unterminated reference is still written to `out` as is, for callers which would
+    /// rather keep it than fail. The unescaper can be reused for another text afterwards.
+    pub fn finish(&mut self, out: &mut String) -> core::result::Result<(), UnescapeError> {
+        let index = core::mem::take(&mut self.index);
+        if self.partial.is_empty() {
+            return Ok(());
+        }
+        out.push_str(&self.partial);
+        self.partial.clear();
+        Err(UnescapeError::Unterminated { index })
+    }
+}
+
+/// Returns the text of an HTML named character reference, e.g. `\u{a0}` for `nbsp`.
+///
+/// The name is given without `&` and `;`. The five entities XML predefines are not included,
+/// `unescape` expands them anyway. To expand references like `&nbsp;` and `&copy;`, pass this
+/// function to `unescape_with`, or use `EntityAction::html` for the reader.
+///
+/// Only available with the `html-entities` feature. The table of 2120 names adds about 90 KB
+/// to the binary when it's used.
+///
+/// ```rust
+/// # use xml_no_std::escape::{html_entity, unescape_with};
+/// assert_eq!(unescape_with("&copy; 2024&nbsp;&amp;", html_entity).unwrap(), "\u{a9} 2024\u{a0}&");
+/// assert_eq!(html_entity("nbsp;"), None);
+/// ```
+#[cfg(feature = "html-entities")]
+#[must_use]
+pub fn html_entity(name: &str) -> Option<&'static str> {
+    let entities = html_entities::HTML_ENTITIES;
+    entities.binary_search_by_key(&name, |&(name, _)| name).ok().map(|i| entities[i].1)
+}
+
+fn predefined_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "lt" => '<',
+        "gt" => '>',
+        "amp" => '&',
+        "apos" => '\'',
+        "quot" => '"',
+        _ => return None,
+    })
+}
+
+/// A reference which can't be expanded by `unescape` or `unescape_with`.
+///
+/// Indices are byte offsets of the `&` the reference starts with.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UnescapeError {
+    /// A `&` isn't followed by a name or a number and a `;`.
+    Unterminated {
+        /// Where the reference starts.
+        index: usize,
+    },
+
+    /// An entity which isn't predefined or known.
+    UnknownEntity {
+        /// Where the reference starts.
+        index: usize,
+        /// The entity name, without `&` and `;`.
+        name: String,
+    },
+
+    /// A character reference which isn't a number or isn't an XML character, e.g. `&#0;`.
+    InvalidCharacterReference {
+        /// Where the reference starts.
+        index: usize,
+        /// The reference, without `&#` and `;`, e.g. `x0`.
+        reference: String,
+    },
+}
+
+impl Display for UnescapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            UnescapeError::Unterminated { index } => write!(f, "unterminated reference at index {index}"),
+            UnescapeError::UnknownEntity { index, name } => write!(f, "unknown entity '{name}' at index {index}"),
+            UnescapeError::InvalidCharacterReference { index, reference } =>
+                write!(f, "invalid character reference '&#{reference};' at index {index}"),
+        }
+    }
 }
 
+#[cfg(feature = "core-error")]
+impl core::error::Error for UnescapeError {}
+
 #[cfg(test)]
 mod tests {
-    use super::{escape_str_attribute, escape_str_pcdata};
+    extern crate alloc;
+
+    use super::{escape_str_attribute, escape_str_attribute_apos, escape_str_pcdata};
 
     #[test]
     fn test_escape_str_attribute() {
-        assert_eq!(escape_str_attribute("<>'\"&\n\r"), "&lt;&gt;&apos;&quot;&amp;&#xA;&#xD;");
+        assert_eq!(escape_str_attribute("<>'\"&\n\r"), "&lt;&gt;&apos;&quot;&amp;&#xA;&#13;");
         assert_eq!(escape_str_attribute("no_escapes"), "no_escapes");
     }
 
+    #[test]
+    fn test_escape_str_attribute_apos() {
+        assert_eq!(escape_str_attribute_apos("<>'\"&\n\r"), "&lt;&gt;&apos;\"&amp;&#xA;&#13;");
+        assert_eq!(escape_str_attribute_apos("\"\""), "\"\"");
+    }
+
     #[test]
     fn test_escape_str_pcdata() {
         assert_eq!(escape_str_pcdata("<>&"), "&lt;&gt;&amp;");
+        assert_eq!(escape_str_pcdata("a\r\nb\n"), "a&#13;\nb\n");
         assert_eq!(escape_str_pcdata("no_escapes"), "no_escapes");
     }
 
+    #[test]
+    fn test_escape_borrows_clean_strings() {
+        use alloc::borrow::Cow;
+
+        assert!(matches!(escape_str_attribute("clean ☃ text"), Cow::Borrowed("clean ☃ text")));
+        assert!(matches!(escape_str_pcdata("\"quoted\" 'text'\n"), Cow::Borrowed(_)));
+        assert_eq!(escape_str_pcdata("a<"), "a&lt;");
+        assert_eq!(escape_str_attribute(">"), "&gt;");
+        assert_eq!(escape_str_attribute("a&&b<<c"), "a&amp;&amp;b&lt;&lt;c");
+    }
+
+    #[test]
+    fn custom_rules_skip_non_ascii() {
+        use super::{escape_str_with, Escapes};
+
+        struct Greedy;
+        impl Escapes for Greedy {
+            fn escape(_: u8) -> Option<&'static str> {
+                Some("?")
+            }
+        }
+        assert_eq!(escape_str_with::<Greedy>("a☃b"), "?☃?");
+    }
+
+    /// Escapes char by char, the straightforward way the run-based code must agree with
+    fn escape_naive<E: super::Escapes>(s: &str) -> alloc::string::String {
+        let mut escaped = alloc::string::String::new();
+        for c in s.chars() {
+            match u8::try_from(c).ok().filter(u8::is_ascii).and_then(E::escape) {
+                Some(replacement) => escaped.push_str(replacement),
+                None => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    fn check_against_naive(s: &str) {
+        use super::{AposAttributeEscapes, AttributeEscapes, Escaped, PcDataEscapes};
+
+        assert_eq!(escape_str_attribute(s), escape_naive::<AttributeEscapes>(s), "{s:?}");
+        assert_eq!(escape_str_pcdata(s), escape_naive::<PcDataEscapes>(s), "{s:?}");
+        assert_eq!(escape_str_attribute_apos(s), escape_naive::<AposAttributeEscapes>(s), "{s:?}");
+        assert_eq!(alloc::format!("{}", Escaped::<AttributeEscapes>::new(s)), escape_naive::<AttributeEscapes>(s));
+    }
+
+    #[test]
+    fn byte_tables_match_naive_escaping() {
+        use super::{AposAttributeEscapes, AttributeEscapes, Escapes, PcDataEscapes};
+
+        for b in 0..=255u8 {
+            assert_eq!(AttributeEscapes::byte_needs_escaping(b), AttributeEscapes::escape(b).is_some());
+            assert_eq!(PcDataEscapes::byte_needs_escaping(b), PcDataEscapes::escape(b).is_some());
+            assert_eq!(AposAttributeEscapes::byte_needs_escaping(b), AposAttributeEscapes::escape(b).is_some());
+        }
+
+        // every string of up to two chars, and three of the interesting ones
+        let alphabet: alloc::vec::Vec<char> = (0..128u8).map(char::from).chain(['é', '☃', '\u{10FFFF}']).collect();
+        let mut buf = alloc::string::String::new();
+        for &a in &alphabet {
+            for &b in &alphabet {
+                buf.clear();
+                buf.extend([a, b]);
+                check_against_naive(&buf);
+            }
+        }
+        let special = ['<', '>', '&', '"', '\'', '\n', '\r', '\t', 'a', 'é'];
+        for &a in &special {
+            for &b in &special {
+                for &c in &special {
+                    buf.clear();
+                    buf.extend([a, b, c]);
+                    check_against_naive(&buf);
+                }
+            }
+        }
+
+        // and longer pseudo-random ones
+        let mut seed = 0x2545_f491_u32;
+        for len in 0..300 {
+            buf.clear();
+            for _ in 0..len {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                buf.push(alphabet[seed as usize % alphabet.len()]);
+            }
+            check_against_naive(&buf);
+        }
+    }
+
     #[test]
     fn test_escape_multibyte_code_points() {
         assert_eq!(escape_str_attribute("☃<"), "☃&lt;");
         assert_eq!(escape_str_pcdata("☃<"), "☃&lt;");
     }
+
+    #[test]
+    fn writer_parity() {
+        use crate::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+        for input in ["", "plain", "<>&\"'", "a\r\nb\tc", "☃ ]]> &amp; \u{10FFFF}"] {
+            let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+            writer.write(XmlEvent::start_element("a").attr("b", input)).unwrap();
+            writer.write(XmlEvent::characters(input)).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+            let expected = alloc::format!("<a b=\"{}\">{}</a>", escape_str_attribute(input), escape_str_pcdata(input));
+            assert_eq!(writer.into_inner(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn pre_escaped_checks() {
+        use super::{PreEscaped, PreEscapedError};
+
+        let err = |index, found| Err(PreEscapedError { index, found });
+        assert_eq!(PreEscaped::new("a &amp; &#60; &#x3C; &x; '>").check_attribute(), Ok(()));
+        assert_eq!(PreEscaped::new("a < b").check_attribute(), err(2, '<'));
+        assert_eq!(PreEscaped::new("\"").check_attribute(), err(0, '"'));
+        for bad in ["&", "&amp", "&;", "&#;", "&#x;", "&#12a;", "&a b;", "& a;"] {
+            assert_eq!(PreEscaped::new(bad).check_attribute(), err(0, '&'), "{bad}");
+        }
+        assert_eq!(PreEscaped::new("\"]]&gt; >").check_pcdata(), Ok(()));
+        assert_eq!(PreEscaped::new("a]]>").check_pcdata(), err(3, '>'));
+        assert_eq!(PreEscaped::new("&lt;<").check_pcdata(), err(4, '<'));
+    }
+
+    #[test]
+    fn streaming_unescape_at_every_boundary() {
+        use super::{unescape, unescape_with, StreamingUnescaper, UnescapeError};
+        use alloc::string::String;
+
+        let inputs = [
+            "plain", "&amp;", "a&lt;b&gt;c&#65;&#x42;\u{e9}&quot;", "&&", "x&amp", "&am p;", "&#xD800;", "&nope;",
+            "\u{e9}&apos;\u{1F600}&", "&#x10FFFF;&#x110000;", "&amp;&amp;&amp;",
+        ];
+        for input in inputs {
+            let expected = unescape(input).map(alloc::borrow::Cow::into_owned);
+            let boundaries: alloc::vec::Vec<_> = (0..=input.len()).filter(|&i| input.is_char_boundary(i)).collect();
+            for &i in &boundaries {
+                for &j in boundaries.iter().filter(|&&j| j >= i) {
+                    let mut unescaper = StreamingUnescaper::new();
+                    let mut out = String::new();
+                    let result = [&input[..i], &input[i..j], &input[j..]].into_iter()
+                        .try_for_each(|chunk| unescaper.push(chunk, &mut out))
+                        .and_then(|()| unescaper.finish(&mut out));
+                    assert_eq!(result.map(|()| out), expected, "{input:?} split at {i} and {j}");
+                }
+            }
+        }
+
+        let entities = |name: &str| (name == "long-name").then(|| "!");
+        let mut unescaper = StreamingUnescaper::with_entities(entities);
+        let mut out = String::new();
+        for c in "a&long-name;b".chars() {
+            unescaper.push(c.encode_utf8(&mut [0; 4]), &mut out).unwrap();
+        }
+        unescaper.finish(&mut out).unwrap();
+        assert_eq!(out, unescape_with("a&long-name;b", entities).unwrap());
+
+        out.clear();
+        unescaper.push("x&lo", &mut out).unwrap();
+        assert!(unescaper.finish(&mut out).is_err());
+        assert_eq!(out, "x&lo");
+        out.clear();
+        unescaper.push("&lt;", &mut out).unwrap();
+        assert_eq!((unescaper.finish(&mut out), out.as_str()), (Ok(()), "<"));
+
+        // a split reference can't grow without bounds
+        let mut unescaper = StreamingUnescaper::new().max_reference_length(8);
+        unescaper.push("ab&long", &mut out).unwrap();
+        assert_eq!(unescaper.push("est", &mut out), Err(UnescapeError::Unterminated { index: 2 }));
+        let mut unescaper = StreamingUnescaper::new();
+        unescaper.push("&#x", &mut out).unwrap();
+        assert!(unescaper.push(&"0".repeat(32), &mut out).is_ok());
+        assert_eq!(unescaper.push("0", &mut out), Err(UnescapeError::Unterminated { index: 0 }));
+        let mut unescaper = StreamingUnescaper::new();
+        let long = "a".repeat(300);
+        assert_eq!(unescaper.push("&", &mut out).and_then(|()| unescaper.push(&long, &mut out)), Err(UnescapeError::Unterminated { index: 0 }));
+    }
+
+    #[test]
+    fn escaping_writer() {
+        use super::{escape_to, AposAttributeEscapes, EscapingWriter, PcDataEscapes};
+        use alloc::string::String;
+        use core::fmt::Write;
+
+        let s = "a<b>c&d\"e'f\r\ng\u{e9}";
+        let mut out = String::new();
+        escape_to(PcDataEscapes, s, &mut out).unwrap();
+        assert_eq!(out, escape_str_pcdata(s));
+
+        let mut writer = EscapingWriter::attribute(String::new());
+        write!(writer, "{s}{}", 1).unwrap();
+        writer.write_char('<').unwrap();
+        writer.get_mut().push('<');
+        assert_eq!(writer.into_inner(), alloc::format!("{}1&lt;<", escape_str_attribute(s)));
+
+        let mut out = String::new();
+        write!(EscapingWriter::new(AposAttributeEscapes, &mut out), "{s}").unwrap();
+        assert_eq!(out, escape_str_attribute_apos(s));
+
+        struct Full(usize);
+        impl Write for Full {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.0 = self.0.checked_sub(s.len()).ok_or(core::fmt::Error)?;
+                Ok(())
+            }
+        }
+        assert!(escape_to(PcDataEscapes, "aaaa&", &mut Full(6)).is_err());
+        assert!(escape_to(PcDataEscapes, "aaaa&", &mut Full(9)).is_ok());
+        let lt = '<';
+        assert!(write!(EscapingWriter::pcdata(Full(4)), "{lt}").is_ok());
+        assert!(write!(EscapingWriter::pcdata(Full(3)), "{lt}").is_err());
+    }
+
+    #[test]
+    fn unescaping() {
+        use super::{unescape, unescape_with, UnescapeError};
+        use alloc::borrow::Cow;
+
+        assert!(matches!(unescape("no references"), Ok(Cow::Borrowed("no references"))));
+        assert_eq!(unescape("&lt;&gt;&amp;&apos;&quot;").unwrap(), "<>&'\"");
+        assert_eq!(unescape("a&#65;&#x42;&#x10FFFF;z").unwrap(), "aAB\u{10FFFF}z");
+        assert_eq!(unescape("&amp;lt;").unwrap(), "&lt;");
+
+        for (input, index) in [("&", 0), ("a&amp", 1), ("& b;", 0), ("&a b;", 0), ("&lt;&", 4)] {
+            assert_eq!(unescape(input), Err(UnescapeError::Unterminated { index }), "{input}");
+        }
+        for (input, reference) in [("&#;", ""), ("&#x;", "x"), ("&#0;", "0"), ("&#xD800;", "xD800"), ("&#12a;", "12a"), ("&#x110000;", "x110000")] {
+            assert_eq!(unescape(input), Err(UnescapeError::InvalidCharacterReference { index: 0, reference: reference.into() }), "{input}");
+        }
+        assert_eq!(unescape("x&;"), Err(UnescapeError::UnknownEntity { index: 1, name: "".into() }));
+        let err = unescape("&foo;").unwrap_err();
+        assert_eq!(alloc::string::ToString::to_string(&err), "unknown entity 'foo' at index 0");
+
+        let entities = |name: &str| (name == "me").then(|| "&me;");
+        assert_eq!(unescape_with("&me;&amp;", entities).unwrap(), "&me;&");
+        assert_eq!(unescape_with("&you;", entities), Err(UnescapeError::UnknownEntity { index: 0, name: "you".into() }));
+
+        for input in ["", "plain", "<>&\"'", "a\r\nb\tc", "☃ ]]> &amp;"] {
+            assert_eq!(unescape(&escape_str_attribute(input)).unwrap(), input);
+            assert_eq!(unescape(&escape_str_pcdata(input)).unwrap(), input);
+        }
+    }
+
+    #[cfg(feature = "html-entities")]
+    #[test]
+    fn html_entities() {
+        use super::{html_entity, html_entities::HTML_ENTITIES};
+
+        assert!(HTML_ENTITIES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(html_entity("nbsp"), Some("\u{a0}"));
+        assert_eq!(html_entity("AElig"), Some("\u{c6}"));
+        assert_eq!(html_entity("zwnj"), Some("\u{200c}"));
+        assert_eq!(html_entity("NotEqualTilde"), Some("\u{2242}\u{338}"));
+        for name in ["", "amp", "lt", "Nbsp", "nbsp;", "nope"] {
+            assert_eq!(html_entity(name), None, "{name}");
+        }
+    }
 }
@@ -0,0 +1,219 @@
+//! `Arbitrary` implementations for structured fuzzing, with the `arbitrary` feature.
+//!
+//! Generated values lean towards the edge cases of XML: empty and reserved names, colons,
+//! `]]>` and `--` in text, references, quotes, carriage returns and astral-plane characters.
+//! Most names are valid, so that documents get past the first element often enough.
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::attribute::OwnedAttribute;
+use crate::common::XmlVersion;
+use crate::name::OwnedName;
+use crate::namespace::{Namespace, NS_XMLNS_URI, NS_XML_URI};
+use crate::reader::ParserConfig;
+use crate::writer::{EmitterConfig, OwnedXmlEvent, SequentialPrefixes, UriTailPrefixes};
+
+/// Pieces of text which are special somewhere in a document
+const TEXT_FRAGMENTS: &[&str] = &[
+    "", " ", "a", "]]>", "]]", "--", "-", "?>", "<![CDATA[", "<!--", "<", ">", "&", "&amp;", "&lt;",
+    "&#x1F600;", "&#0;", "&unknown;", "\"", "'", "\r\n", "\r", "\n", "\t", "\u{a0}", "\u{1F600}",
+    "\u{10FFFF}", "\u{FFFE}", "\u{0}", "\u{1}", "\u{85}",
+];
+
+const NAME_STARTS: &[&str] = &["a", "x", "_", "Z", "\u{e9}", "\u{10000}", "\u{3042}"];
+
+const NAME_CHARS: &[&str] = &["a", "1", "-", ".", "_", "\u{b7}", "\u{300}", "\u{10000}"];
+
+/// Names which break a rule, or have a meaning of their own
+const ODD_NAMES: &[&str] = &["", "xml", "xmlns", "XML", "1a", "-a", ".", ":", "a:", ":a", "a:b", "a b", "a\u{0}", "\u{1F600}"];
+
+const URIS: &[&str] = &["", "urn:a", "urn:b", "http://example.com/ns", NS_XML_URI, NS_XMLNS_URI, "urn:\"'<&]]>"];
+
+fn text(u: &mut Unstructured<'_>) -> Result<String> {
+    let mut text = String::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+        if u.ratio(1, 4)? {
+            text.push_str(u.arbitrary()?);
+        } else {
+            text.push_str(u.choose(TEXT_FRAGMENTS)?);
+        }
+    }
+    Ok(text)
+}
+
+/// Usually a valid `NCName`
+fn name_part(u: &mut Unstructured<'_>) -> Result<String> {
+    if u.ratio(1, 5)? {
+        return Ok(if u.ratio(1, 3)? { u.arbitrary::<&str>()?.into() } else { (*u.choose(ODD_NAMES)?).into() });
+    }
+    let mut name = String::from(*u.choose(NAME_STARTS)?);
+    for _ in 0..u.int_in_range(0..=3)? {
+        name.push_str(u.choose(NAME_CHARS)?);
+    }
+    Ok(name)
+}
+
+fn uri(u: &mut Unstructured<'_>) -> Result<String> {
+    Ok(if u.ratio(1, 4)? { text(u)? } else { (*u.choose(URIS)?).into() })
+}
+
+fn namespace(u: &mut Unstructured<'_>) -> Result<Namespace> {
+    let mut bindings = BTreeMap::new();
+    for _ in 0..u.int_in_range(0..=2)? {
+        let prefix = if u.ratio(1, 3)? { String::new() } else { name_part(u)? };
+        bindings.insert(prefix, uri(u)?);
+    }
+    Ok(bindings.into())
+}
+
+impl<'a> Arbitrary<'a> for OwnedName {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(OwnedName {
+            local_name: name_part(u)?,
+            namespace: if u.ratio(1, 3)? { Some(uri(u)?) } else { None },
+            prefix: if u.ratio(1, 3)? { Some(name_part(u)?) } else { None },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for OwnedAttribute {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(OwnedAttribute { name: u.arbitrary()?, value: text(u)? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for OwnedXmlEvent {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=6)? {
+            0 => OwnedXmlEvent::StartDocument {
+                version: if u.arbitrary()? { XmlVersion::Version11 } else { XmlVersion::Version10 },
+                encoding: if u.arbitrary()? { Some((*u.choose(&["utf-8", "UTF-16", "latin1", "ascii", ""])?).into()) } else { None },
+                standalone: u.arbitrary()?,
+            },
+            1 => OwnedXmlEvent::ProcessingInstruction {
+                name: name_part(u)?,
+                data: if u.arbitrary()? { Some(text(u)?) } else { None },
+            },
+            2 | 3 => OwnedXmlEvent::StartElement {
+                name: u.arbitrary()?,
+                attributes: (0..u.int_in_range(0..=3)?).map(|_| u.arbitrary()).collect::<Result<Vec<_>>>()?,
+                namespace: namespace(u)?,
+            },
+            4 => OwnedXmlEvent::EndElement { name: if u.ratio(1, 2)? { None } else { Some(u.arbitrary()?) } },
+            5 => match u.int_in_range(0..=2)? {
+                0 => OwnedXmlEvent::CData(text(u)?),
+                1 => OwnedXmlEvent::Comment(text(u)?),
+                _ => OwnedXmlEvent::Characters(text(u)?),
+            },
+            _ => OwnedXmlEvent::Characters(text(u)?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for EmitterConfig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut config = EmitterConfig::new();
+        config.line_separator = Cow::Borrowed(*u.choose(&["\n", "\r\n", "", "\r"])?);
+        config.indent_string = if u.ratio(1, 4)? { Cow::Owned(text(u)?) } else { Cow::Borrowed(*u.choose(&["  ", "\t", ""])?) };
+        config.perform_indent = u.arbitrary()?;
+        // without escaping, documents are malformed most of the time
+        config.perform_escaping = !u.ratio(1, 8)?;
+        config.write_document_declaration = u.arbitrary()?;
+        config.normalize_empty_elements = u.arbitrary()?;
+        config.cdata_to_characters = u.arbitrary()?;
+        config.keep_element_names_stack = !u.ratio(1, 8)?;
+        config.autopad_comments = u.arbitrary()?;
+        config.pad_self_closing = u.arbitrary()?;
+        config.validate_names = u.arbitrary()?;
+        config.single_quote_attributes = u.arbitrary()?;
+        for _ in 0..u.int_in_range(0..=2)? {
+            config.entity_substitutions.insert(u.arbitrary()?, name_part(u)?);
+        }
+        config.prefix_generator = match u.int_in_range(0..=2)? {
+            0 => None,
+            1 => Some(Arc::new(SequentialPrefixes)),
+            _ => Some(Arc::new(UriTailPrefixes)),
+        };
+        Ok(config)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ParserConfig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut config = ParserConfig::new();
+        config.trim_whitespace = u.arbitrary()?;
+        config.whitespace_to_characters = u.arbitrary()?;
+        config.cdata_to_characters = u.arbitrary()?;
+        config.ignore_comments = u.arbitrary()?;
+        config.coalesce_characters = u.arbitrary()?;
+        for _ in 0..u.int_in_range(0..=2)? {
+            config.extra_entities.insert(name_part(u)?, text(u)?);
+        }
+        config.ignore_end_of_stream = u.ratio(1, 8)?;
+        config.replace_unknown_entity_references = u.arbitrary()?;
+        config.ignore_root_level_whitespace = u.arbitrary()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+    use ::arbitrary::{Arbitrary, Unstructured};
+
+    use crate::name::OwnedName;
+    use crate::reader::ParserConfig;
+    use crate::writer::{EmitterConfig, OwnedXmlEvent};
+
+    /// Writes the events, then reads back whatever was written, like `fuzz/fuzz_targets/round_trip.rs`
+    fn write_and_reparse(emitter: EmitterConfig, parser: ParserConfig, events: &[OwnedXmlEvent]) {
+        let mut writer = emitter.create_writer();
+        for event in events {
+            if writer.write(event.borrow()).is_err() {
+                break;
+            }
+        }
+        let document = writer.into_inner();
+        let mut reader = crate::EventReader::new_with_config(document.as_bytes().iter(), parser);
+        // `ignore_end_of_stream` can make the reader wait forever
+        for _ in 0..=document.len() + 2 {
+            match reader.next() {
+                Ok(crate::reader::XmlEvent::EndDocument) | Err(_) => break,
+                Ok(_) => {},
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_do_not_panic() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut valid_names = 0;
+        for _ in 0..300 {
+            let data: Vec<u8> = (0..2048).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }).collect();
+            let mut u = Unstructured::new(&data);
+            let emitter = Arbitrary::arbitrary(&mut u).unwrap();
+            let parser = Arbitrary::arbitrary(&mut u).unwrap();
+            let events: Vec<OwnedXmlEvent> = (0..16).map_while(|_| OwnedXmlEvent::arbitrary(&mut u).ok()).collect();
+            write_and_reparse(emitter, parser, &events);
+
+            let name = OwnedName::arbitrary(&mut u).unwrap();
+            valid_names += usize::from(crate::name::is_ncname(&name.local_name));
+        }
+        // most names are valid, but not all
+        assert!((150..300).contains(&valid_names), "{valid_names}");
+    }
+}
@@ -1,6 +1,9 @@
 //#![warn(missing_doc)]
 #![cfg_attr(not(test), no_std)]
-#![forbid(non_camel_case_types)]
+#![cfg_attr(not(feature = "serde"), forbid(non_camel_case_types))]
+// `derive(Deserialize)` puts `allow(non_camel_case_types)` on the field enums it generates,
+// which `forbid` would reject, so with the feature it is denied instead
+#![cfg_attr(feature = "serde", deny(non_camel_case_types))]
 #![forbid(unsafe_code)]
 #![allow(clippy::redundant_closure_for_method_calls)]
 #![allow(clippy::module_name_repetitions)]
@@ -10,22 +13,88 @@
 //! Please note that functions of this parser may panic.
 //! If a panic could cause a Denial Of Service in your codebase, *you're* responsible for wrapping access to this library in `catch_unwind`.
 //!
+//! The `serde` feature, off by default, implements `Serialize` and `Deserialize` for `OwnedName`,
+//! `OwnedAttribute`, `Namespace`, `XmlVersion` and the reader's `XmlEvent`.
+//!
+//! The `html-entities` feature, off by default, adds the table of HTML named character
+//! references, e.g. `&nbsp;`, for `escape::html_entity` and `reader::EntityAction::html`.
+//! It's only used when asked for, and adds about 90 KB to binaries which use it.
+//!
+//! The `defmt` feature, off by default, implements `defmt::Format` for the error types,
+//! `TextPosition`, `Encoding`, `XmlVersion`, names and events, for logging on embedded targets
+//! without `core::fmt`.
+//!
+//! The `log` feature, off by default, logs the parser's internals with the `log` crate, for
+//! debugging: the encoding it settles on, the lexer's state transitions and entity expansions
+//! at trace level, and limits being hit and errors at debug level. Without it, none of this
+//! is compiled in, and with it nothing is formatted unless the level is enabled.
+//!
+//! The `arbitrary` feature, off by default, implements `arbitrary::Arbitrary` for `OwnedName`,
+//! `OwnedAttribute`, `writer::OwnedXmlEvent`, `EmitterConfig` and `ParserConfig`, for
+//! structured fuzzing. See `fuzz/` for a target which writes and re-reads generated documents.
+//!
+//! The `std` feature, off by default, adds `EventReader::from_reader` and
+//! `EventWriter::from_writer`, to read from a `std::io::Read` and write to a `std::io::Write`
+//! like xml-rs does. The rest of the API is the same with or without it.
+//!
+//! The `xml-rs-compat` feature, off by default, converts names, attributes, namespaces,
+//! reader events and configurations to and from the types of the `xml` crate (xml-rs),
+//! for code which is moving over from it. See `compat` for what doesn't carry over.
+//!
+//! The `tree` feature, off by default, adds the `tree` module, which builds an `Element`
+//! with its attributes and content from a reader's events, for small documents which are
+//! easier to walk than to handle event by event.
+//!
+//! The `core-error` feature, off by default, implements `core::error::Error` for the error
+//! types, e.g. `reader::Error` and `writer::Error`. It needs Rust 1.81 or newer.
+//!
+//! The `fallible-alloc` feature, off by default, is for targets where running out of memory
+//! mustn't abort. The buffers which grow with the document, i.e. the reader's text, name and
+//! attribute buffers, its element and namespace stacks, entity expansions and the writer's
+//! output, are grown with `try_reserve`, and failing to grow them is an error:
+//! `reader::ErrorKind::OutOfMemory` or `writer::Error::OutOfMemory`. Other allocations, like
+//! the names and namespace maps of events, are small and bounded by the parser's limits, but
+//! still abort. Without the feature, buffers grow as usual.
+//!
+//! The `fixed-capacity` feature, off by default, adds `capacity::Fixed` and
+//! `reader::FixedEventReader`, a reader whose attribute list, element and namespace stacks
+//! and text buffer are arrays of sizes given as const parameters. A document which needs more
+//! is an error, `reader::ErrorKind::LimitExceeded`, instead of an allocation. The events it
+//! returns still own their names and text.
+//!
+//! The `test-util` feature, off by default, adds the `test_util` module, which compares two
+//! documents by their events, for tests of code which writes or transforms XML.
+//!
 
 #![cfg_attr(doctest, doc = include_str!("../README.md"))]
 
 pub use crate::reader::EventReader;
 pub use crate::reader::ParserConfig;
-pub use crate::util::Encoding;
+pub use crate::reformat::{reformat, ReformatError};
+pub use crate::decode::Encoding;
 pub use crate::writer::EmitterConfig;
 pub use crate::writer::EventWriter;
 
+// first, so its logging macros are in scope in the modules below
+#[doc(hidden)] // FIXME: not supposed to be public
+pub mod macros;
 pub mod attribute;
+pub mod canonical;
+pub mod capacity;
+pub mod chars;
 pub mod common;
+#[cfg(feature = "xml-rs-compat")]
+pub mod compat;
+pub mod decode;
 pub mod escape;
-#[doc(hidden)] // FIXME: not supposed to be public
-pub mod macros;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
 pub mod name;
 pub mod namespace;
 pub mod reader;
-mod util;
+mod reformat;
+#[cfg(feature = "tree")]
+pub mod tree;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod writer;
@@ -61,3 +61,26 @@ macro_rules! gen_setters {
         )+
     })
 }
+
+/// Logs a trace-level message with the `log` feature. Without it, the arguments are only
+/// type-checked and nothing is compiled in.
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)+) => (log::trace!($($arg)+))
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)+) => (if false { let _ = format_args!($($arg)+); })
+}
+
+/// Logs a debug-level message with the `log` feature, like `trace!`.
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)+) => (log::debug!($($arg)+))
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)+) => (if false { let _ = format_args!($($arg)+); })
+}
@@ -3,11 +3,17 @@
 extern crate alloc;
 
 use core::fmt;
+use core::marker::PhantomData;
 use core::str::FromStr;
 
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
-use crate::namespace::NS_NO_PREFIX;
+use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char};
+use crate::namespace::{NS_NO_PREFIX, NS_XML_PREFIX, NS_XML_URI};
 
 /// Represents a qualified XML name.
 ///
@@ -47,6 +53,28 @@ use crate::namespace::NS_NO_PREFIX;
 /// ```
 ///
 /// This is added to support easy specification of XML elements when writing XML documents.
+///
+/// # Comparisons
+///
+/// A `Name` and an `OwnedName` are equal if all three parts are, the same as for two names
+/// of the same type. They also hash the same, so a borrowed name can be hashed to find an
+/// `OwnedName` key without allocating.
+///
+/// A name is equal to a string if it has no namespace and the string is the name's
+/// `prefix:local` form, i.e. just the local name if there's no prefix. An empty namespace
+/// counts as none, like in `matches()`. A name with a namespace is never equal to a string,
+/// so `name == "item"` is a quick check for an un-namespaced `item`. String comparisons
+/// don't agree with hashing: don't use them to look up names in a map with string keys.
+///
+/// ```rust
+/// # use xml_no_std::name::{Name, OwnedName};
+/// let name = Name::qualified("item", "urn:x", Some("x"));
+/// assert_eq!(name, OwnedName::qualified("item", "urn:x", Some("x")));
+/// assert_ne!(name, "item");
+/// assert_ne!(name, "x:item");
+/// assert_eq!(Name::local("item"), "item");
+/// assert_eq!(Name::prefixed("item", "x"), "x:item");
+/// ```
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Name<'a> {
     /// A local name, e.g. `string` in `xsi:string`.
@@ -104,7 +132,7 @@ impl<'a> Name<'a> {
     /// Returns a new `Name` instance representing plain local name.
     #[inline]
     #[must_use]
-    pub fn local(local_name: &str) -> Name<'_> {
+    pub const fn local(local_name: &str) -> Name<'_> {
         Name {
             local_name,
             prefix: None,
@@ -115,7 +143,7 @@ impl<'a> Name<'a> {
     /// Returns a new `Name` instance with the given local name and prefix.
     #[inline]
     #[must_use]
-    pub fn prefixed(local_name: &'a str, prefix: &'a str) -> Name<'a> {
+    pub const fn prefixed(local_name: &'a str, prefix: &'a str) -> Name<'a> {
         Name {
             local_name,
             namespace: None,
@@ -127,7 +155,7 @@ impl<'a> Name<'a> {
     /// with a namespace URI.
     #[inline]
     #[must_use]
-    pub fn qualified(local_name: &'a str, namespace: &'a str, prefix: Option<&'a str>) -> Name<'a> {
+    pub const fn qualified(local_name: &'a str, namespace: &'a str, prefix: Option<&'a str>) -> Name<'a> {
         Name {
             local_name,
             namespace: Some(namespace),
@@ -135,6 +163,41 @@ impl<'a> Name<'a> {
         }
     }
 
+    /// Like `Name::local`, but fails if `local_name` is not an `NCName`.
+    ///
+    /// ```rust
+    /// # use xml_no_std::name::Name;
+    /// assert!(Name::try_local("item").is_ok());
+    /// assert_eq!(Name::try_local("my item").unwrap_err().index(), 2);
+    /// ```
+    #[inline]
+    pub fn try_local(local_name: &str) -> Result<Name<'_>, NameError> {
+        check_ncname(local_name, 0)?;
+        Ok(Name::local(local_name))
+    }
+
+    /// Like `Name::qualified`, but fails if the prefix or `local_name` is not an `NCName`.
+    ///
+    /// Error indices count from the start of `prefix:local_name`.
+    #[inline]
+    pub fn try_qualified(local_name: &'a str, namespace: &'a str, prefix: Option<&'a str>) -> Result<Name<'a>, NameError> {
+        let name = Name::qualified(local_name, namespace, prefix);
+        name.validate()?;
+        Ok(name)
+    }
+
+    /// Checks that the prefix and the local name are `NCName`s
+    pub(crate) fn validate(&self) -> Result<(), NameError> {
+        let offset = match self.prefix {
+            Some(prefix) => {
+                check_ncname(prefix, 0)?;
+                prefix.len() + 1
+            },
+            None => 0,
+        };
+        check_ncname(self.local_name, offset)
+    }
+
     /// Returns a correct XML representation of this local name and prefix.
     ///
     /// This method is different from the autoimplemented `to_string()` because it does not
@@ -151,8 +214,51 @@ impl<'a> Name<'a> {
     /// allocations.
     #[inline]
     #[must_use]
-    pub fn repr_display(&self) -> ReprDisplay<'_, '_> {
-        ReprDisplay(self)
+    pub fn repr_display(&self) -> ReprDisplay<'_, 'a> {
+        ReprDisplay(*self, PhantomData)
+    }
+
+    /// Returns true if this name is `local` in the `namespace`, whatever the prefix.
+    ///
+    /// `None` matches names without a namespace. An empty namespace URI counts as no
+    /// namespace, so `Some("")` is the same as `None`.
+    #[inline]
+    #[must_use]
+    pub fn matches(&self, namespace: Option<&str>, local: &str) -> bool {
+        self.local_is(local) && self.namespace.filter(|ns| !ns.is_empty()) == namespace.filter(|ns| !ns.is_empty())
+    }
+
+    /// Returns true if this name is in the `namespace`, whatever the prefix.
+    ///
+    /// An empty `namespace` matches names without a namespace, see `matches()`.
+    #[inline]
+    #[must_use]
+    pub fn in_namespace(&self, namespace: &str) -> bool {
+        self.namespace.unwrap_or("") == namespace
+    }
+
+    /// Returns true if the local name is `local`, whatever the namespace and prefix.
+    #[inline]
+    #[must_use]
+    pub fn local_is(&self, local: &str) -> bool {
+        self.local_name == local
+    }
+
+    /// Returns a structure which displays this name in Clark notation, `{uri}local`, without allocating.
+    ///
+    /// The prefix is left out. Names without a namespace, or with an empty one, are just `local`.
+    #[inline]
+    #[must_use]
+    pub fn to_clark(&self) -> ClarkDisplay<'a> {
+        ClarkDisplay { namespace: self.namespace, local_name: self.local_name }
+    }
+
+    /// Returns the namespace URI and local name of this name, without the prefix, to compare
+    /// or look up names the way namespaces in XML do. Doesn't allocate.
+    #[inline]
+    #[must_use]
+    pub fn expanded(&self) -> ExpandedName<'a> {
+        ExpandedName::new(self.namespace, self.local_name)
     }
 
     /// Returns either a prefix of this name or `namespace::NS_NO_PREFIX` constant.
@@ -163,9 +269,18 @@ impl<'a> Name<'a> {
     }
 }
 
+/// `xml:space`, which tells whether whitespace in an element is significant.
+pub const XML_SPACE: Name<'static> = Name::qualified("space", NS_XML_URI, Some(NS_XML_PREFIX));
+
+/// `xml:lang`, the language of an element's content.
+pub const XML_LANG: Name<'static> = Name::qualified("lang", NS_XML_URI, Some(NS_XML_PREFIX));
+
+/// `xml:base`, the base URI for relative URIs in an element.
+pub const XML_BASE: Name<'static> = Name::qualified("base", NS_XML_URI, Some(NS_XML_PREFIX));
+
 /// A wrapper around `Name` whose `Display` implementation prints the wrapped name as it is
-/// displayed in an XML document.
-pub struct ReprDisplay<'a, 'b>(&'a Name<'b>);
+/// displayed in an XML document, i.e. `prefix:local` or `local`, without the namespace URI.
+pub struct ReprDisplay<'a, 'b>(Name<'b>, PhantomData<&'a Name<'b>>);
 
 impl<'a, 'b: 'a> fmt::Display for ReprDisplay<'a, 'b> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -176,10 +291,29 @@ impl<'a, 'b: 'a> fmt::Display for ReprDisplay<'a, 'b> {
     }
 }
 
+/// Displays a name in Clark notation, see `Name::to_clark`.
+pub struct ClarkDisplay<'a> {
+    namespace: Option<&'a str>,
+    local_name: &'a str,
+}
+
+impl fmt::Display for ClarkDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.namespace {
+            Some(namespace) if !namespace.is_empty() => write!(f, "{{{namespace}}}{}", self.local_name),
+            _ => f.write_str(self.local_name),
+        }
+    }
+}
+
 /// An owned variant of `Name`.
 ///
 /// Everything about `Name` applies to this structure as well.
-#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+///
+/// With the `serde` feature, it's serialized as a struct of its three fields, so that the
+/// prefix is kept. Use `to_clark()` and `from_clark()` for a string form without the prefix.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OwnedName {
     /// A local name, e.g. `string` in `xsi:string`.
     pub local_name: String,
@@ -198,6 +332,25 @@ impl fmt::Display for OwnedName {
     }
 }
 
+/// Formatted as the qualified name, e.g. `p:a`.
+#[cfg(feature = "defmt")]
+impl defmt::Format for Name<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self.prefix {
+            Some(prefix) => defmt::write!(f, "{=str}:{=str}", prefix, self.local_name),
+            None => defmt::write!(f, "{=str}", self.local_name),
+        }
+    }
+}
+
+/// Formatted as the qualified name, e.g. `p:a`.
+#[cfg(feature = "defmt")]
+impl defmt::Format for OwnedName {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&self.borrow(), f);
+    }
+}
+
 impl OwnedName {
     /// Constructs a borrowed `Name` based on this owned name.
     #[must_use]
@@ -233,6 +386,101 @@ impl OwnedName {
         }
     }
 
+    /// Parses a `QName`, telling where it's wrong if it isn't one. Same as `str::parse`.
+    ///
+    /// ```rust
+    /// # use xml_no_std::name::OwnedName;
+    /// let name = OwnedName::try_from_str("p:item").unwrap();
+    /// assert_eq!(name.prefix.as_deref(), Some("p"));
+    /// assert_eq!(OwnedName::try_from_str("p:1tem").unwrap_err().index(), 2);
+    /// ```
+    pub fn try_from_str(s: &str) -> Result<OwnedName, NameError> {
+        check_qname(s)?;
+        Ok(match s.split_once(':') {
+            Some((prefix, local_name)) => OwnedName { local_name: local_name.into(), namespace: None, prefix: Some(prefix.into()) },
+            None => OwnedName::local(s),
+        })
+    }
+
+    /// Parses a name in Clark notation, `{uri}local` or just `local`, as used by ElementTree and lxml.
+    ///
+    /// The name has no prefix. `{}local` is a name without a namespace, like `local`.
+    /// The local name must be an `NCName`; a `{` which isn't closed is reported as the
+    /// offending character.
+    ///
+    /// ```rust
+    /// # use xml_no_std::name::OwnedName;
+    /// let name = OwnedName::from_clark("{http://example.com/ns}item").unwrap();
+    /// assert_eq!(name.namespace.as_deref(), Some("http://example.com/ns"));
+    /// assert_eq!(name.local_name, "item");
+    /// assert_eq!(name.to_clark().to_string(), "{http://example.com/ns}item");
+    /// ```
+    pub fn from_clark(s: &str) -> Result<OwnedName, NameError> {
+        let (namespace, local_name, offset) = match s.strip_prefix('{') {
+            Some(rest) => {
+                let end = rest.find('}').ok_or(NameError { index: 0, found: Some('{'), kind: NameErrorKind::UnclosedBrace })?;
+                (Some(&rest[..end]).filter(|ns| !ns.is_empty()), &rest[end + 1..], end + 2)
+            },
+            None => (None, s, 0),
+        };
+        check_ncname(local_name, offset)?;
+        Ok(OwnedName {
+            local_name: local_name.into(),
+            namespace: namespace.map(From::from),
+            prefix: None,
+        })
+    }
+
+    /// Returns a structure which displays this name in Clark notation, see `Name::to_clark`.
+    #[inline]
+    #[must_use]
+    pub fn to_clark(&self) -> ClarkDisplay<'_> {
+        self.borrow().to_clark()
+    }
+
+    /// Returns the namespace URI and local name of this name, see `Name::expanded`.
+    #[inline]
+    #[must_use]
+    pub fn expanded(&self) -> ExpandedName<'_> {
+        self.borrow().expanded()
+    }
+
+    /// Returns a correct XML representation of this local name and prefix,
+    /// see `Name::to_repr`.
+    #[must_use]
+    pub fn to_repr(&self) -> String {
+        self.borrow().to_repr()
+    }
+
+    /// Returns a structure which displays this local name and prefix without allocating,
+    /// see `Name::repr_display`.
+    #[inline]
+    #[must_use]
+    pub fn repr_display(&self) -> ReprDisplay<'_, '_> {
+        ReprDisplay(self.borrow(), PhantomData)
+    }
+
+    /// Returns true if this name is `local` in the `namespace`, see `Name::matches`.
+    #[inline]
+    #[must_use]
+    pub fn matches(&self, namespace: Option<&str>, local: &str) -> bool {
+        self.borrow().matches(namespace, local)
+    }
+
+    /// Returns true if this name is in the `namespace`, see `Name::in_namespace`.
+    #[inline]
+    #[must_use]
+    pub fn in_namespace(&self, namespace: &str) -> bool {
+        self.borrow().in_namespace(namespace)
+    }
+
+    /// Returns true if the local name is `local`, see `Name::local_is`.
+    #[inline]
+    #[must_use]
+    pub fn local_is(&self, local: &str) -> bool {
+        self.local_name == local
+    }
+
     /// Returns an optional prefix by reference, equivalent to `self.borrow().prefix`
     /// but avoids extra work.
     #[inline]
@@ -250,6 +498,152 @@ impl OwnedName {
     }
 }
 
+impl<'a> PartialEq<OwnedName> for Name<'a> {
+    #[inline]
+    fn eq(&self, other: &OwnedName) -> bool {
+        *self == other.borrow()
+    }
+}
+
+impl<'b> PartialEq<Name<'b>> for OwnedName {
+    #[inline]
+    fn eq(&self, other: &Name<'b>) -> bool {
+        self.borrow() == *other
+    }
+}
+
+impl<'a> PartialEq<str> for Name<'a> {
+    /// See the comparisons section of `Name` docs
+    fn eq(&self, other: &str) -> bool {
+        if self.namespace.map_or(false, |ns| !ns.is_empty()) {
+            return false;
+        }
+        match (self.prefix, other.split_once(':')) {
+            (Some(prefix), Some((other_prefix, other_local))) => prefix == other_prefix && self.local_name == other_local,
+            (None, None) => self.local_name == other,
+            _ => false,
+        }
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for Name<'a> {
+    #[inline]
+    fn eq(&self, other: &&'b str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<str> for OwnedName {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.borrow() == *other
+    }
+}
+
+impl<'b> PartialEq<&'b str> for OwnedName {
+    #[inline]
+    fn eq(&self, other: &&'b str) -> bool {
+        self.borrow() == **other
+    }
+}
+
+/// A name which borrows or owns each of its parts, so it can be made from string literals,
+/// borrowed input or an `OwnedName` without copying anything.
+///
+/// Comparisons and hashing are by value and agree with `OwnedName`, so both can be used
+/// to look up the same map entries, e.g. through `into_owned()` or `borrow()`.
+///
+/// ```rust
+/// # use xml_no_std::name::{CowName, Name, OwnedName};
+/// const ITEM: Name<'static> = Name::qualified("item", "urn:x", Some("x"));
+/// let name = CowName::from(ITEM); // no allocation
+/// assert_eq!(name.borrow(), ITEM);
+/// assert_eq!(name.into_owned(), ITEM.to_owned());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub struct CowName<'a> {
+    /// A local name, e.g. `string` in `xsi:string`.
+    pub local_name: Cow<'a, str>,
+
+    /// A namespace URI, e.g. `http://www.w3.org/2000/xmlns/`.
+    pub namespace: Option<Cow<'a, str>>,
+
+    /// A name prefix, e.g. `xsi` in `xsi:string`.
+    pub prefix: Option<Cow<'a, str>>,
+}
+
+impl<'a> CowName<'a> {
+    /// Constructs a borrowed `Name` based on this name.
+    #[inline]
+    #[must_use]
+    pub fn borrow(&self) -> Name<'_> {
+        Name {
+            local_name: &self.local_name,
+            namespace: self.namespace.as_deref(),
+            prefix: self.prefix.as_deref(),
+        }
+    }
+
+    /// Converts into an `OwnedName`, copying only the borrowed parts.
+    #[must_use]
+    pub fn into_owned(self) -> OwnedName {
+        OwnedName {
+            local_name: self.local_name.into_owned(),
+            namespace: self.namespace.map(Cow::into_owned),
+            prefix: self.prefix.map(Cow::into_owned),
+        }
+    }
+
+    /// Converts into a name which doesn't borrow anything, copying only the borrowed parts.
+    #[must_use]
+    pub fn into_static(self) -> CowName<'static> {
+        self.into_owned().into()
+    }
+}
+
+impl<'a> fmt::Display for CowName<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.borrow(), f)
+    }
+}
+
+impl<'a> From<Name<'a>> for CowName<'a> {
+    #[inline]
+    fn from(n: Name<'a>) -> CowName<'a> {
+        CowName {
+            local_name: Cow::Borrowed(n.local_name),
+            namespace: n.namespace.map(Cow::Borrowed),
+            prefix: n.prefix.map(Cow::Borrowed),
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedName> for CowName<'a> {
+    #[inline]
+    fn from(n: &'a OwnedName) -> CowName<'a> {
+        n.borrow().into()
+    }
+}
+
+impl From<OwnedName> for CowName<'static> {
+    #[inline]
+    fn from(n: OwnedName) -> CowName<'static> {
+        CowName {
+            local_name: Cow::Owned(n.local_name),
+            namespace: n.namespace.map(Cow::Owned),
+            prefix: n.prefix.map(Cow::Owned),
+        }
+    }
+}
+
+impl<'a> From<CowName<'a>> for OwnedName {
+    #[inline]
+    fn from(n: CowName<'a>) -> OwnedName {
+        n.into_owned()
+    }
+}
+
 impl<'a> From<Name<'a>> for OwnedName {
     #[inline]
     fn from(n: Name<'a>) -> OwnedName {
@@ -258,7 +652,7 @@ impl<'a> From<Name<'a>> for OwnedName {
 }
 
 impl FromStr for OwnedName {
-    type Err = ();
+    type Err = NameError;
 
     /// Parses the given string slice into a qualified name.
     ///
@@ -266,31 +660,342 @@ impl FromStr for OwnedName {
     /// name without a namespace (`name.namespace == None`). It should be filled later
     /// using proper `NamespaceStack`.
     ///
-    /// It is supposed that all characters in the argument string are correct
-    /// as defined by the XML specification. No additional checks except a check
-    /// for emptiness are done.
-    fn from_str(s: &str) -> Result<OwnedName, ()> {
-        let mut it = s.split(':');
-
-        let r = match (it.next(), it.next(), it.next()) {
-            (Some(prefix), Some(local_name), None) if !prefix.is_empty() &&
-                                                      !local_name.is_empty() =>
-                Some((local_name.into(), Some(prefix.into()))),
-            (Some(local_name), None, None) if !local_name.is_empty() =>
-                Some((local_name.into(), None)),
-            (_, _, _) => None
+    /// The string must be a `QName`: at most one colon, with non-empty parts on both sides
+    /// of it, and only characters allowed in names. Same as `OwnedName::try_from_str`.
+    #[inline]
+    fn from_str(s: &str) -> Result<OwnedName, NameError> {
+        OwnedName::try_from_str(s)
+    }
+}
+
+/// A namespace URI and a local name: a name as namespaces in XML see it, without the prefix.
+///
+/// Unlike `Name` and `OwnedName`, names with different prefixes for the same namespace are
+/// equal, which makes it the right key for maps of names. An empty namespace URI is the same
+/// as none, like in `Name::matches`. It's displayed in Clark notation, `{uri}local`.
+///
+/// Each part can be borrowed or owned. `OwnedExpandedName` is the one which owns both, and
+/// you can look up keys of that type with a borrowed `ExpandedName`, which doesn't allocate:
+///
+/// ```rust
+/// # use std::collections::BTreeMap;
+/// # use xml_no_std::name::{ExpandedName, Name, OwnedExpandedName, OwnedName};
+/// let mut handlers: BTreeMap<OwnedExpandedName, u32> = BTreeMap::new();
+/// handlers.insert(OwnedName::qualified("item", "urn:x", Some("x")).into(), 1);
+///
+/// let name = Name::qualified("item", "urn:x", Some("y"));
+/// assert_eq!(handlers.get(&name.expanded()), Some(&1));
+/// assert_eq!(name.expanded().to_string(), "{urn:x}item");
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub struct ExpandedName<'a> {
+    namespace: Option<Cow<'a, str>>,
+    local_name: Cow<'a, str>,
+}
+
+/// An `ExpandedName` which owns its parts.
+pub type OwnedExpandedName = ExpandedName<'static>;
+
+impl<'a> ExpandedName<'a> {
+    /// Returns an expanded name with the given namespace URI and local name.
+    #[inline]
+    #[must_use]
+    pub fn new(namespace: Option<&'a str>, local_name: &'a str) -> ExpandedName<'a> {
+        ExpandedName {
+            namespace: namespace.filter(|ns| !ns.is_empty()).map(Cow::Borrowed),
+            local_name: Cow::Borrowed(local_name),
+        }
+    }
+
+    /// The namespace URI, `None` for names without a namespace or with an empty one.
+    #[inline]
+    #[must_use]
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// The local name.
+    #[inline]
+    #[must_use]
+    pub fn local_name(&self) -> &str {
+        &self.local_name
+    }
+
+    /// Borrows the parts of this name.
+    #[inline]
+    #[must_use]
+    pub fn borrow(&self) -> ExpandedName<'_> {
+        ExpandedName::new(self.namespace(), self.local_name())
+    }
+
+    /// Copies the parts which are borrowed, to keep the name beyond their lifetime.
+    #[must_use]
+    pub fn into_owned(self) -> OwnedExpandedName {
+        ExpandedName {
+            namespace: self.namespace.map(|ns| Cow::Owned(ns.into_owned())),
+            local_name: Cow::Owned(self.local_name.into_owned()),
+        }
+    }
+}
+
+impl fmt::Display for ExpandedName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        ClarkDisplay { namespace: self.namespace(), local_name: self.local_name() }.fmt(f)
+    }
+}
+
+impl<'a> From<Name<'a>> for ExpandedName<'a> {
+    #[inline]
+    fn from(n: Name<'a>) -> ExpandedName<'a> {
+        n.expanded()
+    }
+}
+
+impl<'a> From<&'a OwnedName> for ExpandedName<'a> {
+    #[inline]
+    fn from(n: &'a OwnedName) -> ExpandedName<'a> {
+        n.expanded()
+    }
+}
+
+impl From<OwnedName> for OwnedExpandedName {
+    #[inline]
+    fn from(n: OwnedName) -> OwnedExpandedName {
+        ExpandedName {
+            namespace: n.namespace.filter(|ns| !ns.is_empty()).map(Cow::Owned),
+            local_name: Cow::Owned(n.local_name),
+        }
+    }
+}
+
+/// A shared `OwnedName`, handed out by `NameInterner`.
+///
+/// Clones share one allocation. Comparisons and hashing are by value, as for `OwnedName`,
+/// so interned and non-interned names can be mixed in maps keyed by the name.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub struct InternedName(Arc<OwnedName>);
+
+impl InternedName {
+    /// Constructs a borrowed `Name` based on this name.
+    #[inline]
+    #[must_use]
+    pub fn borrow(&self) -> Name<'_> {
+        self.0.borrow()
+    }
+
+    /// Returns true if both names come from the same allocation.
+    #[inline]
+    #[must_use]
+    pub fn ptr_eq(&self, other: &InternedName) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::ops::Deref for InternedName {
+    type Target = OwnedName;
+
+    #[inline]
+    fn deref(&self) -> &OwnedName {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a InternedName> for Name<'a> {
+    #[inline]
+    fn from(n: &'a InternedName) -> Name<'a> {
+        n.borrow()
+    }
+}
+
+impl From<InternedName> for OwnedName {
+    /// Takes the name out without copying if it's not shared anymore.
+    #[inline]
+    fn from(n: InternedName) -> OwnedName {
+        Arc::try_unwrap(n.0).unwrap_or_else(|arc| (*arc).clone())
+    }
+}
+
+impl fmt::Display for InternedName {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+/// Deduplicates names, so that storing the same name many times costs one allocation.
+///
+/// Useful when keeping names of many events, like elements of a large repetitive document:
+///
+/// ```rust
+/// # use xml_no_std::name::{Name, NameInterner};
+/// let mut interner = NameInterner::new();
+/// let a = interner.intern(Name::qualified("row", "urn:x", Some("x")));
+/// let b = interner.intern(Name::prefixed("row", "x"));
+/// let c = interner.intern(Name::qualified("row", "urn:x", Some("x")));
+/// assert!(a.ptr_eq(&c));
+/// assert!(!a.ptr_eq(&b)); // no namespace, so it's a different name
+/// assert_eq!(interner.len(), 2);
+/// ```
+///
+/// Names are never removed, so the interner grows with the number of distinct names.
+/// `ParserConfig2::intern_names` makes the reader intern the names it parses in one.
+#[derive(Clone, Default, Debug)]
+pub struct NameInterner {
+    /// Names with the same local name are compared one by one, there are rarely many
+    by_local_name: BTreeMap<String, Vec<InternedName>>,
+    len: usize,
+}
+
+impl NameInterner {
+    /// Returns an empty interner.
+    #[inline]
+    #[must_use]
+    pub fn new() -> NameInterner {
+        NameInterner::default()
+    }
+
+    /// Returns the shared copy of `name`, allocating one only if the name wasn't seen before.
+    pub fn intern(&mut self, name: Name<'_>) -> InternedName {
+        if let Some(names) = self.by_local_name.get(name.local_name) {
+            if let Some(interned) = names.iter().find(|n| n.borrow() == name) {
+                return interned.clone();
+            }
+        }
+        let interned = InternedName(Arc::new(name.to_owned()));
+        self.by_local_name.entry(name.local_name.into()).or_default().push(interned.clone());
+        self.len += 1;
+        interned
+    }
+
+    /// Returns the number of distinct names interned.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no names have been interned.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Returns true if `s` matches the `NCName` production of Namespaces in XML,
+/// a name without colons, like a prefix or a local name.
+#[must_use]
+pub fn is_ncname(s: &str) -> bool {
+    check_ncname(s, 0).is_ok()
+}
+
+/// Returns true if `s` matches the `QName` production of Namespaces in XML,
+/// an `NCName` optionally preceded by an `NCName` prefix and a colon.
+#[must_use]
+pub fn is_qname(s: &str) -> bool {
+    check_qname(s).is_ok()
+}
+
+fn check_ncname(s: &str, offset: usize) -> Result<(), NameError> {
+    let error = |index, c: char, kind| {
+        let kind = if c == ':' {
+            NameErrorKind::Colon
+        } else if is_whitespace_char(c) {
+            NameErrorKind::Whitespace
+        } else {
+            kind
         };
-        r.map(|(local_name, prefix)| OwnedName {
-            local_name,
-            namespace: None,
-            prefix
-        }).ok_or(())
+        Err(NameError { index: offset + index, found: Some(c), kind })
+    };
+    let mut chars = s.char_indices();
+    match chars.next() {
+        Some((_, c)) if c != ':' && is_name_start_char(c) => {},
+        Some((i, c)) => return error(i, c, NameErrorKind::InvalidStart),
+        None => return Err(NameError { index: offset, found: None, kind: NameErrorKind::Empty }),
+    }
+    match chars.find(|&(_, c)| c == ':' || !is_name_char(c)) {
+        Some((i, c)) => error(i, c, NameErrorKind::InvalidChar),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn check_qname(s: &str) -> Result<(), NameError> {
+    match s.split_once(':') {
+        Some((prefix, local_name)) => {
+            check_ncname(prefix, 0)?;
+            check_ncname(local_name, prefix.len() + 1)
+        },
+        None => check_ncname(s, 0),
     }
 }
 
+/// A string which is not a valid `NCName` or `QName`, see `is_ncname` and `is_qname`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NameError {
+    index: usize,
+    found: Option<char>,
+    kind: NameErrorKind,
+}
+
+/// Which rule for names a `NameError` is about
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NameErrorKind {
+    /// The name, its prefix or its local name is empty
+    Empty,
+    /// A colon in a local name or a prefix, e.g. the second one of `a:b:c`
+    Colon,
+    /// Whitespace, which names can't contain
+    Whitespace,
+    /// A character which can't start a name, e.g. a digit or `-`
+    InvalidStart,
+    /// A character which can't be in a name at all
+    InvalidChar,
+    /// The `{` of Clark notation isn't closed
+    UnclosedBrace,
+}
+
+impl NameError {
+    /// Byte index of the offending character, or of where a character is missing
+    #[inline]
+    #[must_use]
+    pub fn index(&self) -> usize { self.index }
+
+    /// The offending character, or `None` if the name or one of its parts is empty
+    #[inline]
+    #[must_use]
+    pub fn found(&self) -> Option<char> { self.found }
+
+    /// Which rule the name breaks
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> NameErrorKind { self.kind }
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let index = self.index;
+        match (self.kind, self.found) {
+            (NameErrorKind::Empty, _) | (_, None) => write!(f, "empty name or name part at index {index}"),
+            (NameErrorKind::Colon, _) => write!(f, "unexpected colon at index {index}"),
+            (NameErrorKind::Whitespace, Some(c)) => write!(f, "whitespace {c:?} at index {index}"),
+            (NameErrorKind::InvalidStart, Some(c)) => write!(f, "a name can't start with {c:?}, at index {index}"),
+            (NameErrorKind::InvalidChar, Some(c)) => write!(f, "invalid character {c:?} at index {index} of a name"),
+            (NameErrorKind::UnclosedBrace, _) => write!(f, "unclosed '{{' at index {index}"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for NameError {}
+
 #[cfg(test)]
 mod tests {
-    use super::OwnedName;
+    extern crate alloc;
+
+    use super::{is_ncname, is_qname, CowName, Name, NameError, NameErrorKind, NameInterner, OwnedName, XML_BASE, XML_LANG, XML_SPACE};
 
     #[test]
     fn test_owned_name_from_str() {
@@ -306,10 +1011,254 @@ mod tests {
             prefix: None
         }));
 
-        assert_eq!("".parse(), Err::<OwnedName, ()>(()));
-        assert_eq!(":".parse(), Err::<OwnedName, ()>(()));
-        assert_eq!(":a".parse(), Err::<OwnedName, ()>(()));
-        assert_eq!("a:".parse(), Err::<OwnedName, ()>(()));
-        assert_eq!("a:b:c".parse(), Err::<OwnedName, ()>(()));
+        let kind = |s: &str| s.parse::<OwnedName>().map_err(|e| (e.index(), e.kind()));
+        assert_eq!(kind(""), Err((0, NameErrorKind::Empty)));
+        assert_eq!(kind(":"), Err((0, NameErrorKind::Empty)));
+        assert_eq!(kind(":a"), Err((0, NameErrorKind::Empty)));
+        assert_eq!(kind("a:"), Err((2, NameErrorKind::Empty)));
+        assert_eq!(kind("a:b:c"), Err((3, NameErrorKind::Colon)));
+        assert_eq!(kind("a::b"), Err((2, NameErrorKind::Colon)));
+        assert_eq!(kind("a b"), Err((1, NameErrorKind::Whitespace)));
+        assert_eq!(kind(" a"), Err((0, NameErrorKind::Whitespace)));
+        assert_eq!(kind("a:\tb"), Err((2, NameErrorKind::Whitespace)));
+        assert_eq!(kind("a:-b"), Err((2, NameErrorKind::InvalidStart)));
+        assert_eq!(kind("a/b"), Err((1, NameErrorKind::InvalidChar)));
+    }
+
+    #[test]
+    fn name_validation() {
+        for valid in ["a", "_x", "a-b.c1", "\u{e9}t\u{e9}", "\u{4e2d}\u{6587}", "x\u{300}", "\u{10000}"] {
+            assert!(is_ncname(valid), "{valid}");
+            assert!(is_qname(valid), "{valid}");
+        }
+        for invalid in ["", "1a", "-a", ".a", "a b", "a:b", "\u{b7}a", "a\u{d7}"] {
+            assert!(!is_ncname(invalid), "{invalid}");
+        }
+        assert!(is_qname("p:\u{e9}"));
+        for invalid in ["", ":", ":a", "a:", "a:b:c", "a :b", "1:a", "a:1"] {
+            assert!(!is_qname(invalid), "{invalid}");
+        }
+
+        assert_eq!(Name::try_local(""), Err(NameError { index: 0, found: None, kind: NameErrorKind::Empty }));
+        assert_eq!(Name::try_local("1a"), Err(NameError { index: 0, found: Some('1'), kind: NameErrorKind::InvalidStart }));
+        assert_eq!(Name::try_local("p:a"), Err(NameError { index: 1, found: Some(':'), kind: NameErrorKind::Colon }));
+        assert_eq!(Name::try_qualified("a", "urn:x", Some("p")), Ok(Name::qualified("a", "urn:x", Some("p"))));
+        assert_eq!(Name::try_qualified("a b", "urn:x", Some("pre")), Err(NameError { index: 5, found: Some(' '), kind: NameErrorKind::Whitespace }));
+        assert_eq!(Name::try_qualified("a", "urn:x", Some("")), Err(NameError { index: 0, found: None, kind: NameErrorKind::Empty }));
+
+        assert_eq!(OwnedName::try_from_str("p:a"), "p:a".parse::<OwnedName>());
+        assert_eq!(OwnedName::try_from_str("a:"), Err(NameError { index: 2, found: None, kind: NameErrorKind::Empty }));
+        assert_eq!(OwnedName::try_from_str("a:b:c"), Err(NameError { index: 3, found: Some(':'), kind: NameErrorKind::Colon }));
+        assert_eq!(OwnedName::try_from_str("\u{e9}:\u{e9}!"), Err(NameError { index: 5, found: Some('!'), kind: NameErrorKind::InvalidChar }));
+    }
+
+    #[test]
+    fn repr_display() {
+        let names = [
+            (Name::local("a"), "a", "a"),
+            (Name::prefixed("a", "p"), "p:a", "p:a"),
+            (Name::qualified("a", "urn:x", None), "a", "{urn:x}a"),
+            (Name::qualified("a", "urn:x", Some("p")), "p:a", "{urn:x}p:a"),
+        ];
+        for (name, repr, display) in names {
+            assert_eq!(alloc::format!("{}", name.repr_display()), repr);
+            assert_eq!(name.to_repr(), repr);
+            assert_eq!(alloc::format!("{name}"), display);
+
+            let owned = name.to_owned();
+            assert_eq!(alloc::format!("{}", owned.repr_display()), repr);
+            assert_eq!(owned.to_repr(), repr);
+            assert_eq!(alloc::format!("{owned}"), display);
+        }
+    }
+
+    #[test]
+    fn name_interner() {
+        let mut interner = NameInterner::new();
+        assert!(interner.is_empty());
+        let names = [Name::local("a"), Name::prefixed("a", "p"), Name::qualified("a", "urn:x", Some("p")), Name::local("b")];
+        let first: alloc::vec::Vec<_> = names.iter().map(|&n| interner.intern(n)).collect();
+        for _ in 0..3 {
+            for (&name, interned) in names.iter().zip(&first) {
+                let again = interner.intern(name);
+                assert!(again.ptr_eq(interned));
+                assert_eq!(again.borrow(), name);
+                assert_eq!(*again, name.to_owned());
+            }
+        }
+        assert_eq!(interner.len(), names.len());
+
+        drop(interner);
+        let [a, ..] = <[_; 4]>::try_from(first).unwrap();
+        assert_eq!(OwnedName::from(a), OwnedName::local("a"));
+    }
+
+    #[test]
+    fn expanded_name_matching() {
+        const NS: &str = "urn:x";
+        // default namespace and prefixed forms of the same expanded name
+        let names = [Name::qualified("item", NS, None), Name::qualified("item", NS, Some("x"))];
+        for name in names {
+            assert!(name.matches(Some(NS), "item"));
+            assert!(!name.matches(None, "item"));
+            assert!(!name.matches(Some(NS), "other"));
+            assert!(name.in_namespace(NS));
+            assert!(!name.in_namespace(""));
+            assert!(name.local_is("item"));
+
+            let owned = name.to_owned();
+            assert!(owned.matches(Some(NS), "item"));
+            assert!(owned.in_namespace(NS));
+            assert!(owned.local_is("item"));
+        }
+
+        for name in [Name::local("item"), Name::prefixed("item", "x"), Name::qualified("item", "", None)] {
+            assert!(name.matches(None, "item"));
+            assert!(name.matches(Some(""), "item"));
+            assert!(!name.matches(Some(NS), "item"));
+            assert!(name.in_namespace(""));
+            assert!(!name.in_namespace(NS));
+        }
+    }
+
+    #[test]
+    fn const_names() {
+        const TITLE: Name<'static> = Name::local("title");
+        const DC_TITLE: Name<'static> = Name::prefixed("title", "dc");
+        const ATOM_TITLE: Name<'static> = Name::qualified("title", "http://www.w3.org/2005/Atom", None);
+        static NAMES: [Name<'static>; 3] = [TITLE, DC_TITLE, ATOM_TITLE];
+
+        assert_eq!(NAMES[0], "title");
+        assert_eq!(NAMES[1].to_repr(), "dc:title");
+        assert!(NAMES[2].in_namespace("http://www.w3.org/2005/Atom"));
+        assert_eq!(alloc::format!("{XML_SPACE}"), "{http://www.w3.org/XML/1998/namespace}xml:space");
+        assert_eq!(XML_LANG.to_repr(), "xml:lang");
+        assert_eq!(XML_BASE.to_repr(), "xml:base");
+    }
+
+    #[test]
+    fn clark_notation() {
+        for (clark, namespace, local) in [
+            ("{urn:x}a", Some("urn:x"), "a"),
+            ("a", None, "a"),
+            ("{}a", None, "a"),
+        ] {
+            let name = OwnedName::from_clark(clark).unwrap();
+            assert_eq!(name.namespace.as_deref(), namespace);
+            assert_eq!(name.local_name, local);
+            assert_eq!(name.prefix, None);
+        }
+
+        assert_eq!(OwnedName::from_clark("{urn:x}a}b"), Err(NameError { index: 8, found: Some('}'), kind: NameErrorKind::InvalidChar }));
+        assert_eq!(OwnedName::from_clark("{urn:x"), Err(NameError { index: 0, found: Some('{'), kind: NameErrorKind::UnclosedBrace }));
+        assert_eq!(OwnedName::from_clark("{urn:x}{a}"), Err(NameError { index: 7, found: Some('{'), kind: NameErrorKind::InvalidStart }));
+        assert_eq!(OwnedName::from_clark("a{b}"), Err(NameError { index: 1, found: Some('{'), kind: NameErrorKind::InvalidChar }));
+        assert_eq!(OwnedName::from_clark("{urn:x}"), Err(NameError { index: 7, found: None, kind: NameErrorKind::Empty }));
+        assert_eq!(OwnedName::from_clark("{urn:x}p:a"), Err(NameError { index: 8, found: Some(':'), kind: NameErrorKind::Colon }));
+
+        let name = Name::qualified("a", "urn:x", Some("p"));
+        assert_eq!(alloc::format!("{}", name.to_clark()), "{urn:x}a");
+        assert_eq!(alloc::format!("{}", name.to_owned().to_clark()), "{urn:x}a");
+        assert_eq!(alloc::format!("{}", Name::prefixed("a", "p").to_clark()), "a");
+        assert_eq!(alloc::format!("{}", Name::qualified("a", "", None).to_clark()), "a");
+        assert_eq!(OwnedName::from_clark(&alloc::format!("{}", name.to_clark())), Ok(OwnedName::qualified("a", "urn:x", None::<&str>)));
+    }
+
+    /// The bytes hashed for `value`, to check that hashes agree whatever the hasher
+    fn hash(value: impl core::hash::Hash) -> alloc::vec::Vec<u8> {
+        #[derive(Default)]
+        struct Bytes(alloc::vec::Vec<u8>);
+        impl core::hash::Hasher for Bytes {
+            fn finish(&self) -> u64 { 0 }
+            fn write(&mut self, bytes: &[u8]) { self.0.extend_from_slice(bytes); }
+        }
+        let mut hasher = Bytes::default();
+        value.hash(&mut hasher);
+        hasher.0
+    }
+
+    #[test]
+    fn cow_name() {
+        use alloc::borrow::Cow;
+
+        for name in [Name::local("a"), Name::prefixed("a", "p"), Name::qualified("a", "urn:x", Some("p"))] {
+            let borrowed = CowName::from(name);
+            assert!(matches!(borrowed.local_name, Cow::Borrowed(_)));
+            let owned = CowName::from(name.to_owned());
+            assert!(matches!(owned.local_name, Cow::Owned(_)));
+
+            assert_eq!(borrowed, owned);
+            assert_eq!(borrowed.borrow(), name);
+            assert_eq!(hash(&borrowed), hash(name.to_owned()));
+            assert_eq!(alloc::format!("{borrowed}"), alloc::format!("{name}"));
+            assert_eq!(borrowed.clone().into_static().into_owned(), name.to_owned());
+            assert_eq!(OwnedName::from(borrowed), name.to_owned());
+        }
+        assert!(CowName::from(Name::local("a")) < CowName::from(Name::local("b")));
+    }
+
+    #[test]
+    fn cross_type_equality() {
+        for name in [Name::local("a"), Name::prefixed("a", "p"), Name::qualified("a", "urn:x", Some("p")), Name::qualified("a", "urn:x", None)] {
+            let owned = name.to_owned();
+            assert_eq!(name, owned);
+            assert_eq!(owned, name);
+            assert_eq!(hash(name), hash(&owned));
+            assert_ne!(Name::local("b"), owned);
+            assert_ne!(owned, Name::local("b"));
+        }
+
+        assert!(Name::local("a") == "a" && OwnedName::local("a") == "a");
+        assert!(Name::prefixed("a", "p") == "p:a" && OwnedName::qualified("a", "", Some("p")) == *"p:a");
+        assert!(Name::qualified("a", "", None) == "a");
+        for (name, s) in [
+            (Name::local("a"), "p:a"),
+            (Name::local("a:b"), "a:b"),
+            (Name::prefixed("a", "p"), "a"),
+            (Name::prefixed("a", "p"), "q:a"),
+            (Name::qualified("a", "urn:x", None), "a"),
+            (Name::qualified("a", "urn:x", Some("p")), "p:a"),
+        ] {
+            let owned = name.to_owned();
+            assert!(name != s && owned != s, "{name:?} {s}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        for name in [Name::local("a"), Name::prefixed("a", "p"), Name::qualified("a", "urn:x", Some("p")), Name::qualified("a", "urn:x", None)] {
+            let bytes = postcard::to_allocvec(&name.to_owned()).unwrap();
+            assert_eq!(postcard::from_bytes::<OwnedName>(&bytes).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn expanded_name() {
+        use alloc::collections::BTreeMap;
+        use super::{ExpandedName, OwnedExpandedName};
+
+        let names = [
+            Name::qualified("a", "urn:x", Some("p")),
+            Name::qualified("a", "urn:x", Some("q")),
+            Name::qualified("a", "urn:x", None),
+        ];
+        for name in names {
+            assert_eq!(name.expanded(), names[0].expanded());
+            assert_eq!(hash(name.expanded()), hash(OwnedExpandedName::from(name.to_owned())));
+            assert_eq!(alloc::format!("{}", name.expanded()), "{urn:x}a");
+        }
+        assert_eq!(Name::local("a").expanded(), Name::qualified("a", "", Some("p")).expanded());
+        assert_ne!(Name::local("a").expanded(), names[0].expanded());
+        assert_eq!(Name::local("a").expanded().namespace(), None);
+        assert!(Name::local("b").expanded() > Name::local("a").expanded());
+
+        let mut map: BTreeMap<OwnedExpandedName, usize> = BTreeMap::new();
+        map.insert(OwnedName::qualified("a", "urn:x", Some("p")).into(), 1);
+        map.insert(ExpandedName::new(None, "a").into_owned(), 2);
+        let local = alloc::string::String::from("a");
+        assert_eq!(map.get(&ExpandedName::new(Some("urn:x"), &local)), Some(&1));
+        assert_eq!(map.get(&Name::local(&local).expanded()), Some(&2));
+        assert_eq!(map.get(&Name::qualified("a", "urn:y", None).expanded()), None);
     }
 }
@@ -7,9 +7,15 @@ use alloc::collections::btree_map::{BTreeMap, Entry};
 use alloc::collections::BTreeSet;
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::iter::{Map, Rev};
+use core::fmt;
+use core::iter::{FusedIterator, Rev};
+use core::mem;
 use core::slice::Iter;
 
+use crate::attribute::Attribute;
+use crate::capacity::Stack;
+use crate::name::Name;
+
 /// Designates prefix for namespace definitions.
 ///
 /// See [Namespaces in XML][namespace] spec for more information.
@@ -64,27 +70,138 @@ pub const NS_NO_PREFIX: &str = "";
 /// empty prefix corresponds to absent namespace in `NamespaceStack` instances created with
 /// `NamespaceStack::default()`. Therefore, it can be used to restore `NS_NO_PREFIX` mapping
 /// in a namespace back to its default value.
+///
+/// Mapping `NS_NO_PREFIX` to it means there's no default namespace, like `xmlns=""` does
+/// in a document: unprefixed names read in its scope have no namespace, and the writer
+/// declares `xmlns=""` where it undoes a default namespace which is in scope.
 pub const NS_EMPTY_URI: &str = "";
 
+/// A namespace binding which the [Namespaces in XML][namespace] spec reserves, see `check_reserved()`.
+///
+///   [namespace]: http://www.w3.org/TR/xml-names/#xmlReserved
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReservedNamespaceError {
+    /// `xml` bound to a URI other than `NS_XML_URI`
+    XmlPrefix,
+    /// `xmlns` declared as a prefix
+    XmlnsPrefix,
+    /// `NS_XML_URI` bound to a prefix other than `xml`, or made the default namespace
+    XmlUri,
+    /// `NS_XMLNS_URI` bound to a prefix or made the default namespace
+    XmlnsUri,
+}
+
+impl fmt::Display for ReservedNamespaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReservedNamespaceError::XmlPrefix => write!(f, "the {NS_XML_PREFIX} prefix can only be bound to {NS_XML_URI}"),
+            ReservedNamespaceError::XmlnsPrefix => write!(f, "the {NS_XMLNS_PREFIX} prefix can't be declared"),
+            ReservedNamespaceError::XmlUri => write!(f, "{NS_XML_URI} can only be bound to the {NS_XML_PREFIX} prefix"),
+            ReservedNamespaceError::XmlnsUri => write!(f, "{NS_XMLNS_URI} can't be bound to a prefix"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for ReservedNamespaceError {}
+
+/// Checks that binding `prefix` to `uri` doesn't break the rules for the reserved `xml` and
+/// `xmlns` prefixes and their URIs. Use `NS_NO_PREFIX` for the default namespace.
+///
+/// The bindings they always have, `xml` to `NS_XML_URI` and `xmlns` to `NS_XMLNS_URI`, pass,
+/// even though the latter can't be declared in a document.
+///
+/// ```
+/// use xml_no_std::namespace::{check_reserved, ReservedNamespaceError, NS_XML_URI};
+///
+/// assert_eq!(check_reserved("xml", NS_XML_URI), Ok(()));
+/// assert_eq!(check_reserved("x", NS_XML_URI), Err(ReservedNamespaceError::XmlUri));
+/// assert_eq!(check_reserved("xml", "urn:x"), Err(ReservedNamespaceError::XmlPrefix));
+/// ```
+pub fn check_reserved(prefix: &str, uri: &str) -> Result<(), ReservedNamespaceError> {
+    match (prefix, uri) {
+        (NS_XML_PREFIX, NS_XML_URI) | (NS_XMLNS_PREFIX, NS_XMLNS_URI) => Ok(()),
+        (NS_XML_PREFIX, _) => Err(ReservedNamespaceError::XmlPrefix),
+        (NS_XMLNS_PREFIX, _) => Err(ReservedNamespaceError::XmlnsPrefix),
+        (_, NS_XML_URI) => Err(ReservedNamespaceError::XmlUri),
+        (_, NS_XMLNS_URI) => Err(ReservedNamespaceError::XmlnsUri),
+        _ => Ok(()),
+    }
+}
+
 /// Namespace is a map from prefixes to namespace URIs.
 ///
 /// No prefix (i.e. default namespace) is designated by `NS_NO_PREFIX` constant.
-#[derive(PartialEq, Eq, Clone, Debug)]
-pub struct Namespace(pub BTreeMap<String, String>);
+///
+/// Up to 4 mappings are stored inline, so the usual namespace of an event, the `xml`, `xmlns`
+/// and default mappings and maybe one prefix, doesn't allocate a map.
+#[derive(Clone)]
+pub struct Namespace(Storage);
+
+/// The number of mappings stored without a map: the `xml`, `xmlns` and default mappings
+/// every squashed namespace has, and one declared prefix.
+const INLINE_MAPPINGS: usize = 4;
+
+// being large is the point, it's what saves the allocation
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Storage {
+    /// The first `usize` slots are mappings sorted by prefix, the others are empty
+    Inline(usize, [(String, String); INLINE_MAPPINGS]),
+    Map(BTreeMap<String, String>),
+}
+
+impl Storage {
+    /// Inserts a mapping for a prefix which isn't there yet, at `index` of the inline ones
+    fn insert(&mut self, index: usize, prefix: String, uri: String) {
+        match self {
+            Storage::Inline(len, slots) if *len < INLINE_MAPPINGS => {
+                slots[*len] = (prefix, uri);
+                slots[index..=*len].rotate_right(1);
+                *len += 1;
+            },
+            Storage::Inline(len, slots) => {
+                let mut map: BTreeMap<_, _> = slots[..*len].iter_mut().map(mem::take).collect();
+                map.insert(prefix, uri);
+                *self = Storage::Map(map);
+            },
+            Storage::Map(map) => {
+                map.insert(prefix, uri);
+            },
+        }
+    }
+}
+
+/// Where `prefix` is or would be among sorted inline mappings
+fn search(slots: &[(String, String)], prefix: &str) -> Result<usize, usize> {
+    slots.binary_search_by(|(p, _)| (**p).cmp(prefix))
+}
 
 impl Namespace {
     /// Returns an empty namespace.
     #[inline]
     #[must_use]
     pub fn empty() -> Namespace {
-        Namespace(BTreeMap::new())
+        Namespace(Storage::Inline(0, Default::default()))
     }
 
     /// Checks whether this namespace is empty.
     #[inline]
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.len() == 0
+    }
+
+    /// Returns the number of mappings in this namespace.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Storage::Inline(len, _) => *len,
+            Storage::Map(map) => map.len(),
+        }
     }
 
     /// Checks whether this namespace is essentially empty, that is, it does not contain
@@ -92,9 +209,9 @@ impl Namespace {
     #[must_use]
     pub fn is_essentially_empty(&self) -> bool {
         // a shortcut for a namespace which is definitely not empty
-        if self.0.len() > 3 { return false; }
+        if self.len() > 3 { return false; }
 
-        self.0.iter().all(|(k, v)| match (&**k, &**v) {
+        self.into_iter().all(|(k, v)| match (k, v) {
             (NS_NO_PREFIX,    NS_EMPTY_URI) => true,
             (NS_XMLNS_PREFIX, NS_XMLNS_URI) => true,
             (NS_XML_PREFIX,   NS_XML_URI)   => true,
@@ -111,7 +228,7 @@ impl Namespace {
     /// `true` if this namespace contains the given prefix, `false` otherwise.
     #[inline]
     pub fn contains<P: ?Sized + AsRef<str>>(&self, prefix: &P) -> bool {
-        self.0.contains_key(prefix.as_ref())
+        self.get(prefix).is_some()
     }
 
     /// Puts a mapping into this namespace.
@@ -131,12 +248,22 @@ impl Namespace {
     pub fn put<P, U>(&mut self, prefix: P, uri: U) -> bool
         where P: Into<String>, U: Into<String>
     {
-        match self.0.entry(prefix.into()) {
-            Entry::Occupied(_) => false,
-            Entry::Vacant(ve) => {
-                ve.insert(uri.into());
-                true
-            }
+        let prefix = prefix.into();
+        match &mut self.0 {
+            Storage::Inline(len, slots) => match search(&slots[..*len], &prefix) {
+                Ok(_) => false,
+                Err(index) => {
+                    self.0.insert(index, prefix, uri.into());
+                    true
+                },
+            },
+            Storage::Map(map) => match map.entry(prefix) {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(ve) => {
+                    ve.insert(uri.into());
+                    true
+                }
+            },
         }
     }
 
@@ -156,7 +283,17 @@ impl Namespace {
     pub fn force_put<P, U>(&mut self, prefix: P, uri: U) -> Option<String>
         where P: Into<String>, U: Into<String>
     {
-        self.0.insert(prefix.into(), uri.into())
+        let prefix = prefix.into();
+        match &mut self.0 {
+            Storage::Inline(len, slots) => match search(&slots[..*len], &prefix) {
+                Ok(index) => Some(mem::replace(&mut slots[index].1, uri.into())),
+                Err(index) => {
+                    self.0.insert(index, prefix, uri.into());
+                    None
+                },
+            },
+            Storage::Map(map) => map.insert(prefix, uri.into()),
+        }
     }
 
     /// Queries the namespace for the given prefix.
@@ -167,7 +304,39 @@ impl Namespace {
     /// # Return value
     /// Namespace URI corresponding to the given prefix, if it is present.
     pub fn get<'a, P: ?Sized + AsRef<str>>(&'a self, prefix: &P) -> Option<&'a str> {
-        self.0.get(prefix.as_ref()).map(|s| &**s)
+        match &self.0 {
+            Storage::Inline(len, slots) => search(&slots[..*len], prefix.as_ref()).ok().map(|index| &*slots[index].1),
+            Storage::Map(map) => map.get(prefix.as_ref()).map(|s| &**s),
+        }
+    }
+
+    /// Returns the default namespace URI, or `None` if it's not mapped or mapped to `NS_EMPTY_URI`.
+    #[inline]
+    #[must_use]
+    pub fn default_namespace(&self) -> Option<&str> {
+        self.get(NS_NO_PREFIX).filter(|uri| *uri != NS_EMPTY_URI)
+    }
+
+    /// Queries the namespace for the given prefix, same as `get()`.
+    #[inline]
+    pub fn uri_for_prefix<'a, P: ?Sized + AsRef<str>>(&'a self, prefix: &P) -> Option<&'a str> {
+        self.get(prefix)
+    }
+
+    /// Returns a prefix mapped to the given URI, if there's one.
+    ///
+    /// If several are, a non-empty prefix is preferred over the default namespace,
+    /// and then the first one in prefix order.
+    #[must_use]
+    pub fn prefix_for_uri(&self, uri: &str) -> Option<&str> {
+        prefix_for_uri(self.into_iter(), uri)
+    }
+
+    /// Returns `xmlns` attributes declaring the mappings in this namespace, see
+    /// `NamespaceStack::to_declarations()`.
+    #[must_use]
+    pub fn to_declarations(&self) -> Vec<Attribute<'_>> {
+        declarations(self.into_iter())
     }
 
     /// Borrowed namespace for the writer
@@ -177,21 +346,141 @@ impl Namespace {
     }
 }
 
-/// An alias for iterator type for namespace mappings contained in a namespace.
-pub type NamespaceMappings<'a> = Map<
-    Entries<'a, String, String>,
-    for<'b> fn((&'b String, &'b String)) -> UriMapping<'b>
->;
+/// An iterator over the mappings of a namespace, sorted by prefix.
+#[derive(Clone, Debug)]
+pub struct NamespaceMappings<'a>(MappingsIter<'a>);
+
+#[derive(Clone, Debug)]
+enum MappingsIter<'a> {
+    Inline(Iter<'a, (String, String)>),
+    Map(Entries<'a, String, String>),
+}
+
+impl<'a> Iterator for NamespaceMappings<'a> {
+    type Item = UriMapping<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<UriMapping<'a>> {
+        match &mut self.0 {
+            MappingsIter::Inline(iter) => iter.next().map(|(prefix, uri)| (&**prefix, &**uri)),
+            MappingsIter::Map(iter) => iter.next().map(|(prefix, uri)| (&**prefix, &**uri)),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            MappingsIter::Inline(iter) => iter.size_hint(),
+            MappingsIter::Map(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for NamespaceMappings<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            MappingsIter::Inline(iter) => iter.next_back().map(|(prefix, uri)| (&**prefix, &**uri)),
+            MappingsIter::Map(iter) => iter.next_back().map(|(prefix, uri)| (&**prefix, &**uri)),
+        }
+    }
+}
+
+impl ExactSizeIterator for NamespaceMappings<'_> {}
+
+impl FusedIterator for NamespaceMappings<'_> {}
 
 impl<'a> IntoIterator for &'a Namespace {
     type Item = UriMapping<'a>;
     type IntoIter = NamespaceMappings<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        fn mapper<'a>((prefix, uri): (&'a String, &'a String)) -> UriMapping<'a> {
-            (prefix, uri)
+        NamespaceMappings(match &self.0 {
+            Storage::Inline(len, slots) => MappingsIter::Inline(slots[..*len].iter()),
+            Storage::Map(map) => MappingsIter::Map(map.iter()),
+        })
+    }
+}
+
+impl Default for Namespace {
+    #[inline]
+    fn default() -> Namespace {
+        Namespace::empty()
+    }
+}
+
+impl PartialEq for Namespace {
+    fn eq(&self, other: &Namespace) -> bool {
+        self.len() == other.len() && self.into_iter().eq(other)
+    }
+}
+
+impl Eq for Namespace {}
+
+impl fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Namespace").field(&MappingsDebug(self)).finish()
+    }
+}
+
+/// Formats the mappings like a map
+struct MappingsDebug<'a>(&'a Namespace);
+
+impl fmt::Debug for MappingsDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0).finish()
+    }
+}
+
+/// Serialized as a newtype struct of a map from prefixes to URIs.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Namespace {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Mappings<'a>(&'a Namespace);
+
+        impl serde::Serialize for Mappings<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_map(self.0)
+            }
+        }
+
+        serializer.serialize_newtype_struct("Namespace", &Mappings(self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Namespace {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Namespace, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Namespace")]
+        struct Mappings(BTreeMap<String, String>);
+
+        Mappings::deserialize(deserializer).map(|Mappings(map)| Namespace::from(map))
+    }
+}
+
+impl From<BTreeMap<String, String>> for Namespace {
+    #[inline]
+    fn from(map: BTreeMap<String, String>) -> Namespace {
+        if map.len() > INLINE_MAPPINGS {
+            return Namespace(Storage::Map(map));
+        }
+        let mut slots: [(String, String); INLINE_MAPPINGS] = Default::default();
+        let len = map.len();
+        for (slot, mapping) in slots.iter_mut().zip(map) {
+            *slot = mapping;
+        }
+        Namespace(Storage::Inline(len, slots))
+    }
+}
+
+impl From<Namespace> for BTreeMap<String, String> {
+    #[inline]
+    fn from(namespace: Namespace) -> BTreeMap<String, String> {
+        match namespace.0 {
+            Storage::Inline(len, slots) => slots.into_iter().take(len).collect(),
+            Storage::Map(map) => map,
         }
-        self.0.iter().map(mapper)
     }
 }
 
@@ -199,8 +488,11 @@ impl<'a> IntoIterator for &'a Namespace {
 ///
 /// Namespace stack is used to represent cumulative namespace consisting of
 /// combined namespaces from nested elements.
+///
+/// The namespaces are in a `Vec` by default. The reader keeps them in the `Stack` of its
+/// `Capacity`, e.g. an array with the `fixed-capacity` feature.
 #[derive(Clone, Eq, PartialEq, Debug)]
-pub struct NamespaceStack(pub Vec<Namespace>);
+pub struct NamespaceStack<S = Vec<Namespace>>(pub S);
 
 impl NamespaceStack {
     /// Returns an empty namespace stack.
@@ -220,23 +512,47 @@ impl NamespaceStack {
     #[must_use]
     pub fn default() -> NamespaceStack {
         let mut nst = NamespaceStack::empty();
-        nst.push_empty();
+        nst.push_default();
+        nst
+    }
+}
+
+impl<S: Stack<Namespace>> NamespaceStack<S> {
+    /// Adds a namespace with the default items of `default()` to the top of this stack.
+    pub fn push_default(&mut self) -> &mut NamespaceStack<S> {
+        self.push_empty();
         // xml namespace
-        nst.put(NS_XML_PREFIX, NS_XML_URI);
+        self.put(NS_XML_PREFIX, NS_XML_URI);
         // xmlns namespace
-        nst.put(NS_XMLNS_PREFIX, NS_XMLNS_URI);
+        self.put(NS_XMLNS_PREFIX, NS_XMLNS_URI);
         // empty namespace
-        nst.put(NS_NO_PREFIX, NS_EMPTY_URI);
-        nst
+        self.put(NS_NO_PREFIX, NS_EMPTY_URI);
+        self
     }
 
     /// Adds an empty namespace to the top of this stack.
     #[inline]
-    pub fn push_empty(&mut self) -> &mut NamespaceStack {
+    pub fn push_empty(&mut self) -> &mut NamespaceStack<S> {
         self.0.push(Namespace::empty());
         self
     }
 
+    /// Adds a namespace with the given mappings to the top of this stack.
+    ///
+    /// Unlike `push_empty()` followed by `extend()`, which keeps the first URI, two different
+    /// URIs for the same prefix are an error. The stack is left as it was then.
+    pub fn push_checked<'b, T>(&mut self, mappings: T) -> Result<(), ConflictingBinding>
+        where T: IntoIterator<Item = UriMapping<'b>>
+    {
+        let mut ns = Namespace::empty();
+        for (prefix, uri) in mappings {
+            check_conflict(&ns, prefix, uri)?;
+            ns.put(prefix, uri);
+        }
+        self.0.push(ns);
+        Ok(())
+    }
+
     /// Removes the topmost namespace in this stack.
     ///
     /// Panics if the stack is empty.
@@ -290,7 +606,7 @@ impl NamespaceStack {
         where P: Into<String> + AsRef<str>,
               U: Into<String> + AsRef<str>
     {
-        if self.0.iter().any(|ns| ns.get(&prefix) == Some(uri.as_ref())) {
+        if self.0.as_slice().iter().any(|ns| ns.get(&prefix) == Some(uri.as_ref())) {
             false
         } else {
             self.put(prefix, uri);
@@ -335,7 +651,7 @@ impl NamespaceStack {
     #[inline]
     pub fn get<'a, P: ?Sized + AsRef<str>>(&'a self, prefix: &P) -> Option<&'a str> {
         let prefix = prefix.as_ref();
-        for ns in self.0.iter().rev() {
+        for ns in self.0.as_slice().iter().rev() {
             match ns.get(prefix) {
                 None => {},
                 r => return r,
@@ -344,28 +660,109 @@ impl NamespaceStack {
         None
     }
 
+    /// Returns the default namespace URI in effect, or `None` if there's none, including
+    /// when it's been undeclared by mapping it to `NS_EMPTY_URI`.
+    #[inline]
+    #[must_use]
+    pub fn default_namespace(&self) -> Option<&str> {
+        self.get(NS_NO_PREFIX).filter(|uri| *uri != NS_EMPTY_URI)
+    }
+
+    /// Performs a search for the given prefix in the whole stack, same as `get()`.
+    #[inline]
+    pub fn uri_for_prefix<'a, P: ?Sized + AsRef<str>>(&'a self, prefix: &P) -> Option<&'a str> {
+        self.get(prefix)
+    }
+
+    /// Returns a prefix which is currently bound to the given URI, if there's one.
+    ///
+    /// Prefixes whose binding is shadowed by an inner namespace are not considered. If several
+    /// prefixes are bound to the URI, a non-empty prefix is preferred over the default
+    /// namespace, and then the innermost binding, so that e.g. `xsi:type` values can be
+    /// written with a prefix which means the same wherever it's used.
+    #[must_use]
+    pub fn prefix_for_uri(&self, uri: &str) -> Option<&str> {
+        prefix_for_uri(self.iter(), uri)
+    }
+
+    /// Returns `xmlns` attributes declaring every mapping in effect at the top of this stack.
+    ///
+    /// These make a subtree cut out of a document self-contained when added to its root element.
+    /// Shadowed bindings are left out, as are the implicit `xml` and `xmlns` prefixes. There's
+    /// no attribute for a default namespace or a prefix which is mapped to `NS_EMPTY_URI`,
+    /// since at the root of a document that's the same as not being bound. Default namespace
+    /// declaration comes first, then the prefixed ones sorted by prefix.
+    ///
+    /// # Example
+    /// ```
+    /// use xml_no_std::namespace::NamespaceStack;
+    ///
+    /// let mut nst = NamespaceStack::empty();
+    /// nst.push_empty().put("", "urn:outer");
+    /// nst.put("p", "urn:p");
+    /// nst.push_empty().put("", "");
+    /// nst.put("p", "urn:q");
+    /// let declarations: Vec<_> = nst.to_declarations().iter().map(|attr| (attr.name.to_repr(), attr.value)).collect();
+    /// assert_eq!(declarations, [("xmlns:p".to_owned(), "urn:q")]);
+    /// ```
+    #[must_use]
+    pub fn to_declarations(&self) -> Vec<Attribute<'_>> {
+        declarations(self.iter())
+    }
+
+    /// Returns the mappings of `incoming` which are not already in effect in this stack.
+    ///
+    /// These are the `xmlns` declarations an element with the `incoming` namespace needs
+    /// to have when written at this point. A mapping of the default namespace to the empty
+    /// URI is included when the default namespace has to be undeclared, i.e. `xmlns=""`.
+    /// The `xml` and `xmlns` prefixes and the empty default namespace count as bound to
+    /// their usual values when the stack has no mappings for them.
+    pub fn delta<'a>(&'a self, incoming: &'a Namespace) -> impl Iterator<Item = UriMapping<'a>> + 'a {
+        delta(self.0.as_slice(), incoming)
+    }
+
     /// Combines this stack of namespaces into a single namespace.
     ///
     /// Namespaces are combined in left-to-right order, that is, rightmost namespace
     /// elements take priority over leftmost ones.
     #[must_use]
     pub fn squash(&self) -> Namespace {
-        let mut result = BTreeMap::new();
-        for ns in &self.0 {
-            result.extend(ns.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let mut namespaces = self.0.as_slice().iter().filter(|ns| !ns.is_empty());
+        let mut result = namespaces.next().cloned().unwrap_or_else(Namespace::empty);
+        for (prefix, uri) in namespaces.flatten() {
+            result.force_put(prefix, uri);
         }
-        Namespace(result)
+        result
     }
 
     /// Returns an object which implements `Extend` using `put_checked()` instead of `put()`.
     ///
     /// See `CheckedTarget` for more information.
     #[inline]
-    pub fn checked_target(&mut self) -> CheckedTarget<'_> {
+    pub fn checked_target(&mut self) -> CheckedTarget<'_, S> {
         CheckedTarget(self)
     }
 
+    /// Returns the namespace in effect at the top of this stack, with every prefix in scope
+    /// mapped to its innermost binding.
+    ///
+    /// Unlike `squash()`, the result always has the implicit `xml` and `xmlns` bindings, even
+    /// if the stack doesn't, so it's the complete context names are resolved in. Iterating
+    /// over it gives bindings sorted by prefix.
+    #[must_use]
+    pub fn combined(&self) -> Namespace {
+        let mut result = self.squash();
+        result.put(NS_XML_PREFIX, NS_XML_URI);
+        result.put(NS_XMLNS_PREFIX, NS_XMLNS_URI);
+        result
+    }
+
     /// Returns an iterator over all mappings in this namespace stack.
+    ///
+    /// Each prefix is yielded once, with its innermost binding. Mappings of the topmost
+    /// namespace come first, then those of the namespace below it which aren't shadowed,
+    /// and so on; within a namespace they're sorted by prefix. Use `combined()` to get
+    /// all of them sorted by prefix.
     #[inline]
     #[must_use]
     pub fn iter(&self) -> NamespaceStackMappings<'_> {
@@ -433,19 +830,61 @@ impl<'a> Iterator for NamespaceStackMappings<'a> {
     }
 }
 
-impl<'a> IntoIterator for &'a NamespaceStack {
+impl<'a, S: Stack<Namespace>> IntoIterator for &'a NamespaceStack<S> {
     type Item = UriMapping<'a>;
     type IntoIter = NamespaceStackMappings<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
         NamespaceStackMappings {
-            namespaces: self.0.iter().rev(),
+            namespaces: self.0.as_slice().iter().rev(),
             current_namespace: None,
             used_keys: BTreeSet::new(),
         }
     }
 }
 
+/// Mappings of `incoming` which differ from what's in effect in `scope`, see `NamespaceStack::delta`
+pub(crate) fn delta<'a>(scope: &'a [Namespace], incoming: &'a Namespace) -> impl Iterator<Item = UriMapping<'a>> + 'a {
+    incoming.into_iter().filter(move |&(prefix, uri)| {
+        let current = scope.iter().rev().find_map(|ns| ns.get(prefix)).or(match prefix {
+            NS_NO_PREFIX => Some(NS_EMPTY_URI),
+            NS_XML_PREFIX => Some(NS_XML_URI),
+            NS_XMLNS_PREFIX => Some(NS_XMLNS_URI),
+            _ => None,
+        });
+        current != Some(uri)
+    })
+}
+
+/// `xmlns` attributes for `mappings`, see `NamespaceStack::to_declarations`
+fn declarations<'a>(mappings: impl Iterator<Item = UriMapping<'a>>) -> Vec<Attribute<'a>> {
+    let mut declarations: Vec<_> = mappings.filter_map(|(prefix, uri)| {
+        let name = match prefix {
+            _ if uri == NS_EMPTY_URI => return None,
+            NS_XML_PREFIX | NS_XMLNS_PREFIX => return None,
+            NS_NO_PREFIX => Name::qualified(NS_XMLNS_PREFIX, NS_XMLNS_URI, None),
+            prefix => Name::qualified(prefix, NS_XMLNS_URI, Some(NS_XMLNS_PREFIX)),
+        };
+        Some(Attribute::new(name, uri))
+    }).collect();
+    declarations.sort_by_key(|attr| attr.name.prefix.map(|_| attr.name.local_name));
+    declarations
+}
+
+/// The first prefix mapped to `uri`, unless it's the default namespace and there's another
+fn prefix_for_uri<'a>(mappings: impl Iterator<Item = UriMapping<'a>>, uri: &str) -> Option<&'a str> {
+    let mut default = None;
+    for (prefix, u) in mappings {
+        if u == uri {
+            if prefix != NS_NO_PREFIX {
+                return Some(prefix);
+            }
+            default = Some(prefix);
+        }
+    }
+    default
+}
+
 /// A type alias for a pair of `(prefix, uri)` values returned by namespace iterators.
 pub type UriMapping<'a> = (&'a str, &'a str);
 
@@ -457,7 +896,7 @@ impl<'a> Extend<UriMapping<'a>> for Namespace {
     }
 }
 
-impl<'a> Extend<UriMapping<'a>> for NamespaceStack {
+impl<'a, S: Stack<Namespace>> Extend<UriMapping<'a>> for NamespaceStack<S> {
     fn extend<T>(&mut self, iterable: T) where T: IntoIterator<Item=UriMapping<'a>> {
         for (prefix, uri) in iterable {
             self.put(prefix, uri);
@@ -503,12 +942,230 @@ impl<'a> Extend<UriMapping<'a>> for NamespaceStack {
 ///     nst.iter().collect::<Vec<_>>()
 /// );
 /// ```
-pub struct CheckedTarget<'a>(&'a mut NamespaceStack);
+pub struct CheckedTarget<'a, S = Vec<Namespace>>(&'a mut NamespaceStack<S>);
 
-impl<'a, 'b> Extend<UriMapping<'b>> for CheckedTarget<'a> {
+impl<'a, S: Stack<Namespace>> CheckedTarget<'a, S> {
+    /// Like `extend()`, but a mapping of a prefix which the topmost namespace maps to
+    /// a different URI is an error instead of being left out.
+    ///
+    /// Mappings before the conflicting one are added.
+    pub fn try_extend<'b, T>(&mut self, iterable: T) -> Result<(), ConflictingBinding>
+        where T: IntoIterator<Item = UriMapping<'b>>
+    {
+        for (prefix, uri) in iterable {
+            if let Some(top) = self.0.0.last() {
+                check_conflict(top, prefix, uri)?;
+            }
+            self.0.put_checked(prefix, uri);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'b, S: Stack<Namespace>> Extend<UriMapping<'b>> for CheckedTarget<'a, S> {
+    /// Mappings of prefixes which the topmost namespace maps already are left out, see
+    /// `try_extend()` to find out about them.
     fn extend<T>(&mut self, iterable: T) where T: IntoIterator<Item=UriMapping<'b>> {
         for (prefix, uri) in iterable {
             self.0.put_checked(prefix, uri);
         }
     }
 }
+
+/// A prefix bound to two different URIs in one namespace, see `NamespaceStack::push_checked`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConflictingBinding {
+    /// The prefix, `NS_NO_PREFIX` for the default namespace
+    pub prefix: String,
+    /// The URI it's bound to already
+    pub existing_uri: String,
+    /// The other URI
+    pub new_uri: String,
+}
+
+impl fmt::Display for ConflictingBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.prefix {
+            NS_NO_PREFIX => f.write_str("the default namespace")?,
+            prefix => write!(f, "prefix '{prefix}'")?,
+        }
+        write!(f, " is bound to both '{}' and '{}'", self.existing_uri, self.new_uri)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for ConflictingBinding {}
+
+fn check_conflict(ns: &Namespace, prefix: &str, uri: &str) -> Result<(), ConflictingBinding> {
+    match ns.get(prefix) {
+        Some(existing) if existing != uri => Err(ConflictingBinding {
+            prefix: prefix.into(),
+            existing_uri: existing.into(),
+            new_uri: uri.into(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::{Namespace, NamespaceStack, UriMapping, INLINE_MAPPINGS, NS_EMPTY_URI, NS_NO_PREFIX, NS_XMLNS_PREFIX, NS_XMLNS_URI, NS_XML_PREFIX, NS_XML_URI};
+
+    #[test]
+    fn inline_mappings() {
+        let mappings = [("d", "urn:d"), ("", "urn:x"), ("b", "urn:b"), ("f", "urn:f"), ("a", "urn:a"), ("c", "urn:c")];
+        assert!(mappings.len() > INLINE_MAPPINGS);
+        let mut ns = Namespace::empty();
+        let mut map = BTreeMap::new();
+        for (prefix, uri) in mappings {
+            assert!(ns.put(prefix, uri));
+            assert!(!ns.put(prefix, "urn:other"));
+            map.insert(String::from(prefix), String::from(uri));
+
+            let expected: Vec<UriMapping<'_>> = map.iter().map(|(p, u)| (&**p, &**u)).collect();
+            assert_eq!(ns.into_iter().collect::<Vec<_>>(), expected);
+            assert_eq!(ns.into_iter().rev().collect::<Vec<_>>(), expected.iter().rev().copied().collect::<Vec<_>>());
+            assert_eq!(ns.len(), map.len());
+            assert!(mappings.iter().all(|&(p, u)| ns.get(p) == map.contains_key(p).then_some(u)));
+            assert_eq!(ns, Namespace::from(map.clone()));
+            assert_eq!(alloc::format!("{ns:?}"), alloc::format!("Namespace({map:?})"));
+        }
+        assert_eq!(BTreeMap::from(ns.clone()), map);
+
+        let mut small = Namespace::empty();
+        small.extend([("b", "urn:b"), ("a", "urn:a"), ("b", "urn:other")]);
+        assert_eq!(small.force_put("a", "urn:a2").as_deref(), Some("urn:a"));
+        assert_eq!(small.force_put("c", "urn:c"), None);
+        assert_eq!(small.into_iter().collect::<Vec<_>>(), [("a", "urn:a2"), ("b", "urn:b"), ("c", "urn:c")]);
+        assert_eq!(BTreeMap::from(small.clone()).len(), 3);
+        assert_ne!(small, ns);
+    }
+
+    #[test]
+    fn in_scope_bindings() {
+        let mut nst = NamespaceStack::empty();
+        nst.push_empty();
+        nst.put("b", "urn:B");
+        nst.put("a", "urn:A");
+        nst.push_empty();
+        nst.put("c", "urn:C");
+        nst.put("a", "urn:A2");
+        nst.put(NS_NO_PREFIX, "urn:D");
+
+        assert_eq!(nst.iter().collect::<Vec<_>>(), [("", "urn:D"), ("a", "urn:A2"), ("c", "urn:C"), ("b", "urn:B")]);
+        assert_eq!(nst.combined().into_iter().collect::<Vec<_>>(), [
+            ("", "urn:D"), ("a", "urn:A2"), ("b", "urn:B"), ("c", "urn:C"),
+            (NS_XML_PREFIX, NS_XML_URI), (NS_XMLNS_PREFIX, NS_XMLNS_URI),
+        ]);
+        assert_eq!(NamespaceStack::default().combined(), NamespaceStack::default().squash());
+    }
+
+    #[test]
+    fn prefix_for_uri() {
+        let mut nst = NamespaceStack::default();
+        nst.push_empty();
+        nst.put(NS_NO_PREFIX, "urn:x");
+        nst.put("x", "urn:x");
+        nst.put("y", "urn:y");
+        assert_eq!(nst.peek().prefix_for_uri("urn:x"), Some("x"));
+        assert_eq!(nst.prefix_for_uri("urn:x"), Some("x"));
+        assert_eq!(nst.prefix_for_uri(NS_XML_URI), Some(NS_XML_PREFIX));
+
+        nst.push_empty();
+        nst.put("x", "urn:other");
+        nst.put("z", "urn:y");
+        assert_eq!(nst.prefix_for_uri("urn:x"), Some(NS_NO_PREFIX));
+        assert_eq!(nst.prefix_for_uri("urn:y"), Some("z"));
+        assert_eq!(nst.prefix_for_uri("urn:none"), None);
+        assert_eq!(nst.uri_for_prefix("x"), Some("urn:other"));
+        assert_eq!(nst.peek().uri_for_prefix("y"), None);
+
+        nst.pop();
+        assert_eq!(nst.peek().prefix_for_uri("urn:y"), Some("y"));
+    }
+
+    #[test]
+    fn delta() {
+        let mut incoming = Namespace::empty();
+        incoming.put(NS_NO_PREFIX, NS_EMPTY_URI);
+        incoming.put(NS_XML_PREFIX, NS_XML_URI);
+        incoming.put("a", "urn:a");
+        incoming.put("b", "urn:b");
+
+        let mut nst = NamespaceStack::empty();
+        assert_eq!(nst.delta(&incoming).collect::<Vec<_>>(), [("a", "urn:a"), ("b", "urn:b")]);
+
+        nst.push_empty();
+        nst.put(NS_NO_PREFIX, "urn:d");
+        nst.put("a", "urn:a");
+        nst.push_empty();
+        nst.put("b", "urn:other");
+        assert_eq!(nst.delta(&incoming).collect::<Vec<_>>(), [(NS_NO_PREFIX, NS_EMPTY_URI), ("b", "urn:b")]);
+        assert_eq!(nst.delta(&nst.squash()).count(), 0);
+    }
+
+    #[test]
+    fn default_namespace() {
+        let mut nst = NamespaceStack::default();
+        assert_eq!(nst.default_namespace(), None);
+        nst.push_empty();
+        nst.put(NS_NO_PREFIX, "urn:x");
+        assert_eq!(nst.default_namespace(), Some("urn:x"));
+        assert_eq!(nst.peek().default_namespace(), Some("urn:x"));
+        nst.push_empty();
+        nst.put(NS_NO_PREFIX, NS_EMPTY_URI);
+        assert_eq!(nst.default_namespace(), None);
+        assert_eq!(nst.peek().default_namespace(), None);
+        nst.pop();
+        assert_eq!(nst.default_namespace(), Some("urn:x"));
+    }
+
+    #[test]
+    fn declarations() {
+        let mut nst = NamespaceStack::default();
+        nst.push_empty().put(NS_NO_PREFIX, "urn:x");
+        nst.put("p", "urn:p");
+        nst.put("q", "urn:q");
+        nst.push_empty().put("p", "urn:inner");
+        nst.put("a", "urn:a");
+        let declarations = |attrs: Vec<crate::attribute::Attribute<'_>>| attrs.iter().map(|attr| alloc::format!("{} {}={}", attr.name.to_clark(), attr.name.repr_display(), attr.value)).collect::<Vec<_>>();
+        let expected = [
+            "{http://www.w3.org/2000/xmlns/}xmlns xmlns=urn:x",
+            "{http://www.w3.org/2000/xmlns/}a xmlns:a=urn:a",
+            "{http://www.w3.org/2000/xmlns/}p xmlns:p=urn:inner",
+            "{http://www.w3.org/2000/xmlns/}q xmlns:q=urn:q",
+        ];
+        assert_eq!(declarations(nst.to_declarations()), expected);
+        assert_eq!(declarations(nst.squash().to_declarations()), expected);
+
+        nst.put(NS_NO_PREFIX, NS_EMPTY_URI);
+        assert_eq!(declarations(nst.to_declarations()), expected[1..]);
+    }
+
+    #[test]
+    fn conflicting_bindings() {
+        use super::ConflictingBinding;
+
+        let mut nst = NamespaceStack::default();
+        nst.push_checked([("p", "urn:a"), ("p", "urn:a"), ("", "urn:x")]).unwrap();
+        assert_eq!(nst.get("p"), Some("urn:a"));
+        let err = nst.push_checked([("q", "urn:q"), ("p", "urn:a"), ("p", "urn:b")]).unwrap_err();
+        assert_eq!(err, ConflictingBinding { prefix: "p".into(), existing_uri: "urn:a".into(), new_uri: "urn:b".into() });
+        assert_eq!(alloc::format!("{err}"), "prefix 'p' is bound to both 'urn:a' and 'urn:b'");
+        assert_eq!(nst.get("q"), None);
+
+        // outer bindings can be shadowed, but not the topmost namespace's
+        nst.push_empty();
+        nst.checked_target().try_extend([("p", "urn:b"), ("", "urn:y")]).unwrap();
+        let err = nst.checked_target().try_extend([("", "urn:z")]).unwrap_err();
+        assert_eq!(alloc::format!("{err}"), "the default namespace is bound to both 'urn:y' and 'urn:z'");
+        assert_eq!((nst.get("p"), nst.get("")), (Some("urn:b"), Some("urn:y")));
+    }
+}
@@ -2,55 +2,113 @@
 //!
 //! The most important type in this module is `EventReader`, which provides an iterator
 //! view for events in XML document.
+extern crate alloc;
 
 use core::iter::FusedIterator;
 use core::result;
 
+use crate::attribute::RawAttribute;
+#[cfg(feature = "fixed-capacity")]
+use crate::capacity::Fixed;
+use crate::capacity::{Capacity, Unbounded};
 use crate::common::{Position, TextPosition};
+use crate::name::InternedName;
 
 pub use self::config::ParserConfig;
-pub use self::config::ParserConfig2;
-pub use self::error::{Error, ErrorKind};
+pub use self::cursor::Cursor;
+pub use self::config::{EntityAction, ParserConfig2, UnknownEntityHandler, WhitespaceHandling};
+pub use self::doctype::Doctype;
+pub use self::error::{Error, ErrorKind, Limit};
 pub use self::events::XmlEvent;
+pub use self::handler::{run, Control, Handler};
+pub use self::path::{MatchState, PathError, PathMatcher, Select};
+#[cfg(feature = "std")]
+pub use self::io::ReadSource;
+pub use self::source::{CharIterSource, IterSource, Source, SourceError, TryIterSource};
 
+use self::lexer::Bytes;
 use self::parser::PullParser;
 
 mod config;
+mod cursor;
+mod doctype;
 mod events;
-mod lexer;
+mod handler;
+#[cfg(feature = "std")]
+mod io;
+pub mod lexer;
 mod parser;
+mod path;
+mod source;
 mod error;
 
 
 /// A result type yielded by `XmlReader`.
 pub type Result<T, E = Error> = result::Result<T, E>;
 
-/// A wrapper around an Iterator which provides pull-based XML parsing.
-pub struct EventReader<'a, S: Iterator<Item = &'a u8>> {
+/// A wrapper around a `Source` of bytes, e.g. a slice or an iterator of bytes, which provides
+/// pull-based XML parsing. Chars which are decoded already are read from a `CharIterSource`.
+///
+/// It keeps its stacks and text buffer in `C`, on the heap by default, see `Capacity`.
+pub struct EventReader<S: Source, C: Capacity = Unbounded> {
     source: S,
-    parser: PullParser,
+    parser: PullParser<C>,
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> EventReader<'a, S> {
-    /// Creates a new reader from an Iterator.
+/// An `EventReader` which keeps its stacks and text buffer in arrays, with the `fixed-capacity`
+/// feature, see `capacity::Fixed` for what the sizes are. Reading a document which needs more
+/// fails with `ErrorKind::LimitExceeded`.
+///
+/// ```rust
+/// use xml_no_std::reader::{ErrorKind, FixedEventReader, Limit, ParserConfig2};
+///
+/// let doc = "<a x='1' y='2' z='3'/>";
+/// let mut reader = FixedEventReader::<_, 2, 4, 64>::new_with_capacity(doc.as_bytes(), ParserConfig2::new());
+/// reader.next().unwrap();
+/// assert_eq!(reader.next().unwrap_err().kind(), &ErrorKind::LimitExceeded(Limit::AttributeCapacity));
+/// ```
+#[cfg(feature = "fixed-capacity")]
+pub type FixedEventReader<S, const ATTRIBUTES: usize, const DEPTH: usize, const TEXT: usize> = EventReader<S, Fixed<ATTRIBUTES, DEPTH, TEXT>>;
+
+impl<S: Source> EventReader<S> {
+    /// Creates a new reader from a `Source`, e.g. `bytes.iter()`.
     #[inline]
-    pub fn new(source: S) -> EventReader<'a, S> {
+    pub fn new(source: S) -> EventReader<S> {
         EventReader::new_with_config(source, ParserConfig2::new())
     }
 
-    /// Creates a new reader with the provided configuration from an Iterator.
+    /// Creates a new reader with the provided configuration from a `Source`.
     #[inline]
-    pub fn new_with_config(source: S, config: impl Into<ParserConfig2>) -> EventReader<'a, S> {
-        EventReader { source, parser: PullParser::new(config) }
+    pub fn new_with_config(source: S, config: impl Into<ParserConfig2>) -> EventReader<S> {
+        EventReader::new_with_capacity(source, config)
     }
 
-    /// Pulls and returns next XML event from the Iterator.
+    /// Turns this reader into an iterator over the events inside elements which match,
+    /// see `PathMatcher`.
+    #[inline]
+    pub fn select(self, matcher: PathMatcher) -> Select<S> {
+        Select::new(self, matcher)
+    }
+}
+
+impl<S: Source, C: Capacity> EventReader<S, C> {
+    /// Creates a new reader with the provided configuration from a `Source`, which keeps its
+    /// stacks and text buffer in `C`, e.g. `FixedEventReader`.
+    #[inline]
+    pub fn new_with_capacity(source: S, config: impl Into<ParserConfig2>) -> EventReader<S, C> {
+        EventReader {
+            source,
+            parser: PullParser::new(config),
+        }
+    }
+
+    /// Pulls and returns next XML event from the `Source`.
     ///
     /// If returned event is `XmlEvent::Error` or `XmlEvent::EndDocument`, then
     /// further calls to this method will return this event again.
     #[inline]
     pub fn next(&mut self) -> Result<XmlEvent> {
-        self.parser.next(&mut self.source)
+        self.parser.next(&mut Bytes(&mut self.source))
     }
 
     /// Skips all XML events until the next end tag at the current level.
@@ -60,30 +118,155 @@ impl<'a, S: Iterator<Item = &'a u8>> EventReader<'a, S> {
     /// skip the entire XML subtree until the corresponding end tag.
     #[inline]
     pub fn skip(&mut self) -> Result<()> {
-        let mut depth = 1;
+        skip_subtree(|| self.next())
+    }
 
-        while depth > 0 {
-            match self.next()? {
-                XmlEvent::StartElement { .. } => depth += 1,
-                XmlEvent::EndElement { .. } => depth -= 1,
-                XmlEvent::EndDocument => unreachable!(),
-                _ => {}
-            }
-        }
+    /// Returns the attributes of the last `StartElement` as they're written in the document,
+    /// with `ParserConfig2::lazy_attributes` enabled. The reader keeps them until the next
+    /// start tag, whatever the source and its encoding.
+    ///
+    /// They're in the order of the event's `attributes`, which has empty values for them, and
+    /// which has the attributes added by `apply_attribute_defaults` after them. Namespace
+    /// declarations aren't among them.
+    ///
+    /// ```rust
+    /// use xml_no_std::reader::{EventReader, ParserConfig2, XmlEvent};
+    ///
+    /// let doc = "<item id='1' title='Tom &amp; Jerry'/>";
+    /// let mut reader = EventReader::new_with_config(doc.as_bytes(), ParserConfig2::new().lazy_attributes(true));
+    /// reader.next().unwrap();
+    /// if let XmlEvent::StartElement { attributes, .. } = reader.next().unwrap() {
+    ///     assert_eq!(attributes[1].value, "");
+    /// }
+    /// let title = reader.raw_attributes().find(|attr| attr.name == "title").unwrap();
+    /// assert_eq!(title.raw_value, "Tom &amp; Jerry");
+    /// assert_eq!(title.value().unwrap(), "Tom & Jerry");
+    /// ```
+    #[inline]
+    pub fn raw_attributes(&self) -> impl Iterator<Item = RawAttribute<'_>> + '_ {
+        self.parser.raw_attributes().map(|(name, raw_value)| RawAttribute { name, raw_value })
+    }
+
+    /// Returns the name of the last `StartElement` or `EndElement` event, with
+    /// `ParserConfig2::intern_names` enabled, which leaves the event's name empty.
+    ///
+    /// Every element with the same name gets the same `InternedName`, so keeping names of
+    /// many elements costs one allocation for each distinct name.
+    ///
+    /// ```rust
+    /// use xml_no_std::reader::{EventReader, ParserConfig2, XmlEvent};
+    ///
+    /// let doc = "<rows><row id='1'/><row id='2'/></rows>";
+    /// let mut reader = EventReader::new_with_config(doc.as_bytes(), ParserConfig2::new().intern_names(true));
+    /// let mut names = Vec::new();
+    /// loop {
+    ///     match reader.next().unwrap() {
+    ///         XmlEvent::StartElement { name, .. } => {
+    ///             assert!(name.local_name.is_empty());
+    ///             names.push(reader.element_name().unwrap().clone());
+    ///         },
+    ///         XmlEvent::EndDocument => break,
+    ///         _ => {},
+    ///     }
+    /// }
+    /// assert_eq!(names[1].local_name, "row");
+    /// assert!(names[1].ptr_eq(&names[2]));
+    /// assert_eq!(reader.attribute_names()[0].local_name, "id");
+    /// ```
+    #[inline]
+    pub fn element_name(&self) -> Option<&InternedName> {
+        self.parser.element_name()
+    }
 
-        Ok(())
+    /// Returns the names of the attributes of the last `StartElement`, with
+    /// `ParserConfig2::intern_names` enabled, in the order of the event's `attributes`.
+    #[inline]
+    pub fn attribute_names(&self) -> &[InternedName] {
+        self.parser.attribute_names()
+    }
+
+    /// Unescapes a raw attribute value, see `raw_attributes`.
+    ///
+    /// It expands references using the predefined entities, the configured extra entities
+    /// and the entities declared in the document so far, which gives the value the attribute
+    /// would have had without `ParserConfig2::lazy_attributes`.
+    #[inline]
+    pub fn unescape_attribute_value(&self, raw: &str) -> Result<alloc::string::String> {
+        self.parser.unescape_attribute(raw)
+    }
+
+    /// Returns the encoding the source is being decoded with.
+    ///
+    /// It's settled once the first event has been produced: it comes from the BOM or an
+    /// `<?xml encoding="…"?>` declaration, falling back to `ParserConfig2::override_encoding`
+    /// and UTF-8. Before that it may be `Encoding::Unknown` or another guess.
+    #[inline]
+    #[must_use]
+    pub fn encoding(&self) -> crate::Encoding {
+        self.parser.encoding()
+    }
+
+    /// Returns the byte range in the source of the last event produced by the reader.
+    ///
+    /// Offsets count bytes of the source as it was given, before decoding, so they index into
+    /// the original buffer whatever the encoding. A byte order mark isn't part of any span.
+    /// Markup events span from their `<` to their `>`. Text spans from the end of the
+    /// previous markup to the start of the next, so it includes the references in it, and
+    /// comments which are ignored while coalescing text.
+    /// For an empty element tag like `<a/>`, both `StartElement` and `EndElement` span the
+    /// whole tag. `EndDocument` and an implied `StartDocument` have empty spans, and so have
+    /// events which come from the replacement text of an entity, after its reference.
+    ///
+    /// ```rust
+    /// use xml_no_std::reader::{EventReader, XmlEvent};
+    ///
+    /// let source = "<a x='1'>b &amp; c<!-- d --></a>";
+    /// let mut reader = EventReader::from_str(source);
+    /// let mut spans = Vec::new();
+    /// loop {
+    ///     let event = reader.next().unwrap();
+    ///     spans.push(&source[reader.last_event_span()]);
+    ///     if event == XmlEvent::EndDocument {
+    ///         break;
+    ///     }
+    /// }
+    /// assert_eq!(spans, ["", "<a x='1'>", "b &amp; c<!-- d -->", "</a>", ""]);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn last_event_span(&self) -> core::ops::Range<usize> {
+        self.parser.last_event_span()
+    }
+
+    /// Returns the number of bytes read from the source so far.
+    ///
+    /// This is what `ParserConfig2::max_document_size` is compared against.
+    #[inline]
+    #[must_use]
+    pub fn bytes_read(&self) -> u64 {
+        self.parser.bytes_read()
+    }
+
+    /// Returns the `<!DOCTYPE>` declaration of the document.
+    ///
+    /// It's `None` unless `ParserConfig2::capture_doctype` is set and the reader has
+    /// produced an event past the declaration.
+    #[inline]
+    #[must_use]
+    pub fn doctype(&self) -> Option<&Doctype> {
+        self.parser.doctype()
     }
 
     pub fn source(&self) -> &S { &self.source }
     pub fn source_mut(&mut self) -> &mut S { &mut self.source }
 
-    /// Unwraps this `EventReader`, returning the underlying Iterator.
+    /// Unwraps this `EventReader`, returning the underlying `Source`.
     pub fn into_inner(self) -> S {
         self.source
     }
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> Position for EventReader<'a, S> {
+impl<S: Source, C: Capacity> Position for EventReader<S, C> {
     /// Returns the position of the last event produced by the reader.
     #[inline]
     fn position(&self) -> TextPosition {
@@ -91,28 +274,28 @@ impl<'a, S: Iterator<Item = &'a u8>> Position for EventReader<'a, S> {
     }
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> IntoIterator for EventReader<'a, S> {
+impl<S: Source, C: Capacity> IntoIterator for EventReader<S, C> {
     type Item = Result<XmlEvent>;
-    type IntoIter = Events<'a, S>;
+    type IntoIter = Events<S, C>;
 
-    fn into_iter(self) -> Events<'a, S> {
+    fn into_iter(self) -> Events<S, C> {
         Events { reader: self, finished: false }
     }
 }
 
-/// An iterator over XML events created from some type implementing `Iterator<Item = &u8>`.
+/// An iterator over XML events created from some type implementing `Source`.
 ///
 /// When the next event is `xml::event::Error` or `xml::event::EndDocument`, then
 /// it will be returned by the iterator once, and then it will stop producing events.
-pub struct Events<'a, S: Iterator<Item = &'a u8>> {
-    reader: EventReader<'a, S>,
+pub struct Events<S: Source, C: Capacity = Unbounded> {
+    reader: EventReader<S, C>,
     finished: bool,
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> Events<'a, S> {
+impl<S: Source, C: Capacity> Events<S, C> {
     /// Unwraps the iterator, returning the internal `EventReader`.
     #[inline]
-    pub fn into_inner(self) -> EventReader<'a, S> {
+    pub fn into_inner(self) -> EventReader<S, C> {
         self.reader
     }
 
@@ -121,10 +304,10 @@ impl<'a, S: Iterator<Item = &'a u8>> Events<'a, S> {
 
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> FusedIterator for Events<'a, S> {
+impl<S: Source, C: Capacity> FusedIterator for Events<S, C> {
 }
 
-impl<'a, S: Iterator<Item = &'a u8>> Iterator for Events<'a, S> {
+impl<S: Source, C: Capacity> Iterator for Events<S, C> {
     type Item = Result<XmlEvent>;
 
     #[inline]
@@ -141,11 +324,646 @@ impl<'a, S: Iterator<Item = &'a u8>> Iterator for Events<'a, S> {
     }
 }
 
-impl<'a> EventReader<'a, core::slice::Iter<'a, u8>> {
+fn skip_subtree(mut next: impl FnMut() -> Result<XmlEvent>) -> Result<()> {
+    let mut depth = 1;
+
+    while depth > 0 {
+        match next()? {
+            XmlEvent::StartElement { .. } => depth += 1,
+            XmlEvent::EndElement { .. } => depth -= 1,
+            XmlEvent::EndDocument => unreachable!(),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> EventReader<core::slice::Iter<'a, u8>> {
     /// A convenience method to create an `XmlReader` from a string slice.
     #[inline]
     #[must_use]
     pub fn from_str(source: &'a str) -> EventReader<core::slice::Iter<'a, u8>> {
         EventReader::new(source.as_bytes().into_iter())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use crate::common::Position;
+    use super::{CharIterSource, EntityAction, EventReader, ParserConfig, ParserConfig2, WhitespaceHandling, XmlEvent};
+
+    #[test]
+    fn interned_names() {
+        let doc = r#"<r xmlns="urn:d" xmlns:p="urn:p"><p:e a="1" p:b="2"/><e xml:space="preserve"> </e><p:e a='3' ></p:e ></r>"#;
+        let config = ParserConfig2::new().whitespace_handling(WhitespaceHandling::Drop);
+        let mut plain = EventReader::new_with_config(doc.as_bytes(), config.clone());
+        let mut interned = EventReader::new_with_config(doc.as_bytes(), config.intern_names(true));
+        let (mut names, mut attribute_names) = (alloc::vec::Vec::new(), alloc::vec::Vec::new());
+        loop {
+            match (plain.next().unwrap(), interned.next().unwrap()) {
+                (XmlEvent::StartElement { name, attributes, namespace }, XmlEvent::StartElement { name: empty, attributes: values, namespace: ns }) => {
+                    assert_eq!((&empty.local_name, &empty.prefix), (&alloc::string::String::new(), &None));
+                    assert_eq!(namespace, ns);
+                    assert_eq!(Some(&name), interned.element_name().map(|n| &**n));
+                    assert!(attributes.iter().map(|attr| &attr.name).eq(interned.attribute_names().iter().map(|n| &**n)));
+                    assert!(attributes.iter().map(|attr| &attr.value).eq(values.iter().map(|attr| &attr.value)));
+                    names.push(interned.element_name().unwrap().clone());
+                    attribute_names.extend(interned.attribute_names().iter().cloned());
+                },
+                (XmlEvent::EndElement { name }, XmlEvent::EndElement { name: empty }) => {
+                    assert!(empty.local_name.is_empty());
+                    assert_eq!(Some(&name), interned.element_name().map(|n| &**n));
+                },
+                (XmlEvent::EndDocument, XmlEvent::EndDocument) => break,
+                (event, other) => assert_eq!(event, other),
+            }
+        }
+        assert_eq!(names.len(), 4);
+        assert!(names[1].ptr_eq(&names[3]));
+        assert!(!names[1].ptr_eq(&names[2]));
+        assert_eq!(names[2].namespace.as_deref(), Some("urn:d"));
+        assert_eq!(attribute_names.len(), 4);
+        assert!(attribute_names[0].ptr_eq(&attribute_names[3]));
+    }
+
+    #[test]
+    fn lazy_attributes() {
+        let doc = r#"<!DOCTYPE a [<!ENTITY e "E"><!ATTLIST p:c f CDATA "&e;">]><a b = "x &amp; &#65;" xmlns:p="urn:&#97;"><p:c d='"&lt;&e;&gt;"'/></a>"#;
+        let config = ParserConfig2::new().lazy_attributes(true).apply_attribute_defaults(true);
+        let mut reader = EventReader::new_with_config(doc.as_bytes(), config);
+        let mut raw = alloc::vec::Vec::new();
+        loop {
+            match reader.next().unwrap() {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    if name.local_name == "c" {
+                        assert_eq!(Some("urn:a"), name.namespace.as_deref());
+                        assert_eq!(attributes.get("f").map(|f| &*f.value), Some("E"));
+                    }
+                    for (attr, raw_attr) in attributes.iter().zip(reader.raw_attributes()) {
+                        assert_eq!(attr.value, "");
+                        let unescaped = reader.unescape_attribute_value(raw_attr.raw_value).unwrap();
+                        raw.push((raw_attr.name.to_owned(), raw_attr.raw_value.to_owned(), unescaped));
+                    }
+                },
+                XmlEvent::EndDocument => break,
+                _ => {},
+            }
+        }
+        assert_eq!(raw, [
+            ("b".into(), "x &amp; &#65;".into(), "x & A".into()),
+            ("d".into(), "\"&lt;&e;&gt;\"".into(), "\"<E>\"".into()),
+        ]);
+        assert!(reader.unescape_attribute_value("&nope;").is_err());
+
+        // references aren't looked at until the value is
+        let doc = "<a b='&nope;' c='12345'/>";
+        let mut reader = EventReader::new_with_config(doc.as_bytes(), ParserConfig2::new().lazy_attributes(true));
+        reader.next().unwrap();
+        reader.next().unwrap();
+        let raw: alloc::vec::Vec<_> = reader.raw_attributes().collect();
+        assert_eq!(raw.len(), 2);
+        assert!(raw[0].value().is_err());
+        assert_eq!(raw[1].value().unwrap(), "12345");
+        let config = ParserConfig2::new().lazy_attributes(true).max_attribute_length(4);
+        assert!(EventReader::new_with_config(doc.as_bytes(), config).into_iter().any(|e| e.is_err()));
+
+        // the reader keeps them, whatever the source
+        let utf16: alloc::vec::Vec<u8> = "\u{feff}<a p:b='x&amp;\u{e9}' xmlns:p='urn:p'/>".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut reader = EventReader::new_with_config(utf16.iter(), ParserConfig2::new().lazy_attributes(true));
+        reader.next().unwrap();
+        reader.next().unwrap();
+        let raw: alloc::vec::Vec<_> = reader.raw_attributes().map(|attr| (attr.name, attr.raw_value)).collect();
+        assert_eq!(raw, [("p:b", "x&amp;\u{e9}")]);
+        let mut reader = EventReader::new_with_config(CharIterSource::new("<a b='&lt;'/>".chars()), ParserConfig2::new().lazy_attributes(true));
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.raw_attributes().next().unwrap().value().unwrap(), "<");
+    }
+
+    fn characters_and_attributes(config: ParserConfig2, doc: &str) -> super::Result<alloc::vec::Vec<alloc::string::String>> {
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), config);
+        let mut texts = alloc::vec::Vec::new();
+        loop {
+            match reader.next()? {
+                XmlEvent::StartElement { attributes, .. } => texts.extend(attributes.into_iter().map(|a| a.value)),
+                XmlEvent::Characters(s) => texts.push(s),
+                XmlEvent::EndDocument => return Ok(texts),
+                _ => {},
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_entities() {
+        let doc = r#"<a b="&x;&y;">&x;&y;<c d="&z;"/></a>"#;
+        let config = ParserConfig2::new().on_unknown_entity(|name| match name {
+            "x" => EntityAction::ReplaceWith("<X>".into()),
+            "y" => EntityAction::KeepReference,
+            _ => EntityAction::Error,
+        });
+        assert!(characters_and_attributes(config.clone(), doc).is_err());
+
+        let doc = r#"<a b="&x;&y;">&x;&y;</a>"#;
+        assert_eq!(characters_and_attributes(config.clone(), doc).unwrap(), ["<X>&y;", "<X>&y;"]);
+        let mut reader = EventReader::new_with_config(doc.as_bytes(), config.lazy_attributes(true));
+        reader.next().unwrap();
+        reader.next().unwrap();
+        let raw = reader.raw_attributes().next().unwrap().raw_value;
+        assert_eq!((raw, reader.unescape_attribute_value(raw).unwrap().as_str()), ("&x;&y;", "<X>&y;"));
+        assert!(characters_and_attributes(ParserConfig2::new(), doc).is_err());
+
+        let config = ParserConfig2::new().max_entity_expansion_length(2)
+            .on_unknown_entity(|_| EntityAction::ReplaceWith("long".into()));
+        assert!(characters_and_attributes(config, doc).is_err());
+    }
+
+    #[test]
+    fn character_references() {
+        let parse = |config: ParserConfig2, reference: &str| {
+            characters_and_attributes(config, &alloc::format!("<a b='{reference}'>{reference}</a>"))
+        };
+        assert_eq!(parse(ParserConfig2::new(), "&#65;&#x42;&#x0000043;&#x10FFFF;").unwrap(), ["ABC\u{10FFFF}"; 2]);
+        for bad in ["&#xFFFFFFFFFFFF;", "&#x;", "&#;", "&#xZZ;", "&#+65;", "&#xD800;", "&#x110000;", "&#0;", "&#4294967361;"] {
+            assert!(parse(ParserConfig2::new(), bad).is_err(), "{bad}");
+        }
+        let err = parse(ParserConfig2::new(), "&#xZZ;").unwrap_err();
+        assert!(alloc::string::ToString::to_string(&err).contains("&#xZZ;"), "{err}");
+        let long = alloc::format!("&#x{};", "0".repeat(100));
+        assert!(parse(ParserConfig2::new(), &long).is_err());
+
+        let replace = ParserConfig2::new().replace_unknown_entity_references(true);
+        assert_eq!(parse(replace.clone(), "&#xD800;&#xFFFFFFFFFFFF;").unwrap(), ["\u{fffd}\u{fffd}"; 2]);
+        assert!(parse(replace, "&#xZZ;").is_err());
+    }
+
+    #[test]
+    fn character_references_match_unescape() {
+        let alphabet = ['x', '0', '1', '9', 'a', 'F', 'Z', '+', '-', '#'];
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..5000 {
+            let mut reference = alloc::string::String::from("&#");
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            for i in 0..(state % 40) {
+                reference.push(alphabet[(state >> (i % 16 * 4)) as usize % alphabet.len()]);
+            }
+            reference.push(';');
+            let unescaped = crate::escape::unescape(&reference);
+            match characters_and_attributes(ParserConfig2::new(), &alloc::format!("<a b='{reference}'>{reference}</a>")) {
+                Ok(texts) => assert_eq!(texts, [unescaped.unwrap().into_owned(), texts[0].clone()], "{reference}"),
+                Err(_) => assert!(unescaped.is_err(), "{reference}"),
+            }
+        }
+    }
+
+    #[test]
+    fn coalesced_characters() {
+        let doc = "<a>x <!-- c --> &amp; <![CDATA[<y>]]> z <?pi?>w</a>";
+        let config = ParserConfig2::new().cdata_to_characters(true);
+        assert_eq!(characters_and_attributes(config.clone(), doc).unwrap(), ["x  & <y> z ", "w"]);
+
+        let texts = characters_and_attributes(config.clone().coalesce_characters(false), doc).unwrap();
+        assert_eq!(texts.concat(), "x  & <y> z w");
+        assert!(texts.len() > 2);
+
+        assert!(characters_and_attributes(config.max_data_length(8), doc).is_err());
+    }
+
+    #[test]
+    fn whitespace_handling() {
+        let doc = "<a> <b>&#32;</b>\n<c xml:space='pres&#101;rve'> <d> </d><e xml:space='default'> </e></c> x </a>";
+        let events = |handling, lazy| {
+            let config = ParserConfig2::new().whitespace_handling(handling).lazy_attributes(lazy);
+            let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), config);
+            let mut texts = alloc::vec::Vec::new();
+            loop {
+                match reader.next().unwrap() {
+                    XmlEvent::Characters(s) => texts.push(alloc::format!("c{s}")),
+                    XmlEvent::Whitespace(s) => texts.push(alloc::format!("w{s}")),
+                    XmlEvent::EndDocument => return texts,
+                    _ => {},
+                }
+            }
+        };
+        for lazy in [false, true] {
+            assert_eq!(events(WhitespaceHandling::Whitespace, lazy), ["w ", "w ", "w\n", "c ", "c ", "w ", "c x "]);
+            assert_eq!(events(WhitespaceHandling::Characters, lazy), ["c ", "c ", "c\n", "c ", "c ", "c ", "c x "]);
+            assert_eq!(events(WhitespaceHandling::Drop, lazy), ["c ", "c ", "c x "]);
+        }
+    }
+
+    #[test]
+    fn document_size() {
+        let doc = "<a><!-- a long comment --></a>";
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().max_document_size(Some(doc.len() as u64)));
+        while reader.next().unwrap() != XmlEvent::EndDocument {}
+        assert_eq!(reader.bytes_read(), doc.len() as u64);
+
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().max_document_size(Some(10)));
+        let err = loop {
+            match reader.next() {
+                Ok(_) => {},
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(err.kind(), &super::ErrorKind::LimitExceeded(super::Limit::DocumentSize));
+        assert_eq!(reader.bytes_read(), 11);
+    }
+
+    #[test]
+    fn encoding() {
+        let doc = b"<?xml version='1.0' encoding='ISO-8859-1'?><a>\xe9</a>";
+        let mut reader = EventReader::new(doc.iter());
+        reader.next().unwrap();
+        assert_eq!(reader.encoding(), crate::Encoding::Latin1);
+
+        let doc = b"<a>\xff</a>";
+        let mut reader = EventReader::new_with_config(doc.iter(), ParserConfig::new().override_encoding(Some(crate::Encoding::Utf8)));
+        assert_eq!(reader.next().unwrap(), XmlEvent::StartDocument {
+            version: crate::common::XmlVersion::Version10, encoding: "UTF-8".into(), standalone: None,
+        });
+        let err = loop {
+            if let Err(e) = reader.next() {
+                break e;
+            }
+        };
+        assert_eq!(err.encoding(), Some(crate::Encoding::Utf8));
+        assert_eq!(reader.encoding(), crate::Encoding::Utf8);
+    }
+
+    #[test]
+    fn char_source() {
+        let doc = "\u{feff}<?xml version='1.0' encoding='UTF-16'?>\n<a>\n  <b>ł</b></a>";
+        let mut reader = EventReader::new(CharIterSource::new(doc.chars()));
+        assert!(matches!(reader.next().unwrap(), XmlEvent::StartDocument { .. }));
+        assert!(matches!(reader.next().unwrap(), XmlEvent::StartElement { .. }));
+        assert_eq!(reader.next().unwrap(), XmlEvent::Whitespace("\n  ".into()));
+        assert!(matches!(reader.next().unwrap(), XmlEvent::StartElement { .. }));
+        assert_eq!(reader.position(), crate::common::TextPosition { row: 2, column: 2 });
+        assert_eq!(reader.next().unwrap(), XmlEvent::Characters("ł".into()));
+        assert_eq!(reader.encoding(), crate::Encoding::Utf8);
+        // up to the `</` after the text, with the U+FEFF
+        assert_eq!(reader.bytes_read(), doc.len() as u64 - 6);
+        assert_eq!(reader.into_iter().filter(|e| e.is_ok()).count(), 3);
+    }
+
+    #[test]
+    fn utf16_surrogate_pairs() {
+        use crate::common::TextPosition;
+
+        let encode = |doc: &str, bom: bool, big_endian: bool| -> alloc::vec::Vec<u8> {
+            let bom = if bom { "\u{feff}" } else { "" };
+            alloc::format!("{bom}{doc}").encode_utf16()
+                .flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() })
+                .collect()
+        };
+        // names may have chars beyond the BMP too, `#x10000-#xEFFFF` are `NameStartChar`s
+        let doc = "<a b='\u{1F600}\u{20000}'>\u{1F600}x\u{1F600}<\u{1F600}/></a>";
+        for (bom, big_endian) in [(true, false), (true, true), (false, false), (false, true)] {
+            let bytes = encode(doc, bom, big_endian);
+            let config = ParserConfig::new().override_encoding((!bom).then(|| crate::Encoding::Utf16));
+            let mut reader = EventReader::new_with_config(bytes.iter(), config);
+            reader.next().unwrap();
+            match reader.next().unwrap() {
+                XmlEvent::StartElement { attributes, .. } => assert_eq!(attributes[0].value, "\u{1F600}\u{20000}"),
+                e => panic!("{e:?}"),
+            }
+            assert_eq!(reader.next().unwrap(), XmlEvent::Characters("\u{1F600}x\u{1F600}".into()));
+            match reader.next().unwrap() {
+                XmlEvent::StartElement { name, .. } => assert_eq!(name.local_name, "\u{1F600}"),
+                e => panic!("{e:?}"),
+            }
+            assert_eq!(reader.into_iter().map(Result::unwrap).count(), 3);
+        }
+
+        // unpaired surrogates are errors at their position
+        for (unit, column) in [(0xD83D, 4), (0xDE00, 4)] {
+            for big_endian in [false, true] {
+                let mut bytes = encode("<a>x", true, big_endian);
+                bytes.extend(if big_endian { u16::to_be_bytes(unit) } else { u16::to_le_bytes(unit) });
+                bytes.extend(encode("y</a>", false, big_endian));
+                let err = EventReader::new(bytes.iter()).into_iter().find_map(Result::err).unwrap();
+                assert_eq!(err.position(), TextPosition { row: 0, column }, "{err}");
+                assert!(matches!(err.kind(), super::ErrorKind::Io(_)), "{err}");
+            }
+        }
+    }
+
+    #[test]
+    fn text_runs() {
+        use super::IterSource;
+
+        // text read from a slice is taken in runs, and from `IterSource` a char at a time
+        fn read<S: super::Source>(source: S, config: ParserConfig2) -> alloc::vec::Vec<(super::Result<XmlEvent>, crate::common::TextPosition, core::ops::Range<usize>, u64)> {
+            let mut reader = EventReader::new_with_config(source, config);
+            let mut events = alloc::vec::Vec::new();
+            loop {
+                let event = reader.next();
+                let done = !matches!(event, Ok(ref e) if *e != XmlEvent::EndDocument);
+                events.push((event, reader.position(), reader.last_event_span(), reader.bytes_read()));
+                if done {
+                    return events;
+                }
+            }
+        }
+
+        let docs: [&[u8]; 8] = [
+            "<a>Hello, world! x > y; \"q\" 'a' = b/c\n  second line ł€😀 tab\there\r\n<b>x</b>y</a>".as_bytes(),
+            b"<a>x &amp; y ]] z ]]> w</a>",
+            "<a>text \u{FFFE} more</a>".as_bytes(),
+            b"<a>text \x01 more</a>",
+            b"<a>text \x7f more</a>",
+            "<?xml version='1.1'?><a>x \u{80} y</a>".as_bytes(),
+            b"<a>abc\xff def</a>",
+            b"<?xml version='1.0' encoding='ISO-8859-1'?><a>caf\xe9 au lait</a>",
+        ];
+        for doc in docs {
+            for config in [
+                ParserConfig2::new(),
+                ParserConfig2::new().trim_whitespace(true),
+                ParserConfig2::new().max_data_length(6),
+                ParserConfig2::new().max_document_size(Some(12)),
+            ] {
+                let runs = read(doc, config.clone());
+                assert_eq!(runs, read(IterSource(doc.iter()), config), "{}", alloc::string::String::from_utf8_lossy(doc));
+            }
+        }
+
+        let mut reader = EventReader::new(&b"<a>one\ntwo three</a>"[..]);
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.next().unwrap(), XmlEvent::Characters("one\ntwo three".into()));
+        assert_eq!(reader.last_event_span(), 3..16);
+        reader.next().unwrap();
+        assert_eq!((reader.position().row, reader.position().column), (1, 9));
+    }
+
+    #[test]
+    fn characters_chunks() {
+        use super::{IterSource, Source};
+        use alloc::string::String;
+        use alloc::vec::Vec;
+
+        // text events, their column and their source text
+        fn chunks(source: impl Source, config: ParserConfig2, doc: &str) -> super::Result<Vec<(String, u64, &str)>> {
+            let mut reader = EventReader::new_with_config(source, config);
+            let mut chunks = Vec::new();
+            loop {
+                match reader.next()? {
+                    XmlEvent::Characters(s) => chunks.push((s, reader.position().column, &doc[reader.last_event_span()])),
+                    XmlEvent::Whitespace(s) => chunks.push((alloc::format!("ws{s}"), reader.position().column, &doc[reader.last_event_span()])),
+                    XmlEvent::EndDocument => return Ok(chunks),
+                    _ => {},
+                }
+            }
+        }
+        let read = |doc: &'static str, config: ParserConfig2| {
+            let runs = chunks(doc.as_bytes(), config.clone(), doc);
+            assert_eq!(runs, chunks(IterSource(doc.as_bytes().iter()), config, doc), "{doc}");
+            runs
+        };
+        let texts = |chunks: Vec<(String, u64, &str)>| chunks.into_iter().map(|(s, _, _)| s).collect::<Vec<_>>();
+
+        let config = ParserConfig2::new().characters_chunk_size(Some(8));
+        assert_eq!(read("<a>Hello, wonderful world!</a>", config.clone()).unwrap(), [
+            ("Hello, w".into(), 3, "Hello, w"), ("onderful".into(), 11, "onderful"), (" world!".into(), 19, " world!"),
+        ]);
+        // at char boundaries, and not inside references
+        assert_eq!(read("<a>łłłłł😀😀</a>", config.clone().characters_chunk_size(Some(5))).unwrap(), [
+            ("łł".into(), 3, "łł"), ("łł".into(), 5, "łł"), ("ł".into(), 7, "ł"), ("😀".into(), 8, "😀"), ("😀".into(), 9, "😀"),
+        ]);
+        assert_eq!(read("<a>abcdef&amp;gh&#x1F600;ij</a>", config.clone().characters_chunk_size(Some(7))).unwrap(), [
+            ("abcdef&".into(), 3, "abcdef&amp;"), ("gh😀i".into(), 14, "gh&#x1F600;i"), ("j".into(), 26, "j"),
+        ]);
+        assert_eq!(read("<a>abcdefg&amp;</a>", config.clone().characters_chunk_size(Some(7))).unwrap(), [
+            ("abcdefg".into(), 3, "abcdefg"), ("&".into(), 10, "&amp;"),
+        ]);
+        let doc = "<!DOCTYPE a [<!ENTITY e 'entity text'>]><a>abcd&e;xyz<b/></a>";
+        assert_eq!(texts(read(doc, config.clone().characters_chunk_size(Some(6))).unwrap()), ["abcd", "entity text", "xyz"]);
+        // whitespace isn't split, nor is the text before its first other char
+        assert_eq!(texts(read("<a>     </a>", config.clone().characters_chunk_size(Some(2))).unwrap()), ["ws     "]);
+        assert_eq!(texts(read("<a>   abc</a>", config.clone().characters_chunk_size(Some(2))).unwrap()), ["   a", "bc"]);
+        let trimmed = config.clone().characters_chunk_size(Some(3)).trim_whitespace(true);
+        assert_eq!(texts(read("<a>  ab  cd  </a>", trimmed.clone()).unwrap()), ["ab ", " cd"]);
+        assert_eq!(texts(read("<a> &#32;abc  d </a>", trimmed).unwrap()), ["ab", "c  ", "d"]);
+        // no coalescing
+        let coalescing = ParserConfig2::new().cdata_to_characters(true);
+        assert_eq!(texts(read("<a>x<!--c-->y<![CDATA[z]]></a>", coalescing.clone()).unwrap()), ["xyz"]);
+        assert_eq!(texts(read("<a>x<!--c-->y<![CDATA[z]]></a>", coalescing.characters_chunk_size(Some(8))).unwrap()), ["x", "y", "z"]);
+        // the limit is for each chunk
+        let doc = "<a>a longer text than the limit</a>";
+        assert!(read(doc, ParserConfig2::new().max_data_length(8)).is_err());
+        assert_eq!(read(doc, config.clone().max_data_length(8)).unwrap().len(), 4);
+        assert!(read(doc, config.max_data_length(4)).is_err());
+    }
+
+    #[test]
+    fn event_spans() {
+        let doc = "<!DOCTYPE a [<!ENTITY e '<b/>'>]>\n<!-- c --><a x='1'>t<!--c-->u<?p?>\n  <b/><![CDATA[v]]>&e;</a>\n";
+        let mut reader = EventReader::new(doc.as_bytes().iter());
+        let mut spans = alloc::vec::Vec::new();
+        while reader.next().unwrap() != XmlEvent::EndDocument {
+            spans.push(&doc[reader.last_event_span()]);
+        }
+        assert_eq!(spans, ["", "<a x='1'>", "t<!--c-->u", "<?p?>", "\n  ", "<b/>", "<b/>", "<![CDATA[v]]>", "", "", "</a>"]);
+        assert_eq!(reader.last_event_span(), doc.len()..doc.len());
+
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().ignore_comments(false));
+        reader.next().unwrap();
+        assert_eq!(reader.next().unwrap(), XmlEvent::Comment(" c ".into()));
+        assert_eq!(&doc[reader.last_event_span()], "<!-- c -->");
+
+        // offsets are in bytes of the source, not of the decoded text, BOM included
+        let doc: alloc::vec::Vec<u8> = "\u{feff}<a>ł</a>".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut reader = EventReader::new(doc.iter());
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.last_event_span(), 2..8);
+        assert_eq!(reader.next().unwrap(), XmlEvent::Characters("ł".into()));
+        assert_eq!(reader.last_event_span(), 8..10);
+
+        let mut reader = EventReader::new(CharIterSource::new("<a>ł</a>".chars()));
+        reader.next().unwrap();
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.last_event_span(), 3..5);
+    }
+
+    #[test]
+    fn attribute_defaults() {
+        let doc = r#"<!DOCTYPE r [
+            <!ATTLIST item currency CDATA "USD" id ID #IMPLIED kind (a|b) 'a'
+                           xmlns:p CDATA #FIXED "urn:p" p:x CDATA "&lt;x>">
+            <!ATTLIST item currency CDATA "EUR" tags NMTOKENS '  x  y '>
+        ]><r><item id="1"/><item currency="PLN" kind=" b " id="  &#x20;2&#9; "/></r>"#;
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().apply_attribute_defaults(true));
+        let mut items = alloc::vec::Vec::new();
+        loop {
+            match reader.next().unwrap() {
+                XmlEvent::StartElement { name, attributes, .. } if name.local_name == "item" => {
+                    items.push(attributes.iter().map(|a| {
+                        alloc::format!("{}={}", a.name, a.value)
+                    }).collect::<alloc::vec::Vec<_>>());
+                },
+                XmlEvent::EndDocument => break,
+                _ => {},
+            }
+        }
+        assert_eq!(items, [
+            ["id=1", "currency=USD", "kind=a", "{urn:p}p:x=<x>", "tags=x y"],
+            ["currency=PLN", "kind=b", "id=2\t", "{urn:p}p:x=<x>", "tags=x y"],
+        ]);
+
+        assert_eq!(characters_and_attributes(ParserConfig2::new(), doc).unwrap(), ["1", "PLN", " b ", "   2\t "]);
+    }
+
+    #[test]
+    fn capture_doctype() {
+        let subset = "\n  <!ENTITY % decls '<!ENTITY e \"]>\">'> %decls;\n  <!-- ]> --> <!ENTITY f \"]\">\n";
+        let doc = alloc::format!("<!DOCTYPE r PUBLIC '-//x//y' \"r.dtd\" [{subset}]><r>&e;&f;</r>");
+        let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().capture_doctype(true));
+        assert_eq!(reader.next().unwrap(), XmlEvent::StartDocument {
+            version: crate::common::XmlVersion::Version10, encoding: "UTF-8".into(), standalone: None,
+        });
+        assert!(reader.doctype().is_none());
+        reader.next().unwrap();
+        let doctype = reader.doctype().unwrap();
+        assert_eq!(doctype.name, "r");
+        assert_eq!(doctype.public_id.as_deref(), Some("-//x//y"));
+        assert_eq!(doctype.system_id.as_deref(), Some("r.dtd"));
+        assert_eq!(doctype.internal_subset.as_deref(), Some(subset));
+        assert_eq!(reader.next().unwrap(), XmlEvent::Characters("]>]".into()));
+
+        let mut reader = EventReader::new(doc.as_bytes().iter());
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert!(reader.doctype().is_none());
+    }
+
+    #[test]
+    fn invalid_qualified_names() {
+        for (doc, msg) in [
+            ("<a:b:c/>", "1:7 Qualified name is invalid: a:b:c (unexpected colon at index 3)"),
+            ("<:a/>", "1:4 Qualified name is invalid: :a (empty name or name part at index 0)"),
+            ("<a: b='1'/>", "1:4 Qualified name is invalid: a: (empty name or name part at index 2)"),
+            ("<a x:-y='1'/>", "1:8 Qualified name is invalid: x:-y (a name can't start with '-', at index 2)"),
+            ("<a></a:b:c>", "1:11 Qualified name is invalid: a:b:c (unexpected colon at index 3)"),
+        ] {
+            let err = EventReader::new(doc.as_bytes().iter()).into_iter().find_map(super::Result::err).unwrap();
+            assert_eq!(alloc::format!("{err}"), msg, "{doc}");
+        }
+    }
+
+    #[test]
+    fn not_well_formed() {
+        for doc in [
+            "<? pi?><a/>",
+            "<!DOCTYPE a [<?xml version='1.0'?>]><a/>",
+            "<!DOCTYPE a [<?XmL ?>]><a/>",
+            "<!DOCTYPE a [<? pi?>]><a/>",
+            "<!DOCTYPE a [<?\u{d7} ?>]><a/>",
+            "<!DOCTYPEa><a/>",
+            "<!DOCTYPE [<!ENTITY e 'x'>] a><a/>",
+            "<!DOCTYPE a [<!ENTITY e '&#60;'>]><a x='&e;'/>",
+            "<?xml version='1.1'?><a><!-- \u{b} --></a>",
+            "<?xml version='1.1'?><a><?pi \u{86}?></a>",
+            "<?xml version='1.1'?><a><![CDATA[\u{84}]]></a>",
+        ] {
+            assert!(EventReader::from_str(doc).into_iter().any(|e| e.is_err()), "{doc:?}");
+        }
+        for doc in [
+            "<!DOCTYPE a [<?pi data?><?xml-stylesheet href='s'?>]><a/>",
+            "<!DOCTYPE a [<!ENTITY e '&amp;'>]><a x='&e;'/>",
+            "<?xml version='1.1'?><a><!-- \u{85} --></a>",
+        ] {
+            assert!(EventReader::from_str(doc).into_iter().all(|e| e.is_ok()), "{doc:?}");
+        }
+    }
+
+    #[test]
+    fn reserved_namespaces() {
+        for (doc, msg) in [
+            (r#"<a xmlns:xml="urn:x"/>"#, "Default XMLNS prefix cannot be rebound to another value"),
+            (r#"<a xmlns:xmlns="http://www.w3.org/2000/xmlns/"/>"#, "Cannot redefine XMLNS prefix"),
+            (r#"<a xmlns:x="http://www.w3.org/XML/1998/namespace"/>"#, "Reserved namespace binding: http://www.w3.org/XML/1998/namespace can only be bound to the xml prefix"),
+            (r#"<a xmlns:x="http://www.w3.org/2000/xmlns/"/>"#, "Reserved namespace binding: http://www.w3.org/2000/xmlns/ can't be bound to a prefix"),
+            (r#"<a xmlns="http://www.w3.org/2000/xmlns/"/>"#, "Namespace 'http://www.w3.org/2000/xmlns/' cannot be default"),
+        ] {
+            let err = EventReader::new(doc.as_bytes().iter()).into_iter().find_map(super::Result::err).unwrap();
+            assert_eq!(err.msg(), msg, "{doc}");
+        }
+
+        let doc = r#"<a xmlns:xml="http://www.w3.org/XML/1998/namespace" xml:lang="en"/>"#;
+        let lang = EventReader::new(doc.as_bytes().iter()).into_iter().find_map(|e| match e.unwrap() {
+            XmlEvent::StartElement { attributes, .. } => Some(attributes[0].name.clone()),
+            _ => None,
+        });
+        assert_eq!(lang, Some(crate::name::XML_LANG.to_owned()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let doc = r#"<?xml version="1.1" standalone="yes"?><p:a xmlns:p="urn:p" xmlns="urn:x" p:b="1" c="2"><?pi data?>text<![CDATA[cdata]]><!--c--></p:a>"#;
+        let config = ParserConfig::new().cdata_to_characters(false).ignore_comments(false);
+        let events: alloc::vec::Vec<XmlEvent> = EventReader::new_with_config(doc.as_bytes().iter(), config).into_iter().map(Result::unwrap).collect();
+        let bytes = postcard::to_allocvec(&events).unwrap();
+        assert_eq!(postcard::from_bytes::<alloc::vec::Vec<XmlEvent>>(&bytes).unwrap(), events);
+    }
+
+    #[cfg(feature = "fixed-capacity")]
+    #[test]
+    fn fixed_capacity() {
+        use super::{ErrorKind, FixedEventReader, Limit};
+
+        let doc = r#"<!DOCTYPE r [<!ENTITY e "&#60;b>entity</b>">]><r xmlns="urn:d" xmlns:p="urn:p"><p:e a="1" p:b="&amp;"/><e xml:space="preserve"> <p:e> &e; text </p:e> </e><!--c--><![CDATA[<cdata>]]>&lt;end&gt;</r>"#;
+        for config in [
+            ParserConfig2::new(),
+            ParserConfig2::new().intern_names(true).lazy_attributes(true),
+            ParserConfig2::new().characters_chunk_size(Some(4)).whitespace_handling(WhitespaceHandling::Drop),
+        ] {
+            let fixed = FixedEventReader::<_, 2, 5, 16>::new_with_capacity(doc.as_bytes(), config.clone());
+            let events: alloc::vec::Vec<_> = fixed.into_iter().collect();
+            let expected: alloc::vec::Vec<_> = EventReader::new_with_config(doc.as_bytes(), config).into_iter().collect();
+            assert_eq!(events, expected);
+        }
+
+        for (doc, limit) in [
+            ("<a x='1' y='2' z='3'/>", Limit::AttributeCapacity),
+            ("<a><b><c><d/></c></b></a>", Limit::DepthCapacity),
+            ("<a>more than sixteen bytes</a>", Limit::TextCapacity),
+            ("<a>sixteen bytes &amp; then some</a>", Limit::TextCapacity),
+            ("<a x='more than sixteen bytes'/>", Limit::TextCapacity),
+            ("<more-than-sixteen-bytes/>", Limit::TextCapacity),
+        ] {
+            let mut reader = FixedEventReader::<_, 2, 4, 16>::new_with_capacity(doc.as_bytes(), ParserConfig2::new());
+            let err = loop {
+                match reader.next() {
+                    Ok(XmlEvent::EndDocument) => panic!("{doc}"),
+                    Ok(_) => {},
+                    Err(err) => break err,
+                }
+            };
+            assert_eq!(err.kind(), &ErrorKind::LimitExceeded(limit), "{doc}");
+            assert_eq!(reader.next().unwrap_err(), err);
+        }
+    }
+
+    #[test]
+    fn attributes_in_document_order() {
+        let doc = r#"<a z="1" xmlns:p="urn:p" p:y="2" b="3" xmlns:q="urn:p" q:x="4"/>"#;
+        let attributes = EventReader::new(doc.as_bytes().iter()).into_iter().find_map(|e| match e.unwrap() {
+            XmlEvent::StartElement { attributes, .. } => Some(attributes),
+            _ => None,
+        }).unwrap();
+        let names: alloc::vec::Vec<_> = attributes.iter().map(|a| a.name.to_repr()).collect();
+        assert_eq!(names, ["z", "p:y", "b", "q:x"]);
+        assert_eq!(attributes.get("b").unwrap().value, "3");
+        assert_eq!(attributes.get_ns("urn:p", "x").unwrap().value, "4");
+        assert_eq!(attributes.get_qname(Some("p"), "y").unwrap().value, "2");
+        assert!(attributes.get("y").is_none() && attributes.get_ns("urn:p", "b").is_none() && attributes.get_qname(None, "x").is_none());
+    }
+}
@@ -3,9 +3,10 @@ extern crate alloc;
 
 use alloc::collections::BTreeMap;
 use alloc::string::String;
+use core::fmt;
 
-use crate::reader::EventReader;
-use crate::util::Encoding;
+use crate::reader::{EventReader, Source};
+use crate::decode::Encoding;
 
 /// Limits to defend from billion laughs attack
 const DEFAULT_MAX_ENTITY_EXPANSION_LENGTH: usize = 1_000_000;
@@ -60,7 +61,13 @@ pub struct ParserConfig {
     ///
     /// Multiple sequential `Characters` events are only possible if either
     /// `cdata_to_characters` or `ignore_comments` are set. Otherwise character
-    /// events will always be separated by other events.
+    /// events will always be separated by other events. With `cdata_to_characters`,
+    /// text and CDATA sections next to each other become one `Characters` event,
+    /// and with `ignore_comments` the text on both sides of a comment is merged too.
+    /// Processing instructions always end the text.
+    ///
+    /// The merged text is subject to `ParserConfig2::max_data_length`.
+    /// It's turned off by `ParserConfig2::characters_chunk_size`.
     pub coalesce_characters: bool,
 
     /// A map of extra entities recognized by the parser. Default is an empty map.
@@ -85,9 +92,10 @@ pub struct ParserConfig {
 
     /// Whether or not non-unicode entity references get replaced with the replacement character
     ///
-    /// When true, any decimal or hexadecimal character reference that cannot be converted from a
-    /// u32 to a char using [std::char::from_u32](https://doc.rust-lang.org/std/char/fn.from_u32.html)
-    /// will be converted into the unicode REPLACEMENT CHARACTER (U+FFFD).
+    /// When true, any decimal or hexadecimal character reference to a surrogate, to a code point
+    /// above U+10FFFF or to a character not allowed in the document will be converted into the
+    /// unicode REPLACEMENT CHARACTER (U+FFFD). References which aren't numbers, like `&#xZZ;`,
+    /// are still errors.
     pub replace_unknown_entity_references: bool,
 
     /// Whether or not whitespace at the root level of the document is ignored. Default is true.
@@ -147,7 +155,7 @@ impl ParserConfig {
     /// This method is exactly equivalent to calling `EventReader::new_with_config()` with
     /// this configuration object.
     #[inline]
-    pub fn create_reader<'a, S: Iterator<Item = &'a u8>>(self, source: S) -> EventReader<'a, S> {
+    pub fn create_reader<S: Source>(self, source: S) -> EventReader<S> {
         EventReader::new_with_config(source, self)
     }
 
@@ -224,6 +232,160 @@ pub struct ParserConfig2 {
 
     /// Maximum length of strings reprsenting characters, comments, and processing instructions
     pub max_data_length: usize,
+
+    /// Keep attribute values as they're written rather than unescape them into every event.
+    /// Default is false.
+    ///
+    /// When true, the attributes in `StartElement` events have empty values, and
+    /// `EventReader::raw_attributes()` returns them as they're written, with references, from
+    /// a buffer the reader reuses for every start tag. `RawAttribute::value()` unescapes one
+    /// on demand, so attributes of elements that are skipped cost neither an allocation nor
+    /// unescaping.
+    ///
+    /// Namespace declarations (`xmlns` attributes) are always unescaped, since they are
+    /// needed to resolve names, and so are defaults of `apply_attribute_defaults`, which
+    /// aren't written in the element. `xml:space` is still looked at for `whitespace_handling`.
+    pub lazy_attributes: bool,
+
+    /// Decides what to do with references to entities that are neither predefined,
+    /// in `extra_entities`, nor declared in the DTD. Default is `None`, which is an error.
+    ///
+    /// The function gets the entity name, without `&` and `;`. It's used both in text
+    /// and in attribute values. Replacement text is not parsed as markup, and it may
+    /// not be longer than `max_entity_expansion_length`.
+    pub on_unknown_entity: Option<UnknownEntityHandler>,
+
+    /// Add attributes with default values declared with `<!ATTLIST>` in the internal DTD subset.
+    /// Default is false.
+    ///
+    /// When true, elements which don't have a declared attribute get it with its default value,
+    /// as if it was written in the document. Declarations without a default (`#IMPLIED`,
+    /// `#REQUIRED`) are ignored. Defaults are namespace-processed like other attributes,
+    /// so defaulted `xmlns` declarations bind their prefixes too.
+    ///
+    /// Declared types are applied too: the values of attributes which aren't `CDATA`, like `ID`
+    /// or `NMTOKENS`, lose leading and trailing spaces and have runs of spaces collapsed to one,
+    /// as XML requires, except for the raw values kept by `lazy_attributes`.
+    pub apply_attribute_defaults: bool,
+
+    /// How to report text that is nothing but whitespace. Default is `None`, which leaves it
+    /// to `trim_whitespace` and `whitespace_to_characters`.
+    ///
+    /// Whitespace from character references, like `&#32;`, counts as whitespace too.
+    /// Inside elements with `xml:space="preserve"` whitespace is always reported as `Characters`.
+    pub whitespace_handling: Option<WhitespaceHandling>,
+
+    /// Abort with `ErrorKind::LimitExceeded` after reading more than this many bytes of the source.
+    /// Default is `None`, no limit.
+    ///
+    /// Every byte counts, including markup, skipped comments and the DTD.
+    /// `EventReader::bytes_read()` tells how much has been read so far.
+    pub max_document_size: Option<u64>,
+
+    /// Abort with `ErrorKind::LimitExceeded` at a start tag which would nest elements deeper
+    /// than this. Default is `None`, no limit. The root element is at depth 1.
+    pub max_element_depth: Option<usize>,
+
+    /// Keep the `<!DOCTYPE>` declaration, available from `EventReader::doctype()`
+    /// after the parser has read past it. Default is false.
+    ///
+    /// The internal subset is kept exactly as written, with parameter entity references
+    /// unexpanded, and isn't limited by what the parser understands of the DTD.
+    pub capture_doctype: bool,
+
+    /// Deliver text in `Characters` events of at most this many bytes. Default is `None`,
+    /// which delivers all the text between two pieces of markup in one event.
+    ///
+    /// Longer text is split into consecutive `Characters` events, so a consumer has to expect
+    /// several in a row, and concatenate them to get the text. It's split only before a char
+    /// of the document or before a reference, never inside a char or an entity's replacement
+    /// text, so a chunk is longer only when a reference by itself is. Text isn't split before
+    /// its first char other than whitespace, so whitespace-only text is one event, and
+    /// CDATA sections aren't split.
+    ///
+    /// Chunks are never merged, so this turns off `coalesce_characters`. `max_data_length`
+    /// limits each chunk rather than the whole text. With `trim_whitespace`, the first chunk is
+    /// trimmed at the start and the last one at the end, where it's dropped if nothing is left.
+    pub characters_chunk_size: Option<usize>,
+
+    /// Keep one shared copy of each distinct element and attribute name rather than allocate
+    /// names for every event. Default is false.
+    ///
+    /// When true, the names in `StartElement` and `EndElement` events and of their attributes
+    /// are empty, and `EventReader::element_name()` and `EventReader::attribute_names()` return
+    /// them as `InternedName`s from a `NameInterner` the reader keeps. A document which repeats
+    /// the same few names then costs one allocation for each name, however many elements it has.
+    /// Names are resolved to namespaces as usual.
+    pub intern_names: bool,
+
+    /// Replace tabs and line breaks written in attribute values with spaces, as XML's attribute
+    /// value normalization does, keeping the ones from character references. For `canonicalize`.
+    pub(crate) normalize_attribute_whitespace: bool,
+}
+
+/// What to do with whitespace-only text, see `ParserConfig2::whitespace_handling`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WhitespaceHandling {
+    /// Report it as `Whitespace` events
+    Whitespace,
+    /// Report it as `Characters` events, like any other text
+    Characters,
+    /// Don't report it at all
+    Drop,
+}
+
+/// Function deciding about references to unknown entities, see `ParserConfig2::on_unknown_entity`
+#[derive(Clone, Copy)]
+pub struct UnknownEntityHandler(pub fn(&str) -> EntityAction);
+
+impl PartialEq for UnknownEntityHandler {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        // same function, as far as addresses can tell
+        self.0 as usize == other.0 as usize
+    }
+}
+
+impl Eq for UnknownEntityHandler {}
+
+impl fmt::Debug for UnknownEntityHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UnknownEntityHandler")
+    }
+}
+
+/// What to do with a reference to an unknown entity, see `ParserConfig2::on_unknown_entity`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EntityAction {
+    /// Use this text in place of the reference
+    ReplaceWith(String),
+    /// Keep the reference as literal text, i.e. `&name;`
+    KeepReference,
+    /// Fail parsing with an unexpected entity error
+    Error,
+}
+
+impl EntityAction {
+    /// Replaces HTML named character references, like `&nbsp;`, and fails on other unknown
+    /// entities. Meant for `ParserConfig2::on_unknown_entity`, for documents which use
+    /// HTML entities without declaring them. See `escape::html_entity`.
+    ///
+    /// ```rust
+    /// # use xml_no_std::reader::{EntityAction, EventReader, ParserConfig2, XmlEvent};
+    /// let config = ParserConfig2::new().on_unknown_entity(EntityAction::html);
+    /// let mut reader = EventReader::new_with_config(b"<p>&copy;&nbsp;2024</p>".iter(), config);
+    /// reader.next().unwrap();
+    /// reader.next().unwrap();
+    /// assert_eq!(reader.next().unwrap(), XmlEvent::Characters("\u{a9}\u{a0}2024".into()));
+    /// ```
+    #[cfg(feature = "html-entities")]
+    #[must_use]
+    pub fn html(name: &str) -> EntityAction {
+        match crate::escape::html_entity(name) {
+            Some(text) => EntityAction::ReplaceWith(text.into()),
+            None => EntityAction::Error,
+        }
+    }
 }
 
 impl Default for ParserConfig2 {
@@ -239,6 +401,16 @@ impl Default for ParserConfig2 {
             max_attribute_length: 1<<30,
             max_data_length: 1<<30,
             max_name_length: 1<<18,
+            lazy_attributes: false,
+            on_unknown_entity: None,
+            apply_attribute_defaults: false,
+            whitespace_handling: None,
+            max_document_size: None,
+            max_element_depth: None,
+            capture_doctype: false,
+            characters_chunk_size: None,
+            intern_names: false,
+            normalize_attribute_whitespace: false,
         }
     }
 }
@@ -250,6 +422,23 @@ impl ParserConfig2 {
         Self::default()
     }
 
+    /// Handle references to unknown entities with this function instead of failing.
+    ///
+    /// ```rust
+    /// use xml_no_std::reader::{EntityAction, ParserConfig};
+    ///
+    /// let config = ParserConfig::new().on_unknown_entity(|name| match name {
+    ///     "nbsp" => EntityAction::ReplaceWith("\u{a0}".into()),
+    ///     _ => EntityAction::KeepReference,
+    /// });
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn on_unknown_entity(mut self, handler: fn(&str) -> EntityAction) -> Self {
+        self.on_unknown_entity = Some(UnknownEntityHandler(handler));
+        self
+    }
+
     /// Read character encoding from `Content-Type` header.
     /// Set this when parsing XML documents fetched over HTTP.
     ///
@@ -286,7 +475,7 @@ impl ParserConfig2 {
     /// This method is exactly equivalent to calling `EventReader::new_with_config()` with
     /// this configuration object.
     #[inline]
-    pub fn create_reader<'a, S: Iterator<Item = &'a u8>>(self, source: S) -> EventReader<'a, S> {
+    pub fn create_reader<S: Source>(self, source: S) -> EventReader<S> {
         EventReader::new_with_config(source, self)
     }
 }
@@ -318,8 +507,24 @@ gen_setters! { ParserConfig2,
     max_attribute_length: val usize,
     /// Maximum length of strings reprsenting characters, comments, and processing instructions
     max_data_length: val usize,
+    /// Maximum number of bytes read from the source
+    max_document_size: val Option<u64>,
+    /// Maximum nesting depth of elements
+    max_element_depth: val Option<usize>,
     /// Allow `<?xml encoding="bogus"?>`
-    ignore_invalid_encoding_declarations: val bool
+    ignore_invalid_encoding_declarations: val bool,
+    /// Deliver raw attribute values, to be unescaped on demand
+    lazy_attributes: val bool,
+    /// Add default attribute values declared in the DTD internal subset
+    apply_attribute_defaults: val bool,
+    /// Report, fold into `Characters`, or drop whitespace-only text
+    whitespace_handling: into Option<WhitespaceHandling>,
+    /// Keep the name, external IDs and internal subset of `<!DOCTYPE>`
+    capture_doctype: val bool,
+    /// Split long text into `Characters` events of at most this many bytes
+    characters_chunk_size: val Option<usize>,
+    /// Keep one shared copy of each element and attribute name
+    intern_names: val bool
 }
 
 gen_setters! { ParserConfig,
@@ -342,6 +547,24 @@ gen_setters! { ParserConfig,
     max_attribute_length: c2 usize,
     /// Maximum length of strings reprsenting characters, comments, and processing instructions
     max_data_length: c2 usize,
+    /// Maximum number of bytes read from the source
+    max_document_size: c2 Option<u64>,
+    /// Maximum nesting depth of elements
+    max_element_depth: c2 Option<usize>,
+    /// Deliver raw attribute values, to be unescaped on demand
+    lazy_attributes: c2 bool,
+    /// Decide what to do with references to unknown entities
+    on_unknown_entity: c2 fn(&str) -> EntityAction,
+    /// Add default attribute values declared in the DTD internal subset
+    apply_attribute_defaults: c2 bool,
+    /// Report, fold into `Characters`, or drop whitespace-only text
+    whitespace_handling: c2 Option<WhitespaceHandling>,
+    /// Keep the name, external IDs and internal subset of `<!DOCTYPE>`
+    capture_doctype: c2 bool,
+    /// Split long text into `Characters` events of at most this many bytes
+    characters_chunk_size: c2 Option<usize>,
+    /// Keep one shared copy of each element and attribute name
+    intern_names: c2 bool,
 
     /// Set encoding from the MIME type. Important for HTTP compatibility.
     content_type: c2 &str
@@ -0,0 +1,237 @@
+//! Contains `Cursor`, for reading documents of a known structure one expected event at a time.
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+
+use core::fmt;
+
+use crate::attribute::Attributes;
+use crate::common::{Position, TextPosition};
+use crate::reader::{Error, EventReader, Result, Source, XmlEvent};
+
+/// A wrapper around an `EventReader` for hand-written deserializers, which checks that the
+/// events are the expected ones.
+///
+/// Each `expect_` method reads the next event and fails if it's something else, with an error
+/// which tells what was expected, what was found, and where. The document declaration is
+/// always skipped, and by default comments, processing instructions and `Whitespace` events
+/// are too, see `skip_ignorable`.
+///
+/// ```rust
+/// use xml_no_std::reader::Cursor;
+/// use xml_no_std::EventReader;
+///
+/// let doc = "<point x='1'><!-- y --><y>2</y></point>";
+/// let mut cursor = Cursor::new(EventReader::from_str(doc));
+/// let attributes = cursor.expect_start("point").unwrap();
+/// assert_eq!(attributes.get("x").unwrap().value, "1");
+/// assert!(cursor.optional_start("z").unwrap().is_none());
+/// cursor.expect_start("y").unwrap();
+/// assert_eq!(cursor.expect_text().unwrap(), "2");
+/// cursor.expect_end().unwrap();
+/// let err = cursor.expect_start("z").unwrap_err();
+/// assert_eq!(err.to_string(), "1:32 Expected start tag <z>, found end tag </point>");
+/// ```
+pub struct Cursor<S: Source> {
+    reader: EventReader<S>,
+    peeked: Option<XmlEvent>,
+    skip_ignorable: bool,
+}
+
+impl<S: Source> Cursor<S> {
+    /// Wraps the reader, skipping comments, processing instructions and whitespace.
+    #[inline]
+    pub fn new(reader: EventReader<S>) -> Self {
+        Cursor { reader, peeked: None, skip_ignorable: true }
+    }
+
+    /// Sets whether comments, processing instructions and `Whitespace` events are skipped.
+    /// When false, they're events like others, and the `expect_` methods fail on them.
+    #[inline]
+    #[must_use]
+    pub fn skip_ignorable(mut self, skip: bool) -> Self {
+        self.skip_ignorable = skip;
+        self
+    }
+
+    /// Reads the next event which isn't skipped.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<XmlEvent> {
+        if let Some(event) = self.peeked.take() {
+            return Ok(event);
+        }
+        loop {
+            match self.reader.next()? {
+                XmlEvent::StartDocument { .. } => {},
+                XmlEvent::Comment(_) | XmlEvent::ProcessingInstruction { .. } | XmlEvent::Whitespace(_) if self.skip_ignorable => {},
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Returns the next event which isn't skipped, without consuming it.
+    pub fn peek(&mut self) -> Result<&XmlEvent> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.next()?);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    /// Reads a start tag with the local name `local`, in any namespace, and returns its attributes.
+    pub fn expect_start(&mut self, local: &str) -> Result<Attributes> {
+        match self.next()? {
+            XmlEvent::StartElement { name, attributes, .. } if name.local_name == local => Ok(attributes),
+            found => Err(self.unexpected(format_args!("start tag <{local}>"), &found)),
+        }
+    }
+
+    /// Reads a start tag with the local name `local` in the namespace `uri`, and returns its attributes.
+    ///
+    /// An empty `uri` expects an element without a namespace.
+    pub fn expect_start_ns(&mut self, uri: &str, local: &str) -> Result<Attributes> {
+        match self.next()? {
+            XmlEvent::StartElement { name, attributes, .. } if name.matches(Some(uri), local) => Ok(attributes),
+            found => Err(self.unexpected(format_args!("start tag <{{{uri}}}{local}>"), &found)),
+        }
+    }
+
+    /// Reads a start tag if it has the local name `local`, in any namespace, and returns its
+    /// attributes. Otherwise the next event is left to be read.
+    pub fn optional_start(&mut self, local: &str) -> Result<Option<Attributes>> {
+        match self.peek()? {
+            XmlEvent::StartElement { name, .. } if name.local_name == local => {},
+            _ => return Ok(None),
+        }
+        match self.peeked.take() {
+            Some(XmlEvent::StartElement { attributes, .. }) => Ok(Some(attributes)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads an end tag. The reader has checked that it matches its start tag.
+    pub fn expect_end(&mut self) -> Result<()> {
+        match self.next()? {
+            XmlEvent::EndElement { .. } => Ok(()),
+            found => Err(self.unexpected(format_args!("end tag"), &found)),
+        }
+    }
+
+    /// Reads the text and CDATA up to the next tag, and returns it concatenated.
+    ///
+    /// The tag is left to be read. It's an empty string before an end tag, like in `<a></a>`,
+    /// and an error before a start tag.
+    pub fn expect_text(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.peek()? {
+                XmlEvent::Characters(data) | XmlEvent::CData(data) | XmlEvent::Whitespace(data) => text.push_str(data),
+                XmlEvent::EndElement { .. } => return Ok(text),
+                _ => {
+                    let found = self.peeked.take().unwrap();
+                    return Err(self.unexpected(format_args!("text"), &found));
+                },
+            }
+            self.peeked = None;
+        }
+    }
+
+    /// Reads everything up to and including the end tag of the element which was started last.
+    pub fn skip_to_end(&mut self) -> Result<()> {
+        let mut depth = 1_usize;
+        while depth > 0 {
+            match self.next()? {
+                XmlEvent::StartElement { .. } => depth += 1,
+                XmlEvent::EndElement { .. } => depth -= 1,
+                XmlEvent::EndDocument => return Err(self.unexpected(format_args!("end tag"), &XmlEvent::EndDocument)),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// Unwraps the cursor, returning the reader. An event which was peeked at is lost.
+    #[inline]
+    pub fn into_inner(self) -> EventReader<S> {
+        self.reader
+    }
+
+    #[cold]
+    fn unexpected(&self, expected: fmt::Arguments<'_>, found: &XmlEvent) -> Error {
+        let found: String = match found {
+            XmlEvent::StartDocument { .. } => "document declaration".into(),
+            XmlEvent::EndDocument => "end of document".into(),
+            XmlEvent::StartElement { name, .. } => format!("start tag <{name}>"),
+            XmlEvent::EndElement { name } => format!("end tag </{name}>"),
+            XmlEvent::ProcessingInstruction { name, .. } => format!("processing instruction <?{name}?>"),
+            XmlEvent::CData(_) => "CDATA".into(),
+            XmlEvent::Comment(_) => "comment".into(),
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => format!("text {text:?}"),
+        };
+        (self, format!("Expected {expected}, found {found}")).into()
+    }
+}
+
+impl<S: Source> Position for Cursor<S> {
+    /// Returns the position of the last event read from the reader, which is the peeked one if any.
+    #[inline]
+    fn position(&self) -> TextPosition {
+        self.reader.position()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::string::ToString;
+
+    use super::Cursor;
+    use crate::reader::XmlEvent;
+    use crate::EventReader;
+
+    const DOC: &str = r#"<?xml version="1.0"?>
+<p:order xmlns:p="urn:p" id="7">
+  <!-- lines -->
+  <line><sku>a&amp;b</sku><qty>2</qty><note/></line>
+  <?pi?>
+  <line><sku>c<![CDATA[<d>]]></sku><extra><x/></extra></line>
+</p:order>"#;
+
+    #[test]
+    fn structure() {
+        let mut cursor = Cursor::new(EventReader::from_str(DOC));
+        assert_eq!(cursor.expect_start_ns("urn:p", "order").unwrap().get("id").unwrap().value, "7");
+        let mut skus = alloc::vec::Vec::new();
+        while cursor.optional_start("line").unwrap().is_some() {
+            cursor.expect_start("sku").unwrap();
+            skus.push(cursor.expect_text().unwrap());
+            cursor.expect_end().unwrap();
+            if cursor.optional_start("qty").unwrap().is_some() {
+                assert_eq!(cursor.expect_text().unwrap(), "2");
+                cursor.expect_end().unwrap();
+            }
+            cursor.skip_to_end().unwrap();
+        }
+        assert_eq!(skus, ["a&b", "c<d>"]);
+        cursor.expect_end().unwrap();
+        assert_eq!(cursor.next().unwrap(), XmlEvent::EndDocument);
+    }
+
+    #[test]
+    fn errors() {
+        let mut cursor = Cursor::new(EventReader::from_str(DOC));
+        let err = cursor.expect_start("invoice").unwrap_err();
+        assert_eq!(err.to_string(), "2:1 Expected start tag <invoice>, found start tag <{urn:p}p:order>");
+        assert_eq!(cursor.expect_start_ns("urn:q", "line").unwrap_err().msg(),
+            "Expected start tag <{urn:q}line>, found start tag <line>");
+        assert_eq!(cursor.expect_text().unwrap_err().msg(), "Expected text, found start tag <sku>");
+        assert_eq!(cursor.expect_end().unwrap_err().msg(), "Expected end tag, found text \"a&b\"");
+
+        let config = crate::ParserConfig::new().ignore_comments(false);
+        let mut cursor = Cursor::new(EventReader::new_with_config(DOC.as_bytes().iter(), config)).skip_ignorable(false);
+        cursor.expect_start("order").unwrap();
+        assert_eq!(cursor.expect_start("line").unwrap_err().msg(), "Expected start tag <line>, found text \"\\n  \"");
+        assert_eq!(cursor.expect_start("line").unwrap_err().msg(), "Expected start tag <line>, found comment");
+    }
+}
@@ -0,0 +1,104 @@
+//! Contains `Doctype`, the `<!DOCTYPE>` declaration captured by the parser.
+extern crate alloc;
+
+use alloc::string::String;
+
+use crate::chars::{is_name_char, is_whitespace_char};
+
+/// Document type declaration, as written in the document.
+///
+/// Captured when `ParserConfig2::capture_doctype` is set, and available from
+/// `EventReader::doctype()` once the parser is past it.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Doctype {
+    /// Name of the root element
+    pub name: String,
+    /// Public identifier of `PUBLIC "…" "…"`
+    pub public_id: Option<String>,
+    /// System identifier of `PUBLIC "…" "…"` or `SYSTEM "…"`
+    pub system_id: Option<String>,
+    /// Exact source text between `[` and `]`, if there's an internal subset
+    pub internal_subset: Option<String>,
+}
+
+impl Doctype {
+    /// Splits the source text between `<!DOCTYPE` and the closing `>`.
+    ///
+    /// Malformed parts are left out; the parser reports errors in them separately.
+    pub(crate) fn from_raw(raw: &str) -> Doctype {
+        let mut doctype = Doctype::default();
+        let rest = raw.trim_start_matches(is_whitespace_char);
+        let name_end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        doctype.name = rest[..name_end].into();
+        let mut rest = rest[name_end..].trim_start_matches(is_whitespace_char);
+
+        if let Some(r) = rest.strip_prefix("PUBLIC") {
+            if let Some((public_id, r)) = quoted(r.trim_start_matches(is_whitespace_char)) {
+                doctype.public_id = Some(public_id.into());
+                rest = r.trim_start_matches(is_whitespace_char);
+                if let Some((system_id, r)) = quoted(rest) {
+                    doctype.system_id = Some(system_id.into());
+                    rest = r.trim_start_matches(is_whitespace_char);
+                }
+            }
+        } else if let Some(r) = rest.strip_prefix("SYSTEM") {
+            if let Some((system_id, r)) = quoted(r.trim_start_matches(is_whitespace_char)) {
+                doctype.system_id = Some(system_id.into());
+                rest = r.trim_start_matches(is_whitespace_char);
+            }
+        }
+
+        if let Some(subset) = rest.strip_prefix('[') {
+            doctype.internal_subset = Some(subset[..internal_subset_len(subset)].into());
+        }
+        doctype
+    }
+}
+
+/// Splits a quoted literal off the start of `s`
+fn quoted(s: &str) -> Option<(&str, &str)> {
+    let quote = s.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let s = &s[1..];
+    let end = s.find(quote)?;
+    Some((&s[..end], &s[end + 1..]))
+}
+
+/// Finds the `]` closing the internal subset, skipping literals, comments and PIs which may contain one
+fn internal_subset_len(subset: &str) -> usize {
+    let mut pos = 0;
+    while let Some(c) = subset[pos..].chars().next() {
+        let rest = &subset[pos..];
+        let skip_to = |end: &str, from: usize| rest[from..].find(end).map_or(subset.len(), |i| pos + from + i + end.len());
+        pos = match c {
+            ']' => return pos,
+            '"' | '\'' => skip_to(if c == '"' { "\"" } else { "'" }, 1),
+            '<' if rest.starts_with("<!--") => skip_to("-->", 4),
+            '<' if rest.starts_with("<?") => skip_to("?>", 2),
+            _ => pos + c.len_utf8(),
+        };
+    }
+    subset.len()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::Doctype;
+
+    #[test]
+    fn doctype_from_raw() {
+        let d = Doctype::from_raw(r#" html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" 'x.dtd'"#);
+        assert_eq!(d.name, "html");
+        assert_eq!(d.public_id.as_deref(), Some("-//W3C//DTD XHTML 1.0 Strict//EN"));
+        assert_eq!(d.system_id.as_deref(), Some("x.dtd"));
+        assert_eq!(d.internal_subset, None);
+
+        let subset = "\n<!ENTITY a \"]\"> <!-- ] ' --> <?pi ]?>\n";
+        let d = Doctype::from_raw(&alloc::format!(" r SYSTEM \"r.dtd\" [{subset}]\n"));
+        assert_eq!(d.name, "r");
+        assert_eq!(d.public_id, None);
+        assert_eq!(d.system_id.as_deref(), Some("r.dtd"));
+        assert_eq!(d.internal_subset.as_deref(), Some(subset));
+    }
+}
@@ -2,6 +2,7 @@ extern crate alloc;
 
 use crate::Encoding;
 use crate::reader::lexer::Token;
+use crate::namespace::ReservedNamespaceError;
 
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
@@ -12,7 +13,8 @@ use core::fmt;
 use core::str;
 
 use crate::common::{Position, TextPosition};
-use crate::util;
+use crate::decode::DecodeError;
+use crate::reader::SourceError;
 
 #[derive(Debug)]
 pub enum ErrorKind {
@@ -20,6 +22,41 @@ pub enum ErrorKind {
     Io(String),
     Utf8(str::Utf8Error),
     UnexpectedEof,
+    /// The document exceeded this limit set in `ParserConfig2`, or the reader's `Capacity`
+    LimitExceeded(Limit),
+    /// A buffer couldn't grow, with the `fallible-alloc` feature
+    OutOfMemory,
+    /// The `Source` failed, with its message
+    Source(String),
+}
+
+/// A parser limit, reported with `ErrorKind::LimitExceeded`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Limit {
+    /// `ParserConfig2::max_document_size`
+    DocumentSize,
+    /// `ParserConfig2::max_element_depth`
+    ElementDepth,
+    /// The attributes a start tag can have, see `capacity::Capacity`
+    AttributeCapacity,
+    /// The elements which can be nested, see `capacity::Capacity`
+    DepthCapacity,
+    /// The text, name or attribute value which can be read at once, see `capacity::Capacity`
+    TextCapacity,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::DocumentSize => "Document is larger than the configured maximum size",
+            Limit::ElementDepth => "Elements are nested deeper than the configured maximum depth",
+            Limit::AttributeCapacity => "Element has more attributes than the reader has room for",
+            Limit::DepthCapacity => "Elements are nested deeper than the reader has room for",
+            Limit::TextCapacity => "Text is longer than the reader has room for",
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -52,8 +89,10 @@ pub(crate) enum SyntaxError {
     /// which is an error, see section 2.6 of XML 1.1 spec
     UnexpectedProcessingInstruction(Box<str>, Token),
     CannotUndefinePrefix(Box<str>),
+    InvalidAttributeListDeclaration(Box<str>),
     InvalidCharacterEntity(u32),
     InvalidDefaultNamespace(Box<str>),
+    ReservedNamespace(ReservedNamespaceError),
     InvalidNamePrefix(Box<str>),
     InvalidNumericEntity(Box<str>),
     InvalidStandaloneDeclaration(Box<str>),
@@ -95,10 +134,12 @@ impl SyntaxError {
             Self::UnexpectedOpeningTag => "'<' is not allowed in attributes".into(),
             Self::CannotUndefinePrefix(ref ln) => alloc::format!("Cannot undefine prefix '{ln}'").into(),
             Self::ConflictingEncoding(a, b) => alloc::format!("Declared encoding {a}, but uses {b}").into(),
+            Self::InvalidAttributeListDeclaration(ref decl) => alloc::format!("Invalid attribute list declaration: <!ATTLIST {decl}>").into(),
             Self::InvalidCharacterEntity(num) => alloc::format!("Invalid character U+{num:04X}").into(),
             Self::InvalidDefaultNamespace(ref name) => alloc::format!( "Namespace '{name}' cannot be default").into(),
+            Self::ReservedNamespace(e) => alloc::format!("Reserved namespace binding: {e}").into(),
             Self::InvalidNamePrefix(ref prefix) => alloc::format!("'{prefix}' cannot be an element name prefix").into(),
-            Self::InvalidNumericEntity(ref v) => alloc::format!("Invalid numeric entity: {v}").into(),
+            Self::InvalidNumericEntity(ref v) => alloc::format!("Invalid numeric entity: &#{v};").into(),
             Self::InvalidQualifiedName(ref e) => alloc::format!("Qualified name is invalid: {e}").into(),
             Self::InvalidStandaloneDeclaration(ref value) => alloc::format!("Invalid standalone declaration value: {value}").into(),
             Self::InvalidXmlProcessingInstruction(ref name) => alloc::format!("Invalid processing instruction: <?{name} - \"<?xml\"-like PI is only valid at the beginning of the document").into(),
@@ -133,18 +174,53 @@ impl SyntaxError {
 pub struct Error {
     pub(crate) pos: TextPosition,
     pub(crate) kind: ErrorKind,
+    /// Set for decoding errors
+    pub(crate) encoding: Option<Encoding>,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use self::ErrorKind::{Io, Syntax, UnexpectedEof, Utf8};
+        use self::ErrorKind::{Io, LimitExceeded, OutOfMemory, Source, Syntax, UnexpectedEof, Utf8};
 
         write!(f, "{} ", self.pos)?;
         match &self.kind {
-            Io(io_error) => io_error.fmt(f),
+            Io(io_error) | Source(io_error) => io_error.fmt(f),
             Utf8(reason) => reason.fmt(f),
             Syntax(msg) => f.write_str(msg),
             UnexpectedEof => f.write_str("Unexpected EOF"),
+            LimitExceeded(limit) => limit.fmt(f),
+            OutOfMemory => f.write_str("Out of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{} {}", self.pos, self.kind);
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ErrorKind {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            ErrorKind::Io(io_error) | ErrorKind::Source(io_error) => defmt::write!(f, "{=str}", io_error),
+            ErrorKind::Utf8(reason) => defmt::write!(f, "invalid UTF-8 after {=usize} valid bytes", reason.valid_up_to()),
+            ErrorKind::Syntax(msg) => defmt::write!(f, "{=str}", msg),
+            ErrorKind::UnexpectedEof => defmt::write!(f, "Unexpected EOF"),
+            ErrorKind::LimitExceeded(limit) => defmt::write!(f, "{}", limit),
+            ErrorKind::OutOfMemory => defmt::write!(f, "Out of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Utf8(reason) => Some(reason),
+            _ => None,
         }
     }
 }
@@ -160,12 +236,18 @@ impl Error {
     #[doc(hidden)]
     #[allow(deprecated)]
     #[must_use] pub fn msg(&self) -> &str {
-        use self::ErrorKind::{Io, Syntax, UnexpectedEof, Utf8};
+        use self::ErrorKind::{Io, LimitExceeded, OutOfMemory, Source, Syntax, UnexpectedEof, Utf8};
         match &self.kind {
-            Io(io_error) => &io_error,
+            Io(io_error) | Source(io_error) => &io_error,
             Utf8(reason) => "UTF8 Error",
             Syntax(msg) => msg.as_ref(),
             UnexpectedEof => "Unexpected EOF",
+            LimitExceeded(Limit::DocumentSize) => "Document is larger than the configured maximum size",
+            LimitExceeded(Limit::ElementDepth) => "Elements are nested deeper than the configured maximum depth",
+            LimitExceeded(Limit::AttributeCapacity) => "Element has more attributes than the reader has room for",
+            LimitExceeded(Limit::DepthCapacity) => "Elements are nested deeper than the reader has room for",
+            LimitExceeded(Limit::TextCapacity) => "Text is longer than the reader has room for",
+            OutOfMemory => "Out of memory",
         }
     }
 
@@ -174,6 +256,13 @@ impl Error {
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+
+    /// Returns the encoding the source was being decoded with, if this is a decoding error.
+    #[must_use]
+    #[inline]
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
 }
 
 impl<'a, P, M> From<(&'a P, M)> for Error where P: Position, M: Into<Cow<'static, str>> {
@@ -182,34 +271,51 @@ impl<'a, P, M> From<(&'a P, M)> for Error where P: Position, M: Into<Cow<'static
         Error {
             pos: orig.0.position(),
             kind: ErrorKind::Syntax(orig.1.into()),
+            encoding: None,
         }
     }
 }
 
-impl From<util::CharReadError> for Error {
+impl From<DecodeError> for Error {
     #[cold]
-    fn from(e: util::CharReadError) -> Self {
-        use crate::util::CharReadError::{Io, UnexpectedEof, Utf8};
+    fn from(e: DecodeError) -> Self {
         Error {
             pos: TextPosition::new(),
             kind: match e {
-                UnexpectedEof => ErrorKind::UnexpectedEof,
-                Utf8(reason) => ErrorKind::Utf8(reason),
-                Io(io_error) => ErrorKind::Io(io_error),
+                DecodeError::UnexpectedEof => ErrorKind::UnexpectedEof,
+                DecodeError::Utf8(reason) => ErrorKind::Utf8(reason),
+                e => ErrorKind::Io(e.to_string()),
             },
+            encoding: None,
         }
     }
 }
 
+impl From<SourceError> for Error {
+    #[cold]
+    fn from(e: SourceError) -> Self {
+        Error {
+            pos: TextPosition::new(),
+            kind: match e {
+                SourceError::Io(message) => ErrorKind::Io(message),
+                e => ErrorKind::Source(e.message().into()),
+            },
+            encoding: None,
+        }
+    }
+}
 
 impl Clone for ErrorKind {
     #[cold]
     fn clone(&self) -> Self {
-        use self::ErrorKind::{Io, Syntax, UnexpectedEof, Utf8};
+        use self::ErrorKind::{Io, LimitExceeded, OutOfMemory, Source, Syntax, UnexpectedEof, Utf8};
         match self {
             UnexpectedEof => UnexpectedEof,
+            LimitExceeded(limit) => LimitExceeded(*limit),
+            OutOfMemory => OutOfMemory,
             Utf8(reason) => Utf8(*reason),
             Io(io_error) => Io(io_error.clone()),
+            Source(message) => Source(message.clone()),
             Syntax(msg) => Syntax(msg.clone()),
         }
     }
@@ -217,11 +323,12 @@ impl Clone for ErrorKind {
 impl PartialEq for ErrorKind {
     #[allow(deprecated)]
     fn eq(&self, other: &ErrorKind) -> bool {
-        use self::ErrorKind::{Io, Syntax, UnexpectedEof, Utf8};
+        use self::ErrorKind::{Io, LimitExceeded, OutOfMemory, Source, Syntax, UnexpectedEof, Utf8};
         match (self, other) {
-            (UnexpectedEof, UnexpectedEof) => true,
+            (UnexpectedEof, UnexpectedEof) | (OutOfMemory, OutOfMemory) => true,
+            (LimitExceeded(left), LimitExceeded(right)) => left == right,
             (Utf8(left), Utf8(right)) => left == right,
-            (Io(left), Io(right)) =>
+            (Io(left), Io(right)) | (Source(left), Source(right)) =>
                 left == right,
             (Syntax(left), Syntax(right)) =>
                 left == right,
@@ -236,3 +343,69 @@ impl Eq for ErrorKind {}
 fn err_size() {
     assert!(std::mem::size_of::<SyntaxError>() <= 24);
 }
+
+#[test]
+fn errors_are_send_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<Error>();
+    assert_send_sync::<crate::writer::Error>();
+    assert_send_sync::<crate::escape::UnescapeError>();
+    assert_send_sync::<crate::escape::PreEscapedError>();
+    assert_send_sync::<crate::attribute::AttributeSyntaxError>();
+    assert_send_sync::<crate::attribute::AttrParseError>();
+    assert_send_sync::<crate::attribute::DuplicateAttribute>();
+    assert_send_sync::<crate::namespace::ConflictingBinding>();
+    assert_send_sync::<crate::reader::PathError>();
+    assert_send_sync::<crate::ReformatError>();
+    assert_send_sync::<crate::canonical::Error>();
+    assert_send_sync::<DecodeError>();
+    assert_send_sync::<crate::reader::SourceError>();
+    #[cfg(feature = "test-util")]
+    assert_send_sync::<crate::test_util::Difference>();
+}
+
+/// Checked by `cargo check --lib --profile test --features defmt`, test binaries using defmt don't link
+#[cfg(all(test, feature = "defmt"))]
+#[allow(dead_code)]
+fn defmt_formats() {
+    fn assert_format<T: defmt::Format>() {}
+    assert_format::<Error>();
+    assert_format::<crate::writer::Error>();
+    assert_format::<crate::escape::UnescapeError>();
+    assert_format::<crate::attribute::AttributeSyntaxError>();
+    assert_format::<crate::attribute::AttrParseError>();
+    assert_format::<crate::attribute::DuplicateAttribute>();
+    assert_format::<crate::namespace::ConflictingBinding>();
+    assert_format::<crate::reader::PathError>();
+    assert_format::<crate::reader::MatchState>();
+    assert_format::<crate::reader::Control>();
+    assert_format::<crate::ReformatError>();
+    assert_format::<crate::canonical::Error>();
+    assert_format::<DecodeError>();
+    assert_format::<crate::reader::SourceError>();
+    #[cfg(feature = "test-util")]
+    assert_format::<crate::test_util::Difference>();
+    assert_format::<TextPosition>();
+    assert_format::<Encoding>();
+    assert_format::<crate::common::XmlVersion>();
+    assert_format::<crate::reader::XmlEvent>();
+    assert_format::<crate::writer::XmlEvent<'_>>();
+    assert_format::<crate::writer::OwnedXmlEvent>();
+}
+
+#[cfg(feature = "core-error")]
+#[test]
+fn error_sources() {
+    use core::error::Error as _;
+
+    let utf8 = str::from_utf8(b"\xff").unwrap_err();
+    let err = Error::from(DecodeError::Utf8(utf8));
+    assert_eq!(err.source().map(ToString::to_string), Some(utf8.to_string()));
+    let boxed: Box<dyn core::error::Error + Send + Sync> = Box::new(err);
+    assert!(boxed.source().is_some());
+
+    let err = crate::writer::Error::ReservedNamespace(ReservedNamespaceError::XmlnsPrefix);
+    assert_eq!(err.source().map(ToString::to_string), Some(ReservedNamespaceError::XmlnsPrefix.to_string()));
+    let name_error = "1a".parse::<crate::name::OwnedName>().unwrap_err();
+    assert!(crate::writer::Error::InvalidName(name_error).source().is_none());
+}
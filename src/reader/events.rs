@@ -5,8 +5,9 @@ use alloc::string::String;
 use alloc::vec::Vec;
 
 use core::fmt;
-use crate::attribute::OwnedAttribute;
+use crate::attribute::Attributes;
 use crate::common::XmlVersion;
+use crate::escape::{AttributeEscapes, Escaped, PcDataEscapes};
 use crate::name::OwnedName;
 use crate::namespace::Namespace;
 
@@ -14,7 +15,10 @@ use crate::namespace::Namespace;
 ///
 /// Items of this enum are emitted by `reader::EventReader`. They correspond to different
 /// elements of an XML document.
+// `StartElement` holds a few attributes and namespace mappings inline, which saves allocations for most elements
+#[allow(clippy::large_enum_variant)]
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum XmlEvent {
     /// Corresponds to XML document declaration.
     ///
@@ -69,8 +73,11 @@ pub enum XmlEvent {
 
         /// A list of attributes associated with the element.
         ///
+        /// They're in document order, followed by any defaults from the DTD's attribute list
+        /// declarations. Namespace declarations are not among them.
+        ///
         /// Currently attributes are not checked for duplicates (TODO)
-        attributes: Vec<OwnedAttribute>,
+        attributes: Attributes,
 
         /// Contents of the namespace mapping at this point of the document.
         namespace: Namespace,
@@ -116,6 +123,68 @@ pub enum XmlEvent {
     Whitespace(String),
 }
 
+/// Text longer than this many characters is truncated by `Display`
+const DISPLAY_TEXT_LENGTH: usize = 40;
+
+/// Displays the event as markup close to what was read, e.g. `<item id="3">`, for logging and
+/// error messages.
+///
+/// Names are written as qualified names, attribute values and text are escaped, and namespace
+/// declarations are left out. Text, CDATA and comments longer than 40 characters are cut off
+/// after them with an ellipsis, `…`. `EndDocument` has no markup and displays as nothing.
+///
+/// ```rust
+/// use xml_no_std::EventReader;
+///
+/// let mut reader = EventReader::from_str("<item xmlns='urn:x' id='3' note='&quot;a&lt;b&quot;'>1 &lt; 2</item>");
+/// let events: Vec<_> = reader.into_iter().map(|e| e.unwrap().to_string()).collect();
+/// assert_eq!(events[1..4], [r#"<item id="3" note="&quot;a&lt;b&quot;">"#, "1 &lt; 2", "</item>"]);
+/// ```
+impl fmt::Display for XmlEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlEvent::StartDocument { version, encoding, standalone } => {
+                write!(f, "<?xml version=\"{version}\" encoding=\"{}\"", Escaped::<AttributeEscapes>::new(encoding))?;
+                if let Some(standalone) = standalone {
+                    f.write_str(if *standalone { " standalone=\"yes\"" } else { " standalone=\"no\"" })?;
+                }
+                f.write_str("?>")
+            },
+            XmlEvent::EndDocument => Ok(()),
+            XmlEvent::ProcessingInstruction { name, data: Some(data) } => write!(f, "<?{name} {data}?>"),
+            XmlEvent::ProcessingInstruction { name, data: None } => write!(f, "<?{name}?>"),
+            XmlEvent::StartElement { name, attributes, .. } => {
+                write!(f, "<{}", name.repr_display())?;
+                for attr in attributes.iter() {
+                    write!(f, " {}=\"{}\"", attr.name.repr_display(), Escaped::<AttributeEscapes>::new(&attr.value))?;
+                }
+                f.write_str(">")
+            },
+            XmlEvent::EndElement { name } => write!(f, "</{}>", name.repr_display()),
+            XmlEvent::Comment(data) => {
+                let (data, ellipsis) = preview(data);
+                write!(f, "<!--{data}{ellipsis}-->")
+            },
+            XmlEvent::CData(data) => {
+                let (data, ellipsis) = preview(data);
+                write!(f, "<![CDATA[{data}{ellipsis}]]>")
+            },
+            XmlEvent::Characters(data) | XmlEvent::Whitespace(data) => {
+                let (data, ellipsis) = preview(data);
+                write!(f, "{}{ellipsis}", Escaped::<PcDataEscapes>::new(data))
+            },
+        }
+    }
+}
+
+/// Cuts `text` to `DISPLAY_TEXT_LENGTH` characters, and returns what to append to show that
+fn preview(text: &str) -> (&str, &'static str) {
+    match text.char_indices().nth(DISPLAY_TEXT_LENGTH) {
+        Some((end, _)) => (&text[..end], "…"),
+        None => (text, ""),
+    }
+}
+
 impl fmt::Debug for XmlEvent {
     #[cold]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -129,15 +198,18 @@ impl fmt::Debug for XmlEvent {
                     Some(ref data) => alloc::format!(", {data}"),
                     None       => String::new()
                 }),
-            XmlEvent::StartElement { ref name, ref attributes, namespace: Namespace(ref namespace) } =>
-                write!(f, "StartElement({}, {:?}{})", name, namespace, if attributes.is_empty() {
+            XmlEvent::StartElement { ref name, ref attributes, ref namespace } => {
+                write!(f, "StartElement({name}, ")?;
+                f.debug_map().entries(namespace).finish()?;
+                write!(f, "{})", if attributes.is_empty() {
                     String::new()
                 } else {
                     let attributes: Vec<String> = attributes.iter().map(
                         |a| alloc::format!("{} -> {}", a.name, a.value)
                     ).collect();
                     alloc::format!(", [{}]", attributes.join(", "))
-                }),
+                })
+            },
             XmlEvent::EndElement { ref name } =>
                 write!(f, "EndElement({name})"),
             XmlEvent::Comment(ref data) =>
@@ -152,6 +224,27 @@ impl fmt::Debug for XmlEvent {
     }
 }
 
+/// Like `Debug`, but attributes are only counted.
+#[cfg(feature = "defmt")]
+impl defmt::Format for XmlEvent {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            XmlEvent::StartDocument { version, encoding, standalone } =>
+                defmt::write!(f, "StartDocument({}, {=str}, {})", version, encoding, standalone),
+            XmlEvent::EndDocument => defmt::write!(f, "EndDocument"),
+            XmlEvent::ProcessingInstruction { name, data } =>
+                defmt::write!(f, "ProcessingInstruction({=str}, {})", name, data.as_deref()),
+            XmlEvent::StartElement { name, attributes, .. } =>
+                defmt::write!(f, "StartElement({}, {=usize} attributes)", name, attributes.len()),
+            XmlEvent::EndElement { name } => defmt::write!(f, "EndElement({})", name),
+            XmlEvent::Comment(data) => defmt::write!(f, "Comment({=str})", data),
+            XmlEvent::CData(data) => defmt::write!(f, "CData({=str})", data),
+            XmlEvent::Characters(data) => defmt::write!(f, "Characters({=str})", data),
+            XmlEvent::Whitespace(data) => defmt::write!(f, "Whitespace({=str})", data),
+        }
+    }
+}
+
 impl XmlEvent {
     /// Obtains a writer event from this reader event.
     ///
@@ -221,3 +314,128 @@ impl XmlEvent {
         }
     }
 }
+
+/// Compares a read event with an event to write, e.g. to check in round-trip tests that the
+/// document reads back as it was meant to be written.
+///
+/// * Names are equal if their namespace URIs, local names and prefixes are. A name to write
+///   which has a namespace but no prefix gets one when it's written, so it's never equal to
+///   a read name.
+/// * Attributes are compared as ordered lists, by name and value.
+/// * Namespaces are compared by the bindings in effect: each binding of the writer event must
+///   be in effect in the read event. Those of a read event also have the inherited and the
+///   implicit ones, while a writer event only needs to have the ones it declares.
+/// * Encodings of `StartDocument` compare case-insensitively, and `None` is `UTF-8`, which
+///   the writer declares then.
+/// * `EndElement` without a name is equal to any end tag, since it closes the current element.
+/// * `Whitespace` is equal to `Characters` with the same text.
+///
+/// `EndDocument` is never equal to a writer event, which has no such variant, and `CData` and
+/// `Characters` with the same text aren't equal either.
+impl PartialEq<crate::writer::XmlEvent<'_>> for XmlEvent {
+    fn eq(&self, other: &crate::writer::XmlEvent<'_>) -> bool {
+        use crate::writer::XmlEvent as Written;
+        match (self, other) {
+            (XmlEvent::StartDocument { version, encoding, standalone },
+             Written::StartDocument { version: written_version, encoding: written_encoding, standalone: written_standalone }) =>
+                version == written_version && standalone == written_standalone &&
+                    encoding.eq_ignore_ascii_case(written_encoding.unwrap_or("UTF-8")),
+            (XmlEvent::ProcessingInstruction { name, data }, Written::ProcessingInstruction { name: written_name, data: written_data }) =>
+                name == written_name && data.as_deref() == *written_data,
+            (XmlEvent::StartElement { name, attributes, namespace },
+             Written::StartElement { name: written_name, attributes: written_attributes, namespace: written_namespace }) =>
+                name == written_name && attributes.len() == written_attributes.len() &&
+                    attributes.iter().zip(written_attributes.iter()).all(|(attr, written)| attr.name == written.name && attr.value == written.value) &&
+                    written_namespace.into_iter().all(|(prefix, uri)| namespace.get(prefix) == Some(uri)),
+            (XmlEvent::EndElement { name }, Written::EndElement { name: written_name }) =>
+                written_name.map_or(true, |written_name| *name == written_name),
+            (XmlEvent::CData(data), Written::CData(written)) |
+            (XmlEvent::Comment(data), Written::Comment(written)) |
+            (XmlEvent::Characters(data) | XmlEvent::Whitespace(data), Written::Characters(written)) => data == written,
+            _ => false,
+        }
+    }
+}
+
+/// Compares an event to write with a read event, see the other way around.
+impl PartialEq<XmlEvent> for crate::writer::XmlEvent<'_> {
+    #[inline]
+    fn eq(&self, other: &XmlEvent) -> bool {
+        other == self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XmlEvent;
+    use crate::common::XmlVersion;
+    use crate::name::Name;
+    use crate::writer::XmlEvent as Written;
+    use crate::{EventReader, ParserConfig};
+    use super::Vec;
+
+    #[test]
+    fn equal_to_written() {
+        let doc = "<?xml version='1.0' encoding='utf-8'?><p:a xmlns:p='urn:p' x='1' p:y='2'><b xmlns='urn:b'><?pi?> <![CDATA[c]]></b><!--d--></p:a>";
+        let events: Vec<_> = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().ignore_comments(false)).into_iter().map(Result::unwrap).collect();
+        let written = [
+            Written::StartDocument { version: XmlVersion::Version10, encoding: None, standalone: None },
+            Written::start_element(Name { prefix: Some("p"), ..Name::qualified("a", "urn:p", None) })
+                .ns("p", "urn:p").attr("x", "1").attr(Name { prefix: Some("p"), ..Name::qualified("y", "urn:p", None) }, "2").into(),
+            Written::start_element(Name::qualified("b", "urn:b", None)).default_ns("urn:b").into(),
+            Written::processing_instruction("pi", None),
+            Written::characters(" "),
+            Written::cdata("c"),
+            Written::end_element().into(),
+            Written::comment("d"),
+            Written::end_element().name(Name { prefix: Some("p"), ..Name::qualified("a", "urn:p", None) }).into(),
+        ];
+        assert_eq!(events.len(), written.len() + 1);
+        for (event, written) in events.iter().zip(&written) {
+            assert!(event == written, "{event:?} != {written:?}");
+            assert!(written == event, "{written:?} != {event:?}");
+        }
+        assert!(events.iter().all(|event| event.as_writer_event().map_or(true, |written| *event == written)));
+        assert_eq!(events.last(), Some(&XmlEvent::EndDocument));
+        assert!(written.iter().all(|written| *written != XmlEvent::EndDocument));
+
+        let unequal = [
+            // no prefix yet
+            (2, Written::start_element(Name::qualified("a", "urn:p", None)).ns("p", "urn:p").attr("x", "1").into()),
+            // attribute order
+            (1, Written::start_element(Name { prefix: Some("p"), ..Name::qualified("a", "urn:p", None) })
+                .ns("p", "urn:p").attr(Name { prefix: Some("p"), ..Name::qualified("y", "urn:p", None) }, "2").attr("x", "1").into()),
+            // a binding which isn't in effect
+            (2, Written::start_element(Name::qualified("b", "urn:b", None)).default_ns("urn:b").ns("q", "urn:q").into()),
+            (5, Written::characters("c")),
+            (8, Written::end_element().name("a").into()),
+        ];
+        for (i, written) in &unequal {
+            assert!(events[*i] != *written, "{:?} == {written:?}", events[*i]);
+        }
+    }
+
+    #[test]
+    fn display() {
+        let long = "0123456789".repeat(5);
+        let doc = format!("<?xml version='1.0' standalone='yes'?><?pi  data ?><p:a xmlns:p='urn:p' p:b='&apos;&amp;&#9;'><![CDATA[<c>]]><!--{long}-->\
+            {long}&amp;<d/></p:a>");
+        let events: Vec<_> = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().ignore_comments(false))
+            .into_iter().map(|e| format!("{}", e.unwrap())).collect();
+        assert_eq!(events, [
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            "<?pi  data ?>",
+            "<p:a p:b=\"&apos;&amp;\t\">",
+            "<![CDATA[<c>]]>",
+            "<!--0123456789012345678901234567890123456789…-->",
+            "0123456789012345678901234567890123456789…",
+            "<d>",
+            "</d>",
+            "</p:a>",
+            "",
+        ]);
+        assert_eq!(format!("{}", XmlEvent::Characters("€".repeat(40))), "€".repeat(40));
+        assert_eq!(format!("{}", XmlEvent::Whitespace(" \n".into())), " \n");
+        assert_eq!(format!("{}", XmlEvent::Characters("<&>".into())), "&lt;&amp;&gt;");
+    }
+}
@@ -0,0 +1,216 @@
+//! Contains the `Handler` trait, for handling the events of a reader with callbacks.
+use crate::attribute::Attributes;
+use crate::common::XmlVersion;
+use crate::name::OwnedName;
+use crate::namespace::Namespace;
+use crate::reader::{Error, EventReader, Result, Source, XmlEvent};
+
+/// What `run` does after `Handler::start_element`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Control {
+    /// Go on with the element's content
+    Continue,
+    /// Skip the element's content and end tag, see `EventReader::skip`
+    SkipSubtree,
+    /// Stop reading, and return successfully
+    Stop,
+}
+
+/// Callbacks for the events of a document, driven by `run`.
+///
+/// Every method does nothing by default, so a handler only needs the ones it's interested in.
+/// What gets called depends on the reader's configuration, like the events do: `comment` isn't
+/// called unless comments are kept, and `whitespace` not unless whitespace is reported as such.
+///
+/// ```rust
+/// use xml_no_std::attribute::Attributes;
+/// use xml_no_std::name::OwnedName;
+/// use xml_no_std::namespace::Namespace;
+/// use xml_no_std::reader::{run, Control, Handler};
+/// use xml_no_std::EventReader;
+///
+/// #[derive(Default)]
+/// struct Titles(Vec<String>, bool);
+///
+/// impl Handler for Titles {
+///     fn start_element(&mut self, name: &OwnedName, _: &Attributes, _: &Namespace) -> Control {
+///         match &*name.local_name {
+///             "draft" => Control::SkipSubtree,
+///             "title" => { self.1 = true; Control::Continue },
+///             _ => Control::Continue,
+///         }
+///     }
+///
+///     fn characters(&mut self, text: &str) {
+///         if self.1 {
+///             self.0.push(text.into());
+///         }
+///     }
+///
+///     fn end_element(&mut self, _: &OwnedName) {
+///         self.1 = false;
+///     }
+/// }
+///
+/// let doc = "<posts><post><title>a</title></post><draft><title>b</title></draft></posts>";
+/// let mut titles = Titles::default();
+/// run(EventReader::from_str(doc), &mut titles).unwrap();
+/// assert_eq!(titles.0, ["a"]);
+/// ```
+#[allow(unused_variables)]
+pub trait Handler {
+    /// The XML declaration, or the defaults if there's none, see `XmlEvent::StartDocument`.
+    fn start_document(&mut self, version: XmlVersion, encoding: &str, standalone: Option<bool>) {}
+
+    /// The end of the document. It's the last call, unless there's an error.
+    fn end_document(&mut self) {}
+
+    /// A start tag. The return value decides whether to read the element's content.
+    ///
+    /// `end_element` isn't called for an element whose subtree is skipped.
+    fn start_element(&mut self, name: &OwnedName, attributes: &Attributes, namespace: &Namespace) -> Control {
+        Control::Continue
+    }
+
+    /// An end tag.
+    fn end_element(&mut self, name: &OwnedName) {}
+
+    /// Character data.
+    fn characters(&mut self, text: &str) {}
+
+    /// Whitespace-only text reported as `XmlEvent::Whitespace`.
+    fn whitespace(&mut self, text: &str) {}
+
+    /// A CDATA section.
+    fn cdata(&mut self, data: &str) {}
+
+    /// A comment.
+    fn comment(&mut self, data: &str) {}
+
+    /// A processing instruction.
+    fn processing_instruction(&mut self, name: &str, data: Option<&str>) {}
+
+    /// The error which ends reading. `run` returns it after this.
+    fn error(&mut self, error: &Error) {}
+}
+
+/// Reads the whole document, calling the handler for every event.
+///
+/// Returns when the document ends, or when `Handler::start_element` asks to stop.
+/// A parsing error is passed to `Handler::error`, and then returned.
+pub fn run<S: Source, H: Handler + ?Sized>(mut reader: EventReader<S>, handler: &mut H) -> Result<()> {
+    match dispatch(&mut reader, handler) {
+        Err(e) => {
+            handler.error(&e);
+            Err(e)
+        },
+        ok => ok,
+    }
+}
+
+fn dispatch<S: Source, H: Handler + ?Sized>(reader: &mut EventReader<S>, handler: &mut H) -> Result<()> {
+    loop {
+        match reader.next()? {
+            XmlEvent::StartDocument { version, encoding, standalone } => handler.start_document(version, &encoding, standalone),
+            XmlEvent::EndDocument => {
+                handler.end_document();
+                return Ok(());
+            },
+            XmlEvent::StartElement { name, attributes, namespace } => match handler.start_element(&name, &attributes, &namespace) {
+                Control::Continue => {},
+                Control::SkipSubtree => reader.skip()?,
+                Control::Stop => return Ok(()),
+            },
+            XmlEvent::EndElement { name } => handler.end_element(&name),
+            XmlEvent::Characters(text) => handler.characters(&text),
+            XmlEvent::Whitespace(text) => handler.whitespace(&text),
+            XmlEvent::CData(data) => handler.cdata(&data),
+            XmlEvent::Comment(data) => handler.comment(&data),
+            XmlEvent::ProcessingInstruction { name, data } => handler.processing_instruction(&name, data.as_deref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::{run, Control, Handler};
+    use crate::attribute::Attributes;
+    use crate::common::XmlVersion;
+    use crate::name::OwnedName;
+    use crate::namespace::Namespace;
+    use crate::reader::{Error, ParserConfig};
+    use crate::EventReader;
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl Handler for Log {
+        fn start_document(&mut self, version: XmlVersion, encoding: &str, _: Option<bool>) {
+            self.0.push(format!("doc {version} {encoding}"));
+        }
+        fn end_document(&mut self) {
+            self.0.push("end doc".into());
+        }
+        fn start_element(&mut self, name: &OwnedName, attributes: &Attributes, _: &Namespace) -> Control {
+            self.0.push(format!("<{name} {}>", attributes.len()));
+            match &*name.local_name {
+                "skip" => Control::SkipSubtree,
+                "stop" => Control::Stop,
+                _ => Control::Continue,
+            }
+        }
+        fn end_element(&mut self, name: &OwnedName) {
+            self.0.push(format!("</{name}>"));
+        }
+        fn characters(&mut self, text: &str) {
+            self.0.push(format!("text {text}"));
+        }
+        fn cdata(&mut self, data: &str) {
+            self.0.push(format!("cdata {data}"));
+        }
+        fn comment(&mut self, data: &str) {
+            self.0.push(format!("comment {data}"));
+        }
+        fn processing_instruction(&mut self, name: &str, data: Option<&str>) {
+            self.0.push(format!("pi {name} {data:?}"));
+        }
+        fn error(&mut self, error: &Error) {
+            self.0.push(format!("error {}", error.msg()));
+        }
+    }
+
+    fn log(doc: &str) -> (Vec<String>, bool) {
+        let mut log = Log::default();
+        let reader = EventReader::new_with_config(doc.as_bytes().iter(), ParserConfig::new().ignore_comments(false));
+        let ok = run(reader, &mut log).is_ok();
+        (log.0, ok)
+    }
+
+    #[test]
+    fn callbacks() {
+        let (calls, ok) = log("<a x='1'>t<!--c--><?p d?><skip><b>u</b></skip><![CDATA[v]]> </a>");
+        assert!(ok);
+        assert_eq!(calls, [
+            "doc 1.0 UTF-8", "<a 1>", "text t", "comment c", "pi p Some(\"d\")",
+            "<skip 0>", "cdata v", "text  ", "</a>", "end doc",
+        ]);
+    }
+
+    #[test]
+    fn stop_and_error() {
+        let (calls, ok) = log("<a><stop/>t</a>");
+        assert!(ok);
+        assert_eq!(calls.last().unwrap(), "<stop 0>");
+
+        let (calls, ok) = log("<a><b></a>");
+        assert!(!ok);
+        assert!(calls.last().unwrap().starts_with("error "), "{calls:?}");
+    }
+}
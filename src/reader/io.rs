@@ -0,0 +1,174 @@
+//! `EventReader` over a `std::io::Read`, with the `std` feature.
+extern crate alloc;
+extern crate std;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use std::io::{ErrorKind, Read};
+
+use super::{EventReader, ParserConfig2, Source, SourceError};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// The bytes of an `io::Read`, read in chunks, see `EventReader::from_reader`.
+///
+/// An I/O error ends the bytes. The reader then fails with `ErrorKind::Io` rather than
+/// reporting the end of the document.
+pub struct ReadSource<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    /// Returned from then on, reading isn't retried
+    error: Option<String>,
+}
+
+impl<R> ReadSource<R> {
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying reader. Reading from it directly skips
+    /// the bytes the parser would have seen.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader. Bytes which were read into the buffer but not parsed
+    /// yet are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Source for ReadSource<R> {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        if self.pos == self.len {
+            if let Some(e) = &self.error {
+                return Some(Err(SourceError::Io(e.clone())));
+            }
+            self.pos = 0;
+            self.len = loop {
+                match self.inner.read(&mut self.buf) {
+                    Ok(len) => break len,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {},
+                    Err(e) => {
+                        self.len = 0;
+                        let e = self.error.insert(e.to_string());
+                        return Some(Err(SourceError::Io(e.clone())));
+                    },
+                }
+            };
+            if self.len == 0 {
+                return None;
+            }
+        }
+        let byte = self.buf[self.pos];
+        self.pos += 1;
+        Some(Ok(byte))
+    }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        debug_assert!(self.pos + n <= self.len);
+        self.pos += n;
+    }
+}
+
+impl<R: Read> EventReader<ReadSource<R>> {
+    /// Creates a new reader which reads the document from `source`, like xml-rs does.
+    ///
+    /// Reads are buffered, so `source` doesn't need to be. I/O errors fail the reader with
+    /// `ErrorKind::Io`. Only available with the `std` feature.
+    ///
+    /// ```rust
+    /// use xml_no_std::reader::{EventReader, XmlEvent};
+    ///
+    /// let mut reader = EventReader::from_reader(&b"<a>text</a>"[..]);
+    /// reader.next().unwrap();
+    /// reader.next().unwrap();
+    /// assert_eq!(reader.next().unwrap(), XmlEvent::Characters("text".into()));
+    /// ```
+    pub fn from_reader(source: R) -> Self {
+        Self::from_reader_with_config(source, ParserConfig2::new())
+    }
+
+    /// Creates a new reader with the provided configuration which reads the document from
+    /// `source`, see `from_reader`.
+    pub fn from_reader_with_config(source: R, config: impl Into<ParserConfig2>) -> Self {
+        let source = ReadSource { inner: source, buf: vec![0; BUFFER_SIZE].into_boxed_slice(), pos: 0, len: 0, error: None };
+        EventReader::new_with_config(source, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::string::ToString;
+    use std::io::{self, Read};
+
+    use crate::reader::{ErrorKind, EventReader, XmlEvent};
+
+    /// Gives out at most `chunk` bytes per read, then fails after `fail_at` bytes.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        fail_at: usize,
+        interrupted: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if self.fail_at == 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "connection reset"));
+            }
+            let len = self.chunk.min(buf.len()).min(self.data.len()).min(self.fail_at);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            self.fail_at -= len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn from_reader() {
+        let doc = "<a b='\u{e9}'>\u{1F600} text<c/></a>".as_bytes();
+        let expected: alloc::vec::Vec<_> = EventReader::new(doc.iter()).into_iter().map(Result::unwrap).collect();
+        for chunk in 1..4 {
+            let source = Trickle { data: doc, chunk, fail_at: usize::MAX, interrupted: false };
+            let events: alloc::vec::Vec<_> = EventReader::from_reader(source).into_iter().map(Result::unwrap).collect();
+            assert_eq!(events, expected);
+        }
+
+        // surrogate pairs split across reads
+        let utf16: alloc::vec::Vec<u8> = "\u{feff}<a b='\u{e9}'>\u{1F600} text<c/></a>".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        for chunk in 1..4 {
+            let source = Trickle { data: &utf16, chunk, fail_at: usize::MAX, interrupted: false };
+            let events: alloc::vec::Vec<_> = EventReader::from_reader(source).into_iter().map(Result::unwrap).collect();
+            assert_eq!(events[1..], expected[1..]);
+        }
+
+        let source = Trickle { data: doc, chunk: 2, fail_at: 5, interrupted: false };
+        let mut reader = EventReader::from_reader(source);
+        assert!(matches!(reader.next(), Ok(XmlEvent::StartDocument { .. })));
+        let err = reader.next().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Io("connection reset".into()));
+        assert!(err.to_string().ends_with("connection reset"), "{err}");
+        assert_eq!(reader.next().unwrap_err(), err);
+        assert_eq!(reader.into_iter().count(), 1);
+    }
+}
@@ -1,25 +1,48 @@
 //! Contains simple lexer for XML documents.
 //!
-//! This module is for internal use. Use `xml::pull` module to do parsing.
+//! The lexer splits a document into `Token`s and reports the byte range each of them
+//! occupies in the source. It does no namespace processing and doesn't check
+//! well-formedness beyond token boundaries, which makes it suitable for tools that only
+//! need lexical structure, like syntax highlighters. `EventReader` is built on top of it.
+//! `Tokenizer` groups the tokens into `Lexeme`s like names, attribute values and runs of text.
+//!
+//! ```
+//! use xml_no_std::reader::ParserConfig2;
+//! use xml_no_std::reader::lexer::{Lexer, Token};
+//!
+//! let source = "<a>b</a>";
+//! let mut lexer = Lexer::new(&ParserConfig2::default());
+//! let mut bytes = source.as_bytes().iter();
+//! assert_eq!(lexer.next_token(&mut bytes).unwrap(), Some(Token::OpeningTagStart));
+//! assert_eq!(lexer.token_span(), 0..1);
+//! ```
 extern crate alloc;
 
 use alloc::string::String;
 
-use crate::reader::ErrorKind;
+use crate::reader::{ErrorKind, Limit};
 use crate::reader::error::SyntaxError;
 use alloc::collections::VecDeque;
 use core::fmt;
+use core::ops::Range;
 use core::result;
-use crate::common::{is_name_char, is_whitespace_char, Position, TextPosition, is_xml10_char, is_xml11_char};
+use crate::capacity::Text;
+use crate::chars::{is_name_char, is_whitespace_char, is_xml11_char, is_xml_char};
+use crate::common::{Position, TextPosition};
 use crate::reader::Error;
-use crate::util::{CharReader, Encoding};
+use crate::decode::{DecodeError, Decoder, Encoding};
+use crate::reader::{Source, SourceError};
 
 use super::ParserConfig2;
 
 /// `Token` represents a single lexeme of an XML document. These lexemes
 /// are used to perform actual parsing.
+///
+/// Text is reported one `Character` at a time, so runs of text, names and
+/// attribute values are sequences of `Character` tokens between the delimiters.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub(crate) enum Token {
+#[non_exhaustive]
+pub enum Token {
     /// `<?`
     ProcessingInstructionStart,
     /// `?>`
@@ -88,6 +111,8 @@ impl fmt::Display for Token {
 }
 
 impl Token {
+    /// Returns the source text of the token, unless it's a `Character`.
+    #[must_use]
     pub fn as_static_str(self) -> Option<&'static str> {
         match self {
             Token::OpeningTagStart            => Some("<"),
@@ -106,15 +131,17 @@ impl Token {
             Token::EqualsSign                 => Some("="),
             Token::SingleQuote                => Some("'"),
             Token::DoubleQuote                => Some("\""),
+            Token::MarkupDeclarationStart     => Some("<!"),
             _                                 => None
         }
     }
 
+    /// Appends the source text of the token to `target`.
     // using String.push_str(token.to_string()) is simply way too slow
-    pub fn push_to_string(self, target: &mut String) {
+    pub fn push_to_string<T: Text>(self, target: &mut T) {
         match self {
             Token::Character(c) => {
-                debug_assert!(is_xml10_char(c) || is_xml11_char(c));
+                debug_assert!(is_xml_char(c) || is_xml11_char(c));
                 target.push(c);
             },
             _ => if let Some(s) = self.as_static_str() {
@@ -124,7 +151,7 @@ impl Token {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 enum State {
     /// Default state
     Normal,
@@ -162,30 +189,30 @@ enum State {
     InsideMarkupDeclarationQuotedString(QuoteStyle),
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 enum QuoteStyle {
     Single, Double
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 enum ClosingSubstate {
     First, Second
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 enum DoctypeStartedSubstate {
     D, DO, DOC, DOCT, DOCTY, DOCTYP
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 enum CDataStartedSubstate {
     E, C, CD, CDA, CDAT, CDATA
 }
 
 /// `Result` represents lexing result. It is either a token or an error message.
-pub(crate) type Result<T = Option<Token>, E = Error> = result::Result<T, E>;
+pub type Result<T = Option<Token>, E = Error> = result::Result<T, E>;
 
 /// Helps to set up a dispatch table for lexing large unambigous tokens like
 /// `<![CDATA[` or `<!DOCTYPE `.
@@ -208,31 +235,147 @@ macro_rules! dispatch_on_enum_state(
     )
 );
 
+/// Bytes which `Lexer::plain_text` takes at once: all but `<`, `&` and `]`, which may start
+/// markup, line endings, which start a new row, and the ASCII controls, which are errors.
+const PLAIN_TEXT: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut b = 0;
+    while b < table.len() {
+        table[b] = !matches!(b as u8, b'<' | b'&' | b']' | b'\n' | b'\r' | 0..=0x08 | 0x0B..=0x1F | 0x7F);
+        b += 1;
+    }
+    table
+};
+
 /// `Lexer` is a lexer for XML documents, which implements pull API.
 ///
-/// Main method is `next_token` which accepts an `std::io::Read` instance and
-/// tries to read the next lexeme from it.
+/// Main method is `next_token` which accepts a byte iterator and
+/// tries to read the next lexeme from it. The byte range of the token
+/// in the source is available with `token_span` afterwards.
 ///
 /// When `skip_errors` flag is set, invalid lexemes will be returned as `Chunk`s.
 /// When it is not set, errors will be reported as `Err` objects with a string message.
 /// By default this flag is not set. Use `enable_errors` and `disable_errors` methods
 /// to toggle the behavior.
-pub(crate) struct Lexer {
+pub struct Lexer {
     st: State,
-    reader: CharReader,
+    decoder: Decoder,
     pos: TextPosition,
     head_pos: TextPosition,
-    char_queue: VecDeque<char>,
+    /// Bytes read from the source so far
+    head_offset: usize,
+    /// Offset at which the current token starts
+    token_start: usize,
+    /// Chars to dispatch before reading more, with their width in the source
+    char_queue: VecDeque<(char, u8)>,
+    /// Sum of the widths in `char_queue`
+    queued_width: usize,
+    /// Source widths of the last two dispatched chars, the last one at the end
+    last_widths: [u8; 2],
     /// Default state to go back to after a tag end (may be `InsideDoctype`)
     normal_state: State,
     inside_token: bool,
     eof_handled: bool,
+    decoded: bool,
     reparse_depth: u8,
     #[cfg(test)]
     skip_errors: bool,
 
     max_entity_expansion_depth: u8,
     max_entity_expansion_length: usize,
+    max_document_size: Option<u64>,
+}
+
+/// Where the lexer takes chars from
+pub(crate) trait CharSource {
+    /// Chars come already decoded, bypassing `Decoder`
+    const DECODED: bool;
+
+    /// Returns the next char, and how many bytes of the source were taken for it
+    fn next_char(&mut self, decoder: &mut Decoder) -> (result::Result<Option<char>, CharError>, usize);
+
+    /// Returns UTF-8 bytes which can be taken at once, or none if the source is in another encoding
+    #[inline]
+    fn buffered_utf8(&self, _decoder: &Decoder) -> &[u8] {
+        &[]
+    }
+
+    /// Takes the first `n` bytes of those `buffered_utf8` returned
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        debug_assert_eq!(n, 0);
+    }
+}
+
+/// Why there's no next char
+pub(crate) enum CharError {
+    Decode(DecodeError),
+    Source(SourceError),
+}
+
+/// Bytes of a `Source` decoded by `Decoder`
+pub(crate) struct Bytes<'s, S>(pub &'s mut S);
+
+impl<S: Source> CharSource for Bytes<'_, S> {
+    const DECODED: bool = S::DECODED;
+
+    #[inline]
+    fn next_char(&mut self, decoder: &mut Decoder) -> (result::Result<Option<char>, CharError>, usize) {
+        if S::DECODED {
+            return match self.0.next_char() {
+                Some(Ok(c)) => (Ok(Some(c)), c.len_utf8()),
+                Some(Err(e)) => (Err(CharError::Source(e)), 0),
+                None => (Ok(None), 0),
+            };
+        }
+        let mut counted = CountingSource { inner: &mut *self.0, count: 0, error: None };
+        let c = decoder.next_char(&mut counted);
+        let c = match counted.error {
+            // the bytes ended because of it
+            Some(e) => Err(CharError::Source(e)),
+            None => c.map_err(CharError::Decode),
+        };
+        (c, counted.count)
+    }
+
+    #[inline]
+    fn buffered_utf8(&self, decoder: &Decoder) -> &[u8] {
+        match decoder.encoding() {
+            _ if S::DECODED => &[],
+            Encoding::Utf8 | Encoding::Default => self.0.buffered(),
+            _ => &[],
+        }
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        self.0.consume(n);
+    }
+}
+
+/// Counts bytes taken from the source, and ends them at its error
+struct CountingSource<'s, S> {
+    inner: &'s mut S,
+    count: usize,
+    error: Option<SourceError>,
+}
+
+impl<S: Source> Iterator for CountingSource<'_, S> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        match self.inner.next_byte()? {
+            Ok(b) => {
+                self.count += 1;
+                Some(b)
+            },
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
 }
 
 impl Position for Lexer {
@@ -243,51 +386,146 @@ impl Position for Lexer {
 
 impl Lexer {
     /// Returns a new lexer with default state.
-    pub(crate) fn new(config: &ParserConfig2) -> Lexer {
+    ///
+    /// Only the entity expansion and document size limits are taken from the config.
+    #[must_use]
+    pub fn new(config: &ParserConfig2) -> Lexer {
         Lexer {
-            reader: CharReader::new(),
+            decoder: Decoder::new(),
             pos: TextPosition::new(),
             head_pos: TextPosition::new(),
+            head_offset: 0,
+            token_start: 0,
             char_queue: VecDeque::with_capacity(4),  // TODO: check size
+            queued_width: 0,
+            last_widths: [0; 2],
             st: State::Normal,
             normal_state: State::Normal,
             inside_token: false,
             eof_handled: false,
+            decoded: false,
             reparse_depth: 0,
             #[cfg(test)]
             skip_errors: false,
 
             max_entity_expansion_depth: config.max_entity_expansion_depth,
             max_entity_expansion_length: config.max_entity_expansion_length,
+            max_document_size: config.max_document_size,
         }
     }
 
-    pub(crate) fn encoding(&mut self) -> Encoding {
-        self.reader.encoding
+    /// Returns the encoding used for decoding the source.
+    #[must_use]
+    pub fn encoding(&self) -> Encoding {
+        self.decoder.encoding()
+    }
+
+    /// Sets the encoding used for decoding the source.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        debug!("encoding set to {}", encoding);
+        self.decoder.set_encoding(encoding);
+    }
+
+    /// Returns the byte range in the source of the token last returned by `next_token`.
+    ///
+    /// Offsets count bytes of the source as it was given to the lexer, so they index into
+    /// the original buffer whatever the encoding. A byte order mark isn't part of any token.
+    /// Tokens produced from entity replacement text, which the event parser feeds back into
+    /// the lexer, have an empty range positioned after the reference.
+    #[inline]
+    #[must_use]
+    pub fn token_span(&self) -> Range<usize> {
+        self.token_start..self.head_offset - self.queued_width
+    }
+
+    /// Returns the number of bytes taken from the source so far.
+    #[inline]
+    #[must_use]
+    pub fn bytes_read(&self) -> u64 {
+        self.head_offset as u64
+    }
+
+    #[inline]
+    fn push_width(&mut self, width: u8) {
+        self.last_widths = [self.last_widths[1], width];
+    }
+
+    /// Returns text at the head of the source which would be only `Character` tokens, up to
+    /// `max_len` bytes, if the source has it buffered. It's taken with `skip_text`.
+    ///
+    /// It's only in the `Normal` state between tokens, so it's up to the caller to know that
+    /// the lexer is in character data rather than in a tag. The text may still contain chars
+    /// above U+007F which are not allowed in the document.
+    pub(crate) fn plain_text<'s, S: CharSource>(&self, source: &'s S, max_len: usize) -> &'s str {
+        if !matches!((self.st, self.normal_state), (State::Normal, State::Normal)) ||
+            self.inside_token || self.eof_handled || !self.char_queue.is_empty() {
+            return "";
+        }
+        let mut max_len = max_len;
+        if let Some(max) = self.max_document_size {
+            let left = max.saturating_sub(self.bytes_read());
+            max_len = max_len.min(usize::try_from(left).unwrap_or(usize::MAX));
+        }
+        let bytes = source.buffered_utf8(&self.decoder);
+        let bytes = &bytes[..bytes.len().min(max_len)];
+        let len = bytes.iter().position(|&b| !PLAIN_TEXT[usize::from(b)]).unwrap_or(bytes.len());
+        // a char cut off by the end of the buffer or `max_len`, or invalid UTF-8, is left to `next_token`
+        match core::str::from_utf8(&bytes[..len]) {
+            Ok(text) => text,
+            Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        }
     }
 
-    pub(crate) fn set_encoding(&mut self, encoding: Encoding) {
-        self.reader.encoding = encoding;
+    /// Takes `text`, which is a prefix of what `plain_text` returned, from the source, as if
+    /// it had been read as `Character` tokens.
+    pub(crate) fn skip_text<S: CharSource>(&mut self, source: &mut S, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        source.consume(text.len());
+        self.pos = self.head_pos;
+        self.token_start = self.head_offset;
+        self.head_offset += text.len();
+        let chars = if text.is_ascii() { text.len() } else { text.chars().count() };
+        self.head_pos.column += chars as u64;
+        let mut last = text.chars().rev().map(|c| c.len_utf8() as u8);
+        let width = last.next().unwrap_or(0);
+        self.last_widths = [last.next().unwrap_or(0), width];
     }
 
     /// Disables error handling so `next_token` will return `Some(Chunk(..))`
     /// upon invalid lexeme with this lexeme content.
     #[cfg(test)] fn disable_errors(&mut self) { self.skip_errors = true; }
 
+    /// Whether the source is read as chars, so the encoding doesn't apply
+    #[inline]
+    pub(crate) fn is_decoded(&self) -> bool { self.decoded }
+
     /// Reset the eof handled flag of the lexer.
     #[inline]
-    pub fn reset_eof_handled(&mut self) { self.eof_handled = false; }
+    pub(crate) fn reset_eof_handled(&mut self) { self.eof_handled = false; }
 
     /// Tries to read the next token from the buffer.
     ///
-    /// It is possible to pass different instaces of `BufReader` each time
+    /// It is possible to pass different iterators each time
     /// this method is called, but the resulting behavior is undefined in this case.
     ///
     /// Return value:
     /// * `Err(reason) where reason: reader::Error` - when an error occurs;
     /// * `Ok(None)` - upon end of stream is reached;
     /// * `Ok(Some(token)) where token: Token` - in case a complete-token has been read from the stream.
-    pub fn next_token<'a, S: Iterator<Item = &'a u8>>(&mut self, b: &mut S) -> Result {
+    #[inline]
+    pub fn next_token<S: Source>(&mut self, b: &mut S) -> Result {
+        self.next_token_from(&mut Bytes(b))
+    }
+
+    pub(crate) fn next_token_from<S: CharSource>(&mut self, source: &mut S) -> Result {
+        if S::DECODED && !self.decoded {
+            // offsets count the chars in UTF-8
+            self.decoded = true;
+            self.decoder.set_encoding(Encoding::Utf8);
+        }
+
         // Already reached end of buffer
         if self.eof_handled {
             return Ok(None);
@@ -295,11 +533,14 @@ impl Lexer {
 
         if !self.inside_token {
             self.pos = self.head_pos;
+            self.token_start = self.head_offset - self.queued_width;
             self.inside_token = true;
         }
 
         // Check if we have saved a char or two for ourselves
-        while let Some(c) = self.char_queue.pop_front() {
+        while let Some((c, width)) = self.char_queue.pop_front() {
+            self.queued_width -= usize::from(width);
+            self.push_width(width);
             if let Some(t) = self.dispatch_char(c)? {
                 self.inside_token = false;
                 return Ok(Some(t));
@@ -308,10 +549,36 @@ impl Lexer {
         // if char_queue is empty, all circular reparsing is done
         self.reparse_depth = 0;
         loop {
-            let c = match self.reader.next_char_from(b)? {
-                Some(c) => c,  // got next char
-                None => break, // nothing to read left
+            let (c, width) = source.next_char(&mut self.decoder);
+            self.head_offset += width;
+            if let Some(max) = self.max_document_size {
+                if self.bytes_read() > max {
+                    debug!("max_document_size of {} exceeded at {}", max, self.head_pos);
+                    return Err(Error {
+                        pos: self.head_pos,
+                        kind: ErrorKind::LimitExceeded(Limit::DocumentSize),
+                        encoding: None,
+                    });
+                }
+            }
+            let c = match c {
+                Ok(Some(c)) => c,  // got next char
+                Ok(None) => break, // nothing to read left
+                Err(e) => return Err(self.decoding_error(e)),
             };
+            if self.head_offset == width {
+                // a byte order mark is taken with the first char, and the token starts after it
+                self.token_start = match self.decoder.encoding() {
+                    _ if S::DECODED => if c == '\u{feff}' { width } else { 0 },
+                    Encoding::Utf16Be | Encoding::Utf16Le => width - 2 * c.len_utf16(),
+                    Encoding::Latin1 | Encoding::Ascii => 0,
+                    _ => width - c.len_utf8(),
+                };
+            }
+            if S::DECODED && c == '\u{feff}' && self.head_offset == width {
+                continue; // BOM
+            }
+            self.push_width(width as u8);
 
             if c == '\n' {
                 self.head_pos.new_line();
@@ -359,9 +626,29 @@ impl Lexer {
 
     #[cold]
     fn error(&self, e: SyntaxError) -> Error {
+        debug!("syntax error at {}: {}", self.position(), e);
         Error {
             pos: self.position(),
             kind: ErrorKind::Syntax(e.to_cow()),
+            encoding: None,
+        }
+    }
+
+    #[cold]
+    fn decoding_error(&self, e: CharError) -> Error {
+        match e {
+            CharError::Decode(e) => {
+                debug!("decoding error at {} as {}: {}", self.head_pos, self.decoder.encoding(), e);
+                Error {
+                    pos: self.head_pos,
+                    encoding: Some(self.decoder.encoding()),
+                    ..e.into()
+                }
+            },
+            CharError::Source(e) => {
+                debug!("source error at {}: {}", self.head_pos, e);
+                Error { pos: self.head_pos, ..e.into() }
+            },
         }
     }
 
@@ -391,26 +678,33 @@ impl Lexer {
 
     #[inline]
     fn move_to(&mut self, st: State) -> Result {
+        trace!("{:?} -> {:?}", self.st, st);
         self.st = st;
         Ok(None)
     }
 
     #[inline]
     fn move_to_with(&mut self, st: State, token: Token) -> Result {
+        trace!("{:?} -> {:?} with {}", self.st, st, token);
         self.st = st;
         Ok(Some(token))
     }
 
     #[inline]
     fn move_to_and_reset_normal(&mut self, st: State, token: Token) -> Result {
+        trace!("{:?} -> {:?} with {}, now normal", self.st, st, token);
         self.normal_state = st;
         self.st = st;
         Ok(Some(token))
     }
 
+    /// `cs` are the last dispatched chars, to be dispatched again
     fn move_to_with_unread(&mut self, st: State, cs: &[char], token: Token) -> Result {
-        for c in cs.iter().rev().copied() {
-            self.char_queue.push_front(c);
+        debug_assert!(cs.len() <= self.last_widths.len());
+        let widths = &self.last_widths[self.last_widths.len() - cs.len()..];
+        for (&c, &width) in cs.iter().zip(widths).rev() {
+            self.char_queue.push_front((c, width));
+            self.queued_width += usize::from(width);
         }
         self.move_to_with(st, token)
     }
@@ -421,14 +715,22 @@ impl Lexer {
         }
 
         self.reparse_depth += 1;
+        trace!("reparsing {:?} at depth {}", markup, self.reparse_depth);
         if self.reparse_depth > self.max_entity_expansion_depth || self.char_queue.len() > self.max_entity_expansion_length {
             return Err(self.error(SyntaxError::EntityTooBig))
         }
 
         self.eof_handled = false;
+        #[cfg(feature = "fallible-alloc")]
+        self.char_queue.try_reserve(markup.len()).map_err(|_| Error {
+            pos: self.position(),
+            kind: ErrorKind::OutOfMemory,
+            encoding: None,
+        })?;
+        #[cfg(not(feature = "fallible-alloc"))]
         self.char_queue.reserve(markup.len());
         for c in markup.chars().rev() {
-            self.char_queue.push_front(c);
+            self.char_queue.push_front((c, 0));
         }
 
         Ok(())
@@ -441,8 +743,8 @@ impl Lexer {
         if self.skip_errors {
             let mut chars = chunk.chars();
             let first = chars.next().unwrap_or('\0');
-            self.char_queue.extend(chars);
-            self.char_queue.push_back(c);
+            self.char_queue.extend(chars.map(|c| (c, 0)));
+            self.char_queue.push_back((c, 0));
             return self.move_to_with(State::Normal, Token::Character(first));
         }
         Err(self.error(SyntaxError::UnexpectedTokenBefore(chunk, c)))
@@ -477,7 +779,6 @@ impl Lexer {
             '?'                        => self.move_to(State::ProcessingInstructionClosing),
             '<'                        => Ok(Some(Token::OpeningTagStart)),
             '>'                        => Ok(Some(Token::TagEnd)),
-            '/'                        => Ok(Some(Token::ClosingTagStart)),
             '='                        => Ok(Some(Token::EqualsSign)),
             '"'                        => Ok(Some(Token::DoubleQuote)),
             '\''                       => Ok(Some(Token::SingleQuote)),
@@ -647,8 +948,367 @@ impl Lexer {
     }
 }
 
+/// What a `Lexeme` is, see `Tokenizer`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum LexemeKind {
+    /// A delimiter: `<`, `</`, `>`, `/>`, `=`, a quote around an attribute value, or the start
+    /// or end of a comment, CDATA section, processing instruction or `<!DOCTYPE`
+    Delimiter(Token),
+    /// An element or attribute name, or the target of a processing instruction
+    Name,
+    /// An attribute value between its quotes, with references as they're written
+    AttributeValue,
+    /// Character data between markup, with references as they're written
+    Text,
+    /// The text of a comment
+    Comment,
+    /// The text of a CDATA section
+    CData,
+    /// A processing instruction after its target
+    ProcessingInstructionData,
+    /// Everything between `<!DOCTYPE` and its `>`, with the internal subset
+    Doctype,
+}
+
+/// A lexical token with the byte range it occupies in the source, see `Tokenizer`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Lexeme {
+    /// What it is
+    pub kind: LexemeKind,
+    /// Offsets in the source, like those of `Lexer::token_span`
+    pub span: Range<usize>,
+}
+
+/// What the tokens a `Tokenizer` reads are part of
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Context {
+    Text,
+    Tag,
+    /// Holds the opening quote
+    AttributeValue(Token),
+    Comment,
+    CData,
+    ProcessingInstructionTarget,
+    ProcessingInstruction,
+    /// Holds the number of markup declarations inside which are still open
+    Doctype(usize),
+}
+
+/// What a `Tokenizer` does with a token
+enum Step {
+    Run(LexemeKind),
+    Delimiter,
+    Skip,
+}
+
+/// Groups the `Token`s of a `Lexer` into `Lexeme`s: names, attribute values and runs of text,
+/// with the delimiters between them, each with the byte range it occupies in the source.
+///
+/// Like the lexer, it knows only as much of XML as it takes to tell what a token is part of,
+/// so it doesn't resolve namespaces nor check well-formedness. Whitespace between the names
+/// and attributes of a tag isn't part of any lexeme. The text of the last lexeme, decoded,
+/// is available with `text`.
+///
+/// ```
+/// use xml_no_std::reader::ParserConfig2;
+/// use xml_no_std::reader::lexer::{LexemeKind, Tokenizer};
+///
+/// let source = "<a href='x.html'>link</a>";
+/// let mut tokenizer = Tokenizer::new(&ParserConfig2::default());
+/// let mut bytes = source.as_bytes().iter();
+/// let mut names = Vec::new();
+/// while let Some(lexeme) = tokenizer.next_lexeme(&mut bytes).unwrap() {
+///     match lexeme.kind {
+///         LexemeKind::Name => names.push(&source[lexeme.span]),
+///         LexemeKind::AttributeValue => assert_eq!(tokenizer.text(), "x.html"),
+///         _ => {},
+///     }
+/// }
+/// assert_eq!(names, ["a", "href", "a"]);
+/// ```
+pub struct Tokenizer {
+    lexer: Lexer,
+    context: Context,
+    /// The kind and span of the run being read, if there's one
+    run: Option<(LexemeKind, Range<usize>)>,
+    /// A delimiter which ended the run, to be returned next
+    pending: Option<(Token, Range<usize>)>,
+    text: String,
+}
+
+impl Tokenizer {
+    /// Returns a new tokenizer, with a `Lexer` made from the config.
+    #[must_use]
+    pub fn new(config: &ParserConfig2) -> Self {
+        Tokenizer {
+            lexer: Lexer::new(config),
+            context: Context::Text,
+            run: None,
+            pending: None,
+            text: String::new(),
+        }
+    }
+
+    /// Returns the lexer, for its encoding and position.
+    #[inline]
+    #[must_use]
+    pub fn lexer(&self) -> &Lexer {
+        &self.lexer
+    }
+
+    /// Returns the text of the lexeme last returned by `next_lexeme`, decoded.
+    #[inline]
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Reads the next lexeme from the source, or returns `None` at the end of the document.
+    ///
+    /// Like with `Lexer::next_token`, the source should be the same every time.
+    pub fn next_lexeme<S: Source>(&mut self, source: &mut S) -> Result<Option<Lexeme>> {
+        if let Some((token, span)) = self.pending.take() {
+            return Ok(Some(self.delimiter(token, span)));
+        }
+        loop {
+            let token = match self.lexer.next_token(source)? {
+                Some(token) => token,
+                None => return Ok(self.end_run()),
+            };
+            let span = self.lexer.token_span();
+            let in_attribute_value = matches!(self.context, Context::AttributeValue(_));
+            match self.step(token) {
+                Step::Run(kind) => match &mut self.run {
+                    Some((current, run)) => {
+                        debug_assert_eq!(*current, kind);
+                        run.end = span.end;
+                        token.push_to_string(&mut self.text);
+                    },
+                    None => {
+                        self.text.clear();
+                        token.push_to_string(&mut self.text);
+                        self.run = Some((kind, span));
+                    },
+                },
+                Step::Skip => if let Some(lexeme) = self.end_run() {
+                    return Ok(Some(lexeme));
+                },
+                Step::Delimiter => {
+                    if in_attribute_value && self.run.is_none() {
+                        // an empty value is a lexeme too
+                        self.text.clear();
+                        self.run = Some((LexemeKind::AttributeValue, span.start..span.start));
+                    }
+                    if let Some(lexeme) = self.end_run() {
+                        self.pending = Some((token, span));
+                        return Ok(Some(lexeme));
+                    }
+                    return Ok(Some(self.delimiter(token, span)));
+                },
+            }
+        }
+    }
+
+    fn end_run(&mut self) -> Option<Lexeme> {
+        self.run.take().map(|(kind, span)| Lexeme { kind, span })
+    }
+
+    fn delimiter(&mut self, token: Token, span: Range<usize>) -> Lexeme {
+        self.text.clear();
+        token.push_to_string(&mut self.text);
+        Lexeme { kind: LexemeKind::Delimiter(token), span }
+    }
+
+    /// Decides what a token is part of, and moves to the context after it
+    fn step(&mut self, token: Token) -> Step {
+        let is_whitespace = matches!(token, Token::Character(c) if is_whitespace_char(c));
+        match (self.context, token) {
+            (Context::Text | Context::Tag, Token::OpeningTagStart | Token::ClosingTagStart) => self.move_to(Context::Tag),
+            (Context::Text | Context::Tag, Token::CommentStart) => self.move_to(Context::Comment),
+            (Context::Text | Context::Tag, Token::CDataStart) => self.move_to(Context::CData),
+            (Context::Text | Context::Tag, Token::ProcessingInstructionStart) => self.move_to(Context::ProcessingInstructionTarget),
+            (Context::Text | Context::Tag, Token::DoctypeStart) => self.move_to(Context::Doctype(0)),
+            (Context::Text, _) => Step::Run(LexemeKind::Text),
+
+            (Context::Tag, Token::TagEnd | Token::EmptyTagEnd) => self.move_to(Context::Text),
+            (Context::Tag, Token::EqualsSign) => Step::Delimiter,
+            (Context::Tag, Token::SingleQuote | Token::DoubleQuote) => self.move_to(Context::AttributeValue(token)),
+            (Context::Tag, _) if is_whitespace => Step::Skip,
+            (Context::Tag, _) => Step::Run(LexemeKind::Name),
+
+            (Context::AttributeValue(quote), _) if token == quote => self.move_to(Context::Tag),
+            (Context::AttributeValue(_), _) => Step::Run(LexemeKind::AttributeValue),
+
+            (Context::Comment, Token::CommentEnd) | (Context::CData, Token::CDataEnd) |
+            (Context::ProcessingInstructionTarget | Context::ProcessingInstruction, Token::ProcessingInstructionEnd) => self.move_to(Context::Text),
+            (Context::Comment, _) => Step::Run(LexemeKind::Comment),
+            (Context::CData, _) => Step::Run(LexemeKind::CData),
+
+            (Context::ProcessingInstructionTarget, _) if is_whitespace => {
+                self.context = Context::ProcessingInstruction;
+                Step::Skip
+            },
+            (Context::ProcessingInstructionTarget, _) => Step::Run(LexemeKind::Name),
+            (Context::ProcessingInstruction, _) if is_whitespace && self.run.is_none() => Step::Skip,
+            (Context::ProcessingInstruction, _) => Step::Run(LexemeKind::ProcessingInstructionData),
+
+            (Context::Doctype(0), Token::TagEnd) => self.move_to(Context::Text),
+            (Context::Doctype(depth), Token::TagEnd) => {
+                self.context = Context::Doctype(depth - 1);
+                Step::Run(LexemeKind::Doctype)
+            },
+            (Context::Doctype(depth), Token::MarkupDeclarationStart) => {
+                self.context = Context::Doctype(depth + 1);
+                Step::Run(LexemeKind::Doctype)
+            },
+            (Context::Doctype(_), _) => Step::Run(LexemeKind::Doctype),
+        }
+    }
+
+    #[inline]
+    fn move_to(&mut self, context: Context) -> Step {
+        self.context = context;
+        Step::Delimiter
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use crate::reader::{CharIterSource, ParserConfig2};
+    use crate::decode::Encoding;
+
+    use super::{Lexer, Token};
+
+    fn tokens_with_text(lex: &mut Lexer, source: &[u8]) -> Vec<(Token, Vec<u8>)> {
+        let mut buf = source.iter();
+        let mut tokens = Vec::new();
+        while let Some(t) = lex.next_token(&mut buf).unwrap() {
+            tokens.push((t, source[lex.token_span()].to_vec()));
+        }
+        tokens
+    }
+
+    #[test]
+    fn token_spans() {
+        let mut lex = Lexer::new(&ParserConfig2::default());
+        let tokens = tokens_with_text(&mut lex, "<a b='é'>]]x</a>".as_bytes());
+        let expected: &[(Token, &[u8])] = &[
+            (Token::OpeningTagStart, b"<"),
+            (Token::Character('a'), b"a"),
+            (Token::Character(' '), b" "),
+            (Token::Character('b'), b"b"),
+            (Token::EqualsSign, b"="),
+            (Token::SingleQuote, b"'"),
+            (Token::Character('é'), "é".as_bytes()),
+            (Token::SingleQuote, b"'"),
+            (Token::TagEnd, b">"),
+            (Token::Character(']'), b"]"),
+            (Token::Character(']'), b"]"),
+            (Token::Character('x'), b"x"),
+            (Token::ClosingTagStart, b"</"),
+            (Token::Character('a'), b"a"),
+            (Token::TagEnd, b">"),
+        ];
+        assert_eq!(tokens.len(), expected.len());
+        for ((t, text), (et, etext)) in tokens.iter().zip(expected) {
+            assert_eq!((t, &text[..]), (et, *etext));
+        }
+    }
+
+    #[test]
+    fn token_spans_utf16() {
+        let mut lex = Lexer::new(&ParserConfig2::default());
+        lex.set_encoding(Encoding::Utf16);
+        // BOM, then `<x/>` in UTF-16LE
+        let source = [0xFF, 0xFE, b'<', 0, b'x', 0, b'/', 0, b'>', 0];
+        let mut buf = source.iter();
+        assert_eq!(lex.next_token(&mut buf).unwrap(), Some(Token::OpeningTagStart));
+        assert_eq!(lex.token_span(), 2..4);
+        assert_eq!(lex.next_token(&mut buf).unwrap(), Some(Token::Character('x')));
+        assert_eq!(lex.token_span(), 4..6);
+        assert_eq!(lex.next_token(&mut buf).unwrap(), Some(Token::EmptyTagEnd));
+        assert_eq!(lex.token_span(), 6..10);
+        assert_eq!(lex.bytes_read(), 10);
+    }
+
+    #[test]
+    fn lexemes() {
+        use super::{LexemeKind, Tokenizer};
+
+        let source = "<!DOCTYPE a [<!ENTITY e 'x>'>]><?pi  some data?><a b='1 &amp; 2' c=\"\">t&e;<!-- c --><![CDATA[<d>]]></a>";
+        let mut tokenizer = Tokenizer::new(&ParserConfig2::default());
+        let mut buf = source.as_bytes().iter();
+        let mut lexemes = Vec::new();
+        while let Some(lexeme) = tokenizer.next_lexeme(&mut buf).unwrap() {
+            assert_eq!(&source[lexeme.span.clone()], tokenizer.text());
+            lexemes.push((lexeme.kind, &source[lexeme.span]));
+        }
+        let delimiter = |t| (LexemeKind::Delimiter(t), t.as_static_str().unwrap());
+        assert_eq!(lexemes, [
+            delimiter(Token::DoctypeStart),
+            (LexemeKind::Doctype, " a [<!ENTITY e 'x>'>]"),
+            delimiter(Token::TagEnd),
+            delimiter(Token::ProcessingInstructionStart),
+            (LexemeKind::Name, "pi"),
+            (LexemeKind::ProcessingInstructionData, "some data"),
+            delimiter(Token::ProcessingInstructionEnd),
+            delimiter(Token::OpeningTagStart),
+            (LexemeKind::Name, "a"),
+            (LexemeKind::Name, "b"),
+            delimiter(Token::EqualsSign),
+            delimiter(Token::SingleQuote),
+            (LexemeKind::AttributeValue, "1 &amp; 2"),
+            delimiter(Token::SingleQuote),
+            (LexemeKind::Name, "c"),
+            delimiter(Token::EqualsSign),
+            delimiter(Token::DoubleQuote),
+            (LexemeKind::AttributeValue, ""),
+            delimiter(Token::DoubleQuote),
+            delimiter(Token::TagEnd),
+            (LexemeKind::Text, "t&e;"),
+            delimiter(Token::CommentStart),
+            (LexemeKind::Comment, " c "),
+            delimiter(Token::CommentEnd),
+            delimiter(Token::CDataStart),
+            (LexemeKind::CData, "<d>"),
+            delimiter(Token::CDataEnd),
+            delimiter(Token::ClosingTagStart),
+            (LexemeKind::Name, "a"),
+            delimiter(Token::TagEnd),
+        ]);
+
+        // spans index into the raw bytes, after the BOM
+        let source: Vec<u8> = "\u{feff}<a>\u{e9}</a>".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let mut tokenizer = Tokenizer::new(&ParserConfig2::default());
+        let mut buf = source.iter();
+        let mut lexemes = Vec::new();
+        while let Some(lexeme) = tokenizer.next_lexeme(&mut buf).unwrap() {
+            lexemes.push((lexeme.kind, lexeme.span, tokenizer.text().to_owned()));
+        }
+        assert_eq!(lexemes[2], (LexemeKind::Delimiter(Token::TagEnd), 6..8, ">".to_owned()));
+        assert_eq!(lexemes[3], (LexemeKind::Text, 8..10, "\u{e9}".to_owned()));
+    }
+
+    #[test]
+    fn token_spans_after_bom() {
+        let source = "\u{feff}<a/>";
+        let mut lex = Lexer::new(&ParserConfig2::default());
+        let mut buf = source.as_bytes().iter();
+        assert_eq!(lex.next_token(&mut buf).unwrap(), Some(Token::OpeningTagStart));
+        assert_eq!(lex.token_span(), 3..4);
+        assert_eq!(lex.next_token(&mut buf).unwrap(), Some(Token::Character('a')));
+        assert_eq!(lex.token_span(), 4..5);
+
+        let mut lex = Lexer::new(&ParserConfig2::default());
+        assert_eq!(lex.next_token(&mut CharIterSource::new(source.chars())).unwrap(), Some(Token::OpeningTagStart));
+        assert_eq!(lex.token_span(), 3..4);
+    }
+
     // use crate::{common::Position, reader::ParserConfig2};
     // use std::io::{BufReader, Cursor};
 
@@ -4,23 +4,27 @@ extern crate alloc;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
-use crate::attribute::OwnedAttribute;
-use crate::common::{is_xml10_char, is_xml11_char, is_xml11_char_not_restricted, is_name_char, is_name_start_char, is_whitespace_char};
+use crate::attribute::{Attributes, OwnedAttribute};
+use crate::capacity::{Capacity, Stack, Text, Unbounded};
+use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char, is_xml11_char, is_xml11_char_not_restricted, is_xml_char};
 use crate::common::{Position, TextPosition, XmlVersion};
-use crate::name::OwnedName;
-use crate::namespace::NamespaceStack;
+use crate::name::{check_qname, InternedName, NameError, NameInterner, OwnedName, XML_SPACE};
+use crate::namespace::{self, NamespaceStack};
 use crate::reader::config::ParserConfig2;
-use crate::reader::error::SyntaxError;
+use crate::reader::doctype::Doctype;
+use crate::reader::error::{Limit, SyntaxError};
 use crate::reader::events::XmlEvent;
-use crate::reader::lexer::{Lexer, Token};
+use crate::reader::lexer::{CharSource, Lexer, Token};
+use crate::decode::Encoding;
 use super::{Error, ErrorKind};
 
-use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::collections::BTreeMap;
+use core::ops::Range;
 
 macro_rules! gen_takes(
     ($($field:ident -> $method:ident, $t:ty, $def:expr);+) => (
         $(
-        impl MarkupData {
+        impl<A: Default> MarkupData<A> {
             #[inline]
             #[allow(clippy::mem_replace_option_with_none)]
             fn $method(&mut self) -> $t {
@@ -40,7 +44,7 @@ gen_takes!(
     element_name -> take_element_name, Option<OwnedName>, None;
 
     attr_name    -> take_attr_name, Option<OwnedName>, None;
-    attributes   -> take_attributes, BTreeSet<OwnedAttribute>, BTreeSet::new()
+    attributes   -> take_attributes, A, A::default()
 );
 
 mod inside_cdata;
@@ -56,34 +60,131 @@ mod outside_tag;
 static DEFAULT_VERSION: XmlVersion = XmlVersion::Version10;
 static DEFAULT_STANDALONE: Option<bool> = None;
 
-type ElementStack = Vec<OwnedName>;
 pub type Result = super::Result<XmlEvent>;
 
-/// Pull-based XML parser.
-pub(crate) struct PullParser {
+/// Pull-based XML parser, which keeps its stacks and text buffer in `C`.
+pub(crate) struct PullParser<C: Capacity = Unbounded> {
     config: ParserConfig2,
     lexer: Lexer,
     st: State,
     state_after_reference: State,
-    buf: String,
+    buf: C::Text,
+    /// Spare buffers to replace `buf` when its text is taken
+    pool: BufferPool,
 
     /// From DTD internal subset
     entities: BTreeMap<String, String>,
+    /// Attributes declared in DTD internal subset, by element name
+    attribute_declarations: BTreeMap<String, Vec<AttributeDeclaration>>,
+    /// Source text of `<!DOCTYPE` read so far, for `capture_doctype`
+    doctype_raw: String,
+    doctype: Option<Doctype>,
 
-    nst: NamespaceStack,
+    nst: NamespaceStack<C::Namespaces>,
 
-    data: MarkupData,
+    data: MarkupData<C::Attributes>,
     final_result: Option<Result>,
     next_event: Option<Result>,
-    est: ElementStack,
+    /// The open elements, and whether `xml:space="preserve"` is in effect in each
+    est: C::Elements,
     pos: Vec<TextPosition>,
 
+    /// The attributes of the last start tag as they're written, see `ParserConfig2::lazy_attributes`
+    raw_attributes: RawAttributeList,
+    /// Shared copies of names, see `ParserConfig2::intern_names`
+    names: NameInterner,
+    /// The interned name of the last `StartElement` or `EndElement`
+    element_name: Option<InternedName>,
+    /// The interned names of the attributes of the last start tag
+    attribute_names: Vec<InternedName>,
+    /// Source offsets of the last event, see `last_event_span`
+    span: Range<usize>,
+    /// Source offsets of `next_event`
+    next_span: Range<usize>,
+    /// Source offset of the start of the markup being parsed, or last parsed
+    markup_start: usize,
+    /// Source offset after the last markup, where text starts
+    markup_end: usize,
+    /// Inside markup which doesn't belong to text, so it ends where text may start
+    inside_markup: bool,
+    /// Source offset and position of the last reference in text, where a chunk may end
+    reference_start: (usize, TextPosition),
+    /// Source offset where the text of the event being emitted ends, if it's a chunk
+    chunk_end: Option<usize>,
+    /// The text being read has been split into chunks, see `characters_chunk_size`
+    chunked: bool,
+
     encountered: Encountered,
     inside_whitespace: bool,
-    read_prefix_separator: bool,
     pop_namespace: bool,
 }
 
+/// Strings which were taken from the parser's `buf` and are done with, kept for their
+/// capacity, so that parsing a name or dropping whitespace doesn't cost an allocation.
+///
+/// Buffers whose text becomes an event's aren't returned, the pool only saves the
+/// allocations which would be dropped right away.
+#[derive(Default)]
+struct BufferPool(Vec<String>);
+
+impl BufferPool {
+    /// Enough for the names of a start tag with a few attributes, also when they're interned
+    /// and each part of a name is a buffer
+    const MAX_BUFFERS: usize = 16;
+
+    /// Returns an empty buffer, with some capacity if one was returned before
+    #[inline]
+    fn take(&mut self) -> String {
+        self.0.pop().unwrap_or_default()
+    }
+
+    /// Keeps `buf` for reuse, unless there are enough already
+    #[inline]
+    fn give(&mut self, mut buf: String) {
+        if buf.capacity() > 0 && self.0.len() < Self::MAX_BUFFERS {
+            buf.clear();
+            self.0.push(buf);
+        }
+    }
+
+    /// Returns a copy of `s` in a buffer from the pool
+    #[inline]
+    fn copy(&mut self, s: &str) -> String {
+        let mut buf = self.take();
+        buf.push_str(s);
+        buf
+    }
+
+    /// Parses a qualified name into buffers from the pool, see `ParserConfig2::intern_names`
+    fn parse_name(&mut self, s: &str) -> core::result::Result<OwnedName, NameError> {
+        check_qname(s)?;
+        let (prefix, local_name) = match s.split_once(':') {
+            Some((prefix, local_name)) => (Some(self.copy(prefix)), local_name),
+            None => (None, s),
+        };
+        Ok(OwnedName { local_name: self.copy(local_name), namespace: None, prefix })
+    }
+
+    /// Keeps the buffers of a name which has been interned
+    fn give_name(&mut self, name: OwnedName) {
+        let OwnedName { local_name, namespace, prefix } = name;
+        self.give(local_name);
+        namespace.into_iter().chain(prefix).for_each(|buf| self.give(buf));
+    }
+}
+
+/// The names of events when they're interned, see `ParserConfig2::intern_names`
+const EMPTY_NAME: OwnedName = OwnedName { local_name: String::new(), namespace: None, prefix: None };
+
+/// An attribute declared with `<!ATTLIST`, for `apply_attribute_defaults`
+struct AttributeDeclaration {
+    name: OwnedName,
+    /// Raw default value, `None` for `#REQUIRED` and `#IMPLIED`
+    default: Option<String>,
+    /// Whether the type is other than `CDATA`, e.g. `ID` or `NMTOKENS`, so spaces in values collapse
+    tokenized: bool,
+}
+
 // Keeps track when XML declaration can happen
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Encountered {
@@ -95,32 +196,42 @@ enum Encountered {
     Element,
 }
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     /// Returns a new parser using the given config.
     #[inline]
-    pub fn new(config: impl Into<ParserConfig2>) -> PullParser {
+    pub fn new(config: impl Into<ParserConfig2>) -> Self {
         let config = config.into();
         Self::new_with_config2(config)
     }
 
     #[inline]
-    fn new_with_config2(config: ParserConfig2) -> PullParser {
+    fn new_with_config2(mut config: ParserConfig2) -> Self {
+        if config.characters_chunk_size.is_some() {
+            config.c.coalesce_characters = false;
+        }
         let mut lexer = Lexer::new(&config);
         if let Some(enc) = config.override_encoding {
+            debug!("encoding overridden to {}", enc);
             lexer.set_encoding(enc);
         }
 
         let mut pos = Vec::with_capacity(16);
         pos.push(TextPosition::new());
+        let mut nst = NamespaceStack(C::Namespaces::default());
+        nst.push_default();
 
         PullParser {
             config,
             lexer,
             st: State::DocumentStart,
             state_after_reference: State::OutsideTag,
-            buf: String::new(),
+            buf: C::Text::default(),
+            pool: BufferPool::default(),
             entities: BTreeMap::new(),
-            nst: NamespaceStack::default(),
+            attribute_declarations: BTreeMap::new(),
+            doctype_raw: String::new(),
+            doctype: None,
+            nst,
 
             data: MarkupData {
                 name: String::new(),
@@ -131,20 +242,72 @@ impl PullParser {
                 element_name: None,
                 quote: None,
                 attr_name: None,
-                attributes: BTreeSet::new(),
+                attributes: C::Attributes::default(),
+                attr_value_start: 0,
+                raw_attributes: RawAttributeList::default(),
             },
             final_result: None,
             next_event: None,
-            est: Vec::new(),
+            est: C::Elements::default(),
             pos,
 
+            raw_attributes: RawAttributeList::default(),
+            names: NameInterner::new(),
+            element_name: None,
+            attribute_names: Vec::new(),
+            span: 0..0,
+            next_span: 0..0,
+            markup_start: 0,
+            markup_end: 0,
+            inside_markup: false,
+            reference_start: (0, TextPosition::new()),
+            chunk_end: None,
+            chunked: false,
+
             encountered: Encountered::None,
             inside_whitespace: true,
-            read_prefix_separator: false,
             pop_namespace: false,
         }
     }
 
+    /// Returns the names and raw values of the attributes of the last start tag,
+    /// see `EventReader::raw_attributes`.
+    #[inline]
+    pub fn raw_attributes(&self) -> impl Iterator<Item = (&str, &str)> + '_ { self.raw_attributes.iter() }
+
+    /// Returns the interned name of the last element event, see `EventReader::element_name`.
+    #[inline]
+    pub fn element_name(&self) -> Option<&InternedName> { self.element_name.as_ref() }
+
+    /// Returns the interned attribute names of the last start tag, see `EventReader::attribute_names`.
+    #[inline]
+    pub fn attribute_names(&self) -> &[InternedName] { &self.attribute_names }
+
+    /// Expands references in a raw attribute value, see `ParserConfig2::lazy_attributes`.
+    pub fn unescape_attribute(&self, raw: &str) -> core::result::Result<String, Error> {
+        self.unescape_attribute_value(raw).map_err(|e| Error {
+            pos: self.lexer.position(),
+            kind: ErrorKind::Syntax(e.to_cow()),
+            encoding: None,
+        })
+    }
+
+    /// Returns the encoding the source is being decoded with.
+    #[inline]
+    pub fn encoding(&self) -> Encoding { self.lexer.encoding() }
+
+    /// Returns the source byte range of the last event, see `EventReader::last_event_span`.
+    #[inline]
+    pub fn last_event_span(&self) -> Range<usize> { self.span.clone() }
+
+    /// Returns the number of bytes taken from the source so far.
+    #[inline]
+    pub fn bytes_read(&self) -> u64 { self.lexer.bytes_read() }
+
+    /// Returns the `<!DOCTYPE>` declaration, if it's been parsed and `capture_doctype` is set.
+    #[inline]
+    pub fn doctype(&self) -> Option<&Doctype> { self.doctype.as_ref() }
+
     /// Checks if this parser ignores the end of stream errors.
     pub fn is_ignoring_end_of_stream(&self) -> bool { self.config.c.ignore_end_of_stream }
 
@@ -171,7 +334,7 @@ impl PullParser {
     }
 }
 
-impl Position for PullParser {
+impl<C: Capacity> Position for PullParser<C> {
     /// Returns the position of the last event produced by the parser
     #[inline]
     fn position(&self) -> TextPosition {
@@ -179,6 +342,81 @@ impl Position for PullParser {
     }
 }
 
+/// Makes room for `additional` bytes in `buf` with the `fallible-alloc` feature, and returns
+/// whether there is. Without the feature, it's left to grow as usual.
+#[inline]
+// it takes the buffer for `try_reserve`, which is only called with the feature
+#[cfg_attr(not(feature = "fallible-alloc"), allow(unused_variables))]
+fn reserve<T: Text>(buf: &mut T, additional: usize) -> bool {
+    #[cfg(feature = "fallible-alloc")]
+    return buf.try_reserve(additional).is_ok();
+    #[cfg(not(feature = "fallible-alloc"))]
+    true
+}
+
+/// The qualified names and raw values of the attributes of a start tag, kept in one buffer which
+/// is reused for every tag, see `ParserConfig2::lazy_attributes`
+#[derive(Default)]
+struct RawAttributeList {
+    /// Values and names one after another
+    text: String,
+    /// Offsets of each name and value in `text`
+    spans: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl RawAttributeList {
+    fn clear(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+    }
+
+    /// Makes room for `push`
+    #[cfg(feature = "fallible-alloc")]
+    fn try_reserve(&mut self, name: &OwnedName) -> core::result::Result<(), alloc::collections::TryReserveError> {
+        self.text.try_reserve(name.prefix_ref().map_or(0, |prefix| prefix.len() + 1) + name.local_name.len())?;
+        self.spans.try_reserve(1)
+    }
+
+    /// Adds the name of an attribute whose value was written to `text` from `value_start` on
+    fn push(&mut self, name: &OwnedName, value_start: usize) {
+        let value = value_start..self.text.len();
+        if let Some(prefix) = name.prefix_ref() {
+            self.text.push_str(prefix);
+            self.text.push(':');
+        }
+        self.text.push_str(&name.local_name);
+        self.spans.push((value.end..self.text.len(), value));
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.spans.iter().map(move |(name, value)| (&self.text[name.clone()], &self.text[value.clone()]))
+    }
+}
+
+/// Whether an attribute declares a namespace, i.e. it's `xmlns` or `xmlns:prefix`
+fn is_namespace_declaration(name: &OwnedName) -> bool {
+    match name.prefix_ref() {
+        Some(prefix) => prefix == namespace::NS_XMLNS_PREFIX,
+        None => name.local_name == namespace::NS_XMLNS_PREFIX,
+    }
+}
+
+/// Drops leading and trailing spaces and replaces runs of them with one, which is how values of
+/// tokenized attribute types are normalized. Other whitespace is from character references and stays.
+fn collapse_spaces(value: &mut String) {
+    if !value.starts_with(' ') && !value.ends_with(' ') && !value.contains("  ") {
+        return;
+    }
+    let mut collapsed = String::with_capacity(value.len());
+    for token in value.split(' ').filter(|token| !token.is_empty()) {
+        if !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(token);
+    }
+    *value = collapsed;
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum State {
     OutsideTag,
@@ -195,6 +433,9 @@ pub enum State {
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum DoctypeSubstate {
+    /// right after `<!DOCTYPE`, which needs whitespace before the root element name
+    BeforeName,
+    NameStart,
     Outside,
     String,
     InsideName,
@@ -211,7 +452,12 @@ pub enum DoctypeSubstate {
     PEReferenceDefinitionStart,
     PEReferenceDefinition,
     SkipDeclaration,
+    /// `<!ATTLIST` collected for `apply_attribute_defaults`
+    AttlistDeclaration,
     Comment,
+    /// `<?` in the DTD, whose target is checked but which is otherwise skipped
+    ProcessingInstructionName,
+    ProcessingInstruction,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -297,7 +543,7 @@ impl QuoteToken {
     }
 }
 
-struct MarkupData {
+struct MarkupData<A> {
     name: String,     // used for processing instruction name
     ref_data: String,  // used for reference content
 
@@ -309,40 +555,67 @@ struct MarkupData {
 
     quote: Option<QuoteToken>,  // used to hold opening quote for attribute value
     attr_name: Option<OwnedName>,  // used to hold attribute name
-    attributes: BTreeSet<OwnedAttribute>,   // used to hold all accumulated attributes
+    attributes: A,   // used to hold all accumulated attributes
+
+    attr_value_start: usize,  // used to hold offset of raw attribute value
+    raw_attributes: RawAttributeList,  // used to hold attributes as they're written
 }
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     /// Returns next event read from the given buffer.
     ///
     /// This method should be always called with the same buffer. If you call it
     /// providing different buffers each time, the result will be undefined.
-    pub fn next<'a, S: Iterator<Item = &'a u8>>(&mut self, r: &mut S) -> Result {
+    pub fn next<S: CharSource>(&mut self, r: &mut S) -> Result {
         if let Some(ref ev) = self.final_result {
             return ev.clone();
         }
 
         if let Some(ev) = self.next_event.take() {
+            self.span = self.next_span.clone();
             return ev;
         }
 
         if self.pop_namespace {
             self.pop_namespace = false;
             self.nst.pop();
+            trace!("namespace scope popped");
         }
 
         loop {
             debug_assert!(self.next_event.is_none());
             debug_assert!(!self.pop_namespace);
 
+            if self.st == State::OutsideTag && !self.inside_whitespace && self.buf_has_data() {
+                if let Err(e) = self.read_text(r) {
+                    return self.set_final_result(Err(e));
+                }
+            }
+
             // While lexer gives us Ok(maybe_token) -- we loop.
             // Upon having a complete XML-event -- we return from the whole function.
-            match self.lexer.next_token(r) {
+            match self.lexer.next_token_from(r) {
                 Ok(Some(token)) => {
-                    match self.dispatch_token(token) {
+                    #[cfg(feature = "fallible-alloc")]
+                    if let Err(e) = self.reserve_for_token() {
+                        return self.set_final_result(Err(e));
+                    }
+                    let prev_st = self.st;
+                    let ev = self.dispatch_token(token);
+                    // text which didn't fit was dropped, so whatever uses the buffer is wrong
+                    if self.buf.is_exceeded() {
+                        self.next_pos();
+                        return self.set_final_result(Err(self.capacity_exceeded(Limit::TextCapacity)));
+                    }
+                    if self.inside_markup && self.st == State::OutsideTag {
+                        self.inside_markup = false;
+                        self.markup_end = self.lexer.token_span().end;
+                    }
+                    match ev {
                         None => {} // continue
                         Some(Ok(xml_event)) => {
                             self.next_pos();
+                            self.set_span(token, prev_st, &xml_event);
                             return Ok(xml_event)
                         },
                         Some(Err(xml_error)) => {
@@ -365,6 +638,8 @@ impl PullParser {
     fn handle_eof(&mut self) -> core::result::Result<XmlEvent, super::Error> {
         // Forward pos to the lexer head
         self.next_pos();
+        let end = self.lexer.token_span().end;
+        self.span = end..end;
         let ev = if self.depth() == 0 {
             if self.encountered == Encountered::Element && self.st == State::OutsideTag {  // all is ok
                 Ok(XmlEvent::EndDocument)
@@ -393,12 +668,57 @@ impl PullParser {
 
     #[cold]
     fn error(&self, e: SyntaxError) -> Result {
+        debug!("syntax error at {}: {}", self.lexer.position(), e);
         Err(Error {
             pos: self.lexer.position(),
             kind: ErrorKind::Syntax(e.to_cow()),
+            encoding: None,
         })
     }
 
+    #[cold]
+    fn out_of_memory(&self) -> Error {
+        debug!("out of memory at {}", self.lexer.position());
+        Error {
+            pos: self.lexer.position(),
+            kind: ErrorKind::OutOfMemory,
+            encoding: None,
+        }
+    }
+
+    /// Makes room for what a token can add to the buffers and stacks, which is at most a char
+    /// or a delimiter like `]]>`, an element or a namespace scope. Attributes are reserved for
+    /// when they're added, and entity replacement text with `reserve`.
+    #[cfg(feature = "fallible-alloc")]
+    fn reserve_for_token(&mut self) -> core::result::Result<(), Error> {
+        let mut reserved = self.buf.try_reserve(4)
+            .and_then(|()| self.data.name.try_reserve(4))
+            .and_then(|()| self.data.ref_data.try_reserve(4))
+            .and_then(|()| self.est.try_reserve(1))
+            .and_then(|()| self.nst.0.try_reserve(1));
+        if self.config.capture_doctype {
+            reserved = reserved.and_then(|()| self.doctype_raw.try_reserve(9));
+        }
+        reserved.map_err(|_| self.out_of_memory())
+    }
+
+    /// The error for a `Stack` or `Text` of the reader's `Capacity` which is full
+    #[cold]
+    fn capacity_exceeded(&self, limit: Limit) -> Error {
+        debug!("{} at {}", limit, self.lexer.position());
+        Error {
+            pos: self.lexer.position(),
+            kind: ErrorKind::LimitExceeded(limit),
+            encoding: None,
+        }
+    }
+
+    #[cold]
+    fn limit_exceeded(&self, limit: &'static str) -> Result {
+        debug!("{} exceeded at {}", limit, self.lexer.position());
+        self.error(SyntaxError::ExceededConfiguredLimit)
+    }
+
     #[inline]
     fn next_pos(&mut self) {
         // unfortunately calls to next_pos will never be perfectly balanced with push_pos,
@@ -412,16 +732,52 @@ impl PullParser {
         }
     }
 
+    /// Marks the start of markup, at the current token, for the span of its event.
+    #[inline]
+    fn start_markup(&mut self) {
+        self.markup_start = self.lexer.token_span().start;
+        self.inside_markup = true;
+    }
+
+    /// Sets the span of an event which `t` produced.
+    ///
+    /// Markup ends with the token which closes it. Text is only known to have ended when the
+    /// next markup starts, so it ends before that token, and began after the previous markup.
+    fn set_span(&mut self, t: Token, prev_st: State, event: &XmlEvent) {
+        let token = self.lexer.token_span();
+        let closes_markup = matches!(t, Token::TagEnd | Token::EmptyTagEnd | Token::CommentEnd |
+            Token::CDataEnd | Token::ProcessingInstructionEnd);
+        self.next_span = self.markup_start..token.end;
+        self.span = match event {
+            XmlEvent::Characters(_) if self.chunk_end.is_some() => {
+                let end = self.chunk_end.take().unwrap_or(token.start);
+                let span = self.markup_end..end;
+                // the next chunk starts where this one ends
+                self.markup_end = end;
+                span
+            },
+            XmlEvent::StartDocument { .. } if !matches!(prev_st, State::InsideDeclaration(_)) => 0..0,
+            XmlEvent::Characters(_) | XmlEvent::Whitespace(_) if !closes_markup => self.markup_end..token.start,
+            _ => self.next_span.clone(),
+        };
+    }
+
     #[inline]
     #[track_caller]
     fn push_pos(&mut self) {
+        self.push_pos_at(self.lexer.position());
+    }
+
+    #[inline]
+    #[track_caller]
+    fn push_pos_at(&mut self, pos: TextPosition) {
         debug_assert!(self.pos.len() != self.pos.capacity(), "You've found a bug in xml-rs, caused by calls to push_pos() in states that don't end up emitting events.
             This case is ignored in release mode, and merely causes document positions to be out of sync.
             Please file a bug and include the XML document that triggers this assert.");
 
         // it has capacity preallocated for more than it ever needs, so this reduces code size
         if self.pos.len() != self.pos.capacity() {
-            self.pos.push(self.lexer.position());
+            self.pos.push(pos);
         } else if self.pos.len() > 1 {
             self.pos.remove(0); // this mitigates the excessive push_pos() call
         }
@@ -453,9 +809,11 @@ impl PullParser {
         !self.buf.is_empty()
     }
 
+    /// Takes the buffer's text, see `BufferPool`
     #[inline]
     fn take_buf(&mut self) -> String {
-        core::mem::take(&mut self.buf)
+        let spare = self.pool.take();
+        self.buf.take_into(spare)
     }
 
     #[inline]
@@ -481,33 +839,34 @@ impl PullParser {
     /// * `t`       --- next token;
     /// * `on_name` --- a callback which is executed when whitespace is encountered.
     fn read_qualified_name<F>(&mut self, t: Token, target: QualifiedNameTarget, on_name: F) -> Option<Result>
-      where F: Fn(&mut PullParser, Token, OwnedName) -> Option<Result> {
-        // We can get here for the first time only when self.data.name contains zero or one character,
-        // but first character cannot be a colon anyway
-        if self.buf.len() <= 1 {
-            self.read_prefix_separator = false;
-        }
-
-        let invoke_callback = move |this: &mut PullParser, t| {
+      where F: Fn(&mut Self, Token, OwnedName) -> Option<Result> {
+        let invoke_callback = move |this: &mut Self, t| {
             let name = this.take_buf();
-            match name.parse() {
-                Ok(name) => on_name(this, t, name),
-                Err(()) => Some(this.error(SyntaxError::InvalidQualifiedName(name.into()))),
+            let parsed = if this.config.intern_names { this.pool.parse_name(&name) } else { OwnedName::try_from_str(&name) };
+            match parsed {
+                Ok(parsed) => {
+                    this.pool.give(name);
+                    on_name(this, t, parsed)
+                },
+                Err(e) => Some(this.error(SyntaxError::InvalidQualifiedName(alloc::format!("{name} ({e})").into()))),
             }
         };
 
         match t {
-            // There can be only one colon, and not as the first character
-            Token::Character(':') if self.buf_has_data() && !self.read_prefix_separator => {
+            // There can be only one colon, and not as the first character,
+            // but the name is checked as a whole to tell what's wrong
+            Token::Character(':') => {
+                if self.buf.len() > self.config.max_name_length {
+                    return Some(self.limit_exceeded("max_name_length"));
+                }
                 self.buf.push(':');
-                self.read_prefix_separator = true;
                 None
             }
 
             Token::Character(c) if c != ':' && (self.buf.is_empty() && is_name_start_char(c) ||
                                           self.buf_has_data() && is_name_char(c)) => {
                 if self.buf.len() > self.config.max_name_length {
-                    return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                    return Some(self.limit_exceeded("max_name_length"));
                 }
                 self.buf.push(c);
                 None
@@ -526,26 +885,36 @@ impl PullParser {
         }
     }
 
+    /// Whether the attribute value being read is kept as it's written rather than unescaped
+    /// into `buf`, see `ParserConfig2::lazy_attributes`. Namespace declarations are needed right away.
+    #[inline]
+    fn is_lazy_value(&self) -> bool {
+        self.config.lazy_attributes && matches!(self.st, State::InsideOpeningTag(_)) &&
+            !self.data.attr_name.as_ref().map_or(false, is_namespace_declaration)
+    }
+
     /// Dispatches tokens in order to process attribute value.
     ///
     /// # Parameters
     /// * `t`        --- next token;
     /// * `on_value` --- a callback which is called when terminating quote is encountered.
     fn read_attribute_value<F>(&mut self, t: Token, on_value: F) -> Option<Result>
-      where F: Fn(&mut PullParser, String) -> Option<Result> {
+      where F: Fn(&mut Self, String) -> Option<Result> {
         match t {
             Token::Character(c) if self.data.quote.is_none() && is_whitespace_char(c) => None, // skip leading whitespace
 
             Token::DoubleQuote | Token::SingleQuote => match self.data.quote {
                 None => {  // Entered attribute value
                     self.data.quote = QuoteToken::from_token(t);
+                    self.data.attr_value_start = self.data.raw_attributes.text.len();
                     None
                 }
                 Some(q) if q.as_token() == t => {
                     self.data.quote = None;
-                    let value = self.take_buf();
+                    let value = if self.is_lazy_value() { String::new() } else { self.take_buf() };
                     on_value(self, value)
                 }
+                _ if self.is_lazy_value() => self.push_raw_attribute_value(t),
                 _ => {
                     if let Token::Character(c) = t {
                         if !self.is_valid_xml_char_not_restricted(c) {
@@ -553,13 +922,16 @@ impl PullParser {
                         }
                     }
                     if self.buf.len() > self.config.max_attribute_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_attribute_length"));
                     }
                     t.push_to_string(&mut self.buf);
                     None
                 }
             },
 
+            // lazy values keep their references, see `ParserConfig2::lazy_attributes`
+            Token::ReferenceStart if self.data.quote.is_some() && self.is_lazy_value() => self.push_raw_attribute_value(t),
+
             Token::ReferenceStart if self.data.quote.is_some() => {
                 self.state_after_reference = self.st;
                 self.into_state_continue(State::InsideReference)
@@ -571,10 +943,20 @@ impl PullParser {
                 Some(self.error(SyntaxError::InvalidCharacterEntity(c as u32)))
             },
 
+            _ if self.data.quote.is_some() && self.is_lazy_value() => self.push_raw_attribute_value(t),
+
+            Token::Character('\t' | '\n' | '\r') if self.data.quote.is_some() && self.config.normalize_attribute_whitespace => {
+                if self.buf.len() > self.config.max_attribute_length {
+                    return Some(self.limit_exceeded("max_attribute_length"));
+                }
+                self.buf.push(' ');
+                None
+            },
+
             // Every character except " and ' and < is okay
             _ if self.data.quote.is_some() => {
                 if self.buf.len() > self.config.max_attribute_length {
-                    return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                    return Some(self.limit_exceeded("max_attribute_length"));
                 }
                 t.push_to_string(&mut self.buf);
                 None
@@ -584,14 +966,46 @@ impl PullParser {
         }
     }
 
+    /// Adds a token of an attribute value to `data.raw_attributes` as it's written
+    fn push_raw_attribute_value(&mut self, t: Token) -> Option<Result> {
+        if self.data.raw_attributes.text.len() - self.data.attr_value_start > self.config.max_attribute_length {
+            return Some(self.limit_exceeded("max_attribute_length"));
+        }
+        if !reserve(&mut self.data.raw_attributes.text, 4) {
+            return Some(Err(self.out_of_memory()));
+        }
+        t.push_to_string(&mut self.data.raw_attributes.text);
+        None
+    }
+
     fn emit_start_element(&mut self, emit_end_element: bool) -> Option<Result> {
         let mut name = self.data.take_element_name()?;
-        let mut attributes: Vec<OwnedAttribute> = self.data.take_attributes().into_iter().collect();
+        let mut attributes: Attributes = self.data.take_attributes().into();
+        // this tag's raw attributes replace the last one's, keeping both buffers
+        core::mem::swap(&mut self.raw_attributes, &mut self.data.raw_attributes);
+        self.data.raw_attributes.clear();
+
+        if let Err(e) = self.add_attribute_defaults(&name, &mut attributes) {
+            return Some(self.error(e));
+        }
+
+        if let Some(max) = self.config.max_element_depth {
+            if self.depth() >= max {
+                debug!("max_element_depth of {} exceeded at {}", max, self.lexer.position());
+                return Some(Err(Error {
+                    pos: self.lexer.position(),
+                    kind: ErrorKind::LimitExceeded(Limit::ElementDepth),
+                    encoding: None,
+                }));
+            }
+        }
+
+        let intern = self.config.intern_names;
 
         // check whether the name prefix is bound and fix its namespace
         match self.nst.get(name.borrow().prefix_repr()) {
             Some("") => name.namespace = None, // default namespace
-            Some(ns) => name.namespace = Some(ns.into()),
+            Some(ns) => name.namespace = Some(if intern { self.pool.copy(ns) } else { ns.into() }),
             None => return Some(self.error(SyntaxError::UnboundElementPrefix(name.to_string().into())))
         }
 
@@ -600,21 +1014,32 @@ impl PullParser {
             if let Some(ref pfx) = attr.name.prefix {
                 let new_ns = match self.nst.get(pfx) {
                     Some("") => None, // default namespace
-                    Some(ns) => Some(ns.into()),
+                    Some(ns) => Some(if intern { self.pool.copy(ns) } else { ns.into() }),
                     None => return Some(self.error(SyntaxError::UnboundAttribute(attr.name.to_string().into())))
                 };
                 attr.name.namespace = new_ns;
             }
         }
 
+        let mut preserve = false;
         if emit_end_element {
             self.pop_namespace = true;
-            self.next_event = Some(Ok(XmlEvent::EndElement {
-                name: name.clone()
-            }));
         } else {
-            self.est.push(name.clone());
+            preserve = match self.xml_space(&attributes) {
+                Ok(preserve) => preserve.unwrap_or_else(|| self.is_space_preserved()),
+                Err(e) => return Some(self.error(e)),
+            };
         }
+        let name = if intern {
+            self.intern_start_tag(name, &mut attributes, emit_end_element, preserve)
+        } else {
+            if emit_end_element {
+                self.next_event = Some(Ok(XmlEvent::EndElement { name: name.clone() }));
+            } else {
+                self.est.push((name.clone(), preserve));
+            }
+            name
+        };
         let namespace = self.nst.squash();
         self.into_state_emit(State::OutsideTag, Ok(XmlEvent::StartElement {
             name,
@@ -623,8 +1048,87 @@ impl PullParser {
         }))
     }
 
+    /// Interns the names of a start tag and gives their buffers back to the pool, see
+    /// `ParserConfig2::intern_names`. Returns the name for the event, which is empty like the
+    /// attribute names.
+    fn intern_start_tag(&mut self, name: OwnedName, attributes: &mut Attributes, emit_end_element: bool, preserve: bool) -> OwnedName {
+        self.element_name = Some(self.names.intern(name.borrow()));
+        self.attribute_names.clear();
+        for attr in attributes {
+            self.attribute_names.push(self.names.intern(attr.name.borrow()));
+            self.pool.give_name(core::mem::replace(&mut attr.name, EMPTY_NAME));
+        }
+        if emit_end_element {
+            self.next_event = Some(Ok(XmlEvent::EndElement { name: EMPTY_NAME }));
+            self.pool.give_name(name);
+        } else {
+            // to check the end tag, whose name is interned again there
+            self.est.push((name, preserve));
+        }
+        EMPTY_NAME
+    }
+
+    /// Adds attributes missing on the element which have defaults in the DTD, and collapses
+    /// spaces in the values of attributes which have a tokenized type
+    fn add_attribute_defaults(&mut self, name: &OwnedName, attributes: &mut Attributes) -> core::result::Result<(), SyntaxError> {
+        // without a DTD, save looking up a name made for it
+        if self.attribute_declarations.is_empty() {
+            return Ok(());
+        }
+        let declarations = match self.attribute_declarations.get(&*name.to_repr()) {
+            Some(declarations) => declarations,
+            None => return Ok(()),
+        };
+        for AttributeDeclaration { name: attr_name, default, tokenized } in declarations {
+            if let Some(attr) = attributes.iter_mut().find(|attr| attr.name == *attr_name) {
+                // lazy values are empty, the reader keeps them as they're written
+                if *tokenized {
+                    collapse_spaces(&mut attr.value);
+                }
+                continue;
+            }
+            let raw_value = match default {
+                Some(raw_value) => raw_value,
+                None => continue,
+            };
+            if is_namespace_declaration(attr_name) {
+                let prefix = match attr_name.prefix_ref() {
+                    Some(_) => &*attr_name.local_name,
+                    None => namespace::NS_NO_PREFIX,
+                };
+                // explicit declarations of this element are in the top namespace
+                if !self.nst.peek().contains(prefix) {
+                    let uri = self.unescape_attribute_value(raw_value)?;
+                    self.nst.put(prefix, uri);
+                }
+                continue;
+            }
+            if attributes.len() >= self.config.max_attributes {
+                debug!("max_attributes exceeded by defaults of <{}>", name);
+                return Err(SyntaxError::ExceededConfiguredLimit);
+            }
+            let mut value = if self.config.normalize_attribute_whitespace {
+                let raw_value = raw_value.replace(['\t', '\n', '\r'], " ");
+                self.unescape_attribute_value(&raw_value)?
+            } else {
+                self.unescape_attribute_value(raw_value)?
+            };
+            if *tokenized {
+                collapse_spaces(&mut value);
+            }
+            attributes.push(OwnedAttribute { name: attr_name.clone(), value });
+        }
+        Ok(())
+    }
+
     fn emit_end_element(&mut self) -> Option<Result> {
         let mut name = self.data.take_element_name()?;
+        let (op_name, _) = self.est.last()?;
+
+        // the prefix is bound as it was in the start tag, so the same text is the same name
+        if name.prefix == op_name.prefix && name.local_name == op_name.local_name {
+            return self.end_open_element();
+        }
 
         // check whether the name prefix is bound and fix its namespace
         match self.nst.get(name.borrow().prefix_repr()) {
@@ -632,15 +1136,50 @@ impl PullParser {
             Some(ns) => name.namespace = Some(ns.into()),
             None => return Some(self.error(SyntaxError::UnboundElementPrefix(name.to_string().into())))
         }
+        Some(self.error(SyntaxError::UnexpectedClosingTag(alloc::format!("{name} != {op_name}").into())))
+    }
+
+    /// Whether `buf` has the name of the element which was started last, as it was written
+    /// there, so that it needn't be parsed
+    fn buf_is_open_element_name(&self) -> bool {
+        let buf = self.buf.as_str();
+        match self.est.last() {
+            Some((OwnedName { local_name, prefix: Some(prefix), .. }, _)) =>
+                buf.len() == prefix.len() + 1 + local_name.len() && buf.starts_with(prefix.as_str()) &&
+                    buf[prefix.len()..].starts_with(':') && buf.ends_with(local_name.as_str()),
+            Some((OwnedName { local_name, prefix: None, .. }, _)) => buf == local_name,
+            None => false,
+        }
+    }
 
-        let op_name = self.est.pop()?;
+    /// Emits the end of the element which was started last
+    fn end_open_element(&mut self) -> Option<Result> {
+        let (mut name, _) = self.est.pop()?;
+        self.pop_namespace = true;
+        if self.config.intern_names {
+            self.element_name = Some(self.names.intern(name.borrow()));
+            self.pool.give_name(core::mem::replace(&mut name, EMPTY_NAME));
+        }
+        self.into_state_emit(State::OutsideTag, Ok(XmlEvent::EndElement { name }))
+    }
 
-        if name == op_name {
-            self.pop_namespace = true;
-            self.into_state_emit(State::OutsideTag, Ok(XmlEvent::EndElement { name }))
-        } else {
-            Some(self.error(SyntaxError::UnexpectedClosingTag(alloc::format!("{name} != {op_name}").into())))
+    /// Whether a start tag's `xml:space` asks for whitespace to be preserved, if it has one
+    fn xml_space(&self, attributes: &Attributes) -> core::result::Result<Option<bool>, SyntaxError> {
+        let attr = match attributes.iter().find(|attr| attr.name.matches(XML_SPACE.namespace, XML_SPACE.local_name)) {
+            Some(attr) => attr,
+            None => return Ok(None),
+        };
+        // a lazy value is empty in the event, see `ParserConfig2::lazy_attributes`
+        if let Some((_, raw)) = self.raw_attributes.iter().find(|&(name, _)| name == "xml:space") {
+            return Ok(Some(self.unescape_attribute_value(raw)? == "preserve"));
         }
+        Ok(Some(attr.value == "preserve"))
+    }
+
+    /// Whether the current element is inside `xml:space="preserve"`
+    #[inline]
+    fn is_space_preserved(&self) -> bool {
+        self.est.last().map_or(false, |&(_, preserve)| preserve)
     }
 
     #[inline]
@@ -648,7 +1187,7 @@ impl PullParser {
         if Some(XmlVersion::Version11) == self.data.version {
             is_xml11_char(c)
         } else {
-            is_xml10_char(c)
+            is_xml_char(c)
         }
     }
 
@@ -657,7 +1196,7 @@ impl PullParser {
         if Some(XmlVersion::Version11) == self.data.version {
             is_xml11_char_not_restricted(c)
         } else {
-            is_xml10_char(c)
+            is_xml_char(c)
         }
     }
 }
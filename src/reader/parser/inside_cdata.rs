@@ -1,10 +1,11 @@
 use crate::reader::error::SyntaxError;
 use crate::reader::lexer::Token;
-use crate::{common::is_whitespace_char, reader::events::XmlEvent};
+use crate::{chars::is_whitespace_char, reader::events::XmlEvent};
 
+use crate::capacity::{Capacity, Text};
 use super::{PullParser, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_cdata(&mut self, t: Token) -> Option<Result> {
         match t {
             Token::CDataEnd => {
@@ -21,13 +22,17 @@ impl PullParser {
                 self.into_state(State::OutsideTag, event)
             }
 
-            Token::Character(c) if !self.is_valid_xml_char(c) => {
+            Token::Character(c) if !self.is_valid_xml_char_not_restricted(c) => {
                 Some(self.error(SyntaxError::InvalidCharacterEntity(c as u32)))
             },
             Token::Character(c) => {
                 if !is_whitespace_char(c) {
                     self.inside_whitespace = false;
                 }
+                // the buffer may also hold coalesced text from before the CDATA
+                if self.buf.len() > self.config.max_data_length {
+                    return Some(self.limit_exceeded("max_data_length"));
+                }
                 self.buf.push(c);
                 None
             }
@@ -1,18 +1,24 @@
 use crate::reader::error::SyntaxError;
-use crate::{common::is_whitespace_char, namespace};
+use crate::{chars::is_whitespace_char, namespace};
 use crate::reader::lexer::Token;
+use crate::capacity::{Capacity, Text};
 use super::{ClosingTagSubstate, PullParser, QualifiedNameTarget, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_closing_tag_name(&mut self, t: Token, s: ClosingTagSubstate) -> Option<Result> {
         match s {
+            // the usual end tag, which needn't allocate a name to be checked
+            ClosingTagSubstate::CTInsideName if t == Token::TagEnd && self.buf_is_open_element_name() => {
+                self.buf.clear();
+                self.end_open_element()
+            },
             ClosingTagSubstate::CTInsideName => self.read_qualified_name(t, QualifiedNameTarget::ClosingTagNameTarget, |this, token, name| {
                 match name.prefix_ref() {
                     Some(prefix) if prefix == namespace::NS_XML_PREFIX ||
                                     prefix == namespace::NS_XMLNS_PREFIX =>
                         Some(this.error(SyntaxError::InvalidNamePrefix(prefix.into()))),
                     _ => {
-                        this.data.element_name = Some(name.clone());
+                        this.data.element_name = Some(name);
                         match token {
                             Token::TagEnd => this.emit_end_element(),
                             Token::Character(c) if is_whitespace_char(c) => this.into_state_continue(State::InsideClosingTag(ClosingTagSubstate::CTAfterName)),
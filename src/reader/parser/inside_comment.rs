@@ -2,9 +2,10 @@ use crate::reader::error::SyntaxError;
 use crate::reader::events::XmlEvent;
 use crate::reader::lexer::Token;
 
+use crate::capacity::{Capacity, Text};
 use super::{PullParser, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_comment(&mut self, t: Token) -> Option<Result> {
         match t {
             Token::CommentEnd if self.config.c.ignore_comments => {
@@ -16,7 +17,7 @@ impl PullParser {
                 self.into_state_emit(State::OutsideTag, Ok(XmlEvent::Comment(data)))
             }
 
-            Token::Character(c) if !self.is_valid_xml_char(c) => {
+            Token::Character(c) if !self.is_valid_xml_char_not_restricted(c) => {
                 Some(self.error(SyntaxError::InvalidCharacterEntity(c as u32)))
             },
 
@@ -24,7 +25,7 @@ impl PullParser {
 
             _ => {
                 if self.buf.len() > self.config.max_data_length {
-                    return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                    return Some(self.limit_exceeded("max_data_length"));
                 }
                 t.push_to_string(&mut self.buf);
                 None
@@ -2,18 +2,20 @@ extern crate alloc;
 
 use alloc::string::ToString;
 
-use crate::common::{is_whitespace_char, XmlVersion};
+use crate::chars::is_whitespace_char;
+use crate::common::XmlVersion;
 use crate::reader::error::SyntaxError;
 use crate::reader::events::XmlEvent;
 use crate::reader::lexer::Token;
-use crate::util::Encoding;
+use crate::decode::Encoding;
 
+use crate::capacity::Capacity;
 use super::{
     DeclarationSubstate, Encountered, PullParser, QualifiedNameTarget, Result, State,
     DEFAULT_VERSION,
 };
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     #[inline(never)]
     fn emit_start_document(&mut self) -> Option<Result> {
         debug_assert!(self.encountered == Encountered::None);
@@ -23,7 +25,8 @@ impl PullParser {
         let encoding = self.data.take_encoding();
         let standalone = self.data.standalone;
 
-        if let Some(new_encoding) = encoding.as_deref() {
+        // decoded chars can't be in any other encoding
+        if let (Some(new_encoding), false) = (encoding.as_deref(), self.lexer.is_decoded()) {
             let new_encoding = match new_encoding.parse() {
                 Ok(e) => e,
                 Err(_) if self.config.ignore_invalid_encoding_declarations => Encoding::Latin1,
@@ -31,12 +34,12 @@ impl PullParser {
             };
             let current_encoding = self.lexer.encoding();
             if current_encoding != new_encoding {
-                let set = match (current_encoding, new_encoding) {
-                    (Encoding::Unknown | Encoding::Default, new) if new != Encoding::Utf16 => new,
-                    (Encoding::Utf16Be | Encoding::Utf16Le, Encoding::Utf16) => current_encoding,
-                    _ if self.config.ignore_invalid_encoding_declarations => current_encoding,
-                    _ => return Some(self.error(SyntaxError::ConflictingEncoding(new_encoding, current_encoding))),
+                let set = match current_encoding.with_declared(new_encoding) {
+                    Some(set) => set,
+                    None if self.config.ignore_invalid_encoding_declarations => current_encoding,
+                    None => return Some(self.error(SyntaxError::ConflictingEncoding(new_encoding, current_encoding))),
                 };
+                debug!("declared encoding {} with {} detected, using {}", new_encoding, current_encoding, set);
                 self.lexer.set_encoding(set);
             }
         }
@@ -1,12 +1,46 @@
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::name::OwnedName;
+use crate::reader::doctype::Doctype;
 use crate::reader::error::SyntaxError;
-use crate::common::{is_name_char, is_name_start_char, is_whitespace_char};
+use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char};
 use crate::reader::lexer::Token;
 
-use super::{DoctypeSubstate, PullParser, QuoteToken, Result, State};
+use crate::capacity::{Capacity, Text};
+use super::{AttributeDeclaration, DoctypeSubstate, PullParser, QuoteToken, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_doctype(&mut self, t: Token, substate: DoctypeSubstate) -> Option<Result> {
+        if self.config.capture_doctype {
+            if substate == DoctypeSubstate::Outside && t == Token::TagEnd {
+                self.doctype = Some(Doctype::from_raw(&core::mem::take(&mut self.doctype_raw)));
+            // parameter entity expansions have no span, only their references are in the source
+            } else if !self.lexer.token_span().is_empty() {
+                if self.doctype_raw.len() > self.config.max_data_length {
+                    return Some(self.limit_exceeded("max_data_length"));
+                }
+                t.push_to_string(&mut self.doctype_raw);
+            }
+        }
+
         match substate {
+            DoctypeSubstate::BeforeName => match t {
+                Token::Character(c) if is_whitespace_char(c) => {
+                    self.into_state_continue(State::InsideDoctype(DoctypeSubstate::NameStart))
+                },
+                _ => Some(self.error(SyntaxError::UnexpectedToken(t))),
+            },
+            DoctypeSubstate::NameStart => match t {
+                Token::Character(c) if is_whitespace_char(c) => None,
+                // the rest of the name is skipped with the external ID
+                Token::Character(c) if is_name_start_char(c) => {
+                    self.into_state_continue(State::InsideDoctype(DoctypeSubstate::Outside))
+                },
+                _ => Some(self.error(SyntaxError::UnexpectedToken(t))),
+            },
             DoctypeSubstate::Outside => match t {
                 Token::TagEnd => self.into_state_continue(State::OutsideTag),
                 Token::MarkupDeclarationStart => {
@@ -21,6 +55,10 @@ impl PullParser {
                 Token::CommentStart => {
                     self.into_state_continue(State::InsideDoctype(DoctypeSubstate::Comment))
                 },
+                Token::ProcessingInstructionStart => {
+                    self.buf.clear();
+                    self.into_state_continue(State::InsideDoctype(DoctypeSubstate::ProcessingInstructionName))
+                },
                 Token::SingleQuote | Token::DoubleQuote => {
                     // just discard string literals
                     self.data.quote = super::QuoteToken::from_token(t);
@@ -45,6 +83,34 @@ impl PullParser {
                 },
                 _ => None,
             },
+            DoctypeSubstate::ProcessingInstructionName => match t {
+                Token::Character(c) if self.buf.is_empty() && is_name_start_char(c) ||
+                                 self.buf_has_data() && is_name_char(c) => {
+                    if self.buf.len() > self.config.max_name_length {
+                        return Some(self.limit_exceeded("max_name_length"));
+                    }
+                    self.buf.push(c);
+                    None
+                },
+                Token::Character(_) | Token::ProcessingInstructionEnd if self.buf.is_empty() => {
+                    Some(self.error(SyntaxError::ProcessingInstructionWithoutName))
+                },
+                Token::Character(c) if is_whitespace_char(c) => self.end_doctype_pi_name(State::InsideDoctype(DoctypeSubstate::ProcessingInstruction)),
+                Token::ProcessingInstructionEnd => self.end_doctype_pi_name(State::InsideDoctype(DoctypeSubstate::Outside)),
+                _ => {
+                    let buf = self.take_buf();
+                    Some(self.error(SyntaxError::UnexpectedProcessingInstruction(buf.into(), t)))
+                },
+            },
+            DoctypeSubstate::ProcessingInstruction => match t {
+                Token::ProcessingInstructionEnd => {
+                    self.into_state_continue(State::InsideDoctype(DoctypeSubstate::Outside))
+                },
+                Token::Character(c) if !self.is_valid_xml_char_not_restricted(c) => {
+                    Some(self.error(SyntaxError::InvalidCharacterEntity(c as u32)))
+                },
+                _ => None,
+            },
             DoctypeSubstate::InsideName => match t {
                 Token::Character(c @ 'A'..='Z') => {
                     self.buf.push(c);
@@ -54,6 +120,7 @@ impl PullParser {
                     let buf = self.take_buf();
                     match buf.as_str() {
                         "ENTITY" => self.into_state_continue(State::InsideDoctype(DoctypeSubstate::BeforeEntityName)),
+                        "ATTLIST" if self.config.apply_attribute_defaults => self.into_state_continue(State::InsideDoctype(DoctypeSubstate::AttlistDeclaration)),
                         "NOTATION" | "ELEMENT" | "ATTLIST" => self.into_state_continue(State::InsideDoctype(DoctypeSubstate::SkipDeclaration)),
                         _ => Some(self.error(SyntaxError::UnknownMarkupDeclaration(buf.into()))),
                     }
@@ -70,7 +137,7 @@ impl PullParser {
                     },
                     Token::Character(c) if is_name_start_char(c) => {
                         if self.data.name.len() > self.config.max_name_length {
-                            return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                            return Some(self.limit_exceeded("max_name_length"));
                         }
                         self.data.name.push(c);
                         self.into_state_continue(State::InsideDoctype(DoctypeSubstate::EntityName))
@@ -84,7 +151,7 @@ impl PullParser {
                 },
                 Token::Character(c) if is_name_char(c) => {
                     if self.data.name.len() > self.config.max_name_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_name_length"));
                     }
                     self.data.name.push(c);
                     None
@@ -151,7 +218,7 @@ impl PullParser {
             DoctypeSubstate::PEReferenceDefinition => match t {
                 Token::Character(c) if is_name_char(c) => {
                     if self.data.name.len() > self.config.max_name_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_name_length"));
                     }
                     self.data.name.push(c);
                     None
@@ -229,7 +296,10 @@ impl PullParser {
                 },
                 Token::Character(c) => {
                     self.data.ref_data.push(c);
-                    None
+                    match self.check_numeric_reference_length(&self.data.ref_data) {
+                        Ok(()) => None,
+                        Err(e) => Some(self.error(e)),
+                    }
                 },
                 _ => Some(self.error(SyntaxError::UnexpectedTokenInEntity(t))),
             },
@@ -239,6 +309,112 @@ impl PullParser {
                 },
                 _ => None,
             },
+            DoctypeSubstate::AttlistDeclaration => match t {
+                Token::TagEnd => {
+                    let decl = self.take_buf();
+                    if let Err(e) = self.add_attlist(&decl) {
+                        return Some(self.error(e));
+                    }
+                    self.into_state_continue(State::InsideDoctype(DoctypeSubstate::Outside))
+                },
+                _ => {
+                    if self.buf.len() > self.config.max_data_length {
+                        return Some(self.limit_exceeded("max_data_length"));
+                    }
+                    t.push_to_string(&mut self.buf);
+                    None
+                },
+            },
+        }
+    }
+
+    /// Checks the target of a PI in the DTD, which can't be `xml` there either
+    fn end_doctype_pi_name(&mut self, next: State) -> Option<Result> {
+        let name = self.take_buf();
+        if "xml".eq_ignore_ascii_case(&name) {
+            return Some(self.error(SyntaxError::InvalidXmlProcessingInstruction(name.into())));
+        }
+        self.into_state_continue(next)
+    }
+
+    /// Remembers default values and types from the body of `<!ATTLIST …>`
+    fn add_attlist(&mut self, decl: &str) -> core::result::Result<(), SyntaxError> {
+        let invalid = || SyntaxError::InvalidAttributeListDeclaration(decl.into());
+        let (element, definitions) = parse_attlist(decl).ok_or_else(invalid)?;
+        for (attr, tokenized, raw_value) in definitions {
+            let name: OwnedName = attr.parse().map_err(|_| SyntaxError::InvalidQualifiedName(attr.into()))?;
+            if let Some(raw_value) = raw_value {
+                // report broken references now, rather than on every element
+                self.unescape_attribute_value(raw_value)?;
+            }
+
+            let declarations = self.attribute_declarations.entry(element.into()).or_default();
+            // the first declaration of an attribute is binding
+            if !declarations.iter().any(|d| d.name == name) {
+                declarations.push(AttributeDeclaration { name, default: raw_value.map(String::from), tokenized });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An attribute name, whether its type is tokenized, and its raw default value
+type AttributeDefinition<'a> = (&'a str, bool, Option<&'a str>);
+
+/// Splits `Name (S AttDef)*` into the element name and its attribute definitions.
+/// `None` if it's malformed.
+fn parse_attlist(decl: &str) -> Option<(&str, Vec<AttributeDefinition<'_>>)> {
+    fn skip_whitespace(s: &str) -> &str {
+        s.trim_start_matches(is_whitespace_char)
+    }
+    fn name(s: &str) -> Option<(&str, &str)> {
+        let end = s.find(|c| !is_name_char(c)).unwrap_or(s.len());
+        if end == 0 { None } else { Some(s.split_at(end)) }
+    }
+    // `(a|b)` of enumerations and notations
+    fn skip_group(s: &str) -> Option<&str> {
+        let s = s.strip_prefix('(')?;
+        let end = s.find(')')?;
+        Some(&s[end + 1..])
+    }
+    fn quoted(s: &str) -> Option<(&str, &str)> {
+        let quote = s.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let s = &s[1..];
+        let end = s.find(quote)?;
+        Some((&s[..end], &s[end + 1..]))
+    }
+
+    let (element, mut rest) = name(skip_whitespace(decl))?;
+    let mut definitions = Vec::new();
+    loop {
+        rest = skip_whitespace(rest);
+        if rest.is_empty() {
+            return Some((element, definitions));
         }
+        let (attr, after_name) = name(rest)?;
+        let after_name = skip_whitespace(after_name);
+        // everything but `CDATA` is tokenized: `ID`, `NMTOKENS`, enumerations…
+        let (tokenized, after_type) = if after_name.starts_with('(') {
+            (true, skip_group(after_name)?)
+        } else {
+            match name(after_name)? {
+                ("NOTATION", r) => (true, skip_group(skip_whitespace(r))?),
+                (ty, r) => (ty != "CDATA", r),
+            }
+        };
+        let default_decl = skip_whitespace(after_type);
+        let value_start = if let Some(r) = default_decl.strip_prefix("#FIXED") {
+            skip_whitespace(r)
+        } else if let Some(r) = default_decl.strip_prefix('#') {
+            // #REQUIRED or #IMPLIED
+            rest = name(r)?.1;
+            definitions.push((attr, tokenized, None));
+            continue;
+        } else {
+            default_decl
+        };
+        let (value, r) = quoted(value_start)?;
+        definitions.push((attr, tokenized, Some(value)));
+        rest = r;
     }
 }
@@ -2,16 +2,17 @@ extern crate alloc;
 
 use alloc::string::ToString;
 
-use crate::reader::error::SyntaxError;
-use crate::common::is_name_start_char;
+use crate::reader::error::{Limit, SyntaxError};
+use crate::chars::is_name_start_char;
 use crate::namespace;
-use crate::{attribute::OwnedAttribute, common::is_whitespace_char};
+use crate::{attribute::OwnedAttribute, chars::is_whitespace_char};
 
 use crate::reader::lexer::Token;
 
+use crate::capacity::{Capacity, Stack, Text};
 use super::{OpeningTagSubstate, PullParser, QualifiedNameTarget, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_opening_tag(&mut self, t: Token, s: OpeningTagSubstate) -> Option<Result> {
         let max_attrs = self.config.max_attributes;
         match s {
@@ -21,7 +22,7 @@ impl PullParser {
                                     prefix == namespace::NS_XMLNS_PREFIX =>
                         Some(this.error(SyntaxError::InvalidNamePrefix(prefix.into()))),
                     _ => {
-                        this.data.element_name = Some(name.clone());
+                        this.data.element_name = Some(name);
                         match token {
                             Token::TagEnd => this.emit_start_element(false),
                             Token::EmptyTagEnd => this.emit_start_element(true),
@@ -38,7 +39,7 @@ impl PullParser {
                 Token::Character(c) if is_whitespace_char(c) => None, // skip whitespace
                 Token::Character(c) if is_name_start_char(c) => {
                     if self.buf.len() > self.config.max_name_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_name_length"));
                     }
                     self.buf.push(c);
                     self.into_state_continue(State::InsideOpeningTag(OpeningTagSubstate::InsideAttributeName))
@@ -49,7 +50,7 @@ impl PullParser {
             OpeningTagSubstate::InsideAttributeName => self.read_qualified_name(t, QualifiedNameTarget::AttributeNameTarget, |this, token, name| {
                 // check that no attribute with such name is already present
                 // if there is one, XML is not well-formed
-                if this.data.attributes.iter().any(|attr| attr.name == name) {
+                if this.data.attributes.as_slice().iter().any(|attr| attr.name == name) {
                     return Some(this.error(SyntaxError::RedefinedAttribute(name.to_string().into())))
                 }
 
@@ -78,6 +79,8 @@ impl PullParser {
                             Some(this.error(SyntaxError::CannotRedefineXmlnsPrefix))
                         } else if ln == namespace::NS_XML_PREFIX && &*value != namespace::NS_XML_URI {
                             Some(this.error(SyntaxError::CannotRedefineXmlPrefix))
+                        } else if let Err(e) = namespace::check_reserved(ln, &value) {
+                            Some(this.error(SyntaxError::ReservedNamespace(e)))
                         } else if value.is_empty() {
                             Some(this.error(SyntaxError::CannotUndefinePrefix(ln.into())))
                         } else {
@@ -100,9 +103,20 @@ impl PullParser {
                     // regular attribute
                     _ => {
                         if this.data.attributes.len() >= max_attrs {
-                            return Some(this.error(SyntaxError::ExceededConfiguredLimit));
+                            return Some(this.limit_exceeded("max_attributes"));
                         }
-                        this.data.attributes.insert(OwnedAttribute {
+                        if this.data.attributes.is_full() {
+                            return Some(Err(this.capacity_exceeded(Limit::AttributeCapacity)));
+                        }
+                        #[cfg(feature = "fallible-alloc")]
+                        if this.data.attributes.try_reserve(1).is_err() ||
+                            this.config.lazy_attributes && this.data.raw_attributes.try_reserve(&name).is_err() {
+                            return Some(Err(this.out_of_memory()));
+                        }
+                        if this.config.lazy_attributes {
+                            this.data.raw_attributes.push(&name, this.data.attr_value_start);
+                        }
+                        this.data.attributes.push(OwnedAttribute {
                             name,
                             value
                         });
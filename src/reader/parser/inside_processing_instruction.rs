@@ -1,19 +1,20 @@
 use crate::reader::error::SyntaxError;
-use crate::common::{is_name_char, is_name_start_char, is_whitespace_char};
+use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char};
 
 use crate::reader::events::XmlEvent;
 use crate::reader::lexer::Token;
 
+use crate::capacity::{Capacity, Text};
 use super::{DeclarationSubstate, ProcessingInstructionSubstate, PullParser, Result, State, Encountered};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_processing_instruction(&mut self, t: Token, s: ProcessingInstructionSubstate) -> Option<Result> {
         match s {
             ProcessingInstructionSubstate::PIInsideName => match t {
                 Token::Character(c) if self.buf.is_empty() && is_name_start_char(c) ||
                                  self.buf_has_data() && is_name_char(c) => {
                     if self.buf.len() > self.config.max_name_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_name_length"));
                     }
                     self.buf.push(c);
                     None
@@ -60,6 +61,9 @@ impl PullParser {
                     let name = self.take_buf();
 
                     match &*name {
+                        // `<? ` has no name
+                        "" => Some(self.error(SyntaxError::ProcessingInstructionWithoutName)),
+
                         // We have not ever encountered an element and have not parsed XML declaration
                         "xml" if self.encountered == Encountered::None =>
                             self.into_state_continue(State::InsideDeclaration(DeclarationSubstate::BeforeVersion)),
@@ -98,14 +102,14 @@ impl PullParser {
                     )
                 },
 
-                Token::Character(c) if !self.is_valid_xml_char(c) => {
+                Token::Character(c) if !self.is_valid_xml_char_not_restricted(c) => {
                     Some(self.error(SyntaxError::InvalidCharacterEntity(c as u32)))
                 },
 
                 // Any other token should be treated as plain characters
                 _ => {
                     if self.buf.len() > self.config.max_data_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                        return Some(self.limit_exceeded("max_data_length"));
                     }
                     t.push_to_string(&mut self.buf);
                     None
@@ -1,78 +1,183 @@
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::reader::config::EntityAction;
 use crate::reader::error::SyntaxError;
 use core::char;
-use crate::common::{is_name_char, is_name_start_char, is_whitespace_char};
+use crate::chars::{is_name_char, is_name_start_char, is_whitespace_char};
+use crate::common::char_reference_value;
+use crate::common::{CharReferenceError, MAX_CHAR_REFERENCE_DIGITS};
 use crate::reader::lexer::Token;
-use super::{PullParser, Result, State};
+use crate::capacity::{Capacity, Text};
+use super::{reserve, PullParser, Result, State};
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn inside_reference(&mut self, t: Token) -> Option<Result> {
         match t {
             Token::Character(c) if !self.data.ref_data.is_empty() && is_name_char(c) ||
                              self.data.ref_data.is_empty() && (is_name_start_char(c) || c == '#') => {
                 self.data.ref_data.push(c);
+                if let Some(num_str) = self.data.ref_data.strip_prefix('#') {
+                    if let Err(e) = self.check_numeric_reference_length(num_str) {
+                        return Some(self.error(e));
+                    }
+                }
                 None
             }
 
             Token::ReferenceEnd => {
-                let name = self.data.take_ref_data();
+                // put back below, to be reused for the next reference
+                let mut name = core::mem::take(&mut self.data.ref_data);
                 if name.is_empty() {
                     return Some(self.error(SyntaxError::EmptyEntity));
                 }
 
-                let c = match &*name {
-                    "lt"   => Some('<'),
-                    "gt"   => Some('>'),
-                    "amp"  => Some('&'),
-                    "apos" => Some('\''),
-                    "quot" => Some('"'),
-                    _ if name.starts_with('#') => match self.numeric_reference_from_str(&name[1..]) {
-                        Ok(c) => Some(c),
-                        Err(e) => return Some(self.error(e))
-                    },
-                    _ => None,
+                let c = match self.predefined_reference(&name) {
+                    Ok(c) => c,
+                    Err(e) => return Some(self.error(e)),
                 };
+                let start = self.buf.len();
+                let mut reparsed = 0;
                 if let Some(c) = c {
                     self.buf.push(c);
                 } else if let Some(v) = self.config.c.extra_entities.get(&name) {
+                    trace!("expanding &{}; from extra_entities to {:?}", name, v);
+                    if !reserve(&mut self.buf, v.len()) {
+                        return Some(Err(self.out_of_memory()));
+                    }
                     self.buf.push_str(v);
                 } else if let Some(v) = self.entities.get(&name) {
+                    trace!("expanding &{}; from the DTD to {:?}", name, v);
                     if self.state_after_reference == State::OutsideTag {
                         // an entity can expand to *elements*, so outside of a tag it needs a full reparse
                         if let Err(e) = self.lexer.reparse(v) {
                             return Some(Err(e));
                         }
+                        reparsed = v.len();
                     } else {
                         // however, inside attributes it's not allowed to affect attribute quoting,
-                        // so it can't be fed to the lexer
+                        // so it can't be fed to the lexer, and can't smuggle a `<` into one
+                        if v.contains('<') {
+                            return Some(self.error(SyntaxError::UnexpectedOpeningTag));
+                        }
+                        if !reserve(&mut self.buf, v.len()) {
+                            return Some(Err(self.out_of_memory()));
+                        }
                         self.buf.push_str(v);
                     }
                 } else {
-                    return Some(self.error(SyntaxError::UnexpectedEntity(name.into())));
+                    match self.unknown_entity(&name) {
+                        Ok(v) if reserve(&mut self.buf, v.len()) => self.buf.push_str(&v),
+                        Ok(_) => return Some(Err(self.out_of_memory())),
+                        Err(e) => return Some(self.error(e)),
+                    }
                 }
+                name.clear();
+                self.data.ref_data = name;
                 let prev_st = self.state_after_reference;
-                if prev_st == State::OutsideTag && !is_whitespace_char(self.buf.chars().last().unwrap_or('\0')) {
-                    self.inside_whitespace = false;
+                let mut chunk = None;
+                if prev_st == State::OutsideTag {
+                    // the chunk ends before the reference, and its replacement text starts the next one
+                    let (end, pos) = self.reference_start;
+                    chunk = self.end_chunk(start, self.buf.len() - start + reparsed, end);
+                    if chunk.is_some() && !self.buf.is_empty() {
+                        self.push_pos_at(pos);
+                    }
+                    if !is_whitespace_char(self.buf.as_str().chars().last().unwrap_or('\0')) {
+                        self.inside_whitespace = false;
+                    }
                 }
-                self.into_state_continue(prev_st)
+                self.into_state(prev_st, chunk)
             }
 
             _ => Some(self.error(SyntaxError::UnexpectedTokenInEntity(t))),
         }
     }
 
-    pub(crate) fn numeric_reference_from_str(&self, num_str: &str) -> core::result::Result<char, SyntaxError> {
-        let val = if let Some(hex) = num_str.strip_prefix('x') {
-            u32::from_str_radix(hex, 16).map_err(move |_| SyntaxError::InvalidNumericEntity(num_str.into()))?
-        } else {
-            u32::from_str_radix(num_str, 10).map_err(move |_| SyntaxError::InvalidNumericEntity(num_str.into()))?
+    /// Resolves predefined entities and character references, `None` for other names
+    fn predefined_reference(&self, name: &str) -> core::result::Result<Option<char>, SyntaxError> {
+        Ok(match name {
+            "lt"   => Some('<'),
+            "gt"   => Some('>'),
+            "amp"  => Some('&'),
+            "apos" => Some('\''),
+            "quot" => Some('"'),
+            _ if name.starts_with('#') => Some(self.numeric_reference_from_str(&name[1..])?),
+            _ => None,
+        })
+    }
+
+    /// Text to use for a reference to an entity that isn't known, according to `on_unknown_entity`
+    fn unknown_entity(&self, name: &str) -> core::result::Result<String, SyntaxError> {
+        let action = match self.config.on_unknown_entity {
+            Some(handler) => (handler.0)(name),
+            None => EntityAction::Error,
         };
-        match char::from_u32(val) {
-            Some(c) if self.is_valid_xml_char(c) => Ok(c),
-            Some(_) if self.config.c.replace_unknown_entity_references => Ok('\u{fffd}'),
-            None if self.config.c.replace_unknown_entity_references => {
-                Ok('\u{fffd}')
-            },
-            _ => Err(SyntaxError::InvalidCharacterEntity(val)),
+        match action {
+            EntityAction::ReplaceWith(v) if v.len() > self.config.max_entity_expansion_length => Err(SyntaxError::EntityTooBig),
+            EntityAction::ReplaceWith(v) => Ok(v),
+            EntityAction::KeepReference => Ok(format!("&{name};")),
+            EntityAction::Error => Err(SyntaxError::UnexpectedEntity(name.into())),
+        }
+    }
+
+    /// Expands references in an attribute value that was delivered raw
+    /// because of `lazy_attributes`, the same way they'd be expanded eagerly.
+    pub(crate) fn unescape_attribute_value(&self, raw: &str) -> core::result::Result<String, SyntaxError> {
+        let mut value = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(start) = rest.find('&') {
+            value.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            let end = rest.find(';').ok_or_else(|| SyntaxError::UnexpectedEntity(rest.into()))?;
+            let name = &rest[..end];
+            rest = &rest[end + 1..];
+            if name.is_empty() {
+                return Err(SyntaxError::EmptyEntity);
+            }
+
+            if let Some(c) = self.predefined_reference(name)? {
+                value.push(c);
+            } else if let Some(v) = self.config.c.extra_entities.get(name) {
+                value.push_str(v);
+            } else if let Some(v) = self.entities.get(name) {
+                if v.contains('<') {
+                    return Err(SyntaxError::UnexpectedOpeningTag);
+                }
+                value.push_str(v);
+            } else {
+                value.push_str(&self.unknown_entity(name)?);
+            }
+            if value.len() > self.config.max_attribute_length {
+                debug!("max_attribute_length exceeded by expanding &{};", name);
+                return Err(SyntaxError::ExceededConfiguredLimit);
+            }
+        }
+        value.push_str(rest);
+        Ok(value)
+    }
+
+    pub(crate) fn numeric_reference_from_str(&self, num_str: &str) -> core::result::Result<char, SyntaxError> {
+        let replace = self.config.c.replace_unknown_entity_references;
+        match char_reference_value(num_str) {
+            Ok(c) if self.is_valid_xml_char(c) => Ok(c),
+            Err(CharReferenceError::Malformed) => Err(SyntaxError::InvalidNumericEntity(num_str.into())),
+            Ok(_) | Err(_) if replace => Ok('\u{fffd}'),
+            Ok(c) => Err(SyntaxError::InvalidCharacterEntity(c as u32)),
+            Err(CharReferenceError::Surrogate(val)) => Err(SyntaxError::InvalidCharacterEntity(val)),
+            Err(CharReferenceError::OutOfRange) => Err(SyntaxError::InvalidNumericEntity(num_str.into())),
+        }
+    }
+
+    /// Fails early on a character reference which is getting too long to be valid, before
+    /// buffering all of it
+    pub(crate) fn check_numeric_reference_length(&self, num_str: &str) -> core::result::Result<(), SyntaxError> {
+        // digits and an `x`
+        if num_str.len() > MAX_CHAR_REFERENCE_DIGITS + 1 {
+            return Err(SyntaxError::InvalidNumericEntity(num_str.into()));
         }
+        Ok(())
     }
 }
@@ -1,20 +1,25 @@
-use crate::reader::error::SyntaxError;
-use crate::common::is_whitespace_char;
+use crate::reader::config::WhitespaceHandling;
+use crate::reader::error::{Limit, SyntaxError};
+use crate::chars::is_whitespace_char;
+use crate::common::Position;
 use crate::reader::events::XmlEvent;
-use crate::reader::lexer::Token;
+use crate::reader::lexer::{CharSource, Token};
+use crate::reader::Error;
 
+use crate::capacity::{Capacity, Stack, Text};
 use super::{
-    ClosingTagSubstate, DoctypeSubstate, Encountered, OpeningTagSubstate,
+    reserve, ClosingTagSubstate, DoctypeSubstate, Encountered, OpeningTagSubstate,
     ProcessingInstructionSubstate, PullParser, Result, State,
 };
 
-impl PullParser {
+impl<C: Capacity> PullParser<C> {
     pub fn outside_tag(&mut self, t: Token) -> Option<Result> {
         match t {
             Token::Character(c) => {
+                let chunk = self.end_chunk_before_token(c.len_utf8());
                 if is_whitespace_char(c) {
                     // skip whitespace outside of the root element
-                    if (self.config.c.trim_whitespace && self.buf.is_empty()) ||
+                    if (self.config.c.trim_whitespace && self.buf.is_empty() && !self.chunked) ||
                         (self.depth() == 0 && self.config.c.ignore_root_level_whitespace) {
                             return None;
                     }
@@ -32,10 +37,10 @@ impl PullParser {
                 if self.buf.is_empty() {
                     self.push_pos();
                 } else if self.buf.len() > self.config.max_data_length {
-                    return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                    return Some(self.limit_exceeded("max_data_length"));
                 }
                 self.buf.push(c);
-                None
+                chunk
             },
 
             Token::CommentEnd | Token::TagEnd | Token::EqualsSign |
@@ -44,32 +49,35 @@ impl PullParser {
                 if self.depth() == 0 {
                     return Some(self.error(SyntaxError::UnexpectedTokenOutsideRoot(t)));
                 }
+                let s = t.as_static_str().unwrap_or_default();
+                let chunk = self.end_chunk_before_token(s.len());
                 self.inside_whitespace = false;
 
-                if let Some(s) = t.as_static_str() {
-                    if self.buf.is_empty() {
-                        self.push_pos();
-                    } else if self.buf.len() > self.config.max_data_length {
-                        return Some(self.error(SyntaxError::ExceededConfiguredLimit));
-                    }
-
-                    self.buf.push_str(s);
+                if self.buf.is_empty() {
+                    self.push_pos();
+                } else if self.buf.len() > self.config.max_data_length {
+                    return Some(self.limit_exceeded("max_data_length"));
                 }
-                None
+                self.buf.push_str(s);
+                chunk
             },
 
             Token::ReferenceStart if self.depth() > 0 => {
+                self.reference_start = (self.lexer.token_span().start, self.lexer.position());
                 self.state_after_reference = State::OutsideTag;
                 self.into_state_continue(State::InsideReference)
             },
 
             Token::ReferenceEnd if self.depth() > 0 => { // Semi-colon in a text outside an entity
+                let chunk = self.end_chunk_before_token(1);
                 self.inside_whitespace = false;
-                if self.buf.len() > self.config.max_data_length {
-                    return Some(self.error(SyntaxError::ExceededConfiguredLimit));
+                if self.buf.is_empty() {
+                    self.push_pos();
+                } else if self.buf.len() > self.config.max_data_length {
+                    return Some(self.limit_exceeded("max_data_length"));
                 }
                 Token::ReferenceEnd.push_to_string(&mut self.buf);
-                None
+                chunk
             },
 
             Token::CommentStart if self.config.c.coalesce_characters && self.config.c.ignore_comments => {
@@ -90,30 +98,65 @@ impl PullParser {
                 // or a whitespace
                 let mut next_event = if self.buf_has_data() {
                     let buf = self.take_buf();
-                    if self.inside_whitespace && self.config.c.trim_whitespace {
+                    if let (Some(handling), true) = (self.config.whitespace_handling, self.inside_whitespace) {
+                        if self.is_space_preserved() {
+                            Some(Ok(XmlEvent::Characters(buf)))
+                        } else {
+                            match handling {
+                                WhitespaceHandling::Whitespace => Some(Ok(XmlEvent::Whitespace(buf))),
+                                WhitespaceHandling::Characters => Some(Ok(XmlEvent::Characters(buf))),
+                                WhitespaceHandling::Drop => {
+                                    // there will be no event to pop the position of the text
+                                    if self.pos.len() > 1 {
+                                        self.pos.pop();
+                                    }
+                                    self.pool.give(buf);
+                                    None
+                                },
+                            }
+                        }
+                    } else if self.inside_whitespace && self.config.c.trim_whitespace {
+                        self.pool.give(buf);
                         None
                     } else if self.inside_whitespace && !self.config.c.whitespace_to_characters {
-                        debug_assert!(buf.chars().all(|ch| ch.is_whitespace()), "ws={buf:?}");
+                        debug_assert!(buf.chars().all(is_whitespace_char), "ws={buf:?}");
                         Some(Ok(XmlEvent::Whitespace(buf)))
                     } else if self.config.c.trim_whitespace {
-                        Some(Ok(XmlEvent::Characters(buf.trim_matches(is_whitespace_char).into())))
+                        // the start of the text was trimmed with the first chunk
+                        let text = if self.chunked { buf.trim_end_matches(is_whitespace_char) } else { buf.trim_matches(is_whitespace_char) };
+                        let ev = if text.is_empty() {
+                            if self.pos.len() > 1 {
+                                self.pos.pop();
+                            }
+                            None
+                        } else {
+                            Some(Ok(XmlEvent::Characters(text.into())))
+                        };
+                        self.pool.give(buf);
+                        ev
                     } else {
                         Some(Ok(XmlEvent::Characters(buf)))
                     }
                 } else { None };
                 self.inside_whitespace = true;  // Reset inside_whitespace flag
+                self.chunked = false;
 
                 // pos is popped whenever an event is emitted, so pushes must happen only if there will be an event to balance it
                 // and ignored comments don't pop
                 if t != Token::CommentStart || !self.config.c.ignore_comments {
                     self.push_pos();
                 }
+                self.start_markup();
                 match t {
                     Token::OpeningTagStart if self.depth() > 0 || self.encountered < Encountered::Element || self.config.allow_multiple_root_elements => {
                         if let Some(e) = self.set_encountered(Encountered::Element) {
                             next_event = Some(e);
                         }
+                        if self.nst.0.is_full() {
+                            return Some(Err(self.capacity_exceeded(Limit::DepthCapacity)));
+                        }
                         self.nst.push_empty();
+                        trace!("namespace scope pushed");
                         self.into_state(State::InsideOpeningTag(OpeningTagSubstate::InsideName), next_event)
                     },
 
@@ -136,7 +179,7 @@ impl PullParser {
                         // We don't have a doctype event so skip this position
                         // FIXME: update when we have a doctype event
                         self.next_pos();
-                        self.into_state(State::InsideDoctype(DoctypeSubstate::Outside), next_event)
+                        self.into_state(State::InsideDoctype(DoctypeSubstate::BeforeName), next_event)
                     },
 
                     Token::ProcessingInstructionStart =>
@@ -152,6 +195,67 @@ impl PullParser {
         }
     }
 
+    /// Takes as much text as the lexer has buffered at once, rather than a `Character` at a
+    /// time. It's for after the first char of the text, which settled whether it's whitespace.
+    pub fn read_text<S: CharSource>(&mut self, r: &mut S) -> core::result::Result<(), Error> {
+        let mut max_len = self.config.max_data_length.saturating_sub(self.buf.len());
+        if let Some(size) = self.config.characters_chunk_size {
+            // the char after it ends the chunk
+            max_len = max_len.min(size.saturating_sub(self.buf.len()));
+        }
+        let mut text = self.lexer.plain_text(r, max_len);
+        if !text.is_ascii() {
+            // an invalid char is reported by `outside_tag`
+            if let Some((i, _)) = text.char_indices().find(|&(_, c)| !self.is_valid_xml_char_not_restricted(c)) {
+                text = &text[..i];
+            }
+        }
+        if !reserve(&mut self.buf, text.len()) {
+            return Err(self.out_of_memory());
+        }
+        let start = self.buf.len();
+        self.buf.push_str(text);
+        if self.buf.is_exceeded() {
+            return Err(self.capacity_exceeded(Limit::TextCapacity));
+        }
+        self.lexer.skip_text(r, &self.buf.as_str()[start..]);
+        Ok(())
+    }
+
+    /// Ends a chunk of the text before its byte `at`, if it and `len` bytes after it, which
+    /// start at `end` in the source, wouldn't fit in `characters_chunk_size`.
+    ///
+    /// The text after `at` is kept for the next chunk, and so are the `len` bytes, which the
+    /// caller adds. The caller also records where the next chunk starts.
+    pub(super) fn end_chunk(&mut self, at: usize, len: usize, end: usize) -> Option<Result> {
+        let size = self.config.characters_chunk_size?;
+        // whitespace-only text may turn out to be a `Whitespace` event
+        if self.inside_whitespace || at == 0 || len == 0 || at + len <= size {
+            return None;
+        }
+        let mut chunk = if at == self.buf.len() {
+            self.take_buf()
+        } else {
+            self.buf.split_to(at)
+        };
+        if self.config.c.trim_whitespace && !self.chunked {
+            let trimmed = chunk.len() - chunk.trim_start_matches(is_whitespace_char).len();
+            chunk.drain(..trimmed);
+        }
+        self.chunked = true;
+        self.chunk_end = Some(end);
+        Some(Ok(XmlEvent::Characters(chunk)))
+    }
+
+    /// Like `end_chunk`, before the token just read, unless it's from an entity's replacement text
+    fn end_chunk_before_token(&mut self, len: usize) -> Option<Result> {
+        let span = self.lexer.token_span();
+        if span.is_empty() {
+            return None;
+        }
+        self.end_chunk(self.buf.len(), len, span.start)
+    }
+
     pub fn document_start(&mut self, t: Token) -> Option<Result> {
         debug_assert!(self.encountered < Encountered::Declaration);
 
@@ -176,25 +280,33 @@ impl PullParser {
             },
 
             Token::CommentStart => {
+                self.start_markup();
                 let next_event = self.set_encountered(Encountered::Comment);
                 self.into_state(State::InsideComment, next_event)
             }
 
             Token::OpeningTagStart => {
+                self.start_markup();
                 let next_event = self.set_encountered(Encountered::Element);
+                if self.nst.0.is_full() {
+                    return Some(Err(self.capacity_exceeded(Limit::DepthCapacity)));
+                }
                 self.nst.push_empty();
+                trace!("namespace scope pushed");
                 self.into_state(State::InsideOpeningTag(OpeningTagSubstate::InsideName), next_event)
             },
 
             Token::DoctypeStart => {
+                self.start_markup();
                 let next_event = self.set_encountered(Encountered::Doctype);
                 // We don't have a doctype event so skip this position
                 // FIXME: update when we have a doctype event
                 self.next_pos();
-                self.into_state(State::InsideDoctype(DoctypeSubstate::Outside), next_event)
+                self.into_state(State::InsideDoctype(DoctypeSubstate::BeforeName), next_event)
             },
 
             Token::ProcessingInstructionStart => {
+                self.start_markup();
                 self.push_pos();
                 self.into_state_continue(State::InsideProcessingInstruction(ProcessingInstructionSubstate::PIInsideName))
             },
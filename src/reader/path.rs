@@ -0,0 +1,354 @@
+//! Contains `PathMatcher`, which follows the element path of an event stream and tells
+//! which events are inside elements at given paths.
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::iter::FusedIterator;
+
+use crate::name::{is_ncname, OwnedName};
+use crate::reader::{EventReader, Result, Source, XmlEvent};
+
+/// Matches the path of each element of an event stream against absolute element paths.
+///
+/// A path is a list of steps, each after a `/`, like `/rss/channel/item/title`. A step is:
+///
+/// * `local`, an element with this local name in any namespace, or none;
+/// * `{uri}local`, an element with this local name in the namespace `uri`;
+/// * `{}local`, an element with this local name and no namespace;
+/// * `*`, `{uri}*` or `{}*`, any element, in the namespace if one is given.
+///
+/// Events are passed to `feed()` in document order, and it returns where they are relative
+/// to the elements which match. Several paths are matched at once; only the paths which
+/// matched the ancestors of an element are tried on it.
+///
+/// ```rust
+/// use xml_no_std::reader::{MatchState, PathMatcher, XmlEvent};
+/// use xml_no_std::EventReader;
+///
+/// let doc = "<rss><channel><title>c</title><item><title>a</title></item><item><title>b</title></item></channel></rss>";
+/// let mut matcher = PathMatcher::new("/rss/channel/item/title").unwrap();
+/// let mut titles = Vec::new();
+/// for event in EventReader::from_str(doc) {
+///     let event = event.unwrap();
+///     if let (MatchState::Inside, XmlEvent::Characters(text)) = (matcher.feed(&event), event) {
+///         titles.push(text);
+///     }
+/// }
+/// assert_eq!(titles, ["a", "b"]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PathMatcher {
+    paths: Vec<Vec<Step>>,
+    /// Indices of all paths, tried on the root element
+    all: Vec<usize>,
+    /// An entry for every element which is open
+    open: Vec<Frame>,
+    /// Number of entries of `open` which matched a path
+    open_matches: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    /// Paths which matched this element, and have steps left for its children
+    candidates: Vec<usize>,
+    /// The first path which ends at this element
+    matched: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Step {
+    /// `None` is any namespace, `Some(None)` no namespace
+    namespace: Option<Option<String>>,
+    /// `None` is any local name
+    local_name: Option<String>,
+}
+
+impl Step {
+    fn matches(&self, name: &OwnedName) -> bool {
+        self.namespace.as_ref().map_or(true, |ns| ns.as_deref() == name.namespace.as_deref())
+            && self.local_name.as_ref().map_or(true, |local| *local == name.local_name)
+    }
+}
+
+/// Where an event is relative to the elements which match, see `PathMatcher::feed`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MatchState {
+    /// The event isn't inside an element which matches
+    Outside,
+    /// The start of an element which matches the path with this index.
+    /// If it matches several, it's the first one.
+    Start(usize),
+    /// The event is inside an element which matches
+    Inside,
+    /// The end of an element which matches the path with this index
+    End(usize),
+}
+
+impl MatchState {
+    /// Returns true for the events of matching elements, including their start and end.
+    #[inline]
+    #[must_use]
+    pub fn is_selected(self) -> bool {
+        self != MatchState::Outside
+    }
+}
+
+/// A path which `PathMatcher` can't use.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PathError {
+    /// Byte index of the step which is wrong
+    pub index: usize,
+    /// What's wrong with it
+    pub reason: &'static str,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid path at index {}: {}", self.index, self.reason)
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for PathError {}
+
+impl PathMatcher {
+    /// Creates a matcher for one path.
+    pub fn new(path: &str) -> core::result::Result<PathMatcher, PathError> {
+        let mut matcher = PathMatcher::default();
+        matcher.add(path)?;
+        Ok(matcher)
+    }
+
+    /// Adds another path, and returns its index, which `MatchState` refers to it by.
+    ///
+    /// Paths should be added before events are fed; elements which are open already
+    /// aren't matched against it.
+    pub fn add(&mut self, path: &str) -> core::result::Result<usize, PathError> {
+        let mut steps = Vec::new();
+        let mut index = 0;
+        while index < path.len() {
+            let rest = path[index..].strip_prefix('/').ok_or(PathError { index, reason: "expected '/'" })?;
+            let start = index + 1;
+            let (namespace, rest) = match rest.strip_prefix('{') {
+                Some(r) => {
+                    let end = r.find('}').ok_or(PathError { index: start, reason: "'{' isn't closed" })?;
+                    (Some(Some(&r[..end]).filter(|ns| !ns.is_empty()).map(String::from)), &r[end + 1..])
+                },
+                None => (None, rest),
+            };
+            let local_name = &rest[..rest.find('/').unwrap_or(rest.len())];
+            let local_name = match local_name {
+                "*" => None,
+                name if is_ncname(name) => Some(String::from(name)),
+                _ => return Err(PathError { index: start, reason: "expected a local name or '*'" }),
+            };
+            index = path.len() - rest.len() + rest.find('/').unwrap_or(rest.len());
+            steps.push(Step { namespace, local_name });
+        }
+        if steps.is_empty() {
+            return Err(PathError { index: 0, reason: "a path needs at least one step" });
+        }
+        self.paths.push(steps);
+        self.all.push(self.paths.len() - 1);
+        Ok(self.paths.len() - 1)
+    }
+
+    /// Follows the element path with the next event of the document, and tells where the event is.
+    pub fn feed(&mut self, event: &XmlEvent) -> MatchState {
+        match event {
+            XmlEvent::StartElement { name, .. } => {
+                let depth = self.open.len();
+                let parent = self.open.last().map_or(&self.all, |frame| &frame.candidates);
+                let mut candidates = Vec::new();
+                let mut matched = None;
+                for &path in parent {
+                    let steps = &self.paths[path];
+                    if !steps[depth].matches(name) {
+                        continue;
+                    }
+                    if steps.len() == depth + 1 {
+                        matched = matched.or(Some(path));
+                    } else {
+                        candidates.push(path);
+                    }
+                }
+                self.open.push(Frame { candidates, matched });
+                match matched {
+                    Some(path) => {
+                        self.open_matches += 1;
+                        MatchState::Start(path)
+                    },
+                    None => self.state(),
+                }
+            },
+            XmlEvent::EndElement { .. } => match self.open.pop().and_then(|frame| frame.matched) {
+                Some(path) => {
+                    self.open_matches -= 1;
+                    MatchState::End(path)
+                },
+                None => self.state(),
+            },
+            _ => self.state(),
+        }
+    }
+
+    /// Forgets the open elements, to start on another document.
+    pub fn reset(&mut self) {
+        self.open.clear();
+        self.open_matches = 0;
+    }
+
+    #[inline]
+    fn state(&self) -> MatchState {
+        if self.open_matches > 0 { MatchState::Inside } else { MatchState::Outside }
+    }
+
+    /// Whether nothing inside the innermost open element can match
+    #[inline]
+    fn is_dead_end(&self) -> bool {
+        self.open_matches == 0 && self.open.last().map_or(false, |frame| frame.candidates.is_empty())
+    }
+}
+
+/// An iterator over the events inside elements which match a `PathMatcher`,
+/// created with `EventReader::select`.
+///
+/// Start and end tags of matching elements are included. Subtrees in which nothing can match
+/// are skipped without being followed. Errors are returned once, and end the iteration.
+pub struct Select<S: Source> {
+    reader: EventReader<S>,
+    matcher: PathMatcher,
+    finished: bool,
+}
+
+impl<S: Source> Select<S> {
+    pub(crate) fn new(reader: EventReader<S>, matcher: PathMatcher) -> Self {
+        Select { reader, matcher, finished: false }
+    }
+
+    /// Unwraps the iterator, returning the internal `EventReader`.
+    #[inline]
+    pub fn into_inner(self) -> EventReader<S> {
+        self.reader
+    }
+
+    fn next_selected(&mut self) -> Result<Option<XmlEvent>> {
+        loop {
+            let event = self.reader.next()?;
+            if event == XmlEvent::EndDocument {
+                return Ok(None);
+            }
+            if self.matcher.feed(&event).is_selected() {
+                return Ok(Some(event));
+            }
+            if self.matcher.is_dead_end() {
+                self.reader.skip()?;
+                self.matcher.open.pop();
+            }
+        }
+    }
+}
+
+impl<S: Source> FusedIterator for Select<S> {
+}
+
+impl<S: Source> Iterator for Select<S> {
+    type Item = Result<XmlEvent>;
+
+    fn next(&mut self) -> Option<Result<XmlEvent>> {
+        if self.finished {
+            return None;
+        }
+        let next = self.next_selected().transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.finished = true;
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::{MatchState, PathMatcher};
+    use crate::reader::XmlEvent;
+    use crate::EventReader;
+
+    const FEED: &str = r#"<rss xmlns:m="urn:m"><channel><title>c</title>
+        <item><title>a</title><m:title>x</m:title></item>
+        <item><title>b</title><link><title>no</title></link></item>
+    </channel></rss>"#;
+
+    fn texts(paths: &[&str]) -> Vec<(usize, alloc::string::String)> {
+        let mut matcher = PathMatcher::new(paths[0]).unwrap();
+        for path in &paths[1..] {
+            matcher.add(path).unwrap();
+        }
+        let mut current = None;
+        let mut found = Vec::new();
+        for event in EventReader::from_str(FEED) {
+            let event = event.unwrap();
+            match (matcher.feed(&event), event) {
+                (MatchState::Start(path), _) => current = Some(path),
+                (MatchState::Inside, XmlEvent::Characters(text)) => found.push((current.unwrap(), text)),
+                _ => {},
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(texts(&["/rss/channel/item/title"]), [(0, "a".into()), (0, "x".into()), (0, "b".into())]);
+        assert_eq!(texts(&["/rss/channel/item/{}title"]), [(0, "a".into()), (0, "b".into())]);
+        assert_eq!(texts(&["/rss/*/item/{urn:m}*"]), [(0, "x".into())]);
+        assert_eq!(texts(&["/rss/channel/title", "/*/*/*/link"]), [(0, "c".into()), (1, "no".into())]);
+        assert!(texts(&["/channel"]).is_empty());
+    }
+
+    #[test]
+    fn match_states() {
+        let mut matcher = PathMatcher::new("/a/b").unwrap();
+        matcher.add("/a/*").unwrap();
+        let states: Vec<_> = EventReader::from_str("<a><b>t<c/></b><d/></a>").into_iter()
+            .map(|e| matcher.feed(&e.unwrap())).collect();
+        assert_eq!(states, [
+            MatchState::Outside, MatchState::Outside,
+            MatchState::Start(0), MatchState::Inside, MatchState::Inside, MatchState::Inside, MatchState::End(0),
+            MatchState::Start(1), MatchState::End(1),
+            MatchState::Outside, MatchState::Outside,
+        ]);
+    }
+
+    #[test]
+    fn invalid_paths() {
+        for (path, index) in [("", 0), ("a/b", 0), ("/a//b", 3), ("/a/{urn:x", 3), ("/a b", 1), ("/a/", 3), ("/p:a", 1)] {
+            assert_eq!(PathMatcher::new(path).unwrap_err().index, index, "{path}");
+        }
+        let matcher = PathMatcher::new("/{http://example.com/a/b}c/*").unwrap();
+        assert_eq!(matcher.paths[0].len(), 2);
+    }
+
+    #[test]
+    fn select() {
+        let selected: Vec<_> = EventReader::from_str(FEED).select(PathMatcher::new("/rss/channel/item/link").unwrap())
+            .map(Result::unwrap).collect();
+        assert_eq!(selected.len(), 5);
+        assert!(matches!(&selected[0], XmlEvent::StartElement { name, .. } if name.local_name == "link"));
+        assert_eq!(selected[2], XmlEvent::Characters("no".into()));
+
+        let mut select = EventReader::from_str("<a><b>x</a>").select(PathMatcher::new("/a/b").unwrap());
+        assert!(select.next().unwrap().is_ok());
+        assert!(select.next().unwrap().is_ok());
+        assert!(select.next().unwrap().is_err());
+        assert!(select.next().is_none());
+    }
+}
@@ -0,0 +1,333 @@
+//! Contains `Source`, the input of `EventReader`.
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use core::borrow::Borrow;
+use core::fmt;
+
+/// Where `EventReader` takes the bytes of the document from.
+///
+/// It's implemented for byte slices and their iterators, so `EventReader::new(doc.iter())` and
+/// `EventReader::new(&doc[..])` work, and for `&mut` of any source. Other iterators of bytes
+/// can be wrapped in `IterSource`, and iterators of `Result`s in `TryIterSource`.
+///
+/// A source which is read in chunks should also implement `buffered` and `consume`, so the
+/// reader can take the bytes it already has at once rather than one by one.
+///
+/// ```rust
+/// use xml_no_std::reader::{EventReader, Source, SourceError, XmlEvent};
+///
+/// /// A document in chunks, like the packets it arrived in
+/// struct Chunks<'a> {
+///     chunks: core::slice::Iter<'a, &'a [u8]>,
+///     current: &'a [u8],
+/// }
+///
+/// impl Source for Chunks<'_> {
+///     fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+///         while self.current.is_empty() {
+///             self.current = self.chunks.next()?;
+///         }
+///         let b = self.current[0];
+///         self.current = &self.current[1..];
+///         Some(Ok(b))
+///     }
+///
+///     fn buffered(&self) -> &[u8] {
+///         self.current
+///     }
+///
+///     fn consume(&mut self, n: usize) {
+///         self.current = &self.current[n..];
+///     }
+/// }
+///
+/// let chunks: &[&[u8]] = &[b"<a>t", b"ext</a>"];
+/// let mut reader = EventReader::new(Chunks { chunks: chunks.iter(), current: &[] });
+/// reader.next().unwrap();
+/// reader.next().unwrap();
+/// assert_eq!(reader.next().unwrap(), XmlEvent::Characters("text".into()));
+/// ```
+pub trait Source {
+    /// Takes the next byte, or returns `None` at the end of the document.
+    ///
+    /// # Errors
+    ///
+    /// An error ends the document: the reader fails with it rather than producing `EndDocument`.
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>>;
+
+    /// Returns bytes which can be taken at once without reading more, like the rest of a
+    /// slice or of a buffer. It may be fewer than are left, and is empty by default.
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Takes the first `n` bytes of those `buffered` returned, as if `next_byte` had been
+    /// called `n` times.
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        debug_assert_eq!(n, 0, "consume() without buffered()");
+    }
+
+    /// Whether the document is decoded already, like in `CharIterSource`. The reader then takes
+    /// it with `next_char` rather than decoding bytes: a leading U+FEFF is skipped like a byte
+    /// order mark, the encoding in the `<?xml?>` declaration isn't checked, and offsets count
+    /// UTF-8 bytes of the chars.
+    const DECODED: bool = false;
+
+    /// Takes the next char of a `DECODED` source, or returns `None` at the end of the document.
+    /// Sources of bytes don't implement it.
+    ///
+    /// # Errors
+    ///
+    /// Like those of `next_byte`.
+    #[inline]
+    fn next_char(&mut self) -> Option<Result<char, SourceError>> {
+        None
+    }
+}
+
+/// An error of a `Source`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SourceError {
+    /// Reading failed, e.g. in `ReadSource`, reported as `ErrorKind::Io`.
+    Io(String),
+    /// Any other error, reported as `ErrorKind::Source`.
+    Other(String),
+}
+
+impl SourceError {
+    /// Creates an error with the message of `e`.
+    #[cold]
+    pub fn new(e: impl fmt::Display) -> Self {
+        SourceError::Other(e.to_string())
+    }
+
+    /// Returns the message of the error.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        match self {
+            SourceError::Io(message) | SourceError::Other(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for SourceError {
+    #[cold]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for SourceError {}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for SourceError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.message());
+    }
+}
+
+impl Source for &[u8] {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        let (&first, rest) = self.split_first()?;
+        *self = rest;
+        Some(Ok(first))
+    }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        self
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        *self = &self[n..];
+    }
+}
+
+impl Source for core::slice::Iter<'_, u8> {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        self.next().map(|&b| Ok(b))
+    }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        self.as_slice()
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        if n > 0 {
+            self.nth(n - 1);
+        }
+    }
+}
+
+impl<S: Source + ?Sized> Source for &mut S {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        (**self).next_byte()
+    }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        (**self).buffered()
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        (**self).consume(n);
+    }
+
+    const DECODED: bool = S::DECODED;
+
+    #[inline]
+    fn next_char(&mut self) -> Option<Result<char, SourceError>> {
+        (**self).next_char()
+    }
+}
+
+/// A `Source` of the bytes of an iterator, of `u8` or `&u8`.
+///
+/// ```rust
+/// use xml_no_std::reader::{EventReader, IterSource};
+///
+/// let doc = [&b"<a>"[..], b"</a>"];
+/// let reader = EventReader::new(IterSource(doc.iter().flat_map(|chunk| chunk.iter())));
+/// assert_eq!(reader.into_iter().count(), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IterSource<I>(pub I);
+
+impl<I: Iterator> Source for IterSource<I> where I::Item: Borrow<u8> {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        self.0.next().map(|b| Ok(*b.borrow()))
+    }
+}
+
+/// A `Source` of the bytes of an iterator which may fail, whose errors are reported as
+/// `ErrorKind::Source`.
+///
+/// ```rust
+/// use xml_no_std::reader::{ErrorKind, EventReader, TryIterSource};
+///
+/// let doc = b"<a>".iter().map(Ok).chain([Err("checksum mismatch")]);
+/// let err = EventReader::new(TryIterSource(doc)).into_iter().find_map(Result::err).unwrap();
+/// assert_eq!(err.kind(), &ErrorKind::Source("checksum mismatch".into()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TryIterSource<I>(pub I);
+
+impl<I: Iterator<Item = Result<B, E>>, B: Borrow<u8>, E: fmt::Display> Source for TryIterSource<I> {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        self.0.next().map(|b| b.map(|b| *b.borrow()).map_err(SourceError::new))
+    }
+}
+
+/// A `Source` of chars which are decoded already, e.g. `str::chars()` or the output of
+/// another decoder, which the reader takes as they are, see `Source::DECODED`. Its bytes
+/// are the chars in UTF-8.
+///
+/// ```rust
+/// use xml_no_std::reader::{CharIterSource, EventReader, XmlEvent};
+///
+/// let mut reader = EventReader::new(CharIterSource::new("\u{feff}<a>ą</a>".chars()));
+/// reader.next().unwrap();
+/// reader.next().unwrap();
+/// assert_eq!(reader.next().unwrap(), XmlEvent::Characters("ą".into()));
+/// assert_eq!(reader.last_event_span(), 3..5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CharIterSource<I> {
+    chars: I,
+    /// The UTF-8 of the char `next_byte` is taking, `utf8[taken..len]` are left
+    utf8: [u8; 4],
+    taken: u8,
+    len: u8,
+}
+
+impl<I: Iterator<Item = char>> CharIterSource<I> {
+    /// Creates a source of the `chars`.
+    #[inline]
+    pub fn new(chars: I) -> Self {
+        CharIterSource { chars, utf8: [0; 4], taken: 0, len: 0 }
+    }
+
+    /// Unwraps the source, returning the chars which haven't been taken.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.chars
+    }
+}
+
+impl<I: Iterator<Item = char>> Source for CharIterSource<I> {
+    #[inline]
+    fn next_byte(&mut self) -> Option<Result<u8, SourceError>> {
+        if self.taken == self.len {
+            let c = self.chars.next()?;
+            self.len = c.encode_utf8(&mut self.utf8).len() as u8;
+            self.taken = 0;
+        }
+        self.taken += 1;
+        Some(Ok(self.utf8[usize::from(self.taken - 1)]))
+    }
+
+    #[inline]
+    fn buffered(&self) -> &[u8] {
+        &self.utf8[usize::from(self.taken)..usize::from(self.len)]
+    }
+
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        self.taken += n as u8;
+    }
+
+    const DECODED: bool = true;
+
+    #[inline]
+    fn next_char(&mut self) -> Option<Result<char, SourceError>> {
+        debug_assert_eq!(self.taken, self.len, "next_char() after part of a char was taken");
+        self.chars.next().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CharIterSource, IterSource, Source, TryIterSource};
+
+    fn drain(mut source: impl Source) -> Result<Vec<u8>, super::SourceError> {
+        let mut bytes = Vec::new();
+        loop {
+            let buffered = source.buffered().len().min(2);
+            if buffered > 0 {
+                bytes.extend_from_slice(&source.buffered()[..buffered]);
+                source.consume(buffered);
+            }
+            match source.next_byte() {
+                Some(b) => bytes.push(b?),
+                None => return Ok(bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn sources() {
+        let doc = b"<a>text</a>";
+        assert_eq!(drain(&doc[..]).unwrap(), doc);
+        assert_eq!(drain(doc.iter()).unwrap(), doc);
+        assert_eq!(drain(&mut doc.iter()).unwrap(), doc);
+        assert_eq!(drain(IterSource(doc.iter().copied())).unwrap(), doc);
+        assert_eq!(drain(TryIterSource(doc.iter().map(Ok::<_, &str>))).unwrap(), doc);
+        assert_eq!(drain(CharIterSource::new("<a>ł€😀</a>".chars())).unwrap(), "<a>ł€😀</a>".as_bytes());
+        let err = drain(TryIterSource(doc.iter().map(Ok).chain([Err("broken")]))).unwrap_err();
+        assert_eq!(err.message(), "broken");
+    }
+}
@@ -0,0 +1,156 @@
+//! Contains `reformat`, which reads a document and writes it again with other settings.
+extern crate alloc;
+
+use alloc::string::String;
+
+use core::fmt;
+
+use crate::common::{Position, TextPosition};
+use crate::reader::{self, ParserConfig2, XmlEvent};
+use crate::writer;
+use crate::{EmitterConfig, EventReader};
+
+/// An error of `reformat`.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReformatError {
+    /// The input couldn't be read, e.g. because it's not well-formed.
+    Read(reader::Error),
+    /// An event of the input couldn't be written. The position is the event's in the input.
+    Write(TextPosition, writer::Error),
+}
+
+impl fmt::Display for ReformatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReformatError::Read(e) => e.fmt(f),
+            ReformatError::Write(pos, e) => write!(f, "{pos} {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for ReformatError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ReformatError::Read(e) => Some(e),
+            ReformatError::Write(_, e) => Some(e),
+        }
+    }
+}
+
+impl Position for ReformatError {
+    #[inline]
+    fn position(&self) -> TextPosition {
+        match self {
+            ReformatError::Read(e) => e.position(),
+            ReformatError::Write(pos, _) => *pos,
+        }
+    }
+}
+
+impl From<reader::Error> for ReformatError {
+    #[cold]
+    fn from(e: reader::Error) -> Self {
+        ReformatError::Read(e)
+    }
+}
+
+/// Reads the `input` document with the `parser` configuration and writes it with the `emitter`
+/// configuration, e.g. to indent or minify it.
+///
+/// Every event the reader reports is written, so the configurations decide what's kept:
+/// comments are only kept with `ignore_comments(false)`, CDATA sections unless
+/// `cdata_to_characters` is set on either side. The document declaration is written with the
+/// input's version, encoding and standalone, unless `write_document_declaration` is false.
+/// Namespace declarations are written where they were.
+///
+/// Whitespace-only text, reported as `XmlEvent::Whitespace`, is written as it was, except when
+/// the emitter indents, since it writes its own. Set `trim_whitespace` or
+/// `whitespace_handling` on the parser to drop it when minifying.
+///
+/// ```rust
+/// use xml_no_std::reader::WhitespaceHandling;
+/// use xml_no_std::{reformat, EmitterConfig, ParserConfig};
+///
+/// let input = "<a>\n  <b x='1'>text</b><!-- c -->\n</a>";
+/// let parser = ParserConfig::new().ignore_comments(false);
+///
+/// let pretty = reformat(input, parser.clone(), EmitterConfig::new().perform_indent(true)).unwrap();
+/// assert_eq!(pretty, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<a>\n  <b x=\"1\">text</b>\n  <!-- c -->\n</a>");
+///
+/// let parser = parser.whitespace_handling(Some(WhitespaceHandling::Drop));
+/// let minified = reformat(&pretty, parser, EmitterConfig::new().write_document_declaration(false)).unwrap();
+/// assert_eq!(minified, "<a><b x=\"1\">text</b><!-- c --></a>");
+/// ```
+pub fn reformat(input: &str, parser: impl Into<ParserConfig2>, emitter: EmitterConfig) -> Result<String, ReformatError> {
+    let drop_whitespace = emitter.perform_indent;
+    let drop_declaration = !emitter.write_document_declaration;
+    let mut reader = EventReader::new_with_config(input.as_bytes().iter(), parser);
+    let mut writer = emitter.create_writer();
+    loop {
+        let event = reader.next()?;
+        match event {
+            XmlEvent::EndDocument => return Ok(writer.into_inner()),
+            XmlEvent::Whitespace(_) if drop_whitespace => {},
+            XmlEvent::StartDocument { .. } if drop_declaration => {},
+            event => {
+                if let Some(event) = event.as_writer_event() {
+                    writer.write(event).map_err(|e| ReformatError::Write(reader.position(), e))?;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reformat, ReformatError};
+    use crate::common::Position;
+    use crate::reader::WhitespaceHandling;
+    use crate::{EmitterConfig, ParserConfig};
+
+    const DOC: &str = r#"<?xml version="1.1" encoding="UTF-8" standalone="yes"?>
+<!-- head -->
+<r xmlns="urn:r" xmlns:p="urn:p">
+    <p:a p:x="&lt;1&gt;"><![CDATA[<raw>]]></p:a>
+    <?pi data?>
+    <b>text &amp; more</b>
+    <c xmlns=""><d/></c>
+</r>"#;
+
+    #[test]
+    fn minify_pretty_minify() {
+        let parser = ParserConfig::new().ignore_comments(false);
+        let minify = |doc: &str| reformat(doc, parser.clone().whitespace_handling(Some(WhitespaceHandling::Drop)), EmitterConfig::new()).unwrap();
+        let pretty = |doc: &str| reformat(doc, parser.clone(), EmitterConfig::new().perform_indent(true)).unwrap();
+
+        let minified = minify(DOC);
+        assert_eq!(minified, r#"<?xml version="1.1" encoding="UTF-8" standalone="yes"?><!-- head --><r xmlns="urn:r" xmlns:p="urn:p"><p:a p:x="&lt;1&gt;"><![CDATA[<raw>]]></p:a><?pi data?><b>text &amp; more</b><c xmlns=""><d /></c></r>"#);
+        let prettified = pretty(&minified);
+        assert!(prettified.contains("\n  <b>text &amp; more</b>\n"), "{prettified}");
+        assert_eq!(minify(&prettified), minified);
+        assert_eq!(pretty(&minify(&prettified)), prettified);
+    }
+
+    #[test]
+    fn configs_decide() {
+        let parser = ParserConfig::new().cdata_to_characters(true).whitespace_handling(Some(WhitespaceHandling::Drop));
+        let emitter = EmitterConfig::new().write_document_declaration(false);
+        assert_eq!(reformat(DOC, parser, emitter).unwrap(),
+            r#"<r xmlns="urn:r" xmlns:p="urn:p"><p:a p:x="&lt;1&gt;">&lt;raw&gt;</p:a><?pi data?><b>text &amp; more</b><c xmlns=""><d /></c></r>"#);
+    }
+
+    #[test]
+    fn errors() {
+        let err = reformat("<a>\n<b></a>", ParserConfig::new(), EmitterConfig::new()).unwrap_err();
+        assert!(matches!(err, ReformatError::Read(_)));
+        assert_eq!(err.position().row, 1);
+
+        // allowed in XML 1.1, but the writer checks values against XML 1.0
+        let doc = "<?xml version='1.1'?>\n<a>\n  <b c='&#x1;'/></a>";
+        let err = reformat(doc, ParserConfig::new(), EmitterConfig::new().validate_names(true)).unwrap_err();
+        assert!(matches!(err, ReformatError::Write(_, crate::writer::Error::InvalidAttribute(_))), "{err}");
+        assert_eq!(err.position().row, 2);
+    }
+}
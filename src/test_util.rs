@@ -0,0 +1,368 @@
+//! Contains `events_equivalent`, for checking that a transformation preserved a document.
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use crate::attribute::{attributes_set_eq, OwnedAttribute};
+use crate::common::{Position, TextPosition};
+use crate::name::OwnedName;
+use crate::reader::{self, ParserConfig2, WhitespaceHandling, XmlEvent};
+use crate::EventReader;
+
+/// Which differences `events_equivalent` ignores. All of them are ignored by default.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EquivalenceOptions {
+    /// Compare the attributes of an element as sets, whatever their order.
+    pub ignore_attribute_order: bool,
+
+    /// Skip whitespace-only text, e.g. the indentation between elements. Whitespace inside
+    /// `xml:space="preserve"` is still compared.
+    ///
+    /// There's no DTD to tell which elements have mixed content, so whitespace between two
+    /// elements of a paragraph, like in `<b>a</b> <i>b</i>`, is skipped too.
+    pub ignore_whitespace: bool,
+
+    /// Compare CDATA sections as text, so `<![CDATA[<]]>` is the same as `&lt;`.
+    pub ignore_cdata: bool,
+
+    /// Compare names by their namespace and local name, whatever their prefix, and skip
+    /// namespace declarations.
+    pub ignore_prefixes: bool,
+
+    /// Skip comments.
+    pub ignore_comments: bool,
+
+    /// Skip the XML declaration.
+    pub ignore_declaration: bool,
+}
+
+impl EquivalenceOptions {
+    /// Returns options which ignore all the differences above.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        EquivalenceOptions {
+            ignore_attribute_order: true,
+            ignore_whitespace: true,
+            ignore_cdata: true,
+            ignore_prefixes: true,
+            ignore_comments: true,
+            ignore_declaration: true,
+        }
+    }
+
+    /// Returns options which ignore none of the differences above. Text is still compared
+    /// after references are expanded and adjacent text is joined, as the reader reports it.
+    #[inline]
+    #[must_use]
+    pub fn strict() -> Self {
+        EquivalenceOptions {
+            ignore_attribute_order: false,
+            ignore_whitespace: false,
+            ignore_cdata: false,
+            ignore_prefixes: false,
+            ignore_comments: false,
+            ignore_declaration: false,
+        }
+    }
+
+    /// Sets `ignore_attribute_order`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_attribute_order(mut self, ignore: bool) -> Self {
+        self.ignore_attribute_order = ignore;
+        self
+    }
+
+    /// Sets `ignore_whitespace`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_whitespace(mut self, ignore: bool) -> Self {
+        self.ignore_whitespace = ignore;
+        self
+    }
+
+    /// Sets `ignore_cdata`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_cdata(mut self, ignore: bool) -> Self {
+        self.ignore_cdata = ignore;
+        self
+    }
+
+    /// Sets `ignore_prefixes`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_prefixes(mut self, ignore: bool) -> Self {
+        self.ignore_prefixes = ignore;
+        self
+    }
+
+    /// Sets `ignore_comments`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_comments(mut self, ignore: bool) -> Self {
+        self.ignore_comments = ignore;
+        self
+    }
+
+    /// Sets `ignore_declaration`.
+    #[inline]
+    #[must_use]
+    pub fn ignore_declaration(mut self, ignore: bool) -> Self {
+        self.ignore_declaration = ignore;
+        self
+    }
+
+    /// The reader does most of the work: it drops what's ignored, and joins the text around it
+    fn parser_config(&self) -> ParserConfig2 {
+        ParserConfig2::new()
+            .ignore_comments(self.ignore_comments)
+            .cdata_to_characters(self.ignore_cdata)
+            .coalesce_characters(true)
+            .whitespace_handling(if self.ignore_whitespace { Some(WhitespaceHandling::Drop) } else { None })
+    }
+
+    fn events_equivalent(&self, a: &XmlEvent, b: &XmlEvent) -> bool {
+        match (a, b) {
+            (XmlEvent::StartDocument { .. }, XmlEvent::StartDocument { .. }) if self.ignore_declaration => true,
+            (XmlEvent::StartDocument { version, encoding, standalone },
+             XmlEvent::StartDocument { version: version_b, encoding: encoding_b, standalone: standalone_b }) =>
+                version == version_b && encoding.eq_ignore_ascii_case(encoding_b) && standalone == standalone_b,
+            (XmlEvent::StartElement { name, attributes, namespace },
+             XmlEvent::StartElement { name: name_b, attributes: attributes_b, namespace: namespace_b }) =>
+                self.names_equivalent(name, name_b) && self.attributes_equivalent(attributes, attributes_b) &&
+                    (self.ignore_prefixes || namespace == namespace_b),
+            (XmlEvent::EndElement { name }, XmlEvent::EndElement { name: name_b }) => self.names_equivalent(name, name_b),
+            // the same text, only reported differently because of `xml:space`
+            (XmlEvent::Characters(text) | XmlEvent::Whitespace(text),
+             XmlEvent::Characters(text_b) | XmlEvent::Whitespace(text_b)) => text == text_b,
+            _ => a == b,
+        }
+    }
+
+    fn names_equivalent(&self, a: &OwnedName, b: &OwnedName) -> bool {
+        if self.ignore_prefixes {
+            a.expanded() == b.expanded()
+        } else {
+            a == b
+        }
+    }
+
+    fn attributes_equivalent(&self, a: &[OwnedAttribute], b: &[OwnedAttribute]) -> bool {
+        if !self.ignore_attribute_order {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.names_equivalent(&a.name, &b.name) && a.value == b.value)
+        } else if self.ignore_prefixes {
+            attributes_set_eq(a, b)
+        } else {
+            attributes_set_eq(a, b) && a.iter().all(|attr| b.contains(attr))
+        }
+    }
+}
+
+impl Default for EquivalenceOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The first difference between two documents, see `events_equivalent`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Difference {
+    /// Where the differing event, or the error, is in the first document.
+    pub position_a: TextPosition,
+
+    /// Where the differing event, or the error, is in the second document. When a document
+    /// can't be read, it's where the other one had been read to.
+    pub position_b: TextPosition,
+
+    /// What differs.
+    pub kind: DifferenceKind,
+}
+
+/// What differs between two documents, see `Difference`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DifferenceKind {
+    /// The first document couldn't be read, e.g. because it's not well-formed.
+    ReadA(reader::Error),
+
+    /// The second document couldn't be read.
+    ReadB(reader::Error),
+
+    /// The events aren't equivalent. They are as the reader reported them, with what's
+    /// ignored already left out. They're boxed because events are large.
+    Events(Box<XmlEvent>, Box<XmlEvent>),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / {}: ", self.position_a, self.position_b)?;
+        match &self.kind {
+            DifferenceKind::ReadA(e) => write!(f, "first document: {}", e.msg()),
+            DifferenceKind::ReadB(e) => write!(f, "second document: {}", e.msg()),
+            DifferenceKind::Events(a, b) => match (&**a, &**b) {
+                (XmlEvent::StartElement { name, attributes, .. },
+                 XmlEvent::StartElement { name: name_b, attributes: attributes_b, .. }) if name.expanded() == name_b.expanded() => {
+                    write!(f, "start tags of <{name}> differ: [{}] != [{}]", list(attributes), list(attributes_b))
+                },
+                (a, b) => write!(f, "{} != {}", describe(a), describe(b)),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for Difference {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match &self.kind {
+            DifferenceKind::ReadA(e) | DifferenceKind::ReadB(e) => Some(e),
+            DifferenceKind::Events(..) => None,
+        }
+    }
+}
+
+fn list(attributes: &[OwnedAttribute]) -> String {
+    attributes.iter().map(|attr| format!("{attr}")).collect::<Vec<_>>().join(", ")
+}
+
+fn describe(event: &XmlEvent) -> String {
+    match event {
+        XmlEvent::StartDocument { version, encoding, standalone } =>
+            format!("declaration of version {version}, encoding {encoding}, standalone {standalone:?}"),
+        XmlEvent::EndDocument => "end of document".into(),
+        XmlEvent::StartElement { name, .. } => format!("start tag <{name}>"),
+        XmlEvent::EndElement { name } => format!("end tag </{name}>"),
+        XmlEvent::ProcessingInstruction { name, data } => format!("processing instruction <?{name} {data:?}?>"),
+        XmlEvent::CData(data) => format!("CDATA {data:?}"),
+        XmlEvent::Comment(data) => format!("comment {data:?}"),
+        XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => format!("text {text:?}"),
+    }
+}
+
+/// Checks that two documents have equivalent events, e.g. that a transformation preserved a
+/// document, and returns the first difference otherwise.
+///
+/// Both documents are read with the same configuration, so what's equivalent is what the
+/// reader makes the same: references are expanded, adjacent text is joined, and what
+/// `options` ignores is left out or compared loosely. Both must be well-formed.
+///
+/// ```rust
+/// use xml_no_std::test_util::{events_equivalent, EquivalenceOptions};
+///
+/// let original = r#"<a xmlns="urn:x" y="2" z="&lt;"><b><![CDATA[text]]></b></a>"#;
+/// let output = "<p:a xmlns:p=\"urn:x\" z='&#60;' y='2'>\n  <p:b>text</p:b>\n</p:a>";
+/// events_equivalent(original, output, EquivalenceOptions::new()).unwrap();
+///
+/// let diff = events_equivalent(original, "<a xmlns='urn:x' y='2' z='&lt;'><b>txt</b></a>", EquivalenceOptions::new()).unwrap_err();
+/// assert_eq!(diff.to_string(), r#"1:36 / 1:36: text "text" != text "txt""#);
+/// ```
+pub fn events_equivalent(a: &str, b: &str, options: EquivalenceOptions) -> Result<(), Difference> {
+    let config = options.parser_config();
+    let mut reader_a = EventReader::new_with_config(a.as_bytes().iter(), config.clone());
+    let mut reader_b = EventReader::new_with_config(b.as_bytes().iter(), config);
+    loop {
+        let event_a = reader_a.next();
+        let event_b = reader_b.next();
+        let (event_a, event_b) = match (event_a, event_b) {
+            (Ok(event_a), Ok(event_b)) => (event_a, event_b),
+            (Err(e), _) => {
+                let position_a = e.position();
+                return Err(Difference { position_a, position_b: reader_b.position(), kind: DifferenceKind::ReadA(e) });
+            },
+            (_, Err(e)) => {
+                let position_b = e.position();
+                return Err(Difference { position_a: reader_a.position(), position_b, kind: DifferenceKind::ReadB(e) });
+            },
+        };
+        if !options.events_equivalent(&event_a, &event_b) {
+            return Err(Difference {
+                position_a: reader_a.position(),
+                position_b: reader_b.position(),
+                kind: DifferenceKind::Events(Box::new(event_a), Box::new(event_b)),
+            });
+        }
+        if event_a == XmlEvent::EndDocument {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::string::ToString;
+
+    use super::{events_equivalent, DifferenceKind, EquivalenceOptions};
+    use crate::reader::XmlEvent;
+    use crate::{reformat, EmitterConfig, ParserConfig};
+
+    const DOC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- orders -->
+<o:orders xmlns:o="urn:o" count="2" day="mon">
+    <o:order id="1"><![CDATA[a < b]]></o:order>
+    <o:order id="2">x &amp; y</o:order>
+    <note xml:space="preserve">  </note>
+</o:orders>"#;
+
+    #[test]
+    fn equivalent() {
+        let options = EquivalenceOptions::new();
+        let same = r#"<orders xmlns="urn:o" day='mon' count='2'><order id="1">a &lt; b</order><order id="2">x &amp; <!--c-->y</order><note xmlns="" xml:space="preserve">  </note></orders>"#;
+        events_equivalent(DOC, same, options).unwrap();
+        events_equivalent(same, DOC, options).unwrap();
+
+        // without the `xml:space="preserve"` element, whose whitespace the indenting emitter drops
+        let doc = DOC.replace("\n    <note xml:space=\"preserve\">  </note>", "");
+        let parser = ParserConfig::new().ignore_comments(false);
+        let pretty = reformat(&doc, parser.clone(), EmitterConfig::new().perform_indent(true)).unwrap();
+        events_equivalent(&doc, &pretty, options.ignore_comments(false).ignore_declaration(false)).unwrap();
+        let identical = reformat(DOC, parser, EmitterConfig::new()).unwrap();
+        events_equivalent(DOC, &identical, EquivalenceOptions::strict()).unwrap();
+    }
+
+    #[test]
+    fn differences() {
+        let different = |b: &str| events_equivalent(DOC, b, EquivalenceOptions::new()).unwrap_err();
+
+        let diff = different(&DOC.replace("day=\"mon\"", "day=\"tue\""));
+        assert_eq!((diff.position_a.row, diff.position_b.row), (2, 2));
+        assert_eq!(diff.to_string(), r#"3:1 / 3:1: start tags of <{urn:o}o:orders> differ: [count="2", day="mon"] != [count="2", day="tue"]"#);
+
+        let diff = different(&DOC.replace("  </note>", "</note>"));
+        assert!(matches!(diff.kind, DifferenceKind::Events(ref a, ref b)
+            if matches!(**a, XmlEvent::Characters(_)) && matches!(**b, XmlEvent::EndElement { .. })), "{diff}");
+        assert_eq!(diff.to_string(), r#"6:32 / 6:32: text "  " != end tag </note>"#);
+
+        let strict = EquivalenceOptions::strict();
+        // each is only equivalent when the difference is ignored
+        for b in [
+            DOC.replace("count=\"2\" day=\"mon\"", "day=\"mon\" count=\"2\""),
+            DOC.replace("\n    <o:order id=\"2\">", "<o:order id=\"2\">"),
+            DOC.replace("<![CDATA[a < b]]>", "a &lt; b"),
+            DOC.replace("o:", "p:").replace("xmlns:o", "xmlns:p"),
+            DOC.replace("<!-- orders -->", ""),
+            DOC.replace("encoding=\"UTF-8\"", "standalone=\"yes\""),
+        ] {
+            assert!(events_equivalent(DOC, &b, strict).is_err(), "{b}");
+            assert!(events_equivalent(DOC, &b, EquivalenceOptions::new()).is_ok(), "{b}");
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let diff = events_equivalent(DOC, "<o:orders>", EquivalenceOptions::new()).unwrap_err();
+        assert!(matches!(diff.kind, DifferenceKind::ReadB(_)), "{diff}");
+        let diff = events_equivalent("<a></b>", "<a></a>", EquivalenceOptions::new()).unwrap_err();
+        assert!(matches!(diff.kind, DifferenceKind::ReadA(_)), "{diff}");
+        assert_eq!(diff.to_string(), "1:7 / 1:4: first document: Unexpected closing tag: b != a");
+    }
+}
@@ -0,0 +1,392 @@
+//! Contains a simple tree of elements, built from the events of an `EventReader`,
+//! with the `tree` feature.
+//!
+//! The tree holds what the reader reports, so its configuration decides what's in it:
+//! comments are only there with `ignore_comments(false)`, CDATA becomes text with
+//! `cdata_to_characters`, and whitespace is kept, trimmed or dropped like the events are.
+//! The reader's limits apply while the tree is built. For documents which aren't trusted,
+//! set `ParserConfig2::max_element_depth`, since dropping a very deep tree uses stack space
+//! proportional to its depth.
+//!
+//! ```rust
+//! use xml_no_std::tree::Element;
+//! use xml_no_std::EventReader;
+//!
+//! let mut reader = EventReader::from_str("<config><port>8080</port><host/></config>");
+//! let root = Element::parse(&mut reader).unwrap();
+//! assert_eq!(root.name.local_name, "config");
+//! assert_eq!(root.find("port").unwrap().text(), "8080");
+//! ```
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use alloc::borrow::Cow;
+
+use crate::attribute::{Attributes, OwnedAttribute};
+use crate::name::{Name, OwnedName};
+use crate::namespace::{Namespace, NS_EMPTY_URI, NS_NO_PREFIX};
+use crate::reader::{EventReader, Result, Source, XmlEvent};
+use crate::writer::{self, EventWriter};
+
+/// An element with its attributes and content.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Element {
+    /// Qualified name of the element.
+    pub name: OwnedName,
+    /// Attributes of the element, in document order.
+    pub attributes: Attributes,
+    /// Namespace mapping in effect at the element's start tag.
+    pub namespace: Namespace,
+    /// Content of the element, in document order.
+    pub children: Vec<Node>,
+}
+
+/// A piece of an element's content.
+// elements hold a few attributes inline, see `Attributes`
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Node {
+    /// A child element.
+    Element(Element),
+    /// Character data, from `Characters` and `Whitespace` events.
+    Text(String),
+    /// A CDATA section.
+    CData(String),
+    /// A comment.
+    Comment(String),
+    /// A processing instruction.
+    ProcessingInstruction {
+        /// Processing instruction target.
+        name: String,
+        /// Processing instruction content.
+        data: Option<String>,
+    },
+}
+
+impl Element {
+    /// Returns an element without attributes or content.
+    ///
+    /// Its namespace is empty, so it's written in whatever namespace bindings are in scope,
+    /// plus the ones its name needs.
+    pub fn new<'a, N: Into<Name<'a>>>(name: N) -> Element {
+        Element { name: name.into().into(), attributes: Attributes::new(), namespace: Namespace::empty(), children: Vec::new() }
+    }
+
+    /// Reads the next element from the reader, up to and including its end tag.
+    ///
+    /// Events before the element's start tag, like the document declaration, comments and
+    /// processing instructions, are skipped. The reader is left right after the end tag,
+    /// so in the middle of a document this reads just one subtree and the reader can carry on
+    /// with the events after it.
+    ///
+    /// It's an error if the enclosing element or the document ends before an element starts.
+    pub fn parse<S: Source>(reader: &mut EventReader<S>) -> Result<Element> {
+        loop {
+            match reader.next()? {
+                XmlEvent::StartElement { name, attributes, namespace } => {
+                    return Element::parse_rest(name, attributes, namespace, reader);
+                },
+                XmlEvent::EndElement { .. } | XmlEvent::EndDocument => {
+                    return Err((&*reader, "Expected an element to build a tree from").into());
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Reads the content and end tag of an element whose `StartElement` event was already
+    /// taken from the reader.
+    ///
+    /// This is for building a tree of an element found while handling events one by one.
+    /// The arguments are the fields of that `StartElement` event.
+    pub fn parse_rest<S: Source>(
+        name: OwnedName, attributes: Attributes, namespace: Namespace, reader: &mut EventReader<S>,
+    ) -> Result<Element> {
+        let mut current = Element { name, attributes, namespace, children: Vec::new() };
+        let mut ancestors = Vec::new();
+        loop {
+            let node = match reader.next()? {
+                XmlEvent::StartElement { name, attributes, namespace } => {
+                    let child = Element { name, attributes, namespace, children: Vec::new() };
+                    ancestors.push(core::mem::replace(&mut current, child));
+                    continue;
+                },
+                XmlEvent::EndElement { .. } => match ancestors.pop() {
+                    Some(parent) => Node::Element(core::mem::replace(&mut current, parent)),
+                    None => return Ok(current),
+                },
+                XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => Node::Text(text),
+                XmlEvent::CData(data) => Node::CData(data),
+                XmlEvent::Comment(data) => Node::Comment(data),
+                XmlEvent::ProcessingInstruction { name, data } => Node::ProcessingInstruction { name, data },
+                // the start tag wasn't the last event taken from the reader
+                XmlEvent::StartDocument { .. } | XmlEvent::EndDocument => {
+                    return Err((&*reader, "Expected the content or end tag of the element").into());
+                },
+            };
+            current.children.push(node);
+        }
+    }
+
+    /// Returns an iterator over the child elements, skipping other content.
+    ///
+    /// All of the content is in the `children` field.
+    pub fn children(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Returns the first child element with the local name `local`, whatever its namespace.
+    #[must_use]
+    pub fn find(&self, local: &str) -> Option<&Element> {
+        self.children().find(|element| element.name.local_name == local)
+    }
+
+    /// Returns the first child element with the local name `local` in the `namespace`.
+    ///
+    /// An empty `namespace` finds elements without a namespace.
+    #[must_use]
+    pub fn find_ns(&self, namespace: &str, local: &str) -> Option<&Element> {
+        self.children().find(|element| element.name.matches(Some(namespace), local))
+    }
+
+    /// Returns the text and CDATA inside this element and its descendants, concatenated
+    /// in document order.
+    #[must_use]
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+
+    fn push_text(&self, text: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Text(data) | Node::CData(data) => text.push_str(data),
+                Node::Element(element) => element.push_text(text),
+                _ => {},
+            }
+        }
+    }
+
+    /// Returns the value of the attribute with the local name `local` and without a namespace.
+    ///
+    /// See `Attributes::get_ns` on the `attributes` field for other attributes.
+    #[must_use]
+    pub fn attr(&self, local: &str) -> Option<&str> {
+        self.attributes.get(local).map(|attr| &*attr.value)
+    }
+
+    /// Sets the value of an attribute, adding the attribute after the others if there's none
+    /// with this name. Returns the previous value.
+    ///
+    /// A prefixed name without a namespace, like `"xlink:href"`, is resolved with the
+    /// element's `namespace`. An attribute already there keeps its prefix.
+    pub fn set_attr<'a, N: Into<Name<'a>>, V: Into<String>>(&mut self, name: N, value: V) -> Option<String> {
+        let mut name = OwnedName::from(name.into());
+        if let (Some(prefix), None) = (&name.prefix, &name.namespace) {
+            name.namespace = self.namespace.get(prefix).map(String::from);
+        }
+        let value = value.into();
+        match self.attributes.iter_mut().find(|attr| attr.name.matches(name.namespace.as_deref(), &name.local_name)) {
+            Some(attr) => Some(core::mem::replace(&mut attr.value, value)),
+            None => {
+                self.attributes.push(OwnedAttribute { name, value });
+                None
+            },
+        }
+    }
+
+    /// Adds an element, text or other content after the element's other content.
+    pub fn push_child<N: Into<Node>>(&mut self, child: N) {
+        self.children.push(child.into());
+    }
+
+    /// Removes the first child element with the local name `local`, whatever its namespace,
+    /// and returns it.
+    pub fn remove_child(&mut self, local: &str) -> Option<Element> {
+        let index = self.children.iter().position(|node| match node {
+            Node::Element(element) => element.name.local_name == local,
+            _ => false,
+        })?;
+        match self.children.remove(index) {
+            Node::Element(element) => Some(element),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Writes the element and its content as events.
+    ///
+    /// Namespace declarations come from the tree: each element declares the bindings of its
+    /// `namespace`, and of its name's and attributes' prefixes, which aren't in scope in the
+    /// writer already. Elements read from a document get the declarations they had, wherever
+    /// they're written, and elements added later need no more than qualified names.
+    pub fn write(&self, writer: &mut EventWriter) -> writer::Result<()> {
+        let attributes: Vec<_> = self.attributes.iter().map(OwnedAttribute::borrow).collect();
+        writer.write(writer::XmlEvent::StartElement {
+            name: self.name.borrow(),
+            attributes: Cow::Owned(attributes),
+            namespace: Cow::Owned(self.declared_namespace()),
+        })?;
+        for node in &self.children {
+            match node {
+                Node::Element(element) => element.write(writer)?,
+                Node::Text(text) => writer.write(writer::XmlEvent::Characters(text))?,
+                Node::CData(data) => writer.write(writer::XmlEvent::CData(data))?,
+                Node::Comment(data) => writer.write(writer::XmlEvent::Comment(data))?,
+                Node::ProcessingInstruction { name, data } => {
+                    writer.write(writer::XmlEvent::ProcessingInstruction { name, data: data.as_deref() })?;
+                },
+            }
+        }
+        writer.write(writer::XmlEvent::EndElement { name: Some(self.name.borrow()) })
+    }
+
+    /// The element's namespace with the bindings its names need. The element name's binding
+    /// wins over an attribute's with the same prefix.
+    fn declared_namespace(&self) -> Namespace {
+        let mut namespace = self.namespace.clone();
+        for attr in self.attributes.iter() {
+            if let (Some(prefix), Some(uri)) = (&attr.name.prefix, &attr.name.namespace) {
+                namespace.force_put(&**prefix, &**uri);
+            }
+        }
+        match (&self.name.prefix, &self.name.namespace) {
+            (Some(prefix), Some(uri)) => { namespace.force_put(&**prefix, &**uri); },
+            (None, Some(uri)) => { namespace.force_put(NS_NO_PREFIX, &**uri); },
+            (None, None) => { namespace.force_put(NS_NO_PREFIX, NS_EMPTY_URI); },
+            (Some(_), None) => {},
+        }
+        namespace
+    }
+}
+
+impl From<Element> for Node {
+    #[inline]
+    fn from(element: Element) -> Self {
+        Node::Element(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::{Element, Node};
+    use crate::attribute::Attributes;
+    use crate::name::OwnedName;
+    use crate::namespace::Namespace;
+    use crate::reader::{ErrorKind, Limit, ParserConfig, XmlEvent};
+    use crate::{EmitterConfig, EventReader};
+
+    #[test]
+    fn whole_document() {
+        let doc = r#"<?xml version="1.0"?><!-- c --><r xmlns="urn:r" a="1">
+  <x:i xmlns:x="urn:x" b="2">t<![CDATA[<d>]]><?p data?><!-- n --></x:i>
+  <e/>
+</r>"#;
+        let config = ParserConfig::new().ignore_comments(false);
+        let root = Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).unwrap();
+        assert_eq!(root.name.namespace.as_deref(), Some("urn:r"));
+        assert_eq!(root.attributes.get("a").unwrap().value, "1");
+        assert_eq!(root.children().count(), 2);
+        assert_eq!(root.text(), "\n  t<d>\n  \n");
+
+        let item = root.find_ns("urn:x", "i").unwrap();
+        assert!(root.find_ns("urn:r", "i").is_none());
+        assert_eq!(item.namespace.get("x"), Some("urn:x"));
+        assert_eq!(item.children, [
+            Node::Text("t".into()),
+            Node::CData("<d>".into()),
+            Node::ProcessingInstruction { name: "p".into(), data: Some("data".into()) },
+            Node::Comment(" n ".into()),
+        ]);
+        assert_eq!(item.text(), "t<d>");
+        assert!(root.find("e").unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn reader_config() {
+        let doc = "<r>\n  <i>a<!-- c --><![CDATA[b]]></i>\n</r>";
+        let config = ParserConfig::new().trim_whitespace(true).cdata_to_characters(true);
+        let root = Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).unwrap();
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.find("i").unwrap().children, [Node::Text("ab".into())]);
+    }
+
+    #[test]
+    fn subtree() {
+        let doc = "<list><item n='1'><v>a</v></item><item n='2'><v>b</v></item></list>";
+        let mut reader = EventReader::from_str(doc);
+        reader.next().unwrap();
+        assert!(matches!(reader.next().unwrap(), XmlEvent::StartElement { .. }));
+        let first = Element::parse(&mut reader).unwrap();
+        assert_eq!(first.attributes.get("n").unwrap().value, "1");
+        assert_eq!(first.find("v").unwrap().text(), "a");
+
+        let (name, attributes, namespace) = match reader.next().unwrap() {
+            XmlEvent::StartElement { name, attributes, namespace } => (name, attributes, namespace),
+            e => panic!("{e:?}"),
+        };
+        let second = Element::parse_rest(name, attributes, namespace, &mut reader).unwrap();
+        assert_eq!(second.find("v").unwrap().text(), "b");
+        assert!(matches!(reader.next().unwrap(), XmlEvent::EndElement { .. }));
+        assert!(Element::parse(&mut reader).is_err());
+        // after the end of the document
+        let err = Element::parse_rest(OwnedName::local("x"), Attributes::new(), Namespace::empty(), &mut reader).unwrap_err();
+        assert_eq!(err.msg(), "Expected the content or end tag of the element");
+    }
+
+    fn write(element: &Element) -> alloc::string::String {
+        let mut writer = EmitterConfig::new().write_document_declaration(false).create_writer();
+        element.write(&mut writer).unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn mutate_and_write() {
+        let doc = r#"<r xmlns="urn:r" xmlns:x="urn:x" a="1"><x:i x:b="2">t &amp; u<!-- c --><?p d?></x:i><e /><k>v</k></r>"#;
+        let config = ParserConfig::new().ignore_comments(false);
+        let mut root = Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).unwrap();
+        assert_eq!(write(&root), doc);
+
+        assert_eq!(root.attr("a"), Some("1"));
+        assert_eq!(root.set_attr("a", "3"), Some("1".into()));
+        assert_eq!(root.set_attr("x:c", "4"), None);
+        assert_eq!(root.attributes.get_ns("urn:x", "c").unwrap().value, "4");
+        assert_eq!(root.remove_child("e").unwrap().name.local_name, "e");
+        assert!(root.remove_child("e").is_none());
+        let mut added = Element::new(OwnedName::qualified("n", "urn:n", Some("n")).borrow());
+        added.push_child(Node::Text("w".into()));
+        added.push_child(Element::new("plain"));
+        root.push_child(added);
+        assert_eq!(write(&root), r#"<r xmlns="urn:r" xmlns:x="urn:x" a="3" x:c="4"><x:i x:b="2">t &amp; u<!-- c --><?p d?></x:i><k>v</k><n:n xmlns:n="urn:n">w<plain xmlns="" /></n:n></r>"#);
+        assert_eq!(root.text(), "t & uvw");
+
+        // a subtree taken out of its document keeps the bindings it uses
+        let item = root.find_ns("urn:x", "i").unwrap();
+        assert_eq!(write(item), r#"<x:i xmlns="urn:r" xmlns:x="urn:x" x:b="2">t &amp; u<!-- c --><?p d?></x:i>"#);
+    }
+
+    #[test]
+    fn limits() {
+        let doc = "<a><b><c><d/></c></b></a>";
+        let config = ParserConfig::new().max_element_depth(Some(3));
+        let err = Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::ElementDepth));
+        let config = ParserConfig::new().max_element_depth(Some(4));
+        assert!(Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).is_ok());
+
+        let config = ParserConfig::new().max_document_size(Some(10));
+        let err = Element::parse(&mut EventReader::new_with_config(doc.as_bytes().iter(), config)).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::LimitExceeded(Limit::DocumentSize));
+        let config = ParserConfig::new().max_data_length(2);
+        assert!(Element::parse(&mut EventReader::new_with_config("<a>long</a>".as_bytes().iter(), config)).is_err());
+    }
+}
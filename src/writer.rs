@@ -7,13 +7,24 @@ extern crate alloc;
 pub use self::config::EmitterConfig;
 pub use self::emitter::EmitterError as Error;
 pub use self::emitter::Result;
-pub use self::events::XmlEvent;
+pub use self::events::{OwnedXmlEvent, XmlEvent};
+pub use self::prefix::{PrefixGenerator, SequentialPrefixes, UriTailPrefixes};
+#[cfg(feature = "std")]
+pub use self::io::IoEventWriter;
 
 use self::emitter::Emitter;
+use self::events::StartElementBuilder;
+use crate::attribute::Attribute;
+use crate::escape::PreEscaped;
+use crate::name::Name;
+use crate::namespace::Namespace;
 
 mod config;
 mod emitter;
 pub mod events;
+#[cfg(feature = "std")]
+mod io;
+mod prefix;
 
 /// A wrapper around a String which emits XML document according to provided
 /// events.
@@ -47,19 +58,54 @@ impl EventWriter {
     /// correspond to a separate closing element or it may cause writing an empty element.
     /// Another example is that `XmlEvent::CData` may be represented as characters in
     /// the output stream.
+    ///
+    /// With the `fallible-alloc` feature, the output is grown for the event before it's written,
+    /// and the write fails with `Error::OutOfMemory` if it can't be.
     pub fn write<'a, E>(&mut self, event: E) -> Result<()> where E: Into<XmlEvent<'a>> {
-        match event.into() {
+        self.write_event(event.into(), None)
+    }
+
+    /// Writes the next event like `write()`, for events which borrow nothing but `'static`
+    /// strings, like literals.
+    ///
+    /// The name of a start element is then kept as it is by `EmitterConfig::keep_element_names_stack`
+    /// for its end tag, where `write()` has to copy it.
+    ///
+    /// ```rust
+    /// use xml_no_std::writer::{EventWriter, XmlEvent};
+    ///
+    /// let mut writer = EventWriter::new();
+    /// writer.write_static(XmlEvent::start_element("row").attr("kind", "a")).unwrap();
+    /// writer.write(XmlEvent::end_element()).unwrap();
+    /// assert!(writer.into_inner().ends_with(r#"<row kind="a" />"#));
+    /// ```
+    pub fn write_static<E>(&mut self, event: E) -> Result<()> where E: Into<XmlEvent<'static>> {
+        let event = event.into();
+        let static_name = match event {
+            XmlEvent::StartElement { name, .. } => Some(name),
+            _ => None,
+        };
+        self.write_event(event, static_name)
+    }
+
+    /// Writes `event`, whose element name is `static_name` if it's known to be `'static`
+    fn write_event(&mut self, event: XmlEvent<'_>, static_name: Option<Name<'static>>) -> Result<()> {
+        #[cfg(feature = "fallible-alloc")]
+        self.reserve(&event)?;
+        match event {
             XmlEvent::StartDocument { version, encoding, standalone } =>
                 self.emitter.emit_start_document(&mut self.sink, version, encoding.unwrap_or("UTF-8"), standalone),
             XmlEvent::ProcessingInstruction { name, data } =>
                 self.emitter.emit_processing_instruction(&mut self.sink, name, data),
-            XmlEvent::StartElement { name, attributes, namespace } => {
-                self.emitter.namespace_stack_mut().push_empty().checked_target().extend(namespace.as_ref());
-                self.emitter.emit_start_element(&mut self.sink, name, &attributes)
-            }
+            XmlEvent::StartElement { name, attributes, namespace } =>
+                self.start_element(name, static_name, &attributes, &namespace, &[]),
             XmlEvent::EndElement { name } => {
                 let r = self.emitter.emit_end_element(&mut self.sink, name);
-                self.emitter.namespace_stack_mut().try_pop();
+                // the bottom namespace has the implicit bindings, and extra end elements mustn't pop it
+                let nst = self.emitter.namespace_stack_mut();
+                if nst.0.len() > 1 {
+                    nst.try_pop();
+                }
                 r
             }
             XmlEvent::Comment(content) => self.emitter.emit_comment(&mut self.sink, content),
@@ -68,6 +114,66 @@ impl EventWriter {
         }
     }
 
+    /// Writes a starting element which has attribute values that are already escaped,
+    /// see `StartElementBuilder::attr_pre_escaped`.
+    pub fn write_start_element(&mut self, element: StartElementBuilder<'_>) -> Result<()> {
+        #[cfg(feature = "fallible-alloc")]
+        self.reserve_start_element(element.name, &element.attributes, &element.namespace)?;
+        self.start_element(element.name, None, &element.attributes, &element.namespace, &element.pre_escaped)
+    }
+
+    /// Writes character data which is already escaped, see `PreEscaped`.
+    pub fn write_pre_escaped(&mut self, content: PreEscaped<'_>) -> Result<()> {
+        #[cfg(feature = "fallible-alloc")]
+        self.emitter.reserve(&mut self.sink, &[content.as_str()], 0)?;
+        self.emitter.emit_pre_escaped_characters(&mut self.sink, content)
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    fn reserve(&mut self, event: &XmlEvent<'_>) -> Result<()> {
+        match event {
+            XmlEvent::StartDocument { encoding, .. } =>
+                self.emitter.reserve(&mut self.sink, &[encoding.unwrap_or("UTF-8")], 0),
+            XmlEvent::ProcessingInstruction { name, data } =>
+                self.emitter.reserve(&mut self.sink, &[name, data.unwrap_or("")], 0),
+            XmlEvent::StartElement { name, attributes, namespace } =>
+                self.reserve_start_element(*name, attributes, namespace),
+            XmlEvent::EndElement { name } => {
+                let name = name.unwrap_or(Name::local(""));
+                self.emitter.reserve(&mut self.sink, &[name.local_name, name.prefix.unwrap_or("")], 0)
+            },
+            XmlEvent::Comment(content) | XmlEvent::CData(content) | XmlEvent::Characters(content) =>
+                self.emitter.reserve(&mut self.sink, &[content], 0),
+        }
+    }
+
+    #[cfg(feature = "fallible-alloc")]
+    fn reserve_start_element(&mut self, name: Name<'_>, attributes: &[Attribute<'_>], namespace: &Namespace) -> Result<()> {
+        let mut texts = alloc::vec::Vec::new();
+        texts.try_reserve(4 + 5 * attributes.len() + 2 * namespace.len()).map_err(|_| Error::OutOfMemory)?;
+        for name in core::iter::once(&name).chain(attributes.iter().map(|attr| &attr.name)) {
+            // a generated prefix is declared with the URI, and written where the name is
+            let uri = name.namespace.unwrap_or("");
+            texts.extend([name.local_name, name.prefix.unwrap_or(""), uri, uri]);
+        }
+        texts.extend(attributes.iter().map(|attr| attr.value));
+        for (prefix, uri) in namespace {
+            texts.extend([prefix, uri]);
+        }
+        self.emitter.reserve(&mut self.sink, &texts, 1 + attributes.len() + namespace.len())
+    }
+
+    fn start_element(&mut self, name: Name<'_>, static_name: Option<Name<'static>>, attributes: &[Attribute<'_>],
+                     namespace: &Namespace, pre_escaped: &[bool]) -> Result<()> {
+        // only the mappings which aren't in scope already get declared
+        self.emitter.namespace_stack_mut().push_checked(namespace).map_err(Error::ConflictingNamespaceBinding)?;
+        let r = self.emitter.emit_start_element(&mut self.sink, name, static_name, attributes, pre_escaped);
+        if r.is_err() {
+            self.emitter.namespace_stack_mut().try_pop();
+        }
+        r
+    }
+
     /// Returns a mutable reference to the underlying String.
     pub fn inner_mut(&mut self) -> &mut alloc::string::String {
         &mut self.sink
@@ -79,3 +185,346 @@ impl EventWriter {
         self.sink
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use super::{EmitterConfig, Error, EventWriter, OwnedXmlEvent, SequentialPrefixes, UriTailPrefixes, XmlEvent};
+    use crate::name::Name;
+    use crate::namespace::{ReservedNamespaceError, NS_XMLNS_URI, NS_XML_URI};
+
+    #[test]
+    fn validate_names() {
+        let config = EmitterConfig::new().write_document_declaration(false).validate_names(true);
+        let mut writer = EventWriter::new_with_config(config.clone());
+        writer.write(XmlEvent::start_element("p:a").ns("p", "urn:p").attr("b", "1")).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        assert_eq!(writer.into_inner(), r#"<p:a xmlns:p="urn:p" b="1" />"#);
+
+        for event in [
+            XmlEvent::start_element("1a"),
+            XmlEvent::start_element("a").attr("b c", ""),
+            XmlEvent::start_element("a").ns("p q", "urn:p"),
+        ] {
+            let mut writer = EventWriter::new_with_config(config.clone());
+            assert!(matches!(writer.write(event), Err(Error::InvalidName(_))));
+        }
+        let mut writer = EventWriter::new_with_config(config.clone());
+        let err = writer.write(XmlEvent::start_element("a").attr("b", "1\u{0}")).unwrap_err();
+        assert!(matches!(err, Error::InvalidAttribute(crate::attribute::AttributeError::Value { index: 1, found: '\0' })));
+
+        let mut writer = EventWriter::new_with_config(config.validate_names(false));
+        writer.write(XmlEvent::start_element("1a")).unwrap();
+    }
+
+    #[test]
+    fn namespace_declarations() {
+        let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+        writer.write(XmlEvent::start_element("a").default_ns("urn:x").ns("p", "urn:p")).unwrap();
+        writer.write(XmlEvent::start_element("p:b").default_ns("urn:x").ns("p", "urn:p")).unwrap();
+        writer.write(XmlEvent::start_element("c").default_ns("").ns("p", "urn:q")).unwrap();
+        writer.write(XmlEvent::start_element("d").default_ns("")).unwrap();
+        for _ in 0..4 {
+            writer.write(XmlEvent::end_element()).unwrap();
+        }
+        assert_eq!(writer.into_inner(), r#"<a xmlns="urn:x" xmlns:p="urn:p"><p:b><c xmlns="" xmlns:p="urn:q"><d /></c></p:b></a>"#);
+    }
+
+    #[test]
+    fn default_namespace_round_trip() {
+        let doc = r#"<a xmlns="urn:x"><b xmlns=""><c/><d xmlns="urn:y"><e xmlns=""/></d></b><f/><p:g xmlns:p="urn:p" xmlns=""><h/></p:g></a>"#;
+        let events: alloc::vec::Vec<_> = crate::EventReader::new(doc.as_bytes().iter()).into_iter().map(Result::unwrap).collect();
+        let mut namespaces = alloc::vec::Vec::new();
+        let mut writer = EventWriter::new_with_config(EmitterConfig::new().normalize_empty_elements(false));
+        for e in &events {
+            if let crate::reader::XmlEvent::StartElement { name, .. } = e {
+                namespaces.push(alloc::format!("{name}"));
+            }
+            if let Some(e) = e.as_writer_event() {
+                writer.write(e).unwrap();
+            }
+        }
+        assert_eq!(namespaces, ["{urn:x}a", "b", "c", "{urn:y}d", "e", "{urn:x}f", "{urn:p}p:g", "h"]);
+        assert_eq!(writer.into_inner(), r#"<?xml version="1.0" encoding="UTF-8"?><a xmlns="urn:x"><b xmlns=""><c></c><d xmlns="urn:y"><e xmlns=""></e></d></b><f></f><p:g xmlns="" xmlns:p="urn:p"><h></h></p:g></a>"#);
+    }
+
+    #[test]
+    fn prefix_generators() {
+        const SVG: &str = "http://www.w3.org/2000/svg";
+        let write = |config: EmitterConfig| {
+            let mut writer = EventWriter::new_with_config(config.write_document_declaration(false));
+            writer.write(XmlEvent::start_element(Name::qualified("a", "urn:a", None)).ns("ns1", "urn:taken")).unwrap();
+            writer.write(XmlEvent::start_element(Name::qualified("svg", SVG, None))
+                .attr(Name::qualified("b", "urn:a", None), "1")
+                .attr(Name::qualified("c", SVG, None), "2")).unwrap();
+            writer.write(XmlEvent::start_element(Name::qualified("d", "urn:deeper/svg", None)).default_ns(SVG)).unwrap();
+            writer.write(XmlEvent::end_element().name(Name::qualified("d", "urn:deeper/svg", None))).unwrap();
+            writer.write(XmlEvent::end_element().name(Name::qualified("svg", SVG, None))).unwrap();
+            writer.write(XmlEvent::end_element().name(Name::qualified("a", "urn:a", None))).unwrap();
+            writer.into_inner()
+        };
+
+        assert_eq!(write(EmitterConfig::new().prefix_generator(SequentialPrefixes)),
+            r#"<ns2:a xmlns:ns1="urn:taken" xmlns:ns2="urn:a"><ns3:svg xmlns:ns3="http://www.w3.org/2000/svg" ns2:b="1" ns3:c="2">"#.to_owned() +
+            r#"<ns4:d xmlns="http://www.w3.org/2000/svg" xmlns:ns4="urn:deeper/svg" /></ns3:svg></ns2:a>"#);
+        assert_eq!(write(EmitterConfig::new().prefix_generator(UriTailPrefixes)),
+            r#"<a:a xmlns:a="urn:a" xmlns:ns1="urn:taken"><svg:svg xmlns:svg="http://www.w3.org/2000/svg" a:b="1" svg:c="2">"#.to_owned() +
+            r#"<svg2:d xmlns="http://www.w3.org/2000/svg" xmlns:svg2="urn:deeper/svg" /></svg:svg></a:a>"#);
+        assert_eq!(write(EmitterConfig::new().keep_element_names_stack(false).prefix_generator(SequentialPrefixes)),
+            r#"<ns2:a xmlns:ns1="urn:taken" xmlns:ns2="urn:a"><ns3:svg xmlns:ns3="http://www.w3.org/2000/svg" ns2:b="1" ns3:c="2">"#.to_owned() +
+            r#"<ns4:d xmlns="http://www.w3.org/2000/svg" xmlns:ns4="urn:deeper/svg" /></ns3:svg></ns2:a>"#);
+    }
+
+    #[test]
+    fn writer_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let config = EmitterConfig::new().prefix_generator(UriTailPrefixes);
+        assert_send(&config);
+        assert_send(&EventWriter::new_with_config(config));
+    }
+
+    #[test]
+    fn static_names() {
+        // end tags are written from the kept names, generated prefixes included
+        let write = |write_static: bool| {
+            let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false).prefix_generator(SequentialPrefixes));
+            let events = [
+                XmlEvent::start_element("a").into(),
+                XmlEvent::start_element(Name::qualified("b", "urn:b", None)).into(),
+                XmlEvent::start_element("p:c").ns("p", "urn:p").into(),
+                XmlEvent::characters("x"),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+                XmlEvent::end_element().into(),
+            ];
+            for event in events {
+                if write_static { writer.write_static(event) } else { writer.write(event) }.unwrap();
+            }
+            writer.into_inner()
+        };
+        assert_eq!(write(true), r#"<a><ns1:b xmlns:ns1="urn:b"><p:c xmlns:p="urn:p">x</p:c></ns1:b></a>"#);
+        assert_eq!(write(true), write(false));
+    }
+
+    #[test]
+    fn cow_attributes() {
+        use alloc::borrow::Cow;
+        use crate::attribute::{CowAttribute, OwnedAttribute};
+        use crate::name::OwnedName;
+
+        let read = [
+            OwnedAttribute::new(OwnedName::local("id"), "a&b"),
+            OwnedAttribute::new(OwnedName::local("n"), "1"),
+        ];
+        let mut rewritten: alloc::vec::Vec<CowAttribute<'_>> = read.iter().map(CowAttribute::from).collect();
+        rewritten[1].value = Cow::Owned("<2>".into());
+        assert!(matches!(rewritten[0].value, Cow::Borrowed(v) if core::ptr::eq(v, read[0].value.as_str())));
+
+        let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+        writer.write(XmlEvent::start_element("a").attrs(&rewritten)).unwrap();
+        writer.write(XmlEvent::start_element("b").attrs(&read)).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        assert_eq!(writer.into_inner(), r#"<a id="a&amp;b" n="&lt;2&gt;"><b id="a&amp;b" n="1" /></a>"#);
+    }
+
+    #[test]
+    fn inferred_attribute_prefixes() {
+        use crate::attribute::Attribute;
+
+        let config = EmitterConfig::new().write_document_declaration(false);
+        let mut writer = EventWriter::new_with_config(config.clone());
+        writer.write(XmlEvent::start_element("a").ns("x", "urn:x")).unwrap();
+        writer.write(XmlEvent::start_element(Name::qualified("b", "urn:x", None))
+            .attrs([Attribute::in_namespace("urn:x", "c", "1")])).unwrap();
+        writer.write(XmlEvent::end_element().name(Name::qualified("b", "urn:x", None))).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        assert_eq!(writer.into_inner(), r#"<a xmlns:x="urn:x"><x:b x:c="1" /></a>"#);
+
+        let mut writer = EventWriter::new_with_config(config);
+        writer.write(XmlEvent::start_element("a").ns("x", "urn:x")).unwrap();
+        let event = XmlEvent::start_element("b").attrs([Attribute::in_namespace("urn:y", "c", "1")]);
+        let err = writer.write(event).unwrap_err();
+        assert!(matches!(&err, Error::UnboundNamespace(uri) if uri == "urn:y"));
+        assert_eq!(err.to_string(), "emitter error: no prefix is bound to namespace 'urn:y'");
+    }
+
+    #[test]
+    fn owned_events() {
+        let doc = r#"<?xml version="1.0" encoding="UTF-8"?><?pi x?><a xmlns:p="urn:p" p:b="&amp;"><![CDATA[d]]>e</a>"#;
+        let events: alloc::vec::Vec<OwnedXmlEvent> = crate::EventReader::new(doc.as_bytes().iter())
+            .into_iter()
+            .filter_map(|e| e.unwrap().as_writer_event().map(OwnedXmlEvent::from))
+            .collect();
+        assert!(matches!(&events[2], OwnedXmlEvent::StartElement { attributes, .. } if attributes[0].value == "&"));
+
+        let mut writer = EventWriter::new();
+        for event in &events {
+            writer.write(event.borrow()).unwrap();
+        }
+        assert_eq!(writer.into_inner(), doc);
+        assert_eq!(OwnedXmlEvent::from(events[2].borrow()), events[2]);
+    }
+
+    #[test]
+    fn pre_escaped() {
+        use crate::escape::{PreEscaped, PreEscapedError};
+
+        let config = EmitterConfig::new().write_document_declaration(false);
+        let mut writer = EventWriter::new_with_config(config.clone());
+        writer.write_start_element(XmlEvent::start_element("p:a")
+            .attr_pre_escaped("b", PreEscaped::new("&amp;&#xA;'"))
+            .attr("c", "&")
+            .attrs([crate::attribute::Attribute::new(Name::local("d"), "<")])
+            .ns("p", "urn:p")).unwrap();
+        writer.write_pre_escaped(PreEscaped::new("&lt;&amp;>")).unwrap();
+        writer.write(XmlEvent::characters("&")).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        assert_eq!(writer.into_inner(), r#"<p:a xmlns:p="urn:p" b="&amp;&#xA;'" c="&amp;" d="&lt;">&lt;&amp;>&amp;</p:a>"#);
+
+        for value in ["a&b", "<", "\"", "&#x;"] {
+            let mut writer = EventWriter::new_with_config(config.clone());
+            let r = writer.write_start_element(XmlEvent::start_element("a").attr_pre_escaped("b", PreEscaped::new(value)));
+            assert!(matches!(r, Err(Error::InvalidPreEscaped(_))), "{value}");
+            assert_eq!(writer.into_inner(), "");
+        }
+        let mut writer = EventWriter::new_with_config(config);
+        writer.write(XmlEvent::start_element("a")).unwrap();
+        let r = writer.write_pre_escaped(PreEscaped::new("]]>"));
+        assert!(matches!(r, Err(Error::InvalidPreEscaped(PreEscapedError { index: 2, found: '>' }))));
+    }
+
+    #[test]
+    fn carriage_returns_round_trip() {
+        for text in ["a\rb", "a\r\nb", "a\n\rb", "trailing\r", "\r"] {
+            let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+            writer.write(XmlEvent::start_element("a").attr("v", text)).unwrap();
+            writer.write(XmlEvent::characters(text)).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+            let doc = writer.into_inner();
+
+            let mut value = None;
+            let read: alloc::string::String = crate::EventReader::new(doc.as_bytes().iter())
+                .into_iter()
+                .filter_map(|e| match e.unwrap() {
+                    crate::reader::XmlEvent::StartElement { attributes, .. } => {
+                        value = Some(attributes[0].value.clone());
+                        None
+                    },
+                    crate::reader::XmlEvent::Characters(s) | crate::reader::XmlEvent::Whitespace(s) => Some(s),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(read, text, "{doc:?}");
+            assert_eq!(value.as_deref(), Some(text), "{doc:?}");
+        }
+    }
+
+    #[test]
+    fn entity_substitutions() {
+        let config = EmitterConfig::new().write_document_declaration(false)
+            .substitute_entities([('\u{20ac}', "euro".into()), ('\u{a0}', "nbsp".into()), ('&', "and".into()), ('<', "lt2".into())]);
+        assert_eq!(config.entity_substitutions.len(), 2);
+        let mut writer = EventWriter::new_with_config(config.clone());
+        writer.write(XmlEvent::start_element("a").attr("price", "5\u{a0}\u{20ac} & <more>")).unwrap();
+        writer.write(XmlEvent::characters("\u{20ac}\u{20ac}x\u{a0}<&")).unwrap();
+        writer.write(XmlEvent::cdata("\u{20ac}")).unwrap();
+        writer.write(XmlEvent::comment("\u{20ac}")).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        let doc = writer.into_inner();
+        assert_eq!(doc, "<a price=\"5&nbsp;&euro; &amp; &lt;more&gt;\">&euro;&euro;x&nbsp;&lt;&amp;<![CDATA[\u{20ac}]]><!-- \u{20ac} --></a>");
+
+        let reader_config = crate::reader::ParserConfig::new().add_entity("euro", "\u{20ac}").add_entity("nbsp", "\u{a0}");
+        let mut texts = alloc::vec::Vec::new();
+        for e in crate::EventReader::new_with_config(doc.as_bytes().iter(), reader_config) {
+            match e.unwrap() {
+                crate::reader::XmlEvent::StartElement { attributes, .. } => texts.extend(attributes.into_iter().map(|a| a.value)),
+                crate::reader::XmlEvent::Characters(s) | crate::reader::XmlEvent::CData(s) => texts.push(s),
+                _ => {},
+            }
+        }
+        assert_eq!(texts, ["5\u{a0}\u{20ac} & <more>", "\u{20ac}\u{20ac}x\u{a0}<&", "\u{20ac}"]);
+
+        let mut writer = EventWriter::new_with_config(EmitterConfig { perform_escaping: false, ..config });
+        writer.write(XmlEvent::start_element("a")).unwrap();
+        writer.write(XmlEvent::characters("\u{20ac}")).unwrap();
+        assert!(writer.into_inner().ends_with("<a>\u{20ac}"));
+    }
+
+    #[test]
+    fn single_quote_attributes() {
+        let value = r#"it's "quoted" & <tagged>"#;
+        let write = |config: EmitterConfig| {
+            let mut writer = EventWriter::new_with_config(config.write_document_declaration(false));
+            writer.write(XmlEvent::start_element("a").ns("p", "urn:'p'").attr("b", value)).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+            writer.into_inner()
+        };
+        let double = write(EmitterConfig::new());
+        let single = write(EmitterConfig::new().single_quote_attributes(true));
+        assert_eq!(double, r#"<a xmlns:p="urn:&apos;p&apos;" b="it&apos;s &quot;quoted&quot; &amp; &lt;tagged&gt;" />"#);
+        assert_eq!(single, r#"<a xmlns:p='urn:&apos;p&apos;' b='it&apos;s "quoted" &amp; &lt;tagged&gt;' />"#);
+
+        for doc in [double, single] {
+            let mut reader = crate::EventReader::new(doc.as_bytes().iter());
+            reader.next().unwrap();
+            match reader.next().unwrap() {
+                crate::reader::XmlEvent::StartElement { attributes, namespace, .. } => {
+                    assert_eq!(attributes.get("b").map(|a| &*a.value), Some(value));
+                    assert_eq!(namespace.get("p"), Some("urn:'p'"));
+                },
+                e => panic!("{e:?}"),
+            }
+        }
+
+        let mut writer = EventWriter::new_with_config(EmitterConfig::new().single_quote_attributes(true));
+        let r = writer.write_start_element(XmlEvent::start_element("a").attr_pre_escaped("b", crate::escape::PreEscaped::new("'")));
+        assert!(matches!(r, Err(Error::InvalidPreEscaped(_))));
+    }
+
+    #[test]
+    fn reserved_namespaces() {
+        let config = EmitterConfig::new().write_document_declaration(false);
+        for (event, expected) in [
+            (XmlEvent::start_element("a").ns("xml", "urn:x"), ReservedNamespaceError::XmlPrefix),
+            (XmlEvent::start_element("a").ns("xmlns", "urn:x"), ReservedNamespaceError::XmlnsPrefix),
+            (XmlEvent::start_element("a").ns("x", NS_XML_URI), ReservedNamespaceError::XmlUri),
+            (XmlEvent::start_element("a").default_ns(NS_XMLNS_URI), ReservedNamespaceError::XmlnsUri),
+        ] {
+            let mut writer = EventWriter::new_with_config(config.clone());
+            assert!(matches!(writer.write(event), Err(Error::ReservedNamespace(e)) if e == expected));
+        }
+
+        let mut writer = EventWriter::new_with_config(config.prefix_generator(SequentialPrefixes));
+        writer.write(XmlEvent::start_element("a").ns("xml", NS_XML_URI).attr(crate::name::XML_LANG, "en")).unwrap();
+        writer.write(XmlEvent::end_element()).unwrap();
+        assert_eq!(writer.into_inner(), r#"<a xml:lang="en" />"#);
+    }
+
+    #[test]
+    fn conflicting_namespace_bindings() {
+        let config = EmitterConfig::new().write_document_declaration(false);
+        for event in [
+            XmlEvent::start_element("a").ns("p", "urn:a").attr("xmlns:p", "urn:b"),
+            XmlEvent::start_element("a").default_ns("urn:a").attr("xmlns", "urn:b"),
+        ] {
+            let mut writer = EventWriter::new_with_config(config.clone());
+            assert!(matches!(writer.write(event), Err(Error::ConflictingNamespaceBinding(_))));
+            // nothing is left of the failed element
+            writer.write(XmlEvent::start_element("b")).unwrap();
+            writer.write(XmlEvent::end_element()).unwrap();
+            assert_eq!(writer.into_inner(), "<b />");
+        }
+
+        // popping the implicit `xml` binding would make the generator invent a prefix for it
+        let mut writer = EventWriter::new_with_config(config.keep_element_names_stack(false).prefix_generator(SequentialPrefixes));
+        writer.write(XmlEvent::start_element("a").ns("p", "urn:a").attr("xmlns:q", "urn:b")).unwrap();
+        for _ in 0..3 {
+            let _ = writer.write(XmlEvent::end_element().name("a"));
+        }
+        writer.write(XmlEvent::start_element("b").attr(Name::qualified("lang", NS_XML_URI, None), "en")).unwrap();
+        writer.write(XmlEvent::end_element().name("b")).unwrap();
+        assert_eq!(writer.into_inner(), r#"<a xmlns:p="urn:a" xmlns:q="urn:b" /></a></a><b xml:lang="en" />"#);
+    }
+}
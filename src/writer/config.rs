@@ -2,7 +2,11 @@
 extern crate alloc;
 
 use alloc::borrow::Cow;
-use crate::writer::EventWriter;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use crate::escape::{escape_into, AposAttributeEscapes, AttributeEscapes, Escapes, PcDataEscapes};
+use crate::writer::{EventWriter, PrefixGenerator};
 
 /// Emitter configuration structure.
 ///
@@ -81,9 +85,78 @@ pub struct EmitterConfig {
     /// this option is also true, the same element would appear `<a />`. If this option is false,
     /// then the same element would appear `<a/>`.
     pub pad_self_closing: bool,
+
+    /// Whether or not to check that element, attribute and namespace prefix names are valid.
+    /// Default is false.
+    ///
+    /// When enabled, names which are not `QName`s of the Namespaces in XML spec, e.g. with
+    /// spaces or a leading digit, fail with `EmitterError::InvalidName` instead of producing
+    /// a malformed document. Attribute values with characters which aren't allowed in XML,
+    /// like `\0`, fail with `EmitterError::InvalidAttribute`, see `Attribute::try_new`.
+    pub validate_names: bool,
+
+    /// Whether to quote attribute values with `'` instead of `"`. Default is false.
+    ///
+    /// Values are escaped to match: with single quotes, `'` is written as `&apos;` and `"` is
+    /// left as is, see `escape_str_attribute_apos`. Namespace declarations are quoted the same way.
+    pub single_quote_attributes: bool,
+
+    /// Characters to write as references to entities, e.g. `€` as `&euro;`. Default is empty.
+    ///
+    /// Maps characters to entity names. It applies to character data and attribute values
+    /// when `perform_escaping` is on, but not inside CDATA, comments or processing instructions.
+    /// The names aren't checked: they should be declared in the DTD the document is read with.
+    /// Use `substitute_entities` to add entries, it skips characters which are always escaped.
+    pub entity_substitutions: BTreeMap<char, String>,
+
+    /// How to invent prefixes for names which have a namespace URI but no prefix.
+    /// Default is `None`.
+    ///
+    /// An element or attribute name with a namespace but no prefix is written with a prefix
+    /// which is already bound to its namespace, and an element in the default namespace keeps
+    /// no prefix. If there's no such binding, the generator makes a prefix, which is then
+    /// declared on the element. When `None`, the write fails with
+    /// `EmitterError::UnboundNamespace` instead.
+    ///
+    /// The generator is shared with `Arc`, and has to be `Send` and `Sync` so that the config
+    /// and the writer can be sent to other threads.
+    pub prefix_generator: Option<Arc<dyn PrefixGenerator + Send + Sync>>,
 }
 
 impl EmitterConfig {
+    pub(crate) fn attribute_quote(&self) -> char {
+        if self.single_quote_attributes { '\'' } else { '"' }
+    }
+
+    pub(crate) fn escape_attribute_value(&self, target: &mut String, value: &str) {
+        if self.single_quote_attributes {
+            self.escape_with_substitutions(AposAttributeEscapes, target, value);
+        } else {
+            self.escape_with_substitutions(AttributeEscapes, target, value);
+        }
+    }
+
+    pub(crate) fn escape_characters(&self, target: &mut String, content: &str) {
+        self.escape_with_substitutions(PcDataEscapes, target, content);
+    }
+
+    fn escape_with_substitutions<E: Escapes + Copy>(&self, rules: E, target: &mut String, s: &str) {
+        if self.entity_substitutions.is_empty() {
+            return escape_into(rules, target, s);
+        }
+        let mut rest = s;
+        while let Some((i, c, name)) = rest.char_indices()
+            .find_map(|(i, c)| self.entity_substitutions.get(&c).map(|name| (i, c, name)))
+        {
+            escape_into(rules, target, &rest[..i]);
+            target.push('&');
+            target.push_str(name);
+            target.push(';');
+            rest = &rest[i + c.len_utf8()..];
+        }
+        escape_into(rules, target, rest);
+    }
+
     /// Creates an emitter configuration with default values.
     ///
     /// You can tweak default options with builder-like pattern:
@@ -110,6 +183,10 @@ impl EmitterConfig {
             keep_element_names_stack: true,
             autopad_comments: true,
             pad_self_closing: true,
+            validate_names: false,
+            single_quote_attributes: false,
+            entity_substitutions: BTreeMap::new(),
+            prefix_generator: None,
         }
     }
 
@@ -153,5 +230,32 @@ gen_setters!(EmitterConfig,
     cdata_to_characters: val bool,
     keep_element_names_stack: val bool,
     autopad_comments: val bool,
-    pad_self_closing: val bool
+    pad_self_closing: val bool,
+    validate_names: val bool,
+    single_quote_attributes: val bool
 );
+
+impl EmitterConfig {
+    /// Sets the strategy for inventing namespace prefixes, e.g. `SequentialPrefixes`.
+    ///
+    /// <small>See [`EmitterConfig`] fields docs for details</small>
+    #[inline]
+    #[must_use]
+    pub fn prefix_generator<G: PrefixGenerator + Send + Sync + 'static>(mut self, generator: G) -> Self {
+        self.prefix_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Adds characters to write as entity references, e.g. `('€', "euro".into())`.
+    ///
+    /// Characters which are always escaped, like `&`, `<` and quotes, are skipped, so the
+    /// predefined references are still used for them.
+    ///
+    /// <small>See [`EmitterConfig`] fields docs for details</small>
+    #[must_use]
+    pub fn substitute_entities(mut self, map: impl IntoIterator<Item = (char, String)>) -> Self {
+        self.entity_substitutions.extend(map.into_iter()
+            .filter(|&(c, _)| !c.is_ascii() || AttributeEscapes::escape(c as u8).is_none()));
+        self
+    }
+}
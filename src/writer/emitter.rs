@@ -4,17 +4,20 @@ use core::fmt;
 use core::fmt::Write;
 use core::result;
 
+use alloc::borrow::Cow;
 use alloc::string::String;
 use alloc::vec::Vec;
 
-use crate::attribute::Attribute;
+use crate::attribute::{Attribute, AttributeError};
+use crate::chars::is_whitespace_char;
 use crate::common;
 use crate::common::XmlVersion;
-use crate::escape::{AttributeEscapes, Escaped, PcDataEscapes};
-use crate::name::{Name, OwnedName};
-use crate::namespace::{NamespaceStack, NS_EMPTY_URI, NS_NO_PREFIX, NS_XMLNS_PREFIX, NS_XML_PREFIX};
+use crate::escape::{PreEscaped, PreEscapedError};
+use crate::name::{is_ncname, CowName, Name, NameError};
+use crate::namespace::{self, ConflictingBinding, NamespaceStack, ReservedNamespaceError, NS_NO_PREFIX, NS_XMLNS_PREFIX, NS_XMLNS_URI, NS_XML_PREFIX};
 
 use crate::writer::config::EmitterConfig;
+use crate::writer::{PrefixGenerator, SequentialPrefixes};
 
 macro_rules! write {
     ($dst:expr, $($arg:tt)*) => {
@@ -41,6 +44,32 @@ pub enum EmitterError {
     /// End element name is not specified when it is needed, for example, when automatic
     /// closing is not enabled in configuration.
     EndElementNameIsNotSpecified,
+
+    /// An element, attribute or namespace prefix name is invalid, see `EmitterConfig::validate_names`.
+    InvalidName(NameError),
+
+    /// An attribute value has a character which can't be written, see
+    /// `EmitterConfig::validate_names`.
+    InvalidAttribute(AttributeError),
+
+    /// `PreEscaped` text has a character which must be escaped where it's written.
+    InvalidPreEscaped(PreEscapedError),
+
+    /// A namespace declaration binds the reserved `xml` or `xmlns` prefix or URI, see
+    /// `namespace::check_reserved`.
+    ReservedNamespace(ReservedNamespaceError),
+
+    /// A prefix is bound to two different URIs in the declarations of one element, e.g. by
+    /// both the element's namespace and an `xmlns:` attribute.
+    ConflictingNamespaceBinding(ConflictingBinding),
+
+    /// An element or attribute name has a namespace but no prefix, no prefix is bound to the
+    /// namespace, and there's no `EmitterConfig::prefix_generator` to make one. Holds the URI.
+    UnboundNamespace(String),
+
+    /// The output couldn't grow to hold the event, with the `fallible-alloc` feature. Nothing
+    /// of the event was written.
+    OutOfMemory,
 }
 
 impl fmt::Display for EmitterError {
@@ -53,6 +82,48 @@ impl fmt::Display for EmitterError {
             EmitterError::LastElementNameNotAvailable => f.write_str("last element name is not available"),
             EmitterError::EndElementNameIsNotEqualToLastStartElementName => f.write_str("end element name is not equal to last start element name"),
             EmitterError::EndElementNameIsNotSpecified => f.write_str("end element name is not specified and can't be inferred"),
+            EmitterError::InvalidName(e) => e.fmt(f),
+            EmitterError::InvalidAttribute(e) => e.fmt(f),
+            EmitterError::InvalidPreEscaped(e) => e.fmt(f),
+            EmitterError::ReservedNamespace(e) => f.write_str(&alloc::format!("reserved namespace binding: {e}")),
+            EmitterError::ConflictingNamespaceBinding(e) => f.write_str(&alloc::format!("conflicting namespace binding: {e}")),
+            EmitterError::UnboundNamespace(uri) => f.write_str(&alloc::format!("no prefix is bound to namespace '{uri}'")),
+            EmitterError::OutOfMemory => f.write_str("out of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for EmitterError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            EmitterError::Io(e) => defmt::write!(f, "I/O error: {=str}", e),
+            EmitterError::DocumentStartAlreadyEmitted => defmt::write!(f, "document start event has already been emitted"),
+            EmitterError::LastElementNameNotAvailable => defmt::write!(f, "last element name is not available"),
+            EmitterError::EndElementNameIsNotEqualToLastStartElementName => defmt::write!(f, "end element name is not equal to last start element name"),
+            EmitterError::EndElementNameIsNotSpecified => defmt::write!(f, "end element name is not specified and can't be inferred"),
+            EmitterError::InvalidName(e) => defmt::write!(f, "{}", e),
+            EmitterError::InvalidAttribute(e) => defmt::write!(f, "{}", e),
+            EmitterError::InvalidPreEscaped(e) => defmt::write!(f, "{}", e),
+            EmitterError::ReservedNamespace(e) => defmt::write!(f, "reserved namespace binding: {}", e),
+            EmitterError::ConflictingNamespaceBinding(e) => defmt::write!(f, "conflicting namespace binding: {}", e),
+            EmitterError::UnboundNamespace(uri) => defmt::write!(f, "no prefix is bound to namespace '{=str}'", uri),
+            EmitterError::OutOfMemory => defmt::write!(f, "out of memory"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for EmitterError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            // displayed as the inner error
+            EmitterError::InvalidName(e) => e.source(),
+            EmitterError::InvalidAttribute(e) => e.source(),
+            EmitterError::InvalidPreEscaped(e) => e.source(),
+            EmitterError::ReservedNamespace(e) => Some(e),
+            EmitterError::ConflictingNamespaceBinding(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -70,7 +141,8 @@ pub struct Emitter {
     indent_level: usize,
     indent_stack: Vec<IndentFlags>,
 
-    element_names: Vec<OwnedName>,
+    /// Names of the open elements, borrowed if they're `'static`, see `EventWriter::write_static`
+    element_names: Vec<CowName<'static>>,
 
     start_document_emitted: bool,
     just_wrote_start_element: bool,
@@ -84,7 +156,8 @@ impl Emitter {
         Emitter {
             config,
 
-            nst: NamespaceStack::empty(),
+            // `xml` is bound without a declaration
+            nst: NamespaceStack::default(),
 
             indent_level: 0,
             indent_stack,
@@ -111,6 +184,39 @@ impl Emitter {
         &mut self.nst
     }
 
+    /// Makes room in `target` and in the emitter's stacks for writing an event, with the
+    /// `fallible-alloc` feature, so that it fails before anything is written if memory is short.
+    ///
+    /// `texts` are the names, values and text the event writes, and `pieces` how many
+    /// attributes and namespace declarations it has, each with a bit of markup around it.
+    /// A prefix made by the `prefix_generator` is assumed to be no longer than its URI.
+    #[cfg(feature = "fallible-alloc")]
+    pub fn reserve(&mut self, target: &mut String, texts: &[&str], pieces: usize) -> Result<()> {
+        let texts: usize = texts.iter().map(|s| self.escaped_len_bound(s)).sum();
+        // the end tag of the last element, if it's written without a name, or `>` left open
+        let last_name = self.element_names.last().map_or(0, |name| {
+            name.local_name.len() + name.prefix.as_ref().map_or(0, |p| p.len() + 1)
+        });
+        let indent = self.config.line_separator.len() + self.config.indent_string.len() * (self.indent_level + 1);
+        let len = texts + pieces * 16 + last_name + indent + 64;
+        target.try_reserve(len)
+            .and_then(|()| self.indent_stack.try_reserve(1))
+            .and_then(|()| self.element_names.try_reserve(1))
+            .and_then(|()| self.nst.0.try_reserve(1))
+            .map_err(|_| EmitterError::OutOfMemory)
+    }
+
+    /// An upper bound of the length of `s` when it's written escaped.
+    #[cfg(feature = "fallible-alloc")]
+    fn escaped_len_bound(&self, s: &str) -> usize {
+        // the longest escapes, like `&quot;` and `&#x1F;`, replace one byte with six
+        let escaped = s.bytes().filter(|&b| b < 0x20 || matches!(b, b'<' | b'>' | b'&' | b'"' | b'\'')).count();
+        let substituted: usize = if self.config.entity_substitutions.is_empty() { 0 } else {
+            s.chars().filter_map(|c| self.config.entity_substitutions.get(&c)).map(|name| name.len() + 2).sum()
+        };
+        s.len() + 5 * escaped + substituted
+    }
+
     #[inline]
     fn wrote_text(&self) -> bool {
         self.indent_stack.last().map_or(false, |&e| e == IndentFlags::WroteText)
@@ -263,28 +369,63 @@ impl Emitter {
     #[track_caller]
     fn emit_start_element_initial(&mut self, target: &mut String,
                                      name: Name<'_>,
-                                     attributes: &[Attribute<'_>]) -> Result<()>
+                                     attributes: &[Attribute<'_>],
+                                     pre_escaped: &[bool]) -> Result<()>
     {
         self.check_document_started(target)?;
         self.fix_non_empty_element(target);
         self.before_start_element(target);
         write!(target, "<{}", name.repr_display());
         self.emit_current_namespace_attributes(target);
-        self.emit_attributes(target, attributes);
+        self.emit_attributes(target, attributes, pre_escaped);
         self.after_start_element();
         Ok(())
     }
 
+    /// Writes a start tag. The values of the attributes which are `true` in `pre_escaped` are
+    /// written as is, see `PreEscaped`; it's empty if none is. `static_name` is `name`, if the
+    /// caller knows it's `'static`, so that it's kept without a copy.
     #[track_caller]
     pub fn emit_start_element(&mut self, target: &mut String,
                                  name: Name<'_>,
-                                 attributes: &[Attribute<'_>]) -> Result<()>
+                                 static_name: Option<Name<'static>>,
+                                 attributes: &[Attribute<'_>],
+                                 pre_escaped: &[bool]) -> Result<()>
     {
+        for (attr, _) in attributes.iter().zip(pre_escaped).filter(|&(_, &pre_escaped)| pre_escaped) {
+            let value = PreEscaped::new(attr.value);
+            let checked = if self.config.single_quote_attributes { value.check_attribute_apos() } else { value.check_attribute() };
+            checked.map_err(EmitterError::InvalidPreEscaped)?;
+        }
+        for (prefix, uri) in self.nst.peek() {
+            namespace::check_reserved(prefix, uri).map_err(EmitterError::ReservedNamespace)?;
+        }
+        self.check_declaring_attributes(attributes)?;
+
+        let prefixes = self.assign_prefixes(name, attributes)?;
+        let (name, attributes) = if prefixes.is_empty() {
+            (name, Cow::Borrowed(attributes))
+        } else {
+            (with_prefix(name, &prefixes[0]), attributes.iter().zip(&prefixes[1..])
+                .map(|(attr, prefix)| Attribute::new(with_prefix(attr.name, prefix), attr.value))
+                .collect())
+        };
+        let attributes = &*attributes;
+
+        if self.config.validate_names {
+            self.validate_start_element(name, attributes)?;
+        }
+
         if self.config.keep_element_names_stack {
-            self.element_names.push(name.to_owned());
+            // unless a prefix was generated for it
+            let kept = match static_name {
+                Some(static_name) if prefixes.is_empty() => CowName::from(static_name),
+                _ => CowName::from(name).into_static(),
+            };
+            self.element_names.push(kept);
         }
 
-        self.emit_start_element_initial(target, name, attributes)?;
+        self.emit_start_element_initial(target, name, attributes, pre_escaped)?;
         self.just_wrote_start_element = true;
 
         if !self.config.normalize_empty_elements {
@@ -294,40 +435,152 @@ impl Emitter {
         Ok(())
     }
 
+    /// Checks `xmlns` attributes written as is against the element's namespace, so that no prefix
+    /// gets declared twice with different URIs
+    fn check_declaring_attributes(&self, attributes: &[Attribute<'_>]) -> Result<()> {
+        for attr in attributes {
+            let prefix = match attr.name.prefix {
+                Some(NS_XMLNS_PREFIX) => attr.name.local_name,
+                None if attr.name.local_name == NS_XMLNS_PREFIX => NS_NO_PREFIX,
+                _ => continue,
+            };
+            match self.nst.peek().get(prefix) {
+                Some(uri) if uri != attr.value => return Err(EmitterError::ConflictingNamespaceBinding(ConflictingBinding {
+                    prefix: prefix.into(),
+                    existing_uri: uri.into(),
+                    new_uri: attr.value.into(),
+                })),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds prefixes for the element and attribute names which have a namespace but no prefix,
+    /// see `EmitterConfig::prefix_generator`. Returns nothing if no name needs one.
+    fn assign_prefixes(&mut self, name: Name<'_>, attributes: &[Attribute<'_>]) -> Result<Vec<Option<String>>> {
+        let unchanged = self.existing_prefix(name, true) == Some(None)
+            && attributes.iter().all(|attr| self.existing_prefix(attr.name, false) == Some(None));
+        if unchanged {
+            return Ok(Vec::new());
+        }
+        let generator = self.config.prefix_generator.clone();
+        let generator = generator.as_deref();
+        let mut prefixes = Vec::with_capacity(1 + attributes.len());
+        prefixes.push(self.assign_prefix(generator, name, true)?);
+        for attr in attributes {
+            prefixes.push(self.assign_prefix(generator, attr.name, false)?);
+        }
+        Ok(prefixes)
+    }
+
+    fn assign_prefix(&mut self, generator: Option<&(dyn PrefixGenerator + Send + Sync)>, name: Name<'_>, is_element: bool) -> Result<Option<String>> {
+        /// After this many taken candidates, fall back to numbered ones, which run out eventually
+        const MAX_ATTEMPTS: usize = 64;
+
+        if let Some(prefix) = self.existing_prefix(name, is_element) {
+            return Ok(prefix.map(String::from));
+        }
+        let uri = match name.namespace {
+            Some(uri) => uri,
+            None => return Ok(None),
+        };
+        let generator = generator.ok_or_else(|| EmitterError::UnboundNamespace(uri.into()))?;
+        let mut attempt = 0;
+        let prefix = loop {
+            let candidate = if attempt < MAX_ATTEMPTS {
+                generator.generate(uri, &self.nst, attempt)
+            } else {
+                SequentialPrefixes.generate(uri, &self.nst, attempt - MAX_ATTEMPTS)
+            };
+            let reserved = candidate.get(..3).map_or(false, |start| start.eq_ignore_ascii_case("xml"));
+            if is_ncname(&candidate) && !reserved && self.nst.get(&candidate).is_none() {
+                break candidate;
+            }
+            attempt += 1;
+        };
+        self.nst.put(prefix.clone(), uri);
+        Ok(Some(prefix))
+    }
+
+    /// For a name with a namespace but no prefix, returns the prefix bound to the namespace.
+    /// Returns `Some(None)` if no prefix needs to be added, i.e. for an element in the default
+    /// namespace or a name which has a prefix or no namespace, and `None` if a new one is needed.
+    fn existing_prefix(&self, name: Name<'_>, is_element: bool) -> Option<Option<&str>> {
+        let uri = match (name.prefix, name.namespace) {
+            // `xmlns` itself, prefixed declarations already have a prefix
+            (None, Some(NS_XMLNS_URI)) => return Some(None),
+            (None, Some(uri)) if !uri.is_empty() => uri,
+            _ => return Some(None),
+        };
+        if is_element && self.nst.default_namespace() == Some(uri) {
+            return Some(None);
+        }
+        self.nst.prefix_for_uri(uri).filter(|prefix| !prefix.is_empty()).map(Some)
+    }
+
+    fn validate_start_element(&self, name: Name<'_>, attributes: &[Attribute<'_>]) -> Result<()> {
+        name.validate().map_err(EmitterError::InvalidName)?;
+        for attr in attributes {
+            attr.validate().map_err(|e| match e {
+                AttributeError::Name(e) => EmitterError::InvalidName(e),
+                e => EmitterError::InvalidAttribute(e),
+            })?;
+        }
+        for (prefix, _) in self.nst.peek() {
+            if prefix != NS_NO_PREFIX {
+                Name::try_local(prefix).map_err(EmitterError::InvalidName)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares the mappings of the topmost namespace which are not in effect already
     #[track_caller]
     pub fn emit_current_namespace_attributes(&mut self, target: &mut String)
     {
-        for (prefix, uri) in self.nst.peek() {
+        let (top, outer) = match self.nst.0.split_last() {
+            Some(frames) => frames,
+            None => return,
+        };
+        let quote = self.config.attribute_quote();
+        for (prefix, uri) in namespace::delta(outer, top) {
             match prefix {
                 // internal namespaces are not emitted
-                NS_XMLNS_PREFIX | NS_XML_PREFIX => (),
-                //// there is already a namespace binding with this prefix in scope
-                //prefix if self.nst.get(prefix) == Some(uri) => Ok(()),
-                // emit xmlns only if it is overridden
-                NS_NO_PREFIX => if uri != NS_EMPTY_URI {
-                    write!(target, " xmlns=\"{uri}\"")
-                },
+                NS_XMLNS_PREFIX | NS_XML_PREFIX => continue,
+                NS_NO_PREFIX => write!(target, " xmlns={quote}"),
                 // everything else
-                prefix => write!(target, " xmlns:{prefix}=\"{uri}\"")
+                prefix => write!(target, " xmlns:{prefix}={quote}"),
             };
+            self.config.escape_attribute_value(target, uri);
+            target.push(quote);
         }
     }
 
     pub fn emit_attributes(&mut self, target: &mut String,
-                                      attributes: &[Attribute<'_>]) {
-        for attr in attributes {            
-            write!(target, " {}=\"", attr.name.repr_display());
-            if self.config.perform_escaping {
-                write!(target, "{}", Escaped::<AttributeEscapes>::new(attr.value));
+                                      attributes: &[Attribute<'_>], pre_escaped: &[bool]) {
+        let quote = self.config.attribute_quote();
+        for (i, attr) in attributes.iter().enumerate() {
+            write!(target, " {}={quote}", attr.name.repr_display());
+            if self.config.perform_escaping && pre_escaped.get(i) != Some(&true) {
+                self.config.escape_attribute_value(target, attr.value);
             } else {
-                write!(target, "{}", attr.value);
+                target.push_str(attr.value);
             }
-            write!(target, "\"");
+            target.push(quote);
         }
     }
 
     pub fn emit_end_element(&mut self, target: &mut String,
                                       name: Option<Name<'_>>) -> Result<()> {
+        if let (true, Some(name)) = (self.config.validate_names, name) {
+            name.validate().map_err(EmitterError::InvalidName)?;
+        }
+        let prefix = match name {
+            Some(name) => self.existing_prefix(name, true).flatten().map(String::from),
+            None => None,
+        };
+        let name = name.map(|name| with_prefix(name, &prefix));
         let owned_name = if self.config.keep_element_names_stack {
             Some(self.element_names.pop().ok_or(EmitterError::LastElementNameNotAvailable)?)
         } else {
@@ -380,7 +633,7 @@ impl Emitter {
         self.fix_non_empty_element(target);
 
         if self.config.perform_escaping {
-            write!(target, "{}", Escaped::<PcDataEscapes>::new(content));
+            self.config.escape_characters(target, content);
         } else {
             target.push_str(content);
         }
@@ -388,6 +641,15 @@ impl Emitter {
         self.after_text();
     }
 
+    pub fn emit_pre_escaped_characters(&mut self, target: &mut String, content: PreEscaped<'_>) -> Result<()> {
+        content.check_pcdata().map_err(EmitterError::InvalidPreEscaped)?;
+        self.check_document_started(target)?;
+        self.fix_non_empty_element(target);
+        target.push_str(content.as_str());
+        self.after_text();
+        Ok(())
+    }
+
     pub fn emit_comment(&mut self, target: &mut String, content: &str) -> Result<()> {
         self.fix_non_empty_element(target);
 
@@ -397,13 +659,13 @@ impl Emitter {
         let write = move |target: &mut String| -> Result<()> {
             target.push_str("<!--");
 
-            if autopad_comments && !content.starts_with(char::is_whitespace) {
+            if autopad_comments && !content.starts_with(is_whitespace_char) {
                 target.push_str(" ");
             }
 
             target.push_str(content);
 
-            if autopad_comments && !content.ends_with(char::is_whitespace) {
+            if autopad_comments && !content.ends_with(is_whitespace_char) {
                 target.push_str(" ");
             }
 
@@ -419,3 +681,10 @@ impl Emitter {
         result
     }
 }
+
+fn with_prefix<'a>(name: Name<'a>, prefix: &'a Option<String>) -> Name<'a> {
+    match prefix {
+        Some(prefix) => Name { prefix: Some(prefix), ..name },
+        None => name,
+    }
+}
@@ -5,15 +5,18 @@ use alloc::borrow::Cow;
 use alloc::vec::Vec;
 use alloc::string::String;
 
-use crate::attribute::Attribute;
+use crate::attribute::{Attribute, OwnedAttribute};
 use crate::common::XmlVersion;
-use crate::name::Name;
+use crate::escape::PreEscaped;
+use crate::name::{Name, OwnedName};
 use crate::namespace::{Namespace, NS_NO_PREFIX};
 
 /// A part of an XML output stream.
 ///
 /// Objects of this enum are consumed by `EventWriter`. They correspond to different parts of
 /// an XML document.
+// an owned namespace holds a few mappings inline, see `Namespace`
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum XmlEvent<'a> {
     /// Corresponds to XML document declaration.
@@ -96,6 +99,25 @@ pub enum XmlEvent<'a> {
     Characters(&'a str),
 }
 
+/// Like `Debug`, but attributes are only counted.
+#[cfg(feature = "defmt")]
+impl defmt::Format for XmlEvent<'_> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            XmlEvent::StartDocument { version, encoding, standalone } =>
+                defmt::write!(f, "StartDocument({}, {}, {})", version, encoding, standalone),
+            XmlEvent::ProcessingInstruction { name, data } =>
+                defmt::write!(f, "ProcessingInstruction({=str}, {})", name, data),
+            XmlEvent::StartElement { name, attributes, .. } =>
+                defmt::write!(f, "StartElement({}, {=usize} attributes)", name, attributes.len()),
+            XmlEvent::EndElement { name } => defmt::write!(f, "EndElement({})", name),
+            XmlEvent::CData(data) => defmt::write!(f, "CData({=str})", data),
+            XmlEvent::Comment(data) => defmt::write!(f, "Comment({=str})", data),
+            XmlEvent::Characters(data) => defmt::write!(f, "Characters({=str})", data),
+        }
+    }
+}
+
 impl<'a> XmlEvent<'a> {
     /// Returns an writer event for a processing instruction.
     #[inline]
@@ -113,6 +135,7 @@ impl<'a> XmlEvent<'a> {
         StartElementBuilder {
             name: name.into(),
             attributes: Vec::new(),
+            pre_escaped: Vec::new(),
             namespace: Namespace::empty(),
         }
     }
@@ -155,6 +178,131 @@ impl<'a> XmlEvent<'a> {
     }
 }
 
+/// An owned version of `XmlEvent`, which can be kept, e.g. in a `Vec` or sent to another
+/// thread, and written later.
+///
+/// It's made from an `XmlEvent` with `From`, and `borrow()` makes the `XmlEvent` which
+/// `EventWriter::write` accepts.
+///
+/// ```rust
+/// # use xml_no_std::writer::{EmitterConfig, EventWriter, OwnedXmlEvent, XmlEvent};
+/// fn events(title: &str) -> Vec<OwnedXmlEvent> {
+///     let name = format!("{title}-list");
+///     vec![
+///         XmlEvent::from(XmlEvent::start_element(&*name).attr("n", "1")).into(),
+///         XmlEvent::characters(title).into(),
+///         XmlEvent::from(XmlEvent::end_element()).into(),
+///     ]
+/// }
+///
+/// let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+/// for event in &events("todo") {
+///     writer.write(event.borrow()).unwrap();
+/// }
+/// assert_eq!(writer.into_inner(), r#"<todo-list n="1">todo</todo-list>"#);
+/// ```
+// `StartElement` holds a few attributes and namespace mappings inline
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedXmlEvent {
+    /// An owned `XmlEvent::StartDocument`.
+    StartDocument {
+        /// XML version.
+        version: XmlVersion,
+
+        /// XML document encoding.
+        encoding: Option<String>,
+
+        /// XML standalone declaration.
+        standalone: Option<bool>,
+    },
+
+    /// An owned `XmlEvent::ProcessingInstruction`.
+    ProcessingInstruction {
+        /// Processing instruction target.
+        name: String,
+
+        /// Processing instruction content.
+        data: Option<String>,
+    },
+
+    /// An owned `XmlEvent::StartElement`.
+    StartElement {
+        /// Qualified name of the element.
+        name: OwnedName,
+
+        /// A list of attributes associated with the element.
+        attributes: Vec<OwnedAttribute>,
+
+        /// Contents of the namespace mapping at this point of the document.
+        namespace: Namespace,
+    },
+
+    /// An owned `XmlEvent::EndElement`.
+    EndElement {
+        /// Optional qualified name of the element.
+        name: Option<OwnedName>,
+    },
+
+    /// An owned `XmlEvent::CData`.
+    CData(String),
+
+    /// An owned `XmlEvent::Comment`.
+    Comment(String),
+
+    /// An owned `XmlEvent::Characters`.
+    Characters(String),
+}
+
+impl OwnedXmlEvent {
+    /// Returns a borrowed `XmlEvent` out of this owned one, for `EventWriter::write`.
+    #[must_use]
+    pub fn borrow(&self) -> XmlEvent<'_> {
+        match self {
+            OwnedXmlEvent::StartDocument { version, encoding, standalone } =>
+                XmlEvent::StartDocument { version: *version, encoding: encoding.as_deref(), standalone: *standalone },
+            OwnedXmlEvent::ProcessingInstruction { name, data } =>
+                XmlEvent::ProcessingInstruction { name, data: data.as_deref() },
+            OwnedXmlEvent::StartElement { name, attributes, namespace } => XmlEvent::StartElement {
+                name: name.borrow(),
+                attributes: attributes.iter().map(OwnedAttribute::borrow).collect(),
+                namespace: Cow::Borrowed(namespace),
+            },
+            OwnedXmlEvent::EndElement { name } => XmlEvent::EndElement { name: name.as_ref().map(OwnedName::borrow) },
+            OwnedXmlEvent::CData(data) => XmlEvent::CData(data),
+            OwnedXmlEvent::Comment(data) => XmlEvent::Comment(data),
+            OwnedXmlEvent::Characters(data) => XmlEvent::Characters(data),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for OwnedXmlEvent {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::Format::format(&self.borrow(), f);
+    }
+}
+
+impl<'a> From<XmlEvent<'a>> for OwnedXmlEvent {
+    fn from(e: XmlEvent<'a>) -> OwnedXmlEvent {
+        match e {
+            XmlEvent::StartDocument { version, encoding, standalone } =>
+                OwnedXmlEvent::StartDocument { version, encoding: encoding.map(From::from), standalone },
+            XmlEvent::ProcessingInstruction { name, data } =>
+                OwnedXmlEvent::ProcessingInstruction { name: name.into(), data: data.map(From::from) },
+            XmlEvent::StartElement { name, attributes, namespace } => OwnedXmlEvent::StartElement {
+                name: name.to_owned(),
+                attributes: attributes.iter().map(Attribute::to_owned).collect(),
+                namespace: namespace.into_owned(),
+            },
+            XmlEvent::EndElement { name } => OwnedXmlEvent::EndElement { name: name.map(|n| n.to_owned()) },
+            XmlEvent::CData(data) => OwnedXmlEvent::CData(data.into()),
+            XmlEvent::Comment(data) => OwnedXmlEvent::Comment(data.into()),
+            XmlEvent::Characters(data) => OwnedXmlEvent::Characters(data.into()),
+        }
+    }
+}
+
 impl<'a> From<&'a str> for XmlEvent<'a> {
     #[inline]
     fn from(s: &'a str) -> XmlEvent<'a> {
@@ -190,9 +338,11 @@ impl<'a> From<EndElementBuilder<'a>> for XmlEvent<'a> {
 
 /// A builder for a starting element event.
 pub struct StartElementBuilder<'a> {
-    name: Name<'a>,
-    attributes: Vec<Attribute<'a>>,
-    namespace: Namespace,
+    pub(crate) name: Name<'a>,
+    pub(crate) attributes: Vec<Attribute<'a>>,
+    /// Whether each of `attributes` is already escaped, empty while none is
+    pub(crate) pre_escaped: Vec<bool>,
+    pub(crate) namespace: Namespace,
 }
 
 impl<'a> StartElementBuilder<'a> {
@@ -212,6 +362,52 @@ impl<'a> StartElementBuilder<'a> {
         where N: Into<Name<'a>>
     {
         self.attributes.push(Attribute::new(name.into(), value));
+        if !self.pre_escaped.is_empty() {
+            self.pre_escaped.push(false);
+        }
+        self
+    }
+
+    /// Adds several attributes at once, e.g. `CowAttribute`s or `OwnedAttribute`s of an element
+    /// which is being rewritten. Their names and values are borrowed, not copied.
+    ///
+    /// The same rules as for `attr()` apply to each of them.
+    #[inline]
+    #[must_use]
+    pub fn attrs<I>(mut self, attributes: I) -> StartElementBuilder<'a>
+        where I: IntoIterator, I::Item: Into<Attribute<'a>>
+    {
+        self.attributes.extend(attributes.into_iter().map(Into::into));
+        if !self.pre_escaped.is_empty() {
+            self.pre_escaped.resize(self.attributes.len(), false);
+        }
+        self
+    }
+
+    /// Adds an attribute whose value is already escaped, see `PreEscaped`. It's written in
+    /// its place among the other attributes.
+    ///
+    /// Such an element is written with `EventWriter::write_start_element`, since an `XmlEvent`
+    /// can't tell which values are escaped already.
+    ///
+    /// ```rust
+    /// # use xml_no_std::escape::PreEscaped;
+    /// # use xml_no_std::writer::{EmitterConfig, EventWriter, XmlEvent};
+    /// let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+    /// writer.write_start_element(XmlEvent::start_element("a")
+    ///     .attr("title", "Tom & Jerry")
+    ///     .attr_pre_escaped("href", PreEscaped::new("?a=1&amp;b=2"))).unwrap();
+    /// writer.write(XmlEvent::end_element()).unwrap();
+    /// assert_eq!(writer.into_inner(), r#"<a title="Tom &amp; Jerry" href="?a=1&amp;b=2" />"#);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn attr_pre_escaped<N>(mut self, name: N, value: PreEscaped<'a>) -> StartElementBuilder<'a>
+        where N: Into<Name<'a>>
+    {
+        self.pre_escaped.resize(self.attributes.len(), false);
+        self.attributes.push(Attribute::new(name.into(), value.as_str()));
+        self.pre_escaped.push(true);
         self
     }
 
@@ -252,6 +448,7 @@ impl<'a> StartElementBuilder<'a> {
 impl<'a> From<StartElementBuilder<'a>> for XmlEvent<'a> {
     #[inline]
     fn from(b: StartElementBuilder<'a>) -> XmlEvent<'a> {
+        debug_assert!(!b.pre_escaped.contains(&true), "pre-escaped attributes are written with `EventWriter::write_start_element`");
         XmlEvent::StartElement {
             name: b.name,
             attributes: Cow::Owned(b.attributes),
@@ -0,0 +1,165 @@
+//! `EventWriter` over a `std::io::Write`, with the `std` feature.
+extern crate alloc;
+extern crate std;
+
+use alloc::string::ToString;
+use std::io::Write;
+
+use super::events::StartElementBuilder;
+use super::{EmitterConfig, Error, EventWriter, Result, XmlEvent};
+use crate::escape::PreEscaped;
+
+/// An `EventWriter` which passes the document on to an `io::Write`, see
+/// `EventWriter::from_writer`.
+///
+/// Each event is written to the output as soon as it's emitted, so the output should be
+/// buffered if it's slow to write small pieces to, e.g. a `BufWriter<File>`.
+pub struct IoEventWriter<W> {
+    writer: EventWriter,
+    output: W,
+}
+
+impl EventWriter {
+    /// Creates a writer which writes the document to `output`, like xml-rs does.
+    ///
+    /// I/O errors fail the write with `Error::Io`. Only available with the `std` feature.
+    ///
+    /// ```rust
+    /// use xml_no_std::writer::{EventWriter, XmlEvent};
+    ///
+    /// let mut writer = EventWriter::from_writer(Vec::new());
+    /// writer.write(XmlEvent::start_element("a")).unwrap();
+    /// writer.write(XmlEvent::end_element()).unwrap();
+    /// let output = writer.finish().unwrap();
+    /// assert!(output.ends_with(b"<a />"));
+    /// ```
+    pub fn from_writer<W: Write>(output: W) -> IoEventWriter<W> {
+        EventWriter::from_writer_with_config(output, EmitterConfig::new())
+    }
+
+    /// Creates a writer with the provided configuration which writes the document to `output`,
+    /// see `from_writer`.
+    pub fn from_writer_with_config<W: Write>(output: W, config: EmitterConfig) -> IoEventWriter<W> {
+        IoEventWriter { writer: EventWriter::new_with_config(config), output }
+    }
+}
+
+impl<W: Write> IoEventWriter<W> {
+    /// Writes the next piece of the document, see `EventWriter::write`.
+    pub fn write<'a, E>(&mut self, event: E) -> Result<()> where E: Into<XmlEvent<'a>> {
+        let result = self.writer.write(event);
+        self.drain()?;
+        result
+    }
+
+    /// Writes a starting element with pre-escaped attribute values, see
+    /// `EventWriter::write_start_element`.
+    pub fn write_start_element(&mut self, element: StartElementBuilder<'_>) -> Result<()> {
+        let result = self.writer.write_start_element(element);
+        self.drain()?;
+        result
+    }
+
+    /// Writes character data which is already escaped, see `EventWriter::write_pre_escaped`.
+    pub fn write_pre_escaped(&mut self, content: PreEscaped<'_>) -> Result<()> {
+        let result = self.writer.write_pre_escaped(content);
+        self.drain()?;
+        result
+    }
+
+    /// Returns a reference to the output.
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /// Returns a mutable reference to the output. Writing to it directly puts the bytes
+    /// in the document as they are.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Flushes the output and returns it.
+    pub fn finish(mut self) -> Result<W> {
+        self.drain()?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    /// Moves what the emitter wrote to the output
+    fn drain(&mut self) -> Result<()> {
+        let written = self.writer.inner_mut();
+        if !written.is_empty() {
+            self.output.write_all(written.as_bytes())?;
+            written.clear();
+        }
+        Ok(())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[cold]
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    extern crate std;
+
+    use alloc::vec::Vec;
+    use std::io::{self, Write};
+
+    use crate::writer::{Error, EventWriter, XmlEvent};
+
+    /// Accepts `room` bytes, and counts flushes
+    struct Limited {
+        written: Vec<u8>,
+        room: usize,
+        flushes: usize,
+    }
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "disk full"));
+            }
+            let len = buf.len().min(self.room).min(3);
+            self.written.extend_from_slice(&buf[..len]);
+            self.room -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    fn write_document(output: Limited) -> Result<Limited, Error> {
+        let mut writer = EventWriter::from_writer(output);
+        writer.write(XmlEvent::start_element("a").attr("b", "<c>"))?;
+        writer.write(XmlEvent::characters("text & more"))?;
+        assert!(!writer.get_ref().written.is_empty());
+        writer.write(XmlEvent::end_element())?;
+        writer.finish()
+    }
+
+    #[test]
+    fn from_writer() {
+        let mut expected = EventWriter::new();
+        expected.write(XmlEvent::start_element("a").attr("b", "<c>")).unwrap();
+        expected.write(XmlEvent::characters("text & more")).unwrap();
+        expected.write(XmlEvent::end_element()).unwrap();
+
+        let output = write_document(Limited { written: Vec::new(), room: usize::MAX, flushes: 0 }).unwrap();
+        assert_eq!(output.written, expected.into_inner().as_bytes());
+        assert_eq!(output.flushes, 1);
+
+        for room in [0, 10, 60] {
+            let err = write_document(Limited { written: Vec::new(), room, flushes: 0 }).map(|_| ()).unwrap_err();
+            assert!(matches!(&err, Error::Io(msg) if msg == "disk full"), "{err}");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Contains `PrefixGenerator`, the strategy the emitter uses to invent namespace prefixes.
+extern crate alloc;
+
+use core::fmt;
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::chars::{is_name_char, is_name_start_char};
+use crate::namespace::NamespaceStack;
+
+/// A strategy for inventing namespace prefixes, see `EmitterConfig::prefix_generator`.
+///
+/// The emitter asks for a prefix when it writes an element or an attribute name which has
+/// a namespace URI but no prefix, and no prefix is bound to that URI yet. The generated
+/// prefix is declared on the element being written.
+pub trait PrefixGenerator: fmt::Debug {
+    /// Returns a candidate prefix for `uri`.
+    ///
+    /// `in_scope` holds the prefixes which are already bound. If the candidate is one of them,
+    /// is reserved (starts with `xml`) or isn't a valid `NCName`, the emitter calls this method
+    /// again with `attempt` increased by one, so a generator should return a different
+    /// candidate for each attempt.
+    fn generate(&self, uri: &str, in_scope: &NamespaceStack, attempt: usize) -> String;
+}
+
+impl PartialEq for dyn PrefixGenerator + Send + Sync {
+    /// Generators are compared by identity, like the `Arc`s they're kept in by `EmitterConfig`.
+    fn eq(&self, other: &(dyn PrefixGenerator + Send + Sync)) -> bool {
+        (self as *const dyn PrefixGenerator).cast::<()>() == (other as *const dyn PrefixGenerator).cast::<()>()
+    }
+}
+
+impl Eq for dyn PrefixGenerator + Send + Sync {}
+
+/// Generates `ns1`, `ns2` and so on, picking the first one which isn't in scope.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct SequentialPrefixes;
+
+impl PrefixGenerator for SequentialPrefixes {
+    fn generate(&self, _uri: &str, _in_scope: &NamespaceStack, attempt: usize) -> String {
+        format!("ns{}", attempt + 1)
+    }
+}
+
+/// Generates a prefix from the last segment of the URI, e.g. `svg` for
+/// `http://www.w3.org/2000/svg`, then `svg2`, `svg3` and so on if it's taken.
+///
+/// Characters which can't be in a prefix are left out, and `ns` is used when nothing is left.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct UriTailPrefixes;
+
+impl UriTailPrefixes {
+    fn base(uri: &str) -> String {
+        let tail = uri.rsplit(['/', ':', '#', '?'])
+            .find(|s| !s.is_empty())
+            .unwrap_or("");
+        let base: String = tail.chars()
+            .filter(|&c| c != ':' && is_name_char(c))
+            .skip_while(|&c| !is_name_start_char(c))
+            .flat_map(char::to_lowercase)
+            .collect();
+        if base.is_empty() || base.starts_with("xml") { "ns".into() } else { base }
+    }
+}
+
+impl PrefixGenerator for UriTailPrefixes {
+    fn generate(&self, uri: &str, _in_scope: &NamespaceStack, attempt: usize) -> String {
+        let base = Self::base(uri);
+        if attempt == 0 { base } else { format!("{base}{}", attempt + 1) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UriTailPrefixes;
+
+    #[test]
+    fn uri_tail_base() {
+        for (uri, base) in [
+            ("http://www.w3.org/2000/svg", "svg"),
+            ("http://example.com/Schema/", "schema"),
+            ("urn:oasis:names:tc:SAML:2.0:assertion", "assertion"),
+            ("http://example.com/ns#v1.2", "v1.2"),
+            ("http://example.com/2001", "ns"),
+            ("http://example.com/-x_y", "x_y"),
+            ("http://example.com/XMLSchema", "ns"),
+            ("", "ns"),
+        ] {
+            assert_eq!(UriTailPrefixes::base(uri), base, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Allocation counts of the reader and the writer, to keep them from growing unnoticed.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use xml_no_std::reader::{ParserConfig2, WhitespaceHandling, XmlEvent};
+use xml_no_std::writer::{EventWriter, XmlEvent as WriterEvent};
+use xml_no_std::EventReader;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// SAFETY: forwards to the system allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // `try_with` because thread-locals may be gone already while a thread exits
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` and returns its result with the number of allocations and reallocations it made
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+/// Allocations made per element of a document with `elements` empty elements
+fn allocations_per_element(root: &str, elements: usize) -> f64 {
+    let doc = format!("{root}{}</r>", "<e/>".repeat(elements));
+    let (count, allocations) = count_allocations(|| {
+        let mut count = 0;
+        for e in EventReader::new(doc.as_bytes().iter()) {
+            if let XmlEvent::StartElement { .. } = e.unwrap() {
+                count += 1;
+            }
+        }
+        count
+    });
+    assert_eq!(count, elements + 1);
+    allocations as f64 / elements as f64
+}
+
+#[test]
+fn namespace_allocations() {
+    let plain = allocations_per_element("<r>", 5000);
+    let declared = allocations_per_element(r#"<r xmlns="urn:x">"#, 5000);
+    eprintln!("NS {plain} {declared}");
+    // 6 and 9 at the time of writing, 7 and 10 when the event's `Namespace` was a map: it holds
+    // its mappings inline now, so the `xml`/`xmlns` strings are 4 of them, and the declared URI
+    // costs one more there and two in names
+    assert!(plain < 7.0, "{plain:.2} allocations per element without namespaces");
+    assert!(declared < 10.0, "{declared:.2} allocations per element with a namespace declared on the root");
+}
+
+#[test]
+fn attribute_allocations() {
+    let per_element = |attributes: &str, lazy: bool| {
+        let doc = format!("<r>{}</r>", format!("<e{attributes}/>").repeat(5000));
+        let config = ParserConfig2::new().lazy_attributes(lazy);
+        let (_, allocations) = count_allocations(|| EventReader::new_with_config(doc.as_bytes().iter(), config).into_iter().map(|e| e.unwrap()).count());
+        allocations as f64 / 5000.0
+    };
+    let none = per_element("", false);
+    let two = per_element(" a='1' b='2'", false);
+    let six = per_element(" a='1' b='2' c='3' d='4' e='5' f='6'", false);
+    // 7, 11 and 21 at the time of writing: each attribute costs its name and value, the list
+    // holds two inline, and more go to a vector which is allocated for 4 and grows once
+    assert!(two - none < 4.5, "{two:.2} allocations per element with two attributes, {none:.2} without");
+    assert!(six - none < 14.5, "{six:.2} allocations per element with six attributes, {none:.2} without");
+    // 15 at the time of writing: lazy values stay in the document, only names are allocated
+    let lazy = per_element(" a='1' b='2' c='3' d='4' e='5' f='6'", true);
+    assert!(lazy - none < 8.5, "{lazy:.2} allocations per element with six lazy attributes, {none:.2} without");
+}
+
+#[test]
+fn transient_allocations() {
+    let per_element = |element: &str, config: ParserConfig2| {
+        let doc = format!("<r>{}</r>", element.repeat(5000));
+        let (_, allocations) = count_allocations(|| {
+            EventReader::new_with_config(doc.as_bytes().iter(), config).into_iter().map(|e| e.unwrap()).count()
+        });
+        allocations as f64 / 5000.0
+    };
+    let empty = per_element("<e/>", ParserConfig2::new());
+    let content = per_element("<e a='v'>t&amp;u</e>", ParserConfig2::new());
+    let indented = per_element("\n  <e/>", ParserConfig2::new().whitespace_handling(Some(WhitespaceHandling::Drop)));
+    // names, references and dropped whitespace are parsed in reused buffers, so the only
+    // allocations are for what's in the events: here the attribute's name and value, and
+    // the text, as the attribute list holds one attribute inline
+    assert!(content - empty < 3.5, "{content:.2} allocations per element with content, {empty:.2} without");
+    assert!(indented - empty < 0.5, "{indented:.2} allocations per indented element, {empty:.2} not indented");
+}
+
+#[test]
+fn interned_name_allocations() {
+    let doc = format!("<r xmlns:p='urn:p'>{}</r>", "<p:row id='1' p:kind='a'><cell>1</cell></p:row>".repeat(5000));
+    let per_row = |intern: bool| {
+        let config = ParserConfig2::new().intern_names(intern);
+        let (mut names, mut interned_names) = (Vec::with_capacity(10000), Vec::with_capacity(10000));
+        let (_, allocations) = count_allocations(|| {
+            let mut reader = EventReader::new_with_config(doc.as_bytes().iter(), config);
+            loop {
+                match reader.next().unwrap() {
+                    XmlEvent::StartElement { name, .. } if !intern => names.push(name),
+                    XmlEvent::StartElement { .. } => interned_names.push(reader.element_name().unwrap().clone()),
+                    XmlEvent::EndDocument => break,
+                    _ => {},
+                }
+            }
+        });
+        allocations as f64 / 5000.0
+    };
+    let owned = per_row(false);
+    let interned = per_row(true);
+    // 29 and 17 at the time of writing: the names of a row are 12 strings, in the events and in
+    // the stack of open elements, and interned ones are parsed in reused buffers instead
+    assert!(owned - interned > 11.5, "{owned:.2} allocations per row keeping its names, {interned:.2} keeping interned names");
+}
+
+#[test]
+fn static_name_allocations() {
+    let per_row = |write_static: bool| {
+        let (_, allocations) = count_allocations(|| {
+            let mut writer = EventWriter::new();
+            for _ in 0..5000 {
+                let event: WriterEvent<'static> = WriterEvent::start_element("row").into();
+                if write_static { writer.write_static(event) } else { writer.write(event) }.unwrap();
+                writer.write(WriterEvent::end_element()).unwrap();
+            }
+            writer.into_inner()
+        });
+        allocations as f64 / 5000.0
+    };
+    let copied = per_row(false);
+    let kept = per_row(true);
+    // 3 and 2 at the time of writing: the names stack copies the name unless it's static
+    assert!(copied - kept > 0.5, "{copied:.2} allocations per row with a copied name, {kept:.2} with a static one");
+}
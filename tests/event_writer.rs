@@ -221,7 +221,7 @@ fn attribute_escaping() {
         "<hello testLt=\"&lt;\" testGt=\"&gt;\" />
 <hello testQuot=\"&quot;\" testApos=\"&apos;\" />
 <hello testAmp=\"&amp;\" />
-<hello testNl=\"&#xA;\" testCr=\"&#xD;\" />
+<hello testNl=\"&#xA;\" testCr=\"&#13;\" />
 <hello testNl=\"\\n\" testCr=\"\\r\" />"
     );
 }
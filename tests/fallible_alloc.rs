@@ -0,0 +1,97 @@
+//! Running out of memory with the `fallible-alloc` feature, simulated by an allocator which
+//! refuses large allocations.
+#![cfg(feature = "fallible-alloc")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ptr;
+
+use xml_no_std::reader::{ErrorKind, XmlEvent};
+use xml_no_std::writer::{self, EmitterConfig, EventWriter};
+use xml_no_std::EventReader;
+
+struct LimitedAllocator;
+
+thread_local! {
+    static MAX_SIZE: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+fn max_size() -> usize {
+    // `try_with` because thread-locals may be gone already while a thread exits
+    MAX_SIZE.try_with(Cell::get).unwrap_or(usize::MAX)
+}
+
+// SAFETY: forwards to the system allocator, or fails
+unsafe impl GlobalAlloc for LimitedAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() > max_size() {
+            return ptr::null_mut();
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > max_size() {
+            return ptr::null_mut();
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LimitedAllocator = LimitedAllocator;
+
+/// Runs `f` with allocations larger than `max_size` failing
+fn with_max_size<R>(max_size: usize, f: impl FnOnce() -> R) -> R {
+    MAX_SIZE.with(|m| m.set(max_size));
+    let result = f();
+    MAX_SIZE.with(|m| m.set(usize::MAX));
+    result
+}
+
+fn read_all(doc: &str) -> Result<usize, xml_no_std::reader::Error> {
+    let mut reader = EventReader::from_str(doc);
+    let mut events = 0;
+    while reader.next()? != XmlEvent::EndDocument {
+        events += 1;
+    }
+    Ok(events)
+}
+
+#[test]
+fn reader_out_of_memory() {
+    let text = format!("<a>{}</a>", "x".repeat(1 << 16));
+    let attribute = format!("<a b='{}'/>", "x".repeat(1 << 16));
+    let entity = format!("<!DOCTYPE a [<!ENTITY e '{}'>]><a b='&e;'/>", "x".repeat(1 << 12));
+
+    for (doc, events) in [(&text, 4), (&attribute, 3)] {
+        let err = with_max_size(1 << 14, || read_all(doc)).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::OutOfMemory);
+        assert_eq!(read_all(doc).unwrap(), events);
+    }
+    let err = with_max_size(1 << 12, || read_all(&entity)).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::OutOfMemory);
+    assert_eq!(read_all(&entity).unwrap(), 3);
+}
+
+#[test]
+fn writer_out_of_memory() {
+    let text = "x".repeat(1 << 16);
+    let mut writer = EventWriter::new_with_config(EmitterConfig::new().write_document_declaration(false));
+    writer.write(writer::XmlEvent::start_element("a")).unwrap();
+
+    let err = with_max_size(1 << 14, || writer.write(writer::XmlEvent::characters(&text))).unwrap_err();
+    assert!(matches!(err, writer::Error::OutOfMemory), "{err}");
+    let err = with_max_size(1 << 14, || writer.write(writer::XmlEvent::start_element("b").attr("c", &text))).unwrap_err();
+    assert!(matches!(err, writer::Error::OutOfMemory), "{err}");
+    assert_eq!(writer.inner_mut(), "<a");
+
+    // nothing was written, so the writer can go on
+    writer.write(writer::XmlEvent::characters("y")).unwrap();
+    writer.write(writer::XmlEvent::end_element()).unwrap();
+    assert_eq!(writer.into_inner(), "<a>y</a>");
+}